@@ -0,0 +1,139 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Derive macros for [logforth](https://docs.rs/logforth).
+//!
+//! Use the macros re-exported by `logforth` with the `derive` feature rather than depending on
+//! this crate directly.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::parse_macro_input;
+use syn::Data;
+use syn::DeriveInput;
+use syn::Fields;
+use syn::LitStr;
+
+/// Derives [`log::kv::Source`] for a struct with named fields, exposing its fields as
+/// key-values.
+///
+/// Fields are attached under their names with their `log::kv::ToValue` implementation, unless:
+///
+/// - `#[log(skip)]` leaves the field out;
+/// - `#[log(rename = "name")]` attaches the field under the given name;
+/// - `#[log(display)]` or `#[log(debug)]` formats the field with `Display` or `Debug`, e.g., for
+///   `String` fields.
+#[proc_macro_derive(LogFields, attributes(log))]
+pub fn derive_log_fields(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+enum Format {
+    ToValue,
+    Display,
+    Debug,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input,
+                    "LogFields can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input,
+                "LogFields can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut pairs = vec![];
+    for field in fields {
+        let ident = field.ident.as_ref().expect("named fields have idents");
+        let mut key = ident.to_string();
+        let mut skip = false;
+        let mut format = Format::ToValue;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("log"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    skip = true;
+                    Ok(())
+                } else if meta.path.is_ident("rename") {
+                    key = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else if meta.path.is_ident("display") {
+                    format = Format::Display;
+                    Ok(())
+                } else if meta.path.is_ident("debug") {
+                    format = Format::Debug;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `skip`, `rename = \"...\"`, `display` or `debug`"))
+                }
+            })?;
+        }
+        if !skip {
+            pairs.push((key, ident, format));
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let count = pairs.len();
+    let visits = pairs.iter().map(|(key, ident, format)| {
+        let value = match format {
+            Format::ToValue => {
+                quote!(::logforth::__private::log::kv::ToValue::to_value(&self.#ident))
+            }
+            Format::Display => {
+                quote!(::logforth::__private::log::kv::Value::from_display(&self.#ident))
+            }
+            Format::Debug => {
+                quote!(::logforth::__private::log::kv::Value::from_debug(&self.#ident))
+            }
+        };
+        quote! {
+            visitor.visit_pair(::logforth::__private::log::kv::Key::from_str(#key), #value)?;
+        }
+    });
+    Ok(quote! {
+        impl #impl_generics ::logforth::__private::log::kv::Source for #name #ty_generics #where_clause {
+            fn visit<'kvs>(
+                &'kvs self,
+                visitor: &mut dyn ::logforth::__private::log::kv::VisitSource<'kvs>,
+            ) -> ::core::result::Result<(), ::logforth::__private::log::kv::Error> {
+                #(#visits)*
+                ::core::result::Result::Ok(())
+            }
+
+            fn count(&self) -> usize {
+                #count
+            }
+        }
+    })
+}
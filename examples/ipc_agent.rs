@@ -0,0 +1,69 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A companion agent that receives records shipped by the IPC appender and logs them to its own
+//! sinks.
+//!
+//! Run the agent with `cargo run --example ipc_agent --features ipc [SOCKET_PATH]`, then point
+//! `IpcWriter` of the application at the same socket path.
+
+use std::io::BufReader;
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+
+use logforth::append::ipc::read_frame;
+use logforth::record::RecordOwned;
+
+fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "/tmp/logforth-agent.sock".to_string());
+
+    // the agent owns the expensive sinks; stdout stands in for them here
+    logforth::stdout().apply();
+
+    // remove the socket left behind by a previous run
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                std::thread::spawn(move || serve(stream));
+            }
+            Err(err) => eprintln!("failed to accept connection: {err}"),
+        }
+    }
+}
+
+fn serve(stream: UnixStream) {
+    let mut reader = BufReader::new(stream);
+    loop {
+        match read_frame(&mut reader) {
+            Ok(Some(payload)) => match parse(&payload) {
+                Ok(record) => record.log(log::logger()),
+                Err(err) => eprintln!("failed to parse record: {err}"),
+            },
+            Ok(None) => break,
+            Err(err) => {
+                eprintln!("failed to read frame: {err}");
+                break;
+            }
+        }
+    }
+}
+
+fn parse(payload: &[u8]) -> anyhow::Result<RecordOwned> {
+    let json = serde_json::from_slice(payload)?;
+    RecordOwned::from_json(&json)
+}
@@ -0,0 +1,348 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use log::kv::Value;
+use log::Level;
+use log::Record;
+
+use crate::append::repeats;
+use crate::append::repeats::DiagnosticSnapshot;
+use crate::append::repeats::Fingerprint;
+use crate::append::Append;
+use crate::clock;
+use crate::Diagnostic;
+use crate::Layout;
+
+/// An appender that collapses consecutive identical records, like the kernel log's
+/// "last message repeated N times".
+///
+/// Records are identical when they have the same fingerprint, by default their target and
+/// message. The first record of a run is forwarded, and the repeats are suppressed. The number of
+/// repeats is forwarded as one "last message repeated N times" record when a different record
+/// arrives, or once per `window` while the run goes on, so a flood produces one record per window.
+/// A run that ends without a following record is reported when the appender is flushed after the
+/// window. The repeats record carries the diagnostics of the first repeat it counts.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use logforth::append;
+/// use logforth::append::Dedup;
+///
+/// logforth::builder()
+///     .dispatch(|d| {
+///         d.append(Dedup::new(
+///             append::Stderr::default(),
+///             Duration::from_secs(30),
+///         ))
+///     })
+///     .apply();
+/// ```
+pub struct Dedup {
+    append: Box<dyn Append>,
+    window: Duration,
+    fingerprint: Fingerprint,
+    last: Mutex<Option<LastRecord>>,
+}
+
+impl fmt::Debug for Dedup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dedup")
+            .field("append", &self.append)
+            .field("window", &self.window)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct LastRecord {
+    fingerprint: String,
+    level: Level,
+    target: String,
+    repeated: usize,
+    // when the first repeat of the current window arrived, and its diagnostics
+    window_start: Instant,
+    diagnostics: DiagnosticSnapshot,
+}
+
+impl LastRecord {
+    fn take_summary(&mut self, now: Instant) -> Option<Summary> {
+        let summary = (self.repeated > 0).then(|| Summary {
+            level: self.level,
+            target: self.target.clone(),
+            repeated: self.repeated,
+            diagnostics: std::mem::take(&mut self.diagnostics),
+        });
+        self.repeated = 0;
+        self.window_start = now;
+        summary
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Summary {
+    level: Level,
+    target: String,
+    repeated: usize,
+    diagnostics: DiagnosticSnapshot,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Decision {
+    Forward,
+    Suppress,
+}
+
+impl Dedup {
+    /// Creates a new [`Dedup`] that wraps the given appender, reporting repeats at least once per
+    /// `window`.
+    pub fn new(append: impl Append, window: Duration) -> Self {
+        Self {
+            append: Box::new(append),
+            window,
+            fingerprint: Fingerprint::default(),
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Sets the fingerprint that tells whether a record repeats the previous one, e.g., its target
+    /// and an `error_code` key-value. Default to the target and the message of records.
+    pub fn with_fingerprint<F>(mut self, fingerprint: F) -> Self
+    where
+        F: Fn(&Record) -> String + Send + Sync + 'static,
+    {
+        self.fingerprint = Fingerprint::new(fingerprint);
+        self
+    }
+
+    fn on_record(
+        &self,
+        fingerprint: String,
+        level: Level,
+        target: &str,
+        diagnostics: &[Diagnostic],
+        now: Instant,
+    ) -> (Option<Summary>, Decision) {
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(last) = last.as_mut().filter(|last| last.fingerprint == fingerprint) {
+            let mut summary = None;
+            if last.repeated > 0 && now.duration_since(last.window_start) >= self.window {
+                summary = last.take_summary(now);
+            }
+            if last.repeated == 0 {
+                last.window_start = now;
+                last.diagnostics = DiagnosticSnapshot::capture(diagnostics);
+            }
+            last.repeated += 1;
+            return (summary, Decision::Suppress);
+        }
+
+        let summary = last.as_mut().and_then(|last| last.take_summary(now));
+        *last = Some(LastRecord {
+            fingerprint,
+            level,
+            target: target.to_string(),
+            repeated: 0,
+            window_start: now,
+            diagnostics: DiagnosticSnapshot::default(),
+        });
+        (summary, Decision::Forward)
+    }
+
+    fn append_summary(&self, summary: Summary, layout: Option<&Layout>) -> anyhow::Result<()> {
+        let Summary {
+            level,
+            target,
+            repeated,
+            diagnostics,
+        } = summary;
+        let kvs = [("repeated", Value::from(repeated))];
+        self.append.append_with_layout(
+            &Record::builder()
+                .level(level)
+                .target(&target)
+                .args(format_args!("last message repeated {repeated} times"))
+                .key_values(&kvs)
+                .build(),
+            &diagnostics.diagnostics(),
+            layout,
+        )
+    }
+}
+
+impl Append for Dedup {
+    fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        let (summary, decision) = self.on_record(
+            self.fingerprint.of(record),
+            record.level(),
+            record.target(),
+            diagnostics,
+            clock::instant(),
+        );
+        if let Some(summary) = summary {
+            self.append_summary(summary, layout)?;
+        }
+        match decision {
+            Decision::Forward => self.append.append_with_layout(record, diagnostics, layout),
+            Decision::Suppress => Ok(()),
+        }
+    }
+
+    fn flush(&self) {
+        self.flush_with_layout(None)
+    }
+
+    fn flush_with_layout(&self, layout: Option<&Layout>) {
+        let now = clock::instant();
+        let summary = {
+            let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+            last.as_mut()
+                .filter(|last| now.duration_since(last.window_start) >= self.window)
+                .and_then(|last| last.take_summary(now))
+        };
+        if let Some(summary) = summary {
+            if let Err(err) = self.append_summary(summary, layout) {
+                repeats::report_flush_error(&self.append, "repeated records summary", err);
+            }
+        }
+        self.append.flush_with_layout(layout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append::test_util;
+    use crate::append::Memory;
+    use crate::append::Testing;
+    use crate::diagnostic::ThreadLocalContext;
+    use crate::diagnostic::ThreadLocalDiagnostic;
+    use crate::layout::TextLayout;
+
+    #[test]
+    fn test_dedup_runs() {
+        let dedup = Dedup::new(Testing::default(), Duration::from_secs(10));
+        let start = Instant::now();
+        let on_record = |message: &str, now: Instant| {
+            dedup.on_record(message.to_string(), Level::Warn, "app", &[], now)
+        };
+        let summary = |repeated: usize| Summary {
+            level: Level::Warn,
+            target: "app".to_string(),
+            repeated,
+            diagnostics: DiagnosticSnapshot::default(),
+        };
+
+        assert_eq!(on_record("retrying", start), (None, Decision::Forward));
+        for _ in 0..3 {
+            assert_eq!(on_record("retrying", start), (None, Decision::Suppress));
+        }
+
+        // a different record ends the run
+        assert_eq!(
+            on_record("connected", start),
+            (Some(summary(3)), Decision::Forward)
+        );
+        assert_eq!(on_record("retrying", start), (None, Decision::Forward));
+
+        // a long run is reported once per window
+        let next = start + Duration::from_secs(1);
+        assert_eq!(on_record("retrying", next), (None, Decision::Suppress));
+        let later = next + Duration::from_secs(10);
+        assert_eq!(
+            on_record("retrying", later),
+            (Some(summary(1)), Decision::Suppress)
+        );
+        assert_eq!(
+            on_record("connected", later),
+            (Some(summary(1)), Decision::Forward)
+        );
+    }
+
+    #[test]
+    fn test_dedup_append() {
+        let testing = Testing::default();
+        let handle = testing.handle();
+        let dedup = Dedup::new(testing, Duration::from_secs(3600));
+        let append = |message: &str| test_util::append(&dedup, Level::Error, message).unwrap();
+
+        append("disk full");
+        append("disk full");
+        append("disk full");
+        append("disk ok");
+        assert_eq!(
+            test_util::messages(handle.records()),
+            ["disk full", "last message repeated 2 times", "disk ok"]
+        );
+        handle.assert_kv("repeated", "2");
+    }
+
+    #[test]
+    fn test_dedup_summary_context() {
+        let memory = Memory::new(10);
+        let handle = memory.handle();
+        let dedup = Dedup::new(memory, Duration::ZERO);
+        let layout = Layout::from(
+            TextLayout::default()
+                .no_color()
+                .no_timestamp()
+                .no_location(),
+        );
+        let append = |message: &str, request: &str| {
+            ThreadLocalContext::new()
+                .with("request", request)
+                .scope(|| {
+                    test_util::with_record(Level::Warn, message, |record| {
+                        let diagnostics = [ThreadLocalDiagnostic::default().into()];
+                        dedup.append_with_layout(record, &diagnostics, Some(&layout))
+                    })
+                })
+                .unwrap();
+        };
+
+        // the summary carries the diagnostics of the repeats, not of the record ending the run
+        append("retrying", "1");
+        append("retrying", "2");
+        append("connected", "3");
+        // the summary emitted on flush is formatted with the layout of the dispatch
+        append("connected", "4");
+        dedup.flush_with_layout(Some(&layout));
+
+        assert_eq!(
+            test_util::messages(handle.snapshot()),
+            [
+                " WARN app: retrying request=1",
+                " WARN app: last message repeated 1 times repeated=1 request=2",
+                " WARN app: connected request=3",
+                " WARN app: last message repeated 1 times repeated=1 request=4",
+            ]
+        );
+    }
+}
@@ -0,0 +1,297 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Context;
+use log::Record;
+
+use crate::append::Append;
+use crate::clock;
+use crate::Diagnostic;
+use crate::Layout;
+
+const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// An appender that routes log records to fallback appenders when the primary one fails.
+///
+/// Appenders are tried in order until one succeeds. An appender that fails is skipped for a
+/// backoff, starting at 1 second and doubling up to 60 seconds, and is tried again afterward; it
+/// is back in use once it succeeds. The last appender is never skipped, so it should be the most
+/// reliable one, e.g., [`Stderr`](crate::append::Stderr).
+///
+/// Failures are reported to stderr when an appender starts failing. The error of the last
+/// appender is returned if every appender fails.
+///
+/// Only failures returned by the appender are detected, so only appenders that write records
+/// synchronously can fail over. Non-blocking appenders, such as `RollingFile` with its worker
+/// thread, and `OpentelemetryLog` queue records and never return an error for a sink that is
+/// down.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::append;
+/// use logforth::append::Failover;
+///
+/// let failover =
+///     Failover::new(append::Stdout::default()).with_fallback(append::Stderr::default());
+///
+/// logforth::builder().dispatch(|d| d.append(failover)).apply();
+/// ```
+#[derive(Debug)]
+pub struct Failover {
+    members: Vec<Member>,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+#[derive(Debug)]
+struct Member {
+    append: Box<dyn Append>,
+    health: Mutex<Health>,
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    failing: bool,
+    backoff: Duration,
+    // unset while failing if the backoff is too long to be represented
+    retry_at: Option<Instant>,
+}
+
+impl Failover {
+    /// Creates a new [`Failover`] with the primary appender.
+    pub fn new(primary: impl Append) -> Self {
+        Self {
+            members: vec![Member::new(primary)],
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+        }
+    }
+
+    /// Adds a fallback appender, tried after the previous ones fail.
+    pub fn with_fallback(mut self, fallback: impl Append) -> Self {
+        self.members.push(Member::new(fallback));
+        self
+    }
+
+    /// Sets the initial and the maximum backoff of failing appenders. Default to 1 second and
+    /// 60 seconds.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    fn append_at(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+        now: Instant,
+    ) -> anyhow::Result<()> {
+        let last = self.members.len() - 1;
+        let mut error = None;
+        for (i, member) in self.members.iter().enumerate() {
+            if i != last && !member.is_available(now) {
+                continue;
+            }
+            match member
+                .append
+                .append_with_layout(record, diagnostics, layout)
+            {
+                Ok(()) => {
+                    member.on_success();
+                    return Ok(());
+                }
+                Err(err) => {
+                    member.on_failure(&err, now, self.initial_backoff, self.max_backoff);
+                    error = Some(err);
+                }
+            }
+        }
+        let error = error.expect("the last appender is always tried");
+        Err(error).context("every appender of the failover failed")
+    }
+}
+
+impl Member {
+    fn new(append: impl Append) -> Self {
+        Self {
+            append: Box::new(append),
+            health: Mutex::new(Health::default()),
+        }
+    }
+
+    fn health(&self) -> std::sync::MutexGuard<'_, Health> {
+        self.health.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn is_available(&self, now: Instant) -> bool {
+        let health = self.health();
+        !health.failing || health.retry_at.is_some_and(|retry_at| now >= retry_at)
+    }
+
+    fn on_success(&self) {
+        let mut health = self.health();
+        if health.failing {
+            *health = Health::default();
+        }
+    }
+
+    fn on_failure(&self, err: &anyhow::Error, now: Instant, initial: Duration, max: Duration) {
+        let mut health = self.health();
+        if !health.failing {
+            eprintln!(
+                "appender {} failed, failing over: {err:#}",
+                self.append.name()
+            );
+            health.failing = true;
+            health.backoff = initial;
+        } else {
+            health.backoff = health.backoff.saturating_mul(2).min(max);
+        }
+        health.retry_at = now.checked_add(health.backoff);
+    }
+}
+
+impl Append for Failover {
+    fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        self.append_at(record, diagnostics, layout, clock::instant())
+    }
+
+    fn flush(&self) {
+        self.flush_with_layout(None)
+    }
+
+    fn flush_with_layout(&self, layout: Option<&Layout>) {
+        for member in &self.members {
+            member.append.flush_with_layout(layout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use log::Level;
+
+    use super::*;
+    use crate::append::test_util;
+    use crate::append::Testing;
+    use crate::append::TestingHandle;
+
+    #[derive(Debug, Default)]
+    struct Flaky {
+        failing: AtomicBool,
+        testing: Testing,
+    }
+
+    impl Append for Flaky {
+        fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+            if self.failing.load(Ordering::Relaxed) {
+                anyhow::bail!("endpoint down");
+            }
+            self.testing.append(record, diagnostics)
+        }
+    }
+
+    #[test]
+    fn test_failover() {
+        let primary = Arc::new(Flaky::default());
+        let primary_handle = primary.testing.handle();
+        let fallback = Testing::default();
+        let fallback_handle = fallback.handle();
+        let failover = Failover::new(primary.clone())
+            .with_fallback(fallback)
+            .with_backoff(Duration::from_secs(1), Duration::from_secs(2));
+
+        let start = Instant::now();
+        let append = |message: &str, now: Instant| {
+            test_util::with_record(Level::Error, message, |record| {
+                failover.append_at(record, &[], None, now)
+            })
+        };
+        let messages = |handle: &TestingHandle| test_util::messages(handle.records());
+
+        append("first", start).unwrap();
+        primary.failing.store(true, Ordering::Relaxed);
+        append("second", start).unwrap();
+        // the primary is skipped during its backoff, then retried
+        primary.failing.store(false, Ordering::Relaxed);
+        append("third", start).unwrap();
+        let retry = start + Duration::from_secs(1);
+        append("fourth", retry).unwrap();
+        assert_eq!(messages(&primary_handle), ["first", "fourth"]);
+        assert_eq!(messages(&fallback_handle), ["second", "third"]);
+
+        // the backoff doubles up to the maximum while the primary keeps failing
+        primary.failing.store(true, Ordering::Relaxed);
+        append("fifth", retry).unwrap();
+        let health = || failover.members[0].health().retry_at.unwrap() - retry;
+        assert_eq!(health(), Duration::from_secs(1));
+        append("sixth", retry + Duration::from_secs(1)).unwrap();
+        append("seventh", retry + Duration::from_secs(3)).unwrap();
+        assert_eq!(health(), Duration::from_secs(5));
+        assert_eq!(fallback_handle.records().len(), 5);
+    }
+
+    #[test]
+    fn test_failover_long_backoff() {
+        let failover = Failover::new(Flaky::default())
+            .with_fallback(Testing::default())
+            .with_backoff(Duration::MAX / 2, Duration::MAX);
+        let member = &failover.members[0];
+        let err = anyhow::anyhow!("endpoint down");
+
+        // the backoff saturates, and a retry time beyond the clock never comes
+        let now = Instant::now();
+        for _ in 0..3 {
+            member.on_failure(&err, now, failover.initial_backoff, failover.max_backoff);
+        }
+        assert_eq!(member.health().backoff, Duration::MAX);
+        assert!(!member.is_available(now));
+    }
+
+    #[test]
+    fn test_failover_all_failed() {
+        let failing = || {
+            let flaky = Flaky::default();
+            flaky.failing.store(true, Ordering::Relaxed);
+            flaky
+        };
+        let failover = Failover::new(failing()).with_fallback(failing());
+        let err = test_util::append(&failover, Level::Error, "lost").unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "every appender of the failover failed: endpoint down"
+        );
+    }
+}
@@ -16,11 +16,18 @@
 
 use std::borrow::Cow;
 
-use jiff::Zoned;
+use jiff::tz::TimeZone;
 use log::Record;
 
 use crate::append::Append;
+use crate::backtrace::is_backtrace_key;
+use crate::backtrace::BACKTRACE_FIELD;
+use crate::clock;
 use crate::diagnostic::Visitor;
+use crate::secret::secret_key;
+use crate::secret::MASK;
+use crate::template::is_message_template_key;
+use crate::template::MESSAGE_TEMPLATE_FIELD;
 use crate::Diagnostic;
 
 /// An appender that adds log records to fastrace as an event associated to the current span.
@@ -34,14 +41,28 @@ use crate::Diagnostic;
 /// ```
 #[derive(Default, Debug, Clone)]
 pub struct FastraceEvent {
-    _private: (), // suppress structure literal syntax
+    backtrace: bool,
+}
+
+impl FastraceEvent {
+    /// Sets whether to add the backtrace captured for the record to the event. Default to
+    /// `false`.
+    ///
+    /// See [`Builder::backtrace_level`](crate::Builder::backtrace_level) to capture backtraces.
+    pub fn with_backtrace(mut self, backtrace: bool) -> Self {
+        self.backtrace = backtrace;
+        self
+    }
 }
 
 impl Append for FastraceEvent {
     fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
         let message = format!("{}", record.args());
 
-        let mut collector = KvCollector { kv: Vec::new() };
+        let mut collector = KvCollector {
+            kv: Vec::new(),
+            backtrace: self.backtrace,
+        };
         record.key_values().visit(&mut collector)?;
         for d in diagnostics {
             d.visit(&mut collector);
@@ -50,7 +71,10 @@ impl Append for FastraceEvent {
         fastrace::Event::add_to_local_parent(message, || {
             [
                 (Cow::from("level"), Cow::from(record.level().as_str())),
-                (Cow::from("timestamp"), Cow::from(Zoned::now().to_string())),
+                (
+                    Cow::from("timestamp"),
+                    Cow::from(clock::now().to_zoned(TimeZone::system()).to_string()),
+                ),
             ]
             .into_iter()
             .chain(
@@ -70,6 +94,26 @@ impl Append for FastraceEvent {
 
 struct KvCollector {
     kv: Vec<(String, String)>,
+    backtrace: bool,
+}
+
+impl KvCollector {
+    fn push(&mut self, key: String, value: String) {
+        let key = match is_message_template_key(&key) {
+            true => MESSAGE_TEMPLATE_FIELD.to_string(),
+            false => key,
+        };
+        if is_backtrace_key(&key) {
+            if self.backtrace {
+                self.kv.push((BACKTRACE_FIELD.to_string(), value));
+            }
+            return;
+        }
+        match secret_key(&key) {
+            Some(key) => self.kv.push((key.to_string(), MASK.to_string())),
+            None => self.kv.push((key, value)),
+        }
+    }
 }
 
 impl<'kvs> log::kv::VisitSource<'kvs> for KvCollector {
@@ -78,7 +122,7 @@ impl<'kvs> log::kv::VisitSource<'kvs> for KvCollector {
         key: log::kv::Key<'kvs>,
         value: log::kv::Value<'kvs>,
     ) -> Result<(), log::kv::Error> {
-        self.kv.push((key.to_string(), value.to_string()));
+        self.push(key.to_string(), value.to_string());
         Ok(())
     }
 }
@@ -91,6 +135,6 @@ impl Visitor for KvCollector {
     {
         let key = key.into().into_owned();
         let value = value.into().into_owned();
-        self.kv.push((key, value));
+        self.push(key, value);
     }
 }
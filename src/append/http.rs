@@ -0,0 +1,719 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Appender for posting batches of log records to an HTTP endpoint, e.g., a webhook or the
+//! [Grafana Loki push API](https://grafana.com/docs/loki/latest/reference/loki-http-api/#ingest-logs).
+//!
+//! Notification services such as Slack, Discord or Microsoft Teams are supported by
+//! [`HttpWriter::webhook`], which renders a body template for each request.
+//!
+//! Records are batched by the non-blocking worker: each write of the worker becomes one request,
+//! split by [`HttpWriter::with_max_batch_records`]. Configure
+//! [`NonBlockingBuilder::buffer_size`] and [`NonBlockingBuilder::flush_interval`] to trade
+//! latency for fewer, larger requests.
+//!
+//! # Examples
+//!
+//!```rust, no_run
+//! use std::time::Duration;
+//!
+//! use logforth::append::http;
+//! use logforth::append::http::Http;
+//! use logforth::append::http::HttpWriter;
+//!
+//! let http_writer = HttpWriter::loki(
+//!     "http://localhost:3100/loki/api/v1/push",
+//!     [("service", "checkout")],
+//! )
+//! .with_level_label("level");
+//! let (non_blocking, _guard) = http::non_blocking(http_writer)
+//!     .buffer_size(64 * 1024)
+//!     .flush_interval(Duration::from_secs(2))
+//!     .finish();
+//!
+//! logforth::builder()
+//!     .dispatch(|d| {
+//!         d.filter(log::LevelFilter::Info)
+//!             .append(Http::new(non_blocking))
+//!     })
+//!     .apply();
+//!
+//! log::info!("This log will be pushed to Loki.");
+//! ```
+//!
+//! Post errors to a Slack incoming webhook, one message per record:
+//!
+//!```rust, no_run
+//! use logforth::append::http;
+//! use logforth::append::http::Http;
+//! use logforth::append::http::HttpWriter;
+//!
+//! let http_writer = HttpWriter::webhook(
+//!     "https://hooks.slack.com/services/T000/B000/XXXX",
+//!     r#"{"text": "[{level}] {message}"}"#,
+//! )
+//! .with_max_batch_records(1);
+//! let (non_blocking, _guard) = http::non_blocking(http_writer).finish();
+//!
+//! logforth::builder()
+//!     .dispatch(|d| {
+//!         d.filter(log::LevelFilter::Error)
+//!             .append(Http::new(non_blocking))
+//!     })
+//!     .apply();
+//! ```
+
+use std::collections::BTreeMap;
+use std::io;
+use std::time::Duration;
+
+use log::Level;
+use log::Record;
+
+use crate::clock;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+use crate::compression::Compression;
+use crate::layout::TextLayout;
+use crate::non_blocking::NonBlocking;
+use crate::non_blocking::NonBlockingBuilder;
+use crate::non_blocking::Writer;
+use crate::Append;
+use crate::Diagnostic;
+use crate::Layout;
+
+/// The header [`Http`] prepends to each formatted record for [`HttpWriter`]: the time of the
+/// record in nanoseconds since the Unix epoch as a big-endian `u64`, and its level.
+const HEADER_LEN: usize = 9;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_MAX_BATCH_RECORDS: usize = 1000;
+const DEFAULT_MAX_RETRIES: usize = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// An appender that posts log records to an HTTP endpoint in batches.
+///
+/// The appender is meant to be used with an [`HttpWriter`], which decodes the time and level
+/// the appender attaches to each record. Avoid [`NonBlockingBuilder::max_record_size`] below
+/// a few bytes, which would cut them off.
+#[derive(Debug)]
+pub struct Http {
+    layout: Option<Layout>,
+    writer: NonBlocking<HttpWriter>,
+}
+
+impl Http {
+    /// Creates a new [`Http`] appender.
+    ///
+    /// This appender by default uses [`TextLayout`] without color to format log records.
+    pub fn new(writer: NonBlocking<HttpWriter>) -> Self {
+        Self {
+            layout: None,
+            writer,
+        }
+    }
+
+    /// Sets the layout used to format log records.
+    pub fn with_layout(mut self, layout: impl Into<Layout>) -> Self {
+        self.layout = Some(layout.into());
+        self
+    }
+}
+
+impl Append for Http {
+    fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        let mut bytes = match self.layout.as_ref().or(layout) {
+            Some(layout) => layout.format(record, diagnostics)?,
+            None => TextLayout::default()
+                .no_color()
+                .format(record, diagnostics)?,
+        };
+        let nanos = u64::try_from(clock::now().as_nanosecond()).unwrap_or_default();
+        let mut header = [0; HEADER_LEN];
+        header[..8].copy_from_slice(&nanos.to_be_bytes());
+        header[8] = record.level() as u8;
+        bytes.splice(0..0, header);
+        self.writer.send(bytes, record.level())?;
+        Ok(())
+    }
+
+    fn flush(&self) {
+        if let Err(err) = self.writer.flush() {
+            eprintln!("failed to flush HTTP appender: {err:?}");
+        }
+    }
+}
+
+/// Create a non-blocking builder for HTTP writers.
+pub fn non_blocking(writer: HttpWriter) -> NonBlockingBuilder<HttpWriter> {
+    NonBlockingBuilder::new("logforth-http", writer)
+}
+
+/// A writer that posts batches of formatted log records to an HTTP endpoint.
+///
+/// Failed requests are retried with exponential backoff when the endpoint is unreachable or
+/// responds with `429` or a `5xx` status, which blocks the non-blocking worker meanwhile. A batch
+/// is dropped after the last retry, and the error is reported to stderr by the worker.
+///
+/// HTTPS requires the `native-tls` feature.
+#[derive(Debug)]
+pub struct HttpWriter {
+    url: String,
+    format: Format,
+    headers: Vec<(String, String)>,
+    timeout: Duration,
+    max_batch_records: usize,
+    max_retries: usize,
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    compression: Option<Compression>,
+    // built on the first request
+    agent: Option<ureq::Agent>,
+}
+
+#[derive(Debug)]
+enum Format {
+    /// Newline-delimited records.
+    Lines,
+    /// A Loki push request, with one stream per level if `level_label` is set.
+    Loki {
+        labels: BTreeMap<String, String>,
+        level_label: Option<String>,
+    },
+    /// A JSON body rendered from a template.
+    Template(Vec<TemplatePart>),
+}
+
+#[derive(Debug)]
+enum TemplatePart {
+    Literal(String),
+    Message,
+    Level,
+    Time,
+    Count,
+}
+
+impl TemplatePart {
+    fn parse(template: &str) -> Vec<TemplatePart> {
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut rest = template;
+        while let Some(c) = rest.chars().next() {
+            let placeholder = rest.split_once('}').and_then(|(name, tail)| {
+                let part = match name {
+                    "{message" => TemplatePart::Message,
+                    "{level" => TemplatePart::Level,
+                    "{time" => TemplatePart::Time,
+                    "{count" => TemplatePart::Count,
+                    _ => return None,
+                };
+                Some((part, tail))
+            });
+            match placeholder {
+                Some((part, tail)) => {
+                    if !literal.is_empty() {
+                        parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(part);
+                    rest = tail;
+                }
+                None => {
+                    literal.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(TemplatePart::Literal(literal));
+        }
+        parts
+    }
+}
+
+impl HttpWriter {
+    /// Create a writer that posts records to `url` as newline-delimited text, e.g., for a generic
+    /// webhook or log collector.
+    ///
+    /// The `Content-Type` is `text/plain; charset=utf-8` unless set with
+    /// [`HttpWriter::with_header`], e.g., to `application/x-ndjson` with a JSON layout.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self::with_format(url.into(), Format::Lines)
+    }
+
+    /// Create a writer that posts records to the Loki push API at `url`, e.g.,
+    /// `http://localhost:3100/loki/api/v1/push`, in a stream with the given labels.
+    pub fn loki<I, K, V>(url: impl Into<String>, labels: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let labels = labels
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect();
+        Self::with_format(
+            url.into(),
+            Format::Loki {
+                labels,
+                level_label: None,
+            },
+        )
+    }
+
+    /// Create a writer that posts records to a webhook at `url` with a JSON body rendered from
+    /// `template`, e.g., `{"text": "[{level}] {message}"}` for Slack.
+    ///
+    /// The placeholders are:
+    ///
+    /// - `{message}`: the formatted records of the request, separated by newlines.
+    /// - `{level}`: the most severe level of the records, e.g., `ERROR`.
+    /// - `{time}`: the time of the first record in RFC 3339 format, in UTC.
+    /// - `{count}`: the number of records.
+    ///
+    /// Values are escaped as the content of JSON strings, so placeholders belong inside quotes;
+    /// other text, including braces, is posted as is. Set
+    /// [`HttpWriter::with_max_batch_records`] to `1` to post one request per record.
+    pub fn webhook(url: impl Into<String>, template: impl AsRef<str>) -> Self {
+        let parts = TemplatePart::parse(template.as_ref());
+        Self::with_format(url.into(), Format::Template(parts))
+    }
+
+    fn with_format(url: String, format: Format) -> Self {
+        Self {
+            url,
+            format,
+            headers: vec![],
+            timeout: DEFAULT_TIMEOUT,
+            max_batch_records: DEFAULT_MAX_BATCH_RECORDS,
+            max_retries: DEFAULT_MAX_RETRIES,
+            #[cfg(any(feature = "gzip", feature = "zstd"))]
+            compression: None,
+            agent: None,
+        }
+    }
+
+    /// Adds a header to every request, e.g., `Authorization` or `X-Scope-OrgID`.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets the timeout of each request. Default to 10 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum number of records posted in one request. Default to 1000.
+    pub fn with_max_batch_records(mut self, max_batch_records: usize) -> Self {
+        self.max_batch_records = max_batch_records.max(1);
+        self
+    }
+
+    /// Sets how many times a failed request is retried. Default to 3.
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Compresses the request bodies with the given algorithm, and sets the `Content-Encoding`
+    /// header accordingly. Requests are not compressed by default.
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Splits the records of a Loki writer into one stream per level, with the level as the value
+    /// of the label `name`. No effect on other writers.
+    pub fn with_level_label(mut self, name: impl Into<String>) -> Self {
+        if let Format::Loki { level_label, .. } = &mut self.format {
+            *level_label = Some(name.into());
+        }
+        self
+    }
+
+    fn agent(&mut self) -> io::Result<&ureq::Agent> {
+        if self.agent.is_none() {
+            #[allow(unused_mut)]
+            let mut builder = ureq::AgentBuilder::new().timeout(self.timeout);
+            #[cfg(feature = "native-tls")]
+            {
+                let connector = native_tls::TlsConnector::new().map_err(io::Error::other)?;
+                builder = builder.tls_connector(std::sync::Arc::new(connector));
+            }
+            self.agent = Some(builder.build());
+        }
+        Ok(self.agent.as_ref().expect("agent is built"))
+    }
+
+    fn encode(&self, records: &[Vec<u8>]) -> io::Result<(Vec<u8>, &'static str)> {
+        match &self.format {
+            Format::Lines => {
+                let mut body = Vec::new();
+                for record in records {
+                    body.extend_from_slice(decode(record).2);
+                    body.push(b'\n');
+                }
+                Ok((body, "text/plain; charset=utf-8"))
+            }
+            Format::Loki {
+                labels,
+                level_label,
+            } => {
+                let mut streams = BTreeMap::<Option<Level>, Vec<[String; 2]>>::new();
+                for record in records {
+                    let (nanos, level, line) = decode(record);
+                    let stream = level.filter(|_| level_label.is_some());
+                    streams.entry(stream).or_default().push([
+                        nanos.to_string(),
+                        String::from_utf8_lossy(line).into_owned(),
+                    ]);
+                }
+                let streams = streams
+                    .into_iter()
+                    .map(|(level, values)| {
+                        let mut labels = labels.clone();
+                        if let (Some(name), Some(level)) = (level_label, level) {
+                            labels.insert(name.clone(), level.as_str().to_lowercase());
+                        }
+                        serde_json::json!({ "stream": labels, "values": values })
+                    })
+                    .collect::<Vec<_>>();
+                let body = serde_json::to_vec(&serde_json::json!({ "streams": streams }))?;
+                Ok((body, "application/json"))
+            }
+            Format::Template(parts) => {
+                let records = records.iter().map(|r| decode(r)).collect::<Vec<_>>();
+                let mut body = String::new();
+                for part in parts {
+                    match part {
+                        TemplatePart::Literal(literal) => body.push_str(literal),
+                        TemplatePart::Message => {
+                            let message = records
+                                .iter()
+                                .map(|(_, _, line)| String::from_utf8_lossy(line))
+                                .collect::<Vec<_>>()
+                                .join("\n");
+                            push_json_escaped(&mut body, &message)?;
+                        }
+                        TemplatePart::Level => {
+                            if let Some(level) = records.iter().filter_map(|r| r.1).min() {
+                                body.push_str(level.as_str());
+                            }
+                        }
+                        TemplatePart::Time => {
+                            let nanos = records.first().map_or(0, |r| r.0);
+                            let time = jiff::Timestamp::from_nanosecond(i128::from(nanos))
+                                .map_err(io::Error::other)?;
+                            body.push_str(&time.to_string());
+                        }
+                        TemplatePart::Count => body.push_str(&records.len().to_string()),
+                    }
+                }
+                Ok((body.into_bytes(), "application/json"))
+            }
+        }
+    }
+
+    fn post(&mut self, body: &[u8], content_type: &str) -> io::Result<()> {
+        let has_content_type = self
+            .headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("content-type"));
+        let agent = self.agent()?.clone();
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempts = 0;
+        loop {
+            let mut request = agent.post(&self.url);
+            if !has_content_type {
+                request = request.set("Content-Type", content_type);
+            }
+            #[cfg(any(feature = "gzip", feature = "zstd"))]
+            if let Some(compression) = self.compression {
+                request = request.set("Content-Encoding", compression.content_encoding());
+            }
+            for (name, value) in &self.headers {
+                request = request.set(name, value);
+            }
+
+            attempts += 1;
+            let err = match request.send_bytes(body) {
+                Ok(_) => return Ok(()),
+                Err(ureq::Error::Status(status, _)) if status == 429 || status >= 500 => {
+                    format!("HTTP status {status}")
+                }
+                Err(ureq::Error::Status(status, response)) => {
+                    let message = response.into_string().unwrap_or_default();
+                    return Err(io::Error::other(format!(
+                        "{} responded with HTTP status {status}: {message}",
+                        self.url
+                    )));
+                }
+                Err(err) => err.to_string(),
+            };
+            if attempts > self.max_retries {
+                return Err(io::Error::other(format!(
+                    "failed to post to {} after {attempts} attempts: {err}",
+                    self.url
+                )));
+            }
+            std::thread::sleep(backoff);
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+}
+
+/// Appends `value` escaped as the content of a JSON string.
+fn push_json_escaped(body: &mut String, value: &str) -> io::Result<()> {
+    let quoted = serde_json::to_string(value)?;
+    body.push_str(&quoted[1..quoted.len() - 1]);
+    Ok(())
+}
+
+/// Splits a record sent by [`Http`] into its time, level and formatted bytes.
+fn decode(record: &[u8]) -> (u64, Option<Level>, &[u8]) {
+    if record.len() < HEADER_LEN {
+        return (0, None, record);
+    }
+    let (header, line) = record.split_at(HEADER_LEN);
+    let nanos = u64::from_be_bytes(header[..8].try_into().expect("8 bytes"));
+    let level = match header[8] {
+        1 => Some(Level::Error),
+        2 => Some(Level::Warn),
+        3 => Some(Level::Info),
+        4 => Some(Level::Debug),
+        5 => Some(Level::Trace),
+        _ => None,
+    };
+    (nanos, level, line)
+}
+
+impl Writer for HttpWriter {
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.write_all_batch(&[buf.to_vec()])
+    }
+
+    fn write_all_batch(&mut self, bufs: &[Vec<u8>]) -> io::Result<()> {
+        for records in bufs.chunks(self.max_batch_records) {
+            let (body, content_type) = self.encode(records)?;
+            #[cfg(any(feature = "gzip", feature = "zstd"))]
+            let body = match self.compression {
+                Some(compression) => compression.compress(&body)?,
+                None => body,
+            };
+            self.post(&body, content_type)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+    use std::io::BufReader;
+    use std::io::Read;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    use super::*;
+    use crate::layout::CustomLayout;
+
+    /// Serves one request per status, and returns the request bodies.
+    fn serve(statuses: &'static [u16]) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/push", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut bodies = vec![];
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut len = 0;
+                let mut gzip = false;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end().to_ascii_lowercase();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.strip_prefix("content-length:") {
+                        len = value.trim().parse().unwrap();
+                    }
+                    if line == "content-encoding: gzip" {
+                        gzip = true;
+                    }
+                }
+                let mut body = vec![0; len];
+                reader.read_exact(&mut body).unwrap();
+                if gzip {
+                    body = decompress_gzip(&body);
+                }
+                bodies.push(String::from_utf8(body).unwrap());
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                )
+                .unwrap();
+            }
+            bodies
+        });
+        (url, handle)
+    }
+
+    #[cfg(feature = "gzip")]
+    fn decompress_gzip(body: &[u8]) -> Vec<u8> {
+        let mut decompressed = vec![];
+        flate2::read::GzDecoder::new(body)
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        decompressed
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    fn decompress_gzip(_: &[u8]) -> Vec<u8> {
+        unreachable!("gzip is disabled")
+    }
+
+    fn append(http: &Http, level: Level, message: &str) {
+        http.append(
+            &Record::builder()
+                .args(format_args!("{message}"))
+                .level(level)
+                .target("app")
+                .build(),
+            &[],
+        )
+        .unwrap();
+    }
+
+    fn message_layout() -> CustomLayout {
+        CustomLayout::new(|record, _| Ok(record.args().to_string().into_bytes()))
+    }
+
+    #[test]
+    fn test_lines_with_retry() {
+        let (url, server) = serve(&[503, 204]);
+        let (non_blocking, guard) = non_blocking(HttpWriter::new(url))
+            .buffer_size(1024)
+            .flush_interval(Duration::from_secs(3600))
+            .finish();
+        let http = Http::new(non_blocking).with_layout(message_layout());
+        append(&http, Level::Info, "first");
+        append(&http, Level::Info, "second");
+        http.flush();
+        drop(http);
+        drop(guard);
+
+        // the batch is posted again after the failure
+        assert_eq!(
+            server.join().unwrap(),
+            ["first\nsecond\n", "first\nsecond\n"]
+        );
+    }
+
+    #[test]
+    fn test_loki_streams() {
+        let (url, server) = serve(&[204]);
+        let writer = HttpWriter::loki(url, [("service", "app")]).with_level_label("level");
+        let (non_blocking, guard) = non_blocking(writer)
+            .buffer_size(1024)
+            .flush_interval(Duration::from_secs(3600))
+            .finish();
+        let http = Http::new(non_blocking).with_layout(message_layout());
+        append(&http, Level::Info, "started");
+        append(&http, Level::Error, "failed");
+        http.flush();
+        drop(http);
+        drop(guard);
+
+        let body = server.join().unwrap().remove(0);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let streams = body["streams"].as_array().unwrap();
+        assert_eq!(streams.len(), 2);
+        assert_eq!(streams[0]["stream"]["service"], "app");
+        assert_eq!(streams[0]["stream"]["level"], "error");
+        assert_eq!(streams[0]["values"][0][1], "failed");
+        assert_eq!(streams[1]["stream"]["level"], "info");
+        assert_eq!(streams[1]["values"][0][1], "started");
+        let nanos: u64 = streams[1]["values"][0][0]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(nanos > 0);
+    }
+
+    #[test]
+    fn test_webhook_template() {
+        let (url, server) = serve(&[204, 204]);
+        let writer = HttpWriter::webhook(url, r#"{"text": "{count} x {level}: {message} {x}"}"#)
+            .with_max_batch_records(2);
+        let (non_blocking, guard) = non_blocking(writer)
+            .buffer_size(1024)
+            .flush_interval(Duration::from_secs(3600))
+            .finish();
+        let http = Http::new(non_blocking).with_layout(message_layout());
+        append(&http, Level::Warn, "disk \"full\"");
+        append(&http, Level::Error, "failed");
+        append(&http, Level::Info, "retrying");
+        http.flush();
+        drop(http);
+        drop(guard);
+
+        let bodies = server.join().unwrap();
+        assert_eq!(
+            bodies,
+            [
+                r#"{"text": "2 x ERROR: disk \"full\"\nfailed {x}"}"#,
+                r#"{"text": "1 x INFO: retrying {x}"}"#,
+            ]
+        );
+        for body in bodies {
+            serde_json::from_str::<serde_json::Value>(&body).unwrap();
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_compression() {
+        let (url, server) = serve(&[204]);
+        let writer = HttpWriter::new(url).with_compression(Compression::Gzip);
+        let (non_blocking, guard) = non_blocking(writer)
+            .buffer_size(1024)
+            .flush_interval(Duration::from_secs(3600))
+            .finish();
+        let http = Http::new(non_blocking).with_layout(message_layout());
+        append(&http, Level::Info, "compressed");
+        http.flush();
+        drop(http);
+        drop(guard);
+
+        assert_eq!(server.join().unwrap(), ["compressed\n"]);
+    }
+}
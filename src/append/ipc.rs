@@ -0,0 +1,266 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Appender for shipping log records to a companion agent process over a Unix socket.
+//!
+//! The application only formats records and hands them to a local agent, which owns the
+//! expensive sinks such as OTLP exporters or files. This isolates the application from the I/O
+//! of those sinks. See the `ipc_agent` example for an agent built with this crate.
+//!
+//! # Protocol
+//!
+//! The appender connects to the agent's Unix stream socket and sends one frame per record. A
+//! frame is the length of the payload in bytes as a big-endian `u32`, followed by the payload:
+//! the record formatted as a JSON object by [`JsonLayout`]. Agents can read frames with
+//! [`read_frame`], and parse and replay records with [`RecordOwned`].
+//!
+//! [`RecordOwned`]: crate::record::RecordOwned
+//!
+//! # Examples
+//!
+//!```rust, no_run
+//! use logforth::append::ipc;
+//! use logforth::append::ipc::Ipc;
+//! use logforth::append::ipc::IpcWriter;
+//!
+//! let ipc_writer = IpcWriter::new("/tmp/logforth-agent.sock");
+//! let (non_blocking, _guard) = ipc::non_blocking(ipc_writer).finish();
+//!
+//! logforth::builder()
+//!     .dispatch(|d| {
+//!         d.filter(log::LevelFilter::Trace)
+//!             .append(Ipc::new(non_blocking))
+//!     })
+//!     .apply();
+//!
+//! log::info!("This log will be shipped to the agent.");
+//! ```
+
+use std::io;
+use std::io::IoSlice;
+use std::io::Read;
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::path::PathBuf;
+
+use log::Record;
+
+use crate::layout::JsonFraming;
+use crate::layout::JsonLayout;
+use crate::non_blocking::NonBlocking;
+use crate::non_blocking::NonBlockingBuilder;
+use crate::Append;
+use crate::Diagnostic;
+
+/// The maximum payload size of a frame accepted by [`read_frame`].
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// An appender that ships log records to an agent process.
+///
+/// Avoid [`NonBlockingBuilder::max_record_size`] with this appender: truncated records would
+/// break the framing.
+#[derive(Debug)]
+pub struct Ipc {
+    layout: JsonLayout,
+    writer: NonBlocking<IpcWriter>,
+}
+
+impl Ipc {
+    /// Creates a new [`Ipc`] appender.
+    pub fn new(writer: NonBlocking<IpcWriter>) -> Self {
+        Self {
+            layout: JsonLayout::default().framing(JsonFraming::LengthPrefixed),
+            writer,
+        }
+    }
+
+    /// Sets the JSON layout used to format log records, e.g., to change the timezone.
+    ///
+    /// The framing of the layout is always [`JsonFraming::LengthPrefixed`].
+    pub fn with_layout(mut self, layout: JsonLayout) -> Self {
+        self.layout = layout.framing(JsonFraming::LengthPrefixed);
+        self
+    }
+}
+
+impl Append for Ipc {
+    fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        let frame = self.layout.format(record, diagnostics)?;
+        self.writer.send(frame, record.level())?;
+        Ok(())
+    }
+
+    fn flush(&self) {
+        if let Err(err) = self.writer.flush() {
+            eprintln!("failed to flush IPC appender: {err:?}");
+        }
+    }
+}
+
+/// Create a non-blocking builder for IPC writers.
+pub fn non_blocking(writer: IpcWriter) -> NonBlockingBuilder<IpcWriter> {
+    NonBlockingBuilder::new("logforth-ipc", writer)
+}
+
+/// A writer that sends frames to the agent listening on a Unix socket.
+///
+/// The writer connects lazily, and reconnects on the next write when the connection breaks, e.g.,
+/// when the agent restarts. Records written while the agent is unavailable are lost, and the
+/// errors are reported to stderr by the non-blocking worker.
+#[derive(Debug)]
+pub struct IpcWriter {
+    path: PathBuf,
+    stream: Option<UnixStream>,
+}
+
+impl IpcWriter {
+    /// Creates a writer for the agent listening on the socket at `path`.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            stream: None,
+        }
+    }
+
+    fn with_stream<T>(
+        &mut self,
+        mut f: impl FnMut(&mut UnixStream) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => self.stream.insert(UnixStream::connect(&self.path)?),
+        };
+        let result = loop {
+            match f(stream) {
+                // nothing was written, so the frame can go on over the same connection
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+        if result.is_err() {
+            // a frame may have been partially written; start over on a new connection
+            self.stream = None;
+        }
+        result
+    }
+}
+
+impl Write for IpcWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.with_stream(|stream| stream.write(buf))
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.with_stream(|stream| stream.write_vectored(bufs))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.stream {
+            Some(stream) => stream.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Reads the payload of the next frame, or returns `None` at the end of the stream.
+///
+/// Returns an error if the payload is larger than [`MAX_FRAME_SIZE`], or if the stream ends in
+/// the middle of a frame.
+pub fn read_frame(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {len} bytes exceeds the maximum frame size"),
+        ));
+    }
+
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixListener;
+
+    use super::*;
+
+    #[test]
+    fn test_frames_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let (non_blocking, guard) = non_blocking(IpcWriter::new(&path)).finish();
+        let ipc = Ipc::new(non_blocking);
+        for message in ["first", "second"] {
+            ipc.append(
+                &Record::builder()
+                    .args(format_args!("{message}"))
+                    .level(log::Level::Warn)
+                    .target("app")
+                    .build(),
+                &[],
+            )
+            .unwrap();
+        }
+        drop(ipc);
+        drop(guard);
+
+        let (mut stream, _) = listener.accept().unwrap();
+        for message in ["first", "second"] {
+            let payload = read_frame(&mut stream).unwrap().unwrap();
+            let record: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+            assert_eq!(record["message"], message);
+            assert_eq!(record["level"], "WARN");
+        }
+        assert!(read_frame(&mut stream).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_retry_interrupted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let mut writer = IpcWriter::new(&path);
+        writer.write_all(b"fr").unwrap();
+        let mut interrupted = false;
+        let written = writer
+            .with_stream(|stream| {
+                if !std::mem::replace(&mut interrupted, true) {
+                    return Err(io::ErrorKind::Interrupted.into());
+                }
+                stream.write(b"ame")
+            })
+            .unwrap();
+        assert_eq!(written, 3);
+        drop(writer);
+
+        // the frame went on over the same connection
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut received = String::new();
+        stream.read_to_string(&mut received).unwrap();
+        assert_eq!(received, "frame");
+    }
+}
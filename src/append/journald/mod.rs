@@ -12,15 +12,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Appender for writing log records to systemd journald.
+
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::io;
 use std::io::Write;
 use std::os::unix::net::UnixDatagram;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 
 use log::Level;
 use log::Record;
 
+use crate::backtrace::is_backtrace_key;
+use crate::backtrace::BACKTRACE_FIELD;
 use crate::diagnostic::Visitor;
+use crate::secret::secret_key;
+use crate::secret::MASK;
+use crate::startup;
+use crate::template::is_message_template_key;
+use crate::template::MESSAGE_TEMPLATE_FIELD;
 use crate::Append;
 use crate::Diagnostic;
 
@@ -30,6 +42,29 @@ mod memfd;
 
 const JOURNALD_PATH: &str = "/run/systemd/journal/socket";
 
+/// Buffers grown beyond this capacity by a large record are not kept for reuse.
+const MAX_REUSED_BUFFER_CAPACITY: usize = 64 * 1024;
+
+thread_local! {
+    static BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Runs `f` with a cleared per-thread buffer, falling back to a fresh buffer if the per-thread
+/// one is in use (e.g., a record logged while sending another).
+fn with_buffer<R>(f: impl FnOnce(&mut Vec<u8>) -> R) -> R {
+    BUFFER.with(|buffer| match buffer.try_borrow_mut() {
+        Ok(mut buffer) => {
+            buffer.clear();
+            let result = f(&mut buffer);
+            if buffer.capacity() > MAX_REUSED_BUFFER_CAPACITY {
+                *buffer = Vec::new();
+            }
+            result
+        }
+        Err(_) => f(&mut Vec::new()),
+    })
+}
+
 fn current_exe_identifier() -> Option<String> {
     let executable = std::env::current_exe().ok()?;
     Some(executable.file_name()?.to_string_lossy().into_owned())
@@ -63,7 +98,7 @@ fn current_exe_identifier() -> Option<String> {
 ///
 /// ## Log levels and Priorities
 ///
-/// [`Level`] gets mapped to journal (syslog) priorities as follows:
+/// By default, [`Level`] gets mapped to journal (syslog) priorities as follows:
 ///
 /// - [`Level::Error`] → `3` (err)
 /// - [`Level::Warn`] → `4` (warning)
@@ -71,7 +106,8 @@ fn current_exe_identifier() -> Option<String> {
 /// - [`Level::Debug`] → `6` (info)
 /// - [`Level::Trace`] → `7` (debug)
 ///
-/// Higher priorities (crit, alert, and emerg) are not used.
+/// Higher priorities (crit, alert, and emerg) are not used unless a custom mapping is set with
+/// [`Journald::with_priority_mapper`].
 ///
 /// ## Custom fields and structured record fields
 ///
@@ -108,6 +144,55 @@ pub struct Journald {
     extra_fields: Vec<u8>,
     /// The syslog identifier.
     syslog_identifier: String,
+    /// The ID of the startup report, to amend it with the syslog identifier.
+    startup_report: u64,
+    /// The number of payloads that exceeded the datagram size limit.
+    large_payloads: AtomicU64,
+    /// Maps record levels to journal priorities.
+    priority_mapper: fn(Level) -> Priority,
+    /// Whether to write the captured backtrace of records.
+    backtrace: bool,
+}
+
+/// A journal (syslog) priority, written to the `PRIORITY` field of journal entries.
+///
+/// See [`Journald::with_priority_mapper`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// System is unusable (`0`).
+    Emergency = 0,
+    /// Action must be taken immediately (`1`).
+    Alert = 1,
+    /// Critical conditions (`2`).
+    Critical = 2,
+    /// Error conditions (`3`).
+    Error = 3,
+    /// Warning conditions (`4`).
+    Warning = 4,
+    /// Normal but significant conditions (`5`).
+    Notice = 5,
+    /// Informational messages (`6`).
+    Informational = 6,
+    /// Debug-level messages (`7`).
+    Debug = 7,
+}
+
+impl Priority {
+    /// Returns the default priority of `level`, as documented in [`Journald`].
+    pub fn from_level(level: Level) -> Self {
+        match level {
+            Level::Error => Priority::Error,
+            Level::Warn => Priority::Warning,
+            Level::Info => Priority::Notice,
+            Level::Debug => Priority::Informational,
+            Level::Trace => Priority::Debug,
+        }
+    }
+
+    /// Returns the numeric value written to the `PRIORITY` field.
+    fn as_bytes(self) -> &'static [u8] {
+        &b"01234567"[self as usize..self as usize + 1]
+    }
 }
 
 impl Journald {
@@ -116,14 +201,26 @@ impl Journald {
     /// Fails if the journald socket couldn't be opened.
     pub fn new() -> io::Result<Self> {
         let socket = UnixDatagram::unbound()?;
-        let sub = Self {
+        let syslog_identifier = current_exe_identifier().unwrap_or_default();
+        let mut sub = Self {
             socket,
             extra_fields: Vec::new(),
-            syslog_identifier: current_exe_identifier().unwrap_or_default(),
+            syslog_identifier,
+            startup_report: 0,
+            large_payloads: AtomicU64::new(0),
+            priority_mapper: Priority::from_level,
+            backtrace: false,
         };
         // Check that we can talk to journald, by sending empty payload which journald discards.
         // However, if the socket didn't exist or if none listened we'd get an error here.
         sub.send_payload(&[])?;
+        sub.startup_report = startup::report(
+            "journald appender",
+            vec![
+                ("socket", JOURNALD_PATH.to_string()),
+                ("syslog_identifier", sub.syslog_identifier.clone()),
+            ],
+        );
         Ok(sub)
     }
 
@@ -196,20 +293,66 @@ impl Journald {
     ///
     /// Defaults to the file name of the executable of the current process, if any.
     pub fn with_syslog_identifier(mut self, identifier: String) -> Self {
+        startup::amend(self.startup_report, "syslog_identifier", identifier.clone());
         self.syslog_identifier = identifier;
         self
     }
 
+    /// Sets how record levels map to journal priorities.
+    ///
+    /// Defaults to [`Priority::from_level`], which never uses the priorities above
+    /// [`Priority::Error`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use log::Level;
+    /// use logforth::append::journald::Priority;
+    /// use logforth::append::Journald;
+    ///
+    /// // page the on-call for errors
+    /// let journald = Journald::new()
+    ///     .unwrap()
+    ///     .with_priority_mapper(|level| match level {
+    ///         Level::Error => Priority::Critical,
+    ///         level => Priority::from_level(level),
+    ///     });
+    /// ```
+    pub fn with_priority_mapper(mut self, mapper: fn(Level) -> Priority) -> Self {
+        self.priority_mapper = mapper;
+        self
+    }
+
+    /// Sets whether to write the backtrace captured for the record as the `BACKTRACE` field.
+    /// Default to `false`.
+    ///
+    /// See [`Builder::backtrace_level`](crate::Builder::backtrace_level) to capture backtraces.
+    pub fn with_backtrace(mut self, backtrace: bool) -> Self {
+        self.backtrace = backtrace;
+        self
+    }
+
     /// Returns the syslog identifier in use.
     pub fn syslog_identifier(&self) -> &str {
         &self.syslog_identifier
     }
 
+    /// Returns the number of records that exceeded the datagram size limit, and were thus sent
+    /// via the fallback for large payloads (`EMSGSIZE`).
+    ///
+    /// Journald reads one entry per datagram, so each record costs one `sendmsg` call, plus a
+    /// memfd round trip when it is too large for a datagram. A growing count indicates that
+    /// records are unexpectedly large.
+    pub fn large_payload_count(&self) -> u64 {
+        self.large_payloads.load(Ordering::Relaxed)
+    }
+
     fn send_payload(&self, payload: &[u8]) -> io::Result<usize> {
         self.socket
             .send_to(payload, JOURNALD_PATH)
             .or_else(|error| {
                 if Some(libc::EMSGSIZE) == error.raw_os_error() {
+                    self.large_payloads.fetch_add(1, Ordering::Relaxed);
                     self.send_large_payload(payload)
                 } else {
                     Err(error)
@@ -232,7 +375,10 @@ impl Journald {
     }
 }
 
-struct WriteKeyValues<'a>(&'a mut Vec<u8>);
+struct WriteKeyValues<'a> {
+    buffer: &'a mut Vec<u8>,
+    backtrace: bool,
+}
 
 impl<'kvs> log::kv::VisitSource<'kvs> for WriteKeyValues<'_> {
     fn visit_pair(
@@ -240,8 +386,24 @@ impl<'kvs> log::kv::VisitSource<'kvs> for WriteKeyValues<'_> {
         key: log::kv::Key<'kvs>,
         value: log::kv::Value<'kvs>,
     ) -> Result<(), log::kv::Error> {
-        let key = key.as_str();
-        field::put_field_length_encoded(self.0, field::FieldName::WriteEscaped(key), value);
+        let key = match key.as_str() {
+            key if is_backtrace_key(key) && !self.backtrace => return Ok(()),
+            key if is_backtrace_key(key) => BACKTRACE_FIELD,
+            key if is_message_template_key(key) => MESSAGE_TEMPLATE_FIELD,
+            key => key,
+        };
+        match secret_key(key) {
+            Some(key) => field::put_field_length_encoded(
+                self.buffer,
+                field::FieldName::WriteEscaped(key),
+                MASK.as_bytes(),
+            ),
+            None => field::put_field_length_encoded(
+                self.buffer,
+                field::FieldName::WriteEscaped(key),
+                value,
+            ),
+        }
         Ok(())
     }
 }
@@ -254,9 +416,17 @@ impl Visitor for WriteKeyValues<'_> {
     {
         let key = key.into();
         let value = value.into();
-        let key = key.as_ref();
-        let value = value.as_bytes();
-        field::put_field_length_encoded(self.0, field::FieldName::WriteEscaped(key), value);
+        let key = match key.as_ref() {
+            key if is_backtrace_key(key) && !self.backtrace => return,
+            key if is_backtrace_key(key) => BACKTRACE_FIELD,
+            key if is_message_template_key(key) => MESSAGE_TEMPLATE_FIELD,
+            key => key,
+        };
+        let (key, value) = match secret_key(key) {
+            Some(key) => (key, MASK.as_bytes()),
+            None => (key, value.as_bytes()),
+        };
+        field::put_field_length_encoded(self.buffer, field::FieldName::WriteEscaped(key), value);
     }
 }
 
@@ -264,63 +434,83 @@ impl Append for Journald {
     /// Extract all fields (standard and custom) from `record`, append all `extra_fields` given
     /// to this appender, and send the result to journald.
     fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
-        use field::*;
+        with_buffer(|buffer| self.append_to_buffer(buffer, record, diagnostics))
+    }
+}
 
-        let mut buffer = vec![];
+impl Journald {
+    fn append_to_buffer(
+        &self,
+        buffer: &mut Vec<u8>,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+    ) -> anyhow::Result<()> {
+        use field::*;
 
         // Write standard fields. Numeric fields can't contain new lines so we
         // write them directly, everything else goes through the put functions
         // for property mangling and length-encoding
-        let priority = match record.level() {
-            Level::Error => b"3",
-            Level::Warn => b"4",
-            Level::Info => b"5",
-            Level::Debug => b"6",
-            Level::Trace => b"7",
-        };
-
-        put_field_bytes(&mut buffer, FieldName::WellFormed("PRIORITY"), priority);
-        put_field_length_encoded(&mut buffer, FieldName::WellFormed("MESSAGE"), record.args());
+        let priority = (self.priority_mapper)(record.level());
+        put_field_bytes(
+            buffer,
+            FieldName::WellFormed("PRIORITY"),
+            priority.as_bytes(),
+        );
+        put_field_length_encoded(buffer, FieldName::WellFormed("MESSAGE"), record.args());
         // Syslog compatibility fields
-        writeln!(&mut buffer, "SYSLOG_PID={}", std::process::id())?;
+        writeln!(buffer, "SYSLOG_PID={}", std::process::id())?;
         if !self.syslog_identifier.is_empty() {
             put_field_bytes(
-                &mut buffer,
+                buffer,
                 FieldName::WellFormed("SYSLOG_IDENTIFIER"),
                 self.syslog_identifier.as_bytes(),
             );
         }
         if let Some(file) = record.file() {
-            put_field_bytes(
-                &mut buffer,
-                FieldName::WellFormed("CODE_FILE"),
-                file.as_bytes(),
-            );
+            put_field_bytes(buffer, FieldName::WellFormed("CODE_FILE"), file.as_bytes());
         }
         if let Some(module) = record.module_path() {
             put_field_bytes(
-                &mut buffer,
+                buffer,
                 FieldName::WellFormed("CODE_MODULE"),
                 module.as_bytes(),
             );
         }
         if let Some(line) = record.line() {
-            writeln!(&mut buffer, "CODE_LINE={}", line)?;
+            writeln!(buffer, "CODE_LINE={}", line)?;
         }
         put_field_bytes(
-            &mut buffer,
+            buffer,
             FieldName::WellFormed("TARGET"),
             record.target().as_bytes(),
         );
         // Put all structured values of the record
-        let mut visitor = WriteKeyValues(&mut buffer);
+        let mut visitor = WriteKeyValues {
+            buffer: &mut *buffer,
+            backtrace: self.backtrace,
+        };
         record.key_values().visit(&mut visitor)?;
         for d in diagnostics {
             d.visit(&mut visitor);
         }
         // Put all extra fields of the appender
         buffer.extend_from_slice(&self.extra_fields);
-        self.send_payload(&buffer)?;
+        self.send_payload(buffer)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    use super::Priority;
+
+    #[test]
+    fn test_priority_bytes() {
+        assert_eq!(Priority::Emergency.as_bytes(), b"0");
+        assert_eq!(Priority::Critical.as_bytes(), b"2");
+        assert_eq!(Priority::from_level(Level::Warn).as_bytes(), b"4");
+        assert_eq!(Priority::from_level(Level::Trace).as_bytes(), b"7");
+    }
+}
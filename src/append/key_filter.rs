@@ -0,0 +1,232 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeSet;
+
+use log::kv::Error;
+use log::kv::Key;
+use log::kv::Source;
+use log::kv::Value;
+use log::kv::VisitSource;
+use log::Record;
+
+use crate::append::repeats::DiagnosticSnapshot;
+use crate::append::Append;
+use crate::backtrace::is_backtrace_key;
+use crate::template::is_message_template_key;
+use crate::Diagnostic;
+use crate::Layout;
+
+/// An appender that forwards only some key-values to the wrapped appender.
+///
+/// Keys are either allowed with [`KeyFilter::allow`], stripping every other key, or denied with
+/// [`KeyFilter::deny`]. For example, strip `user_email` before exporting to a remote backend
+/// while keeping it in a local file. The reserved keys of this crate, such as the
+/// [message template](crate::template) and the [backtrace](crate::backtrace), are always forwarded
+/// by an allowlist; deny them explicitly if needed.
+///
+/// Both the record's key-values and the diagnostics are filtered. The diagnostics that pass the
+/// filter are passed to the wrapped appender as diagnostics.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::append;
+/// use logforth::append::KeyFilter;
+///
+/// let stdout = KeyFilter::deny(append::Stdout::default(), ["user_email"]);
+///
+/// logforth::builder().dispatch(|d| d.append(stdout)).apply();
+///
+/// log::info!(user_id = 42, user_email = "user@example.com"; "user logged in");
+/// ```
+#[derive(Debug)]
+pub struct KeyFilter<A> {
+    append: A,
+    keys: BTreeSet<String>,
+    allow: bool,
+}
+
+impl<A: Append> KeyFilter<A> {
+    /// Creates a new [`KeyFilter`] forwarding only the given keys to the wrapped appender.
+    pub fn allow<K: Into<String>>(append: A, keys: impl IntoIterator<Item = K>) -> Self {
+        Self {
+            append,
+            keys: keys.into_iter().map(Into::into).collect(),
+            allow: true,
+        }
+    }
+
+    /// Creates a new [`KeyFilter`] stripping the given keys before the wrapped appender.
+    pub fn deny<K: Into<String>>(append: A, keys: impl IntoIterator<Item = K>) -> Self {
+        Self {
+            append,
+            keys: keys.into_iter().map(Into::into).collect(),
+            allow: false,
+        }
+    }
+
+    fn forwards(&self, key: &str) -> bool {
+        if self.allow {
+            self.keys.contains(key) || is_message_template_key(key) || is_backtrace_key(key)
+        } else {
+            !self.keys.contains(key)
+        }
+    }
+}
+
+impl<A: Append> Append for KeyFilter<A> {
+    fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        let kvs = FilteredSource {
+            source: record.key_values(),
+            filter: self,
+        };
+        let record = record.to_builder().key_values(&kvs).build();
+        let diagnostics = DiagnosticSnapshot::capture(diagnostics)
+            .retain(|key| self.forwards(key))
+            .diagnostics();
+        self.append
+            .append_with_layout(&record, &diagnostics, layout)
+    }
+
+    fn flush(&self) {
+        self.flush_with_layout(None)
+    }
+
+    fn flush_with_layout(&self, layout: Option<&Layout>) {
+        self.append.flush_with_layout(layout);
+    }
+}
+
+struct FilteredSource<'a, A> {
+    source: &'a dyn Source,
+    filter: &'a KeyFilter<A>,
+}
+
+impl<A: Append> Source for FilteredSource<'_, A> {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), Error> {
+        self.source.visit(&mut FilteredVisitor {
+            visitor,
+            filter: self.filter,
+        })
+    }
+}
+
+struct FilteredVisitor<'a, 'kvs, A> {
+    visitor: &'a mut dyn VisitSource<'kvs>,
+    filter: &'a KeyFilter<A>,
+}
+
+impl<'kvs, A: Append> VisitSource<'kvs> for FilteredVisitor<'_, 'kvs, A> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+        if self.filter.forwards(key.as_str()) {
+            self.visitor.visit_pair(key, value)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::diagnostic::ThreadLocalDiagnostic;
+    use crate::diagnostic::Visitor;
+    use crate::template::MESSAGE_TEMPLATE_KEY;
+
+    #[derive(Debug, Default)]
+    struct Keys(Mutex<Vec<String>>);
+
+    impl Append for &'static Keys {
+        fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+            struct Collect<'a>(&'a mut Vec<String>);
+            impl<'kvs> VisitSource<'kvs> for Collect<'_> {
+                fn visit_pair(&mut self, key: Key<'kvs>, _: Value<'kvs>) -> Result<(), Error> {
+                    self.0.push(key.as_str().to_string());
+                    Ok(())
+                }
+            }
+            impl Visitor for Collect<'_> {
+                fn visit<'k, 'v, K, V>(&mut self, key: K, _: V)
+                where
+                    K: Into<Cow<'k, str>>,
+                    V: Into<Cow<'v, str>>,
+                {
+                    self.0.push(format!("diagnostic {}", key.into()));
+                }
+            }
+            let mut keys = self.0.lock().unwrap();
+            record.key_values().visit(&mut Collect(&mut keys))?;
+            for d in diagnostics {
+                d.visit(&mut Collect(&mut keys));
+            }
+            Ok(())
+        }
+    }
+
+    fn forwarded(filter: impl Fn(&'static Keys) -> KeyFilter<&'static Keys>) -> Vec<String> {
+        let keys: &'static Keys = Box::leak(Box::default());
+        let kvs = [
+            ("user_id", "42"),
+            ("user_email", "user@example.com"),
+            (MESSAGE_TEMPLATE_KEY, "user logged in"),
+        ];
+        let record = Record::builder()
+            .args(format_args!("user logged in"))
+            .key_values(&kvs)
+            .build();
+        ThreadLocalDiagnostic::insert("request_id", "abc");
+        filter(keys)
+            .append(&record, &[ThreadLocalDiagnostic::default().into()])
+            .unwrap();
+        ThreadLocalDiagnostic::remove("request_id");
+        keys.0.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn test_key_filter() {
+        assert_eq!(
+            forwarded(|keys| KeyFilter::deny(keys, ["user_email"])),
+            [
+                "user_id",
+                "logforth.message_template",
+                "diagnostic request_id"
+            ]
+        );
+        assert_eq!(
+            forwarded(|keys| KeyFilter::allow(keys, ["user_id"])),
+            ["user_id", "logforth.message_template"]
+        );
+        assert_eq!(
+            forwarded(|keys| KeyFilter::allow(keys, ["request_id"])),
+            ["logforth.message_template", "diagnostic request_id"]
+        );
+        assert_eq!(
+            forwarded(|keys| KeyFilter::deny(keys, ["request_id"])),
+            ["user_id", "user_email", "logforth.message_template"]
+        );
+    }
+}
@@ -0,0 +1,265 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use log::Record;
+
+use crate::append::Append;
+use crate::Diagnostic;
+use crate::Layout;
+
+// bucket `i` counts latencies in `[2^(i-1), 2^i)` nanoseconds, and bucket 0 zero latencies
+const BUCKETS: usize = 65;
+
+/// An appender that measures the latency of the wrapped appender.
+///
+/// Every call to [`Append::append`] of the wrapped appender is timed into a histogram with
+/// power-of-two buckets, so percentiles are accurate to within a factor of two, which is enough
+/// to compare sinks, e.g., a file against journald or OTLP, in the real environment. Recording
+/// is lock-free. Use [`Latency::handle`] to read the histogram.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::append;
+/// use logforth::append::Latency;
+///
+/// let latency = Latency::new(append::Stderr::default());
+/// let handle = latency.handle();
+///
+/// logforth::builder().dispatch(|d| d.append(latency)).apply();
+///
+/// log::error!("Hello latency!");
+/// let snapshot = handle.snapshot();
+/// eprintln!(
+///     "{} appends, p99 {:?}",
+///     snapshot.count(),
+///     snapshot.percentile(0.99)
+/// );
+/// ```
+#[derive(Debug)]
+pub struct Latency<A> {
+    append: A,
+    histogram: Arc<Histogram>,
+}
+
+#[derive(Debug)]
+struct Histogram {
+    buckets: [AtomicU64; BUCKETS],
+    errors: AtomicU64,
+    sum_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl Histogram {
+    fn record(&self, elapsed: Duration) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        let bucket = (u64::BITS - nanos.leading_zeros()) as usize;
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+}
+
+impl<A: Append> Latency<A> {
+    /// Creates a new [`Latency`] that wraps the given appender.
+    pub fn new(append: A) -> Self {
+        Self {
+            append,
+            histogram: Arc::new(Histogram {
+                buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+                errors: AtomicU64::new(0),
+                sum_nanos: AtomicU64::new(0),
+                max_nanos: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Returns a handle to read the histogram of the appender.
+    pub fn handle(&self) -> LatencyHandle {
+        LatencyHandle {
+            histogram: self.histogram.clone(),
+        }
+    }
+}
+
+impl<A: Append> Append for Latency<A> {
+    fn name(&self) -> &str {
+        self.append.name()
+    }
+
+    fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        let start = Instant::now();
+        let result = self.append.append_with_layout(record, diagnostics, layout);
+        self.histogram.record(start.elapsed());
+        if result.is_err() {
+            self.histogram.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    fn flush(&self) {
+        self.flush_with_layout(None)
+    }
+
+    fn flush_with_layout(&self, layout: Option<&Layout>) {
+        self.append.flush_with_layout(layout);
+    }
+}
+
+/// A handle to read the histogram of a [`Latency`] appender.
+#[derive(Debug, Clone)]
+pub struct LatencyHandle {
+    histogram: Arc<Histogram>,
+}
+
+impl LatencyHandle {
+    /// Returns a snapshot of the histogram.
+    pub fn snapshot(&self) -> LatencySnapshot {
+        let histogram = &self.histogram;
+        LatencySnapshot {
+            buckets: std::array::from_fn(|i| histogram.buckets[i].load(Ordering::Relaxed)),
+            errors: histogram.errors.load(Ordering::Relaxed),
+            sum_nanos: histogram.sum_nanos.load(Ordering::Relaxed),
+            max_nanos: histogram.max_nanos.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Resets the histogram.
+    pub fn reset(&self) {
+        let histogram = &self.histogram;
+        for bucket in &histogram.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        histogram.errors.store(0, Ordering::Relaxed);
+        histogram.sum_nanos.store(0, Ordering::Relaxed);
+        histogram.max_nanos.store(0, Ordering::Relaxed);
+    }
+}
+
+/// A snapshot of the histogram of a [`Latency`] appender.
+#[derive(Debug, Clone)]
+pub struct LatencySnapshot {
+    buckets: [u64; BUCKETS],
+    errors: u64,
+    sum_nanos: u64,
+    max_nanos: u64,
+}
+
+impl LatencySnapshot {
+    /// Returns the number of appends measured.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Returns the number of appends that returned an error.
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// Returns the mean latency, or zero if no append was measured.
+    pub fn mean(&self) -> Duration {
+        match self.count() {
+            0 => Duration::ZERO,
+            count => Duration::from_nanos(self.sum_nanos / count),
+        }
+    }
+
+    /// Returns the maximum latency.
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max_nanos)
+    }
+
+    /// Returns an upper bound of the latency at the given quantile, e.g., `0.99` for the 99th
+    /// percentile, within a factor of two of the actual latency. Returns zero if no append was
+    /// measured.
+    pub fn percentile(&self, quantile: f64) -> Duration {
+        let count = self.count();
+        if count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            seen += bucket;
+            if seen >= rank {
+                let upper = match i {
+                    0 => 0,
+                    64 => u64::MAX,
+                    i => (1 << i) - 1,
+                };
+                return Duration::from_nanos(upper.min(self.max_nanos));
+            }
+        }
+        self.max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append::Null;
+
+    #[test]
+    fn test_latency_percentiles() {
+        let latency = Latency::new(Null::default());
+        let handle = latency.handle();
+        assert_eq!(handle.snapshot().percentile(0.5), Duration::ZERO);
+
+        let histogram = &latency.histogram;
+        for _ in 0..90 {
+            histogram.record(Duration::from_nanos(100));
+        }
+        for _ in 0..10 {
+            histogram.record(Duration::from_micros(100));
+        }
+        let snapshot = handle.snapshot();
+        assert_eq!(snapshot.count(), 100);
+        assert_eq!(snapshot.max(), Duration::from_micros(100));
+        assert_eq!(snapshot.mean(), Duration::from_nanos(10_090));
+        // 100ns falls in [64, 128), 100us is capped by the maximum
+        assert_eq!(snapshot.percentile(0.5), Duration::from_nanos(127));
+        assert_eq!(snapshot.percentile(0.9), Duration::from_nanos(127));
+        assert_eq!(snapshot.percentile(0.99), Duration::from_micros(100));
+
+        latency
+            .append(
+                &Record::builder()
+                    .args(format_args!("Hello"))
+                    .level(log::Level::Info)
+                    .build(),
+                &[],
+            )
+            .unwrap();
+        assert_eq!(handle.snapshot().count(), 101);
+        assert_eq!(handle.snapshot().errors(), 0);
+
+        handle.reset();
+        assert_eq!(handle.snapshot().count(), 0);
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use jiff::Timestamp;
+use log::Level;
+use log::Record;
+
+use crate::append::Append;
+use crate::clock;
+use crate::layout::TextLayout;
+use crate::Diagnostic;
+use crate::Layout;
+
+/// An appender that keeps the last records in memory, e.g., to attach recent logs to crash
+/// reports or to serve them from a debug endpoint.
+///
+/// The appender is a ring buffer: once it holds `capacity` records, each new record evicts the
+/// oldest. Use [`Memory::handle`] to read the records.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::append::Memory;
+///
+/// let memory = Memory::new(100);
+/// let handle = memory.handle();
+///
+/// logforth::builder().dispatch(|d| d.append(memory)).apply();
+///
+/// log::error!("Hello memory!");
+/// for record in handle.snapshot() {
+///     eprint!("{}", record.message);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Memory {
+    layout: Option<Layout>,
+    buffer: Arc<Buffer>,
+}
+
+#[derive(Debug)]
+struct Buffer {
+    capacity: usize,
+    records: Mutex<VecDeque<MemoryRecord>>,
+}
+
+/// A record kept by a [`Memory`] appender.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MemoryRecord {
+    /// The time the record was appended.
+    pub time: Timestamp,
+    /// The level of the record.
+    pub level: Level,
+    /// The target of the record.
+    pub target: String,
+    /// The record formatted with the layout, lossily converted to UTF-8.
+    pub message: String,
+}
+
+impl Memory {
+    /// Creates a [`Memory`] appender keeping the last `capacity` records.
+    ///
+    /// This appender by default uses [`TextLayout`] without color to format log records.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            layout: None,
+            buffer: Arc::new(Buffer {
+                capacity,
+                records: Mutex::new(VecDeque::with_capacity(capacity.min(1024))),
+            }),
+        }
+    }
+
+    /// Sets the layout for the [`Memory`] appender.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::append::Memory;
+    /// use logforth::layout::TextLayout;
+    ///
+    /// let memory_appender = Memory::new(100).with_layout(TextLayout::default().no_color());
+    /// ```
+    pub fn with_layout(mut self, layout: impl Into<Layout>) -> Self {
+        self.layout = Some(layout.into());
+        self
+    }
+
+    /// Returns a handle to read the records of the appender.
+    pub fn handle(&self) -> MemoryHandle {
+        MemoryHandle {
+            buffer: self.buffer.clone(),
+        }
+    }
+}
+
+impl Append for Memory {
+    fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        if self.buffer.capacity == 0 {
+            return Ok(());
+        }
+        let bytes = match self.layout.as_ref().or(layout) {
+            Some(layout) => layout.format(record, diagnostics)?,
+            None => TextLayout::default()
+                .no_color()
+                .format(record, diagnostics)?,
+        };
+        let record = MemoryRecord {
+            time: clock::now(),
+            level: record.level(),
+            target: record.target().to_owned(),
+            message: match String::from_utf8(bytes) {
+                Ok(message) => message,
+                Err(err) => String::from_utf8_lossy(err.as_bytes()).into_owned(),
+            },
+        };
+
+        let mut records = self.buffer.lock();
+        if records.len() == self.buffer.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+        Ok(())
+    }
+}
+
+impl Buffer {
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<MemoryRecord>> {
+        // a panic while holding the lock cannot leave the buffer inconsistent
+        self.records.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// A handle to read the records of a [`Memory`] appender.
+#[derive(Debug, Clone)]
+pub struct MemoryHandle {
+    buffer: Arc<Buffer>,
+}
+
+impl MemoryHandle {
+    /// Returns a copy of the records, from the oldest to the newest.
+    pub fn snapshot(&self) -> Vec<MemoryRecord> {
+        self.buffer.lock().iter().cloned().collect()
+    }
+
+    /// Removes and returns the records, from the oldest to the newest.
+    pub fn drain(&self) -> Vec<MemoryRecord> {
+        self.buffer.lock().drain(..).collect()
+    }
+
+    /// Returns the number of records kept.
+    pub fn len(&self) -> usize {
+        self.buffer.lock().len()
+    }
+
+    /// Returns `true` if no record is kept.
+    pub fn is_empty(&self) -> bool {
+        self.buffer.lock().is_empty()
+    }
+
+    /// Returns the maximum number of records kept.
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append::test_util;
+    use crate::layout::CustomLayout;
+
+    fn append(memory: &Memory, message: &str) {
+        test_util::append(memory, Level::Info, message).unwrap();
+    }
+
+    #[test]
+    fn test_memory_ring_buffer() {
+        let memory = Memory::new(2).with_layout(CustomLayout::new(|record, _| {
+            Ok(record.args().to_string().into_bytes())
+        }));
+        let handle = memory.handle();
+        assert!(handle.is_empty());
+
+        append(&memory, "first");
+        append(&memory, "second");
+        append(&memory, "third");
+        assert_eq!(test_util::messages(handle.snapshot()), ["second", "third"]);
+        assert_eq!(handle.len(), 2);
+
+        let drained = handle.drain();
+        assert_eq!(drained[0].level, Level::Info);
+        assert_eq!(drained[0].target, "app");
+        assert_eq!(test_util::messages(drained), ["second", "third"]);
+        assert!(handle.is_empty());
+
+        append(&memory, "fourth");
+        assert_eq!(test_util::messages(handle.snapshot()), ["fourth"]);
+    }
+
+    #[test]
+    fn test_memory_zero_capacity() {
+        let memory = Memory::new(0);
+        append(&memory, "dropped");
+        assert!(memory.handle().is_empty());
+    }
+}
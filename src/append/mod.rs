@@ -15,41 +15,200 @@
 //! Dispatch log records to various targets.
 
 use std::fmt;
+use std::sync::Arc;
 
 use crate::Diagnostic;
+use crate::Layout;
 
+mod dedup;
+mod failover;
 #[cfg(feature = "fastrace")]
 mod fastrace;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(all(unix, feature = "ipc"))]
+pub mod ipc;
 #[cfg(all(unix, feature = "journald"))]
-mod journald;
+pub mod journald;
+mod key_filter;
+mod latency;
+mod memory;
+mod multi;
+mod null;
 #[cfg(feature = "opentelemetry")]
 pub mod opentelemetry;
+#[cfg(any(feature = "socket", feature = "syslog"))]
+mod reconnect;
+mod repeats;
 #[cfg(feature = "rolling-file")]
 pub mod rolling_file;
+mod router;
+#[cfg(feature = "socket")]
+pub mod socket;
 mod stdio;
+mod storm;
 #[cfg(feature = "syslog")]
 pub mod syslog;
+#[cfg(test)]
+mod test_util;
+mod testing;
+mod throttle;
+mod toggle;
 
+pub use self::dedup::Dedup;
+pub use self::failover::Failover;
 #[cfg(feature = "fastrace")]
 pub use self::fastrace::FastraceEvent;
+#[cfg(feature = "http")]
+pub use self::http::Http;
+#[cfg(all(unix, feature = "ipc"))]
+pub use self::ipc::Ipc;
 #[cfg(all(unix, feature = "journald"))]
 pub use self::journald::Journald;
+pub use self::key_filter::KeyFilter;
+pub use self::latency::Latency;
+pub use self::latency::LatencyHandle;
+pub use self::latency::LatencySnapshot;
+pub use self::memory::Memory;
+pub use self::memory::MemoryHandle;
+pub use self::memory::MemoryRecord;
+pub use self::multi::MultiAppend;
+pub use self::null::Null;
+pub use self::null::NullHandle;
 #[cfg(feature = "opentelemetry")]
 pub use self::opentelemetry::OpentelemetryLog;
 #[cfg(feature = "rolling-file")]
 pub use self::rolling_file::RollingFile;
+pub use self::router::Route;
+pub use self::router::Router;
+#[cfg(feature = "socket")]
+pub use self::socket::Socket;
+pub use self::stdio::SplitStdio;
 pub use self::stdio::Stderr;
 pub use self::stdio::Stdout;
+pub use self::storm::ErrorStorm;
 #[cfg(feature = "syslog")]
 pub use self::syslog::Syslog;
+pub use self::testing::Testing;
+pub use self::testing::TestingHandle;
+pub use self::testing::TestingRecord;
+pub use self::throttle::Throttle;
+pub use self::toggle::AppendHandle;
+pub use self::toggle::Toggle;
 
 /// A trait representing an appender that can process log records.
 ///
 /// Implementors of this trait can handle log records in custom ways.
 pub trait Append: fmt::Debug + Send + Sync + 'static {
+    /// Returns the kind of the appender, which identifies it in errors and diagnostics.
+    ///
+    /// The default implementation returns the type name of the appender.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
     /// Dispatches a log record to the append target.
     fn append(&self, record: &log::Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()>;
 
+    /// Dispatches a log record to the append target, with the layout configured on the dispatch.
+    ///
+    /// Appenders that format log records should use `layout` when they are not configured with a
+    /// layout of their own. The default implementation ignores `layout` and calls
+    /// [`Append::append`].
+    fn append_with_layout(
+        &self,
+        record: &log::Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        let _ = layout;
+        self.append(record, diagnostics)
+    }
+
     /// Flushes any buffered records.
+    ///
+    /// This may block the calling thread: appenders built on a
+    /// [non-blocking](crate::non_blocking) writer wait for its worker thread for at most the
+    /// shutdown timeout of the writer.
     fn flush(&self) {}
+
+    /// Flushes any buffered records, with the layout configured on the dispatch.
+    ///
+    /// Appenders that emit records on flush, such as summaries of suppressed records, should
+    /// format them with `layout` as in [`Append::append_with_layout`]. The default implementation
+    /// ignores `layout` and calls [`Append::flush`].
+    fn flush_with_layout(&self, layout: Option<&Layout>) {
+        let _ = layout;
+        self.flush()
+    }
+}
+
+/// Shares one appender across multiple dispatches.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use logforth::append;
+///
+/// let stdout = Arc::new(append::Stdout::default());
+///
+/// logforth::builder()
+///     .dispatch(|d| d.filter(log::LevelFilter::Error).append(stdout.clone()))
+///     .dispatch(|d| d.filter("my_crate=debug").append(stdout))
+///     .apply();
+/// ```
+impl<A: Append + ?Sized> Append for Arc<A> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn append(&self, record: &log::Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        (**self).append(record, diagnostics)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &log::Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        (**self).append_with_layout(record, diagnostics, layout)
+    }
+
+    fn flush(&self) {
+        (**self).flush()
+    }
+
+    fn flush_with_layout(&self, layout: Option<&Layout>) {
+        (**self).flush_with_layout(layout)
+    }
+}
+
+impl<A: Append + ?Sized> Append for Box<A> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn append(&self, record: &log::Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        (**self).append(record, diagnostics)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &log::Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        (**self).append_with_layout(record, diagnostics, layout)
+    }
+
+    fn flush(&self) {
+        (**self).flush()
+    }
+
+    fn flush_with_layout(&self, layout: Option<&Layout>) {
+        (**self).flush_with_layout(layout)
+    }
 }
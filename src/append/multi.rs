@@ -0,0 +1,177 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use log::Record;
+
+use crate::append::Append;
+use crate::Diagnostic;
+use crate::Layout;
+
+/// An appender that forwards log records to a group of appenders in order.
+///
+/// Every appender of the group receives every record, even if an appender before it fails. If
+/// one appender fails, its error is returned; if several do, an error listing all of them is.
+///
+/// Combined with [`Arc`](std::sync::Arc), a group of appenders can be built once and shared
+/// across dispatches.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use logforth::append;
+/// use logforth::append::MultiAppend;
+///
+/// let console = Arc::new(
+///     MultiAppend::default()
+///         .with_append(append::Stdout::default())
+///         .with_append(append::Stderr::default()),
+/// );
+///
+/// logforth::builder()
+///     .dispatch(|d| d.filter(log::LevelFilter::Error).append(console.clone()))
+///     .dispatch(|d| d.filter("my_crate=debug").append(console))
+///     .apply();
+/// ```
+#[derive(Debug, Default)]
+pub struct MultiAppend {
+    appends: Vec<Box<dyn Append>>,
+}
+
+impl MultiAppend {
+    /// Creates a new [`MultiAppend`] from the given appenders.
+    pub fn new(appends: Vec<Box<dyn Append>>) -> Self {
+        Self { appends }
+    }
+
+    /// Adds an appender to this group.
+    pub fn with_append(mut self, append: impl Append) -> Self {
+        self.appends.push(Box::new(append));
+        self
+    }
+}
+
+impl From<Vec<Box<dyn Append>>> for MultiAppend {
+    fn from(appends: Vec<Box<dyn Append>>) -> Self {
+        Self::new(appends)
+    }
+}
+
+impl Append for MultiAppend {
+    fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        let mut errors = vec![];
+        for append in &self.appends {
+            if let Err(err) = append.append_with_layout(record, diagnostics, layout) {
+                errors.push((append.name(), err));
+            }
+        }
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.pop().expect("one error").1),
+            failed => {
+                let errors: Vec<String> = errors
+                    .iter()
+                    .map(|(name, err)| format!("{name}: {err:#}"))
+                    .collect();
+                Err(anyhow::anyhow!(
+                    "{failed} of {} appenders failed: {}",
+                    self.appends.len(),
+                    errors.join("; ")
+                ))
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.flush_with_layout(None)
+    }
+
+    fn flush_with_layout(&self, layout: Option<&Layout>) {
+        for append in &self.appends {
+            append.flush_with_layout(layout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use log::Level;
+
+    use super::*;
+    use crate::append::test_util::append;
+    use crate::append::test_util::messages;
+    use crate::append::Memory;
+    use crate::layout::CustomLayout;
+
+    #[derive(Debug, Default)]
+    struct Failing {
+        flushes: Arc<AtomicUsize>,
+    }
+
+    impl Append for Failing {
+        fn append(&self, _: &Record, _: &[Diagnostic]) -> anyhow::Result<()> {
+            anyhow::bail!("failing appender failed")
+        }
+
+        fn flush(&self) {
+            self.flushes.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_append_after_failure() {
+        let memory = Memory::new(16).with_layout(CustomLayout::new(|record, _| {
+            Ok(record.args().to_string().into_bytes())
+        }));
+        let handle = memory.handle();
+        let multi = MultiAppend::default()
+            .with_append(Failing::default())
+            .with_append(memory);
+
+        // the second member still receives the record
+        let err = append(&multi, Level::Info, "hello").unwrap_err();
+        assert_eq!(err.to_string(), "failing appender failed");
+        assert_eq!(messages(handle.snapshot()), ["hello"]);
+
+        let multi = multi.with_append(Failing::default());
+        let err = append(&multi, Level::Info, "world").unwrap_err();
+        assert!(err.to_string().starts_with("2 of 3 appenders failed: "));
+        assert_eq!(messages(handle.snapshot()), ["hello", "world"]);
+    }
+
+    #[test]
+    fn test_flush_all() {
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let failing = || Failing {
+            flushes: flushes.clone(),
+        };
+        let multi = MultiAppend::new(vec![Box::new(failing()), Box::new(failing())]);
+        multi.flush();
+        assert_eq!(flushes.load(Ordering::Relaxed), 2);
+    }
+}
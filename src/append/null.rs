@@ -0,0 +1,157 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use log::Record;
+
+use crate::append::Append;
+use crate::layout::TextLayout;
+use crate::pool;
+use crate::Diagnostic;
+use crate::Layout;
+
+/// An appender that discards log records, counting the records and bytes it would have written.
+///
+/// Records are still formatted with the layout, so the appender measures the cost of layouts and
+/// dispatching in isolation from any I/O. It also serves as a placeholder sink, e.g., behind a
+/// [`Toggle`](crate::append::Toggle). Use [`Null::handle`] to query the counters.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::append::Null;
+///
+/// let null = Null::default();
+/// let handle = null.handle();
+///
+/// logforth::builder().dispatch(|d| d.append(null)).apply();
+///
+/// log::error!("Hello null!");
+/// assert_eq!(handle.records(), 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct Null {
+    layout: Option<Layout>,
+    counters: Arc<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    records: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl Null {
+    /// Sets the layout for the [`Null`] appender.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::append::Null;
+    /// use logforth::layout::TextLayout;
+    ///
+    /// let null_appender = Null::default().with_layout(TextLayout::default());
+    /// ```
+    pub fn with_layout(mut self, layout: impl Into<Layout>) -> Self {
+        self.layout = Some(layout.into());
+        self
+    }
+
+    /// Returns a handle to query the counters of the appender.
+    pub fn handle(&self) -> NullHandle {
+        NullHandle {
+            counters: self.counters.clone(),
+        }
+    }
+}
+
+impl Append for Null {
+    fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        let bytes = match self.layout.as_ref().or(layout) {
+            Some(layout) => layout.format(record, diagnostics)?,
+            None => TextLayout::default().format(record, diagnostics)?,
+        };
+        self.counters.records.fetch_add(1, Ordering::Relaxed);
+        self.counters
+            .bytes
+            .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        pool::recycle(bytes);
+        Ok(())
+    }
+}
+
+/// A handle to query the counters of a [`Null`] appender.
+#[derive(Debug, Clone)]
+pub struct NullHandle {
+    counters: Arc<Counters>,
+}
+
+impl NullHandle {
+    /// Returns the number of records discarded.
+    pub fn records(&self) -> u64 {
+        self.counters.records.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes of the formatted records discarded.
+    pub fn bytes(&self) -> u64 {
+        self.counters.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Resets the counters to zero.
+    pub fn reset(&self) {
+        self.counters.records.store(0, Ordering::Relaxed);
+        self.counters.bytes.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    use super::*;
+
+    #[test]
+    fn test_null_counters() {
+        let null = Null::default();
+        let handle = null.handle();
+
+        let record = Record::builder()
+            .args(format_args!("Hello"))
+            .level(Level::Info)
+            .target("app")
+            .build();
+        let expected = TextLayout::default().format(&record, &[]).unwrap().len() as u64;
+
+        null.append(&record, &[]).unwrap();
+        null.append(&record, &[]).unwrap();
+        assert_eq!(handle.records(), 2);
+        assert_eq!(handle.bytes(), 2 * expected);
+
+        handle.reset();
+        assert_eq!(handle.records(), 0);
+        assert_eq!(handle.bytes(), 0);
+    }
+}
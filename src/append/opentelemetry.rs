@@ -24,14 +24,25 @@ use opentelemetry::logs::LogRecord as _;
 use opentelemetry::logs::Logger;
 use opentelemetry::logs::LoggerProvider as ILoggerProvider;
 use opentelemetry::InstrumentationScope;
+use opentelemetry_otlp::Compression;
 use opentelemetry_otlp::LogExporter;
 use opentelemetry_otlp::Protocol;
 use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_otlp::WithTonicConfig;
 use opentelemetry_sdk::logs::LogRecord;
 use opentelemetry_sdk::logs::LoggerProvider;
 
 use crate::append::Append;
+use crate::attachment;
+use crate::attachment::is_attachment_key;
+use crate::backtrace::is_backtrace_key;
+use crate::backtrace::BACKTRACE_FIELD;
+use crate::clock;
 use crate::diagnostic::Visitor;
+use crate::secret::secret_key;
+use crate::secret::MASK;
+use crate::template::is_message_template_key;
+use crate::template::MESSAGE_TEMPLATE_FIELD;
 use crate::Diagnostic;
 use crate::Layout;
 
@@ -50,13 +61,34 @@ pub enum OpentelemetryWireProtocol {
     HttpJson,
 }
 
+/// Specifies the compression algorithm of the requests sent to OpenTelemetry.
+///
+/// This is a logical re-exported [`Compression`] to avoid version lock-in to
+/// `opentelemetry_otlp`.
+///
+/// Compression is only supported by the [`OpentelemetryWireProtocol::Grpc`] protocol, and each
+/// algorithm requires its crate feature: `gzip` for [`Gzip`] and `zstd` for [`Zstd`].
+///
+/// [`Gzip`]: OpentelemetryCompression::Gzip
+/// [`Zstd`]: OpentelemetryCompression::Zstd
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OpentelemetryCompression {
+    /// Compress requests using gzip.
+    Gzip,
+    /// Compress requests using zstd.
+    Zstd,
+}
+
 /// A builder to configure and create an [`OpentelemetryLog`] appender.
 pub struct OpentelemetryLogBuilder {
     name: String,
     endpoint: String,
     protocol: Protocol,
+    compression: Option<Compression>,
     labels: Vec<(Cow<'static, str>, Cow<'static, str>)>,
     layout: Option<Layout>,
+    backtrace: bool,
 }
 
 impl OpentelemetryLogBuilder {
@@ -74,8 +106,10 @@ impl OpentelemetryLogBuilder {
             name: name.into(),
             endpoint: otlp_endpoint.into(),
             protocol: Protocol::Grpc,
+            compression: None,
             labels: vec![],
             layout: None,
+            backtrace: false,
         }
     }
 
@@ -99,6 +133,28 @@ impl OpentelemetryLogBuilder {
         self
     }
 
+    /// Sets the compression algorithm of the requests sent to the collector.
+    ///
+    /// Requests are not compressed by default. Building the appender fails if the algorithm's
+    /// crate feature is not enabled, or if the protocol is not [`OpentelemetryWireProtocol::Grpc`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::append::opentelemetry::OpentelemetryCompression;
+    /// use logforth::append::opentelemetry::OpentelemetryLogBuilder;
+    ///
+    /// let builder = OpentelemetryLogBuilder::new("my_service", "http://localhost:4317");
+    /// builder.compression(OpentelemetryCompression::Gzip);
+    /// ```
+    pub fn compression(mut self, compression: OpentelemetryCompression) -> Self {
+        self.compression = Some(match compression {
+            OpentelemetryCompression::Gzip => Compression::Gzip,
+            OpentelemetryCompression::Zstd => Compression::Zstd,
+        });
+        self
+    }
+
     /// Adds a label to the logs.
     ///
     /// # Examples
@@ -154,6 +210,15 @@ impl OpentelemetryLogBuilder {
         self
     }
 
+    /// Sets whether to add the backtrace captured for the record as the `backtrace` attribute.
+    /// Default to `false`.
+    ///
+    /// See [`Builder::backtrace_level`](crate::Builder::backtrace_level) to capture backtraces.
+    pub fn backtrace(mut self, backtrace: bool) -> Self {
+        self.backtrace = backtrace;
+        self
+    }
+
     /// Builds the [`OpentelemetryLog`] appender.
     ///
     /// # Examples
@@ -171,19 +236,31 @@ impl OpentelemetryLogBuilder {
             name,
             endpoint,
             protocol,
+            compression,
             labels,
             layout,
+            backtrace,
         } = self;
 
         let collector_timeout =
             Duration::from_secs(opentelemetry_otlp::OTEL_EXPORTER_OTLP_TIMEOUT_DEFAULT);
         let exporter = match protocol {
-            Protocol::Grpc => LogExporter::builder()
-                .with_tonic()
-                .with_endpoint(endpoint)
-                .with_protocol(protocol)
-                .with_timeout(collector_timeout)
-                .build(),
+            Protocol::Grpc => {
+                let mut builder = LogExporter::builder()
+                    .with_tonic()
+                    .with_endpoint(endpoint)
+                    .with_protocol(protocol)
+                    .with_timeout(collector_timeout);
+                if let Some(compression) = compression {
+                    builder = builder.with_compression(compression);
+                }
+                builder.build()
+            }
+            Protocol::HttpBinary | Protocol::HttpJson if compression.is_some() => {
+                return Err(opentelemetry_sdk::logs::LogError::Other(
+                    format!("compression is not supported with the {protocol:?} protocol").into(),
+                ));
+            }
             Protocol::HttpBinary | Protocol::HttpJson => LogExporter::builder()
                 .with_http()
                 .with_endpoint(endpoint)
@@ -206,6 +283,7 @@ impl OpentelemetryLogBuilder {
         Ok(OpentelemetryLog {
             name,
             layout,
+            backtrace,
             logger,
             provider,
         })
@@ -231,21 +309,33 @@ impl OpentelemetryLogBuilder {
 pub struct OpentelemetryLog {
     name: String,
     layout: Option<Layout>,
+    backtrace: bool,
     logger: opentelemetry_sdk::logs::Logger,
     provider: LoggerProvider,
 }
 
 impl Append for OpentelemetryLog {
     fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
         let mut log_record = LogRecord::default();
-        log_record.observed_timestamp = Some(SystemTime::now());
+        log_record.observed_timestamp = Some(SystemTime::from(clock::now()));
         log_record.severity_number = Some(log_level_to_otel_severity(record.level()));
         log_record.severity_text = Some(record.level().as_str());
         log_record.target = Some(record.target().to_string().into());
-        log_record.body = Some(AnyValue::Bytes(Box::new(match self.layout.as_ref() {
-            None => record.args().to_string().into_bytes(),
-            Some(layout) => layout.format(record, diagnostics)?,
-        })));
+        log_record.body = Some(AnyValue::Bytes(Box::new(
+            match self.layout.as_ref().or(layout) {
+                None => record.args().to_string().into_bytes(),
+                Some(layout) => layout.format(record, diagnostics)?,
+            },
+        )));
 
         if let Some(module_path) = record.module_path() {
             log_record.add_attribute("module_path", module_path.to_string());
@@ -259,6 +349,7 @@ impl Append for OpentelemetryLog {
 
         let mut extractor = KvExtractor {
             record: &mut log_record,
+            backtrace: self.backtrace,
         };
         record.key_values().visit(&mut extractor)?;
         for d in diagnostics {
@@ -293,6 +384,7 @@ fn log_level_to_otel_severity(level: log::Level) -> opentelemetry::logs::Severit
 
 struct KvExtractor<'a> {
     record: &'a mut LogRecord,
+    backtrace: bool,
 }
 
 impl<'kvs> log::kv::VisitSource<'kvs> for KvExtractor<'_> {
@@ -301,9 +393,15 @@ impl<'kvs> log::kv::VisitSource<'kvs> for KvExtractor<'_> {
         key: log::kv::Key<'kvs>,
         value: log::kv::Value<'kvs>,
     ) -> Result<(), log::kv::Error> {
-        let key = key.to_string();
         let value = value.to_string();
-        self.record.add_attribute(key, value);
+        if is_attachment_key(key.as_str()) {
+            if let Some(bytes) = attachment::decode(&value) {
+                let bytes = AnyValue::Bytes(Box::new(bytes));
+                self.record.add_attribute(key.to_string(), bytes);
+                return Ok(());
+            }
+        }
+        self.add_attribute(key.to_string(), value);
         Ok(())
     }
 }
@@ -316,6 +414,25 @@ impl Visitor for KvExtractor<'_> {
     {
         let key = key.into().into_owned();
         let value = value.into().into_owned();
-        self.record.add_attribute(key, value);
+        self.add_attribute(key, value);
+    }
+}
+
+impl KvExtractor<'_> {
+    fn add_attribute(&mut self, key: String, value: String) {
+        let key = match is_message_template_key(&key) {
+            true => MESSAGE_TEMPLATE_FIELD.to_string(),
+            false => key,
+        };
+        if is_backtrace_key(&key) {
+            if self.backtrace {
+                self.record.add_attribute(BACKTRACE_FIELD, value);
+            }
+            return;
+        }
+        match secret_key(&key) {
+            Some(key) => self.record.add_attribute(key.to_string(), MASK),
+            None => self.record.add_attribute(key, value),
+        }
     }
 }
@@ -0,0 +1,458 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How long connecting to a peer may take before the attempt fails.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a write may block before the connection is considered broken.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(crate) type Stream = Box<dyn Write + Send + Sync>;
+type ConnectFunction = dyn Fn() -> io::Result<Stream> + Send + Sync + 'static;
+
+/// Connects over TCP to the first reachable address of `addr`, with connect and write timeouts.
+///
+/// The address is resolved on every call, so that a peer that moved to another address is
+/// reached on reconnect.
+pub(crate) fn connect_tcp<A: ToSocketAddrs + ?Sized>(addr: &A) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    for addr in addr.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => {
+                stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                return Ok(stream);
+            }
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve to any address",
+        )
+    }))
+}
+
+/// Connects over TLS to the first reachable address of `addr`; see [`connect_tcp`].
+#[cfg(feature = "native-tls")]
+pub(crate) fn connect_tls<A: ToSocketAddrs + ?Sized>(
+    addr: &A,
+    domain: &str,
+    connector: &native_tls::TlsConnector,
+) -> io::Result<native_tls::TlsStream<TcpStream>> {
+    let stream = connect_tcp(addr)?;
+    // bound the handshake as well
+    stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+    connector
+        .connect(domain, stream)
+        .map_err(|err| io::Error::other(err.to_string()))
+}
+
+/// Connects over TLS with rustls to the first reachable address of `addr`; see [`connect_tcp`].
+///
+/// The handshake completes before returning, so that a failing handshake fails the attempt.
+#[cfg(feature = "rustls")]
+pub(crate) fn connect_rustls<A: ToSocketAddrs + ?Sized>(
+    addr: &A,
+    server_name: &rustls::pki_types::ServerName<'static>,
+    config: &std::sync::Arc<rustls::ClientConfig>,
+) -> io::Result<rustls::StreamOwned<rustls::ClientConnection, TcpStream>> {
+    let mut stream = connect_tcp(addr)?;
+    // bound the handshake as well
+    stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+    let mut connection = rustls::ClientConnection::new(config.clone(), server_name.clone())
+        .map_err(io::Error::other)?;
+    while connection.is_handshaking() {
+        connection.complete_io(&mut stream)?;
+    }
+    Ok(rustls::StreamOwned::new(connection, stream))
+}
+
+/// A stream connection that reconnects with exponential backoff when it breaks, and buffers
+/// records meanwhile.
+///
+/// Any failed write is considered a broken connection: the record is buffered and resent on the
+/// next connection. When the buffer is full, the oldest records are dropped. Reconnect events are
+/// reported to stderr.
+///
+/// The backoff keeps growing across connections that break soon after connecting, such as a peer
+/// that accepts and then resets every connection; it is reset once a write succeeds on a
+/// connection that has stayed up for the current backoff.
+pub(crate) struct Reconnecting {
+    endpoint: String,
+    connect: Box<ConnectFunction>,
+    // `None` while disconnected
+    stream: Option<Stream>,
+    pending: VecDeque<Vec<u8>>,
+    max_pending: usize,
+    dropped: usize,
+    backoff: Duration,
+    // `None` when the next write may connect right away
+    next_attempt: Option<Instant>,
+    // when the current connection was established, `None` while disconnected
+    connected_at: Option<Instant>,
+}
+
+impl fmt::Debug for Reconnecting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reconnecting")
+            .field("endpoint", &self.endpoint)
+            .field("connected", &self.stream.is_some())
+            .field("pending", &self.pending.len())
+            .field("max_pending", &self.max_pending)
+            .field("dropped", &self.dropped)
+            .field("backoff", &self.backoff)
+            .field("next_attempt", &self.next_attempt)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Reconnecting {
+    const DEFAULT_MAX_PENDING: usize = 1024;
+    pub(crate) const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+    const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+    /// Creates a connection to `endpoint` that connects with `connect` on the first write.
+    pub(crate) fn new(
+        endpoint: String,
+        connect: impl Fn() -> io::Result<Stream> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            endpoint,
+            connect: Box::new(connect),
+            stream: None,
+            pending: VecDeque::new(),
+            max_pending: Self::DEFAULT_MAX_PENDING,
+            dropped: 0,
+            backoff: Self::INITIAL_BACKOFF,
+            next_attempt: None,
+            connected_at: None,
+        }
+    }
+
+    /// Creates a connection to `endpoint` that connects with `connect` right away.
+    #[cfg(feature = "syslog")]
+    pub(crate) fn connect(
+        endpoint: String,
+        connect: impl Fn() -> io::Result<Stream> + Send + Sync + 'static,
+    ) -> io::Result<Self> {
+        let stream = connect()?;
+        let mut connection = Self::new(endpoint, connect);
+        connection.stream = Some(stream);
+        connection.connected_at = Some(Instant::now());
+        Ok(connection)
+    }
+
+    /// Sets the maximum number of records buffered while disconnected. Zero disables buffering.
+    pub(crate) fn set_max_pending(&mut self, max_pending: usize) {
+        self.max_pending = max_pending;
+    }
+
+    pub(crate) fn send(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.send_at(buf, Instant::now());
+        Ok(())
+    }
+
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        let Some(stream) = &mut self.stream else {
+            return Ok(());
+        };
+        if let Err(err) = stream.flush() {
+            self.disconnect(&err, Instant::now());
+        }
+        Ok(())
+    }
+
+    fn send_at(&mut self, buf: &[u8], now: Instant) {
+        if self.stream.is_none() && !self.reconnect(now) {
+            self.buffer(buf.to_vec());
+            return;
+        }
+
+        let stream = self.stream.as_mut().expect("connected");
+        match stream.write_all(buf) {
+            Ok(()) => self.on_write(now),
+            Err(err) => {
+                // the record may have been partially written; resend it on the new connection
+                self.disconnect(&err, now);
+                self.buffer(buf.to_vec());
+            }
+        }
+    }
+
+    /// Resets the backoff once the connection has proven stable.
+    fn on_write(&mut self, now: Instant) {
+        let stable = self
+            .connected_at
+            .and_then(|connected_at| connected_at.checked_add(self.backoff))
+            .is_some_and(|stable_at| now >= stable_at);
+        if stable {
+            self.backoff = Self::INITIAL_BACKOFF;
+        }
+    }
+
+    fn disconnect(&mut self, err: &io::Error, now: Instant) {
+        eprintln!(
+            "connection to {} broken, reconnecting in {:?}: {err}",
+            self.endpoint, self.backoff
+        );
+        self.stream = None;
+        self.connected_at = None;
+        self.schedule(now);
+    }
+
+    /// Schedules the next connect attempt after the backoff, and grows the backoff.
+    fn schedule(&mut self, now: Instant) {
+        self.next_attempt = now.checked_add(self.backoff);
+        self.backoff = (self.backoff * 2).min(Self::MAX_BACKOFF);
+    }
+
+    fn buffer(&mut self, record: Vec<u8>) {
+        if self.max_pending == 0 {
+            self.dropped += 1;
+            return;
+        }
+        if self.pending.len() >= self.max_pending {
+            self.pending.pop_front();
+            self.dropped += 1;
+        }
+        self.pending.push_back(record);
+    }
+
+    /// Tries to connect if the backoff has elapsed. Returns whether the writer is connected.
+    fn reconnect(&mut self, now: Instant) -> bool {
+        if self
+            .next_attempt
+            .is_some_and(|next_attempt| now < next_attempt)
+        {
+            return false;
+        }
+
+        let mut stream = match (self.connect)() {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!(
+                    "failed to connect to {}, retrying in {:?}: {err}",
+                    self.endpoint, self.backoff
+                );
+                self.schedule(now);
+                return false;
+            }
+        };
+
+        let pending = self.pending.len();
+        while let Some(record) = self.pending.pop_front() {
+            if let Err(err) = stream.write_all(&record) {
+                self.pending.push_front(record);
+                self.disconnect(&err, now);
+                return false;
+            }
+        }
+
+        if pending > 0 || self.dropped > 0 {
+            eprintln!(
+                "reconnected to {}; sent {pending} buffered records, dropped {} records",
+                self.endpoint, self.dropped
+            );
+        }
+        self.stream = Some(stream);
+        self.dropped = 0;
+        self.next_attempt = None;
+        self.connected_at = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// A peer that accepts connections while it is up, and records what they receive.
+    #[derive(Clone, Default)]
+    pub(crate) struct Peer {
+        pub(crate) down: Arc<AtomicBool>,
+        pub(crate) received: Arc<Mutex<Vec<u8>>>,
+        // the number of writes after which the current connection breaks
+        pub(crate) break_after: Arc<Mutex<Option<usize>>>,
+        pub(crate) connects: Arc<AtomicUsize>,
+    }
+
+    impl Peer {
+        pub(crate) fn connect(&self) -> io::Result<Stream> {
+            if self.down.load(Ordering::Relaxed) {
+                return Err(io::ErrorKind::ConnectionRefused.into());
+            }
+            self.connects.fetch_add(1, Ordering::Relaxed);
+            Ok(Box::new(PeerStream {
+                peer: self.clone(),
+                writes: 0,
+            }))
+        }
+
+        pub(crate) fn received(&self) -> String {
+            String::from_utf8(self.received.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    struct PeerStream {
+        peer: Peer,
+        writes: usize,
+    }
+
+    impl Write for PeerStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut break_after = self.peer.break_after.lock().unwrap();
+            if break_after.is_some_and(|n| self.writes >= n) {
+                *break_after = None;
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            self.writes += 1;
+            self.peer.received.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn connection(peer: &Peer) -> Reconnecting {
+        let peer = peer.clone();
+        Reconnecting::new("peer".to_string(), move || peer.connect())
+    }
+
+    #[test]
+    fn test_reconnect_buffers_records() {
+        let peer = Peer::default();
+        peer.down.store(true, Ordering::Relaxed);
+        let mut connection = connection(&peer);
+        connection.set_max_pending(1);
+
+        // the peer is down: records are buffered, keeping only the latest
+        let start = Instant::now();
+        connection.send_at(b"lost\n", start);
+        connection.send_at(b"kept\n", start);
+        assert_eq!(connection.dropped, 1);
+
+        // no attempt before the backoff elapses
+        peer.down.store(false, Ordering::Relaxed);
+        assert!(!connection.reconnect(start + Reconnecting::INITIAL_BACKOFF / 2));
+        assert_eq!(peer.connects.load(Ordering::Relaxed), 0);
+
+        connection.send_at(b"sent\n", start + Reconnecting::INITIAL_BACKOFF);
+        assert_eq!(peer.received(), "kept\nsent\n");
+        assert_eq!(connection.dropped, 0);
+    }
+
+    #[test]
+    fn test_reconnect_on_broken_pipe() {
+        let peer = Peer::default();
+        let mut connection = connection(&peer);
+        let now = Instant::now();
+        connection.send_at(b"first\n", now);
+        *peer.break_after.lock().unwrap() = Some(1);
+
+        // the broken record is resent on the new connection, after the backoff
+        connection.send_at(b"second\n", now);
+        assert!(connection.stream.is_none());
+        connection.send_at(b"third\n", now);
+        assert_eq!(peer.connects.load(Ordering::Relaxed), 1);
+        connection.send_at(b"fourth\n", now + Reconnecting::INITIAL_BACKOFF);
+        assert_eq!(peer.received(), "first\nsecond\nthird\nfourth\n");
+        assert_eq!(peer.connects.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_reconnect_backoff_on_reset() {
+        use std::net::TcpListener;
+
+        // a peer that accepts connections and closes them right away
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                drop(stream);
+            }
+        });
+        let connects = Arc::new(AtomicUsize::new(0));
+        let mut connection = Reconnecting::new("peer".to_string(), {
+            let connects = connects.clone();
+            move || {
+                connects.fetch_add(1, Ordering::Relaxed);
+                Ok(Box::new(connect_tcp(&addr)?))
+            }
+        });
+
+        // each reset connection pushes the next attempt further away
+        let mut now = Instant::now();
+        for attempt in 1..=3 {
+            while connection.stream.is_some() || connects.load(Ordering::Relaxed) < attempt {
+                connection.send_at(b"record\n", now);
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            assert_eq!(connects.load(Ordering::Relaxed), attempt);
+            let next_attempt = connection.next_attempt.unwrap();
+            assert_eq!(
+                next_attempt - now,
+                Reconnecting::INITIAL_BACKOFF * 2u32.pow(attempt as u32 - 1)
+            );
+            now = next_attempt;
+        }
+    }
+
+    #[test]
+    fn test_reset_backoff_on_stable_connection() {
+        let peer = Peer::default();
+        let mut connection = connection(&peer);
+        let now = Instant::now();
+        connection.send_at(b"first\n", now);
+        *peer.break_after.lock().unwrap() = Some(1);
+        connection.send_at(b"second\n", now);
+        assert_eq!(connection.backoff, Reconnecting::INITIAL_BACKOFF * 2);
+
+        // a write right after reconnecting keeps the backoff
+        let now = now + Reconnecting::INITIAL_BACKOFF;
+        connection.send_at(b"third\n", now);
+        assert_eq!(connection.backoff, Reconnecting::INITIAL_BACKOFF * 2);
+        connection.send_at(b"fourth\n", now + connection.backoff);
+        assert_eq!(connection.backoff, Reconnecting::INITIAL_BACKOFF);
+    }
+
+    #[test]
+    fn test_reconnect_backoff() {
+        let peer = Peer::default();
+        peer.down.store(true, Ordering::Relaxed);
+        let mut connection = connection(&peer);
+        let start = Instant::now();
+        for _ in 0..20 {
+            connection.send_at(b"record\n", start);
+            connection.next_attempt = None;
+        }
+        assert_eq!(connection.backoff, Reconnecting::MAX_BACKOFF);
+    }
+}
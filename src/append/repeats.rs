@@ -0,0 +1,96 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Shared by the appenders that suppress repeated records and summarize them.
+
+use std::borrow::Cow;
+
+use log::Record;
+
+use crate::append::Append;
+use crate::diagnostic::LazyDiagnostic;
+use crate::diagnostic::Visitor;
+use crate::Diagnostic;
+
+type FingerprintFunction = dyn Fn(&Record) -> String + Send + Sync;
+
+/// Tells which records are repeats of each other, by default those with the same target and
+/// message.
+#[derive(Default)]
+pub(crate) struct Fingerprint(Option<Box<FingerprintFunction>>);
+
+impl Fingerprint {
+    pub(crate) fn new<F>(fingerprint: F) -> Self
+    where
+        F: Fn(&Record) -> String + Send + Sync + 'static,
+    {
+        Self(Some(Box::new(fingerprint)))
+    }
+
+    pub(crate) fn of(&self, record: &Record) -> String {
+        match &self.0 {
+            Some(fingerprint) => fingerprint(record),
+            None => format!("{}\0{}", record.target(), record.args()),
+        }
+    }
+}
+
+/// The diagnostics of a record, captured so that they can be passed on after being filtered, or
+/// after the record is gone; the summary of a suppressed record carries them rather than the
+/// diagnostics of whichever record or flush emits the summary.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct DiagnosticSnapshot(Vec<(String, String)>);
+
+impl DiagnosticSnapshot {
+    pub(crate) fn capture(diagnostics: &[Diagnostic]) -> Self {
+        let mut snapshot = Self::default();
+        for diagnostic in diagnostics {
+            diagnostic.visit(&mut snapshot);
+        }
+        snapshot
+    }
+
+    /// Keeps only the diagnostics whose key satisfies `keep`.
+    pub(crate) fn retain(mut self, keep: impl Fn(&str) -> bool) -> Self {
+        self.0.retain(|(key, _)| keep(key));
+        self
+    }
+
+    pub(crate) fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.0
+            .iter()
+            .map(|(key, value)| {
+                let value = value.clone();
+                LazyDiagnostic::new(key.clone(), move || Some(value.clone())).into()
+            })
+            .collect()
+    }
+}
+
+impl Visitor for DiagnosticSnapshot {
+    fn visit<'k, 'v, K, V>(&mut self, key: K, value: V)
+    where
+        K: Into<Cow<'k, str>>,
+        V: Into<Cow<'v, str>>,
+    {
+        self.0
+            .push((key.into().into_owned(), value.into().into_owned()));
+    }
+}
+
+/// Reports a summary that failed to be appended on flush, where there is no caller to return the
+/// error to.
+pub(crate) fn report_flush_error(append: &dyn Append, summary: &str, err: anyhow::Error) {
+    eprintln!("failed to append {summary} to {}: {err:?}", append.name());
+}
@@ -24,33 +24,53 @@ use crate::Layout;
 /// An appender that writes log records to rolling files.
 #[derive(Debug)]
 pub struct RollingFile {
-    layout: Layout,
+    layout: Option<Layout>,
     writer: NonBlocking<RollingFileWriter>,
 }
 
 impl RollingFile {
     /// Creates a new [`RollingFile`] appender.
     ///
-    /// This appender by default uses [`TextLayout`] to format log records.
+    /// This appender by default uses [`TextLayout`] without color to format log records.
     pub fn new(writer: NonBlocking<RollingFileWriter>) -> Self {
         Self {
-            layout: TextLayout::default().no_color().into(),
+            layout: None,
             writer,
         }
     }
 
     /// Sets the layout used to format log records.
     pub fn with_layout(mut self, layout: impl Into<Layout>) -> Self {
-        self.layout = layout.into();
+        self.layout = Some(layout.into());
         self
     }
 }
 
 impl Append for RollingFile {
     fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
-        let mut bytes = self.layout.format(record, diagnostics)?;
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        let mut bytes = match self.layout.as_ref().or(layout) {
+            Some(layout) => layout.format(record, diagnostics)?,
+            None => TextLayout::default()
+                .no_color()
+                .format(record, diagnostics)?,
+        };
         bytes.push(b'\n');
-        self.writer.send(bytes)?;
+        self.writer.send(bytes, record.level())?;
         Ok(())
     }
+
+    fn flush(&self) {
+        if let Err(err) = self.writer.flush() {
+            eprintln!("failed to flush rolling file appender: {err:?}");
+        }
+    }
 }
@@ -0,0 +1,249 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::thread::JoinHandle;
+
+use crate::compression::Compression;
+
+type OnCompressed = Box<dyn FnOnce(&Path) + Send>;
+
+/// The files queued or being compressed, mapped to whether they were deleted meanwhile.
+pub(super) type InProgress = HashMap<PathBuf, bool>;
+
+struct Job {
+    path: PathBuf,
+    on_compressed: OnCompressed,
+}
+
+/// Compresses rotated log files one at a time in a background thread.
+///
+/// Dropping the compressor waits for the queued files to be compressed.
+pub(super) struct Compressor {
+    compression: Compression,
+    jobs: Option<mpsc::Sender<Job>>,
+    worker: Option<JoinHandle<()>>,
+    in_progress: Arc<Mutex<InProgress>>,
+}
+
+impl fmt::Debug for Compressor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Compressor")
+            .field("compression", &self.compression)
+            .field("in_progress", &self.in_progress().len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Compressor {
+    pub(super) fn new(compression: Compression) -> Self {
+        Self {
+            compression,
+            jobs: None,
+            worker: None,
+            in_progress: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub(super) fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Returns the files queued or being compressed.
+    ///
+    /// Holding the guard keeps the compressor from replacing a file with its archive, so that the
+    /// log directory can be listed consistently. Set the flag of a file to delete it instead of
+    /// compressing it.
+    pub(super) fn in_progress(&self) -> MutexGuard<'_, InProgress> {
+        self.in_progress
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Queues the file to be compressed and removed once compressed, then `on_compressed` is
+    /// called with the path of the compressed file.
+    pub(super) fn compress<F>(&mut self, path: PathBuf, on_compressed: F)
+    where
+        F: FnOnce(&Path) + Send + 'static,
+    {
+        if self.jobs.is_none() {
+            if let Err(err) = self.spawn() {
+                eprintln!("failed to spawn log file compression: {err}");
+                return;
+            }
+        }
+        self.in_progress().insert(path.clone(), false);
+        let jobs = self.jobs.as_ref().expect("spawned");
+        let job = Job {
+            path,
+            on_compressed: Box::new(on_compressed),
+        };
+        if let Err(mpsc::SendError(job)) = jobs.send(job) {
+            eprintln!("failed to compress log file {}", job.path.display());
+            self.in_progress().remove(&job.path);
+        }
+    }
+
+    fn spawn(&mut self) -> io::Result<()> {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let compression = self.compression;
+        let in_progress = self.in_progress.clone();
+        let worker = std::thread::Builder::new()
+            .name("logforth-rolling-compress".to_string())
+            .spawn(move || {
+                for Job {
+                    path,
+                    on_compressed,
+                } in receiver
+                {
+                    match compress(compression, &path, &in_progress) {
+                        Ok(Some(target)) => on_compressed(&target),
+                        Ok(None) => {}
+                        Err(err) => {
+                            eprintln!("failed to compress log file {}: {err}", path.display())
+                        }
+                    }
+                }
+            })?;
+        self.jobs = Some(sender);
+        self.worker = Some(worker);
+        Ok(())
+    }
+}
+
+impl Drop for Compressor {
+    fn drop(&mut self) {
+        // the worker exits once the queued files are compressed
+        drop(self.jobs.take());
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Compresses the file and removes it, unless it was deleted while queued. Returns the path of
+/// the compressed file, if any.
+fn compress(
+    compression: Compression,
+    path: &Path,
+    in_progress: &Mutex<InProgress>,
+) -> io::Result<Option<PathBuf>> {
+    let lock = || in_progress.lock().unwrap_or_else(|err| err.into_inner());
+    let deleted = lock().get(path).copied().unwrap_or(true);
+    if deleted {
+        lock().remove(path);
+        return remove_deleted(path);
+    }
+
+    let mut target = path.as_os_str().to_owned();
+    target.push(".");
+    target.push(compression.extension());
+    let target = PathBuf::from(target);
+    // compressed under a hidden name that does not count as a log file, and renamed once complete
+    let mut temp = std::ffi::OsString::from(".");
+    temp.push(target.file_name().unwrap_or_default());
+    temp.push(".tmp");
+    let temp = target.with_file_name(temp);
+
+    let result = compress_to(compression, path, &temp);
+    let mut in_progress = lock();
+    let deleted = in_progress.remove(path).unwrap_or(true);
+    if result.is_err() || deleted {
+        let _ = fs::remove_file(&temp);
+    }
+    if deleted {
+        return remove_deleted(path);
+    }
+    result?;
+    fs::rename(&temp, &target)?;
+    fs::remove_file(path)?;
+    Ok(Some(target))
+}
+
+fn remove_deleted(path: &Path) -> io::Result<Option<PathBuf>> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(None),
+    }
+}
+
+fn compress_to(compression: Compression, path: &Path, target: &Path) -> io::Result<()> {
+    let mut input = File::open(path)?;
+    let modified = input.metadata()?.modified()?;
+    let mut encoder = compression.encoder(File::create(target)?)?;
+    io::copy(&mut input, &mut encoder)?;
+    let output = encoder.finish()?;
+    // the archive keeps the age of the log file, which orders the files to delete
+    output.set_modified(modified)?;
+    output.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[cfg(feature = "gzip")]
+    const COMPRESSION: Compression = Compression::Gzip;
+    #[cfg(not(feature = "gzip"))]
+    const COMPRESSION: Compression = Compression::Zstd;
+
+    #[test]
+    fn test_compress() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("app.1.log");
+        fs::write(&path, "hello").unwrap();
+        let in_progress = Mutex::new(InProgress::from([(path.clone(), false)]));
+
+        let target = compress(COMPRESSION, &path, &in_progress).unwrap();
+        let name = format!("app.1.log.{}", COMPRESSION.extension());
+        assert_eq!(target, Some(dir.path().join(&name)));
+        assert_eq!(names(dir.path()), [name]);
+        assert!(in_progress.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_compress_deleted() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("app.1.log");
+        fs::write(&path, "hello").unwrap();
+        // deleted for retention while queued
+        let in_progress = Mutex::new(InProgress::from([(path.clone(), true)]));
+
+        let target = compress(COMPRESSION, &path, &in_progress).unwrap();
+        assert_eq!(target, None);
+        assert!(names(dir.path()).is_empty());
+        assert!(in_progress.lock().unwrap().is_empty());
+    }
+}
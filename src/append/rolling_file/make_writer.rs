@@ -0,0 +1,69 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+/// Opens the writers of the log files of a [`RollingFileWriter`].
+///
+/// By default, log files are opened with [`std::fs::File`] in append mode. Implement this trait
+/// to write through a custom sink, e.g., an encrypting writer or an in-memory buffer in tests,
+/// while reusing the rotation of the rolling file writer. See
+/// [`RollingFileWriterBuilder::make_writer`].
+///
+/// [`RollingFileWriter`]: crate::append::rolling_file::RollingFileWriter
+/// [`RollingFileWriterBuilder::make_writer`]: crate::append::rolling_file::RollingFileWriterBuilder::make_writer
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::OpenOptions;
+/// use std::io;
+/// use std::io::BufWriter;
+/// use std::io::Write;
+/// use std::path::Path;
+///
+/// use logforth::append::rolling_file::MakeWriter;
+/// use logforth::append::rolling_file::RollingFileWriter;
+///
+/// #[derive(Debug)]
+/// struct BufferedFiles;
+///
+/// impl MakeWriter for BufferedFiles {
+///     fn make_writer(&self, path: &Path) -> io::Result<Box<dyn Write + Send + Sync>> {
+///         let file = OpenOptions::new().append(true).create(true).open(path)?;
+///         Ok(Box::new(BufWriter::new(file)))
+///     }
+/// }
+///
+/// let builder = RollingFileWriter::builder().make_writer(BufferedFiles);
+/// ```
+pub trait MakeWriter: fmt::Debug + Send + Sync + 'static {
+    /// Opens a writer of the log file at `path`, appending to the file if it exists.
+    fn make_writer(&self, path: &Path) -> io::Result<Box<dyn Write + Send + Sync>>;
+}
+
+/// Opens log files with [`std::fs::File`] in append mode.
+#[derive(Debug)]
+pub(super) struct AppendFile;
+
+impl MakeWriter for AppendFile {
+    fn make_writer(&self, path: &Path) -> io::Result<Box<dyn Write + Send + Sync>> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        Ok(Box::new(file))
+    }
+}
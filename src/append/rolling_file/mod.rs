@@ -42,16 +42,25 @@
 //! ```
 
 pub use append::RollingFile;
+pub use make_writer::MakeWriter;
 pub use rolling::RollingFileWriter;
 pub use rolling::RollingFileWriterBuilder;
 pub use rotation::Rotation;
 
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+pub use crate::compression::Compression;
 use crate::non_blocking::NonBlockingBuilder;
 
 mod append;
 mod clock;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+mod compression;
+mod make_writer;
+mod path_vars;
+mod registry;
 mod rolling;
 mod rotation;
+mod staging;
 
 /// Create a non-blocking builder for rolling file writers.
 pub fn non_blocking(writer: RollingFileWriter) -> NonBlockingBuilder<RollingFileWriter> {
@@ -0,0 +1,74 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+
+/// Replaces the `${NAME}` placeholders of `template` with the variables, falling back to the
+/// environment variables.
+pub(super) fn expand(template: &str, vars: &BTreeMap<String, String>) -> anyhow::Result<String> {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let (name, tail) = rest[start + 2..]
+            .split_once('}')
+            .with_context(|| format!("unclosed placeholder in log file path: {template}"))?;
+        let value = match vars.get(name) {
+            Some(value) => value.clone(),
+            None => std::env::var(name).with_context(|| {
+                format!("unresolved placeholder ${{{name}}} in log file path: {template}")
+            })?,
+        };
+        if value.is_empty() {
+            anyhow::bail!("empty placeholder ${{{name}}} in log file path: {template}");
+        }
+        expanded.push_str(&value);
+        rest = tail;
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_path_vars() {
+        let vars = BTreeMap::from([("POD_NAME".to_string(), "web-0".to_string())]);
+        assert_eq!(
+            expand("logs/${POD_NAME}/app", &vars).unwrap(),
+            "logs/web-0/app"
+        );
+        assert_eq!(expand("logs/$HOME/{x}", &vars).unwrap(), "logs/$HOME/{x}");
+
+        std::env::set_var("LOGFORTH_TEST_PATH_VAR", "from-env");
+        assert_eq!(
+            expand("${LOGFORTH_TEST_PATH_VAR}-${POD_NAME}", &vars).unwrap(),
+            "from-env-web-0"
+        );
+
+        let err = expand("logs/${LOGFORTH_TEST_UNSET_VAR}", &vars).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unresolved placeholder ${LOGFORTH_TEST_UNSET_VAR} in log file path: \
+             logs/${LOGFORTH_TEST_UNSET_VAR}"
+        );
+        assert!(expand("logs/${POD_NAME", &vars).is_err());
+        let empty = BTreeMap::from([("POD_NAME".to_string(), String::new())]);
+        assert!(expand("logs/${POD_NAME}", &empty).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A registry of the files written by live rolling file writers, to detect writers configured
+//! more than once.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FileSet {
+    dir: PathBuf,
+    prefix: Option<String>,
+    suffix: Option<String>,
+}
+
+fn writers() -> &'static Mutex<HashMap<FileSet, usize>> {
+    static WRITERS: OnceLock<Mutex<HashMap<FileSet, usize>>> = OnceLock::new();
+    WRITERS.get_or_init(Default::default)
+}
+
+/// The registration of a live writer, released on drop.
+#[derive(Debug)]
+pub(super) struct Registration {
+    files: FileSet,
+}
+
+impl Registration {
+    /// Registers a writer of the given files, returning the number of other live writers of the
+    /// same files.
+    pub(super) fn new(
+        dir: &Path,
+        prefix: Option<&str>,
+        suffix: Option<&str>,
+    ) -> (Registration, usize) {
+        let files = FileSet {
+            dir: std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf()),
+            prefix: prefix.map(str::to_string),
+            suffix: suffix.map(str::to_string),
+        };
+        let mut writers = writers().lock().unwrap_or_else(|e| e.into_inner());
+        let count = writers.entry(files.clone()).or_default();
+        *count += 1;
+        (Registration { files }, *count - 1)
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut writers = writers().lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = writers.get_mut(&self.files) {
+            *count -= 1;
+            if *count == 0 {
+                writers.remove(&self.files);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_writers() {
+        let dir = Path::new("registry-test-logs");
+        let (first, others) = Registration::new(dir, Some("app"), Some("log"));
+        assert_eq!(others, 0);
+        let (_other, others) = Registration::new(dir, Some("app"), None);
+        assert_eq!(others, 0);
+
+        let relative = Path::new("./registry-test-logs");
+        let (second, others) = Registration::new(relative, Some("app"), Some("log"));
+        assert_eq!(others, 1);
+
+        drop(first);
+        drop(second);
+        let (_third, others) = Registration::new(dir, Some("app"), Some("log"));
+        assert_eq!(others, 0);
+    }
+}
@@ -12,25 +12,44 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
-use std::fs::File;
-use std::fs::OpenOptions;
 use std::io;
+use std::io::IoSlice;
 use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use anyhow::Context;
 use jiff::Zoned;
 
 use crate::append::rolling_file::clock::Clock;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+use crate::append::rolling_file::compression::Compressor;
+use crate::append::rolling_file::make_writer::AppendFile;
+use crate::append::rolling_file::path_vars;
+use crate::append::rolling_file::registry::Registration;
+use crate::append::rolling_file::staging::StagingWriter;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+use crate::append::rolling_file::Compression;
+use crate::append::rolling_file::MakeWriter;
 use crate::append::rolling_file::Rotation;
+#[cfg(any(test, feature = "test-util"))]
+use crate::clock::ManualClock;
+use crate::startup;
 
 /// A writer for rolling files.
 #[derive(Debug)]
 pub struct RollingFileWriter {
-    state: State,
-    writer: File,
+    sink: Sink,
+}
+
+#[derive(Debug)]
+enum Sink {
+    Direct(Box<FileWriter>),
+    Staged(StagingWriter),
 }
 
 impl RollingFileWriter {
@@ -49,8 +68,41 @@ impl RollingFileWriter {
     }
 }
 
-impl Write for RollingFileWriter {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+impl RollingFileWriter {
+    #[cfg(test)]
+    fn direct(&mut self) -> &mut FileWriter {
+        match &mut self.sink {
+            Sink::Direct(writer) => writer,
+            Sink::Staged(_) => panic!("the writer is staged"),
+        }
+    }
+}
+
+/// Writes directly to the current log file, rolling it over when needed.
+pub(super) struct FileWriter {
+    state: State,
+    writer: Box<dyn Write + Send + Sync>,
+}
+
+impl fmt::Debug for FileWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileWriter")
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FileWriter {
+    /// Reopens the current log file, e.g., after the file system became unavailable.
+    pub(super) fn reopen(&mut self) -> anyhow::Result<()> {
+        let now = self.state.clock.now();
+        self.writer = self
+            .state
+            .create_log_writer(&now, self.state.current_count)?;
+        Ok(())
+    }
+
+    fn rollover_if_needed(&mut self) {
         let now = self.state.clock.now();
         let writer = &mut self.writer;
         if self.state.should_rollover_on_date(&now) {
@@ -61,12 +113,59 @@ impl Write for RollingFileWriter {
             let cnt = self.state.advance_cnt();
             self.state.refresh_writer(&now, cnt, writer);
         }
+    }
+}
 
-        writer
+impl Write for RollingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.sink {
+            Sink::Direct(writer) => writer.write(buf),
+            Sink::Staged(writer) => writer.write(buf),
+        }
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        match &mut self.sink {
+            Sink::Direct(writer) => writer.write_vectored(bufs),
+            Sink::Staged(writer) => writer.write_vectored(bufs),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.sink {
+            Sink::Direct(writer) => writer.flush(),
+            Sink::Staged(writer) => writer.flush(),
+        }
+    }
+}
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.rollover_if_needed();
+        self.writer
             .write(buf)
             .inspect(|&n| self.state.current_filesize += n)
     }
 
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.rollover_if_needed();
+        // write the records up to the size limit only, so that the file rolls over before the
+        // rest like when the records are written one by one
+        let mut filesize = self.state.current_filesize;
+        let count = bufs
+            .iter()
+            .take_while(|buf| {
+                let fits = filesize < self.state.max_size;
+                filesize += buf.len();
+                fits
+            })
+            .count();
+        let bufs = &bufs[..count.max(1).min(bufs.len())];
+        self.writer
+            .write_vectored(bufs)
+            .inspect(|&n| self.state.current_filesize += n)
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         self.writer.flush()
     }
@@ -80,6 +179,15 @@ pub struct RollingFileWriterBuilder {
     suffix: Option<String>,
     max_size: usize,
     max_files: Option<usize>,
+    max_total_size: Option<u64>,
+    omit_first_index: bool,
+    filename_pattern: Option<String>,
+    path_vars: BTreeMap<String, String>,
+    on_rotate: Option<OnRotate>,
+    make_writer: Option<Box<dyn MakeWriter>>,
+    max_staged_bytes: Option<usize>,
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    compression: Option<Compression>,
     clock: Clock,
 }
 
@@ -99,6 +207,15 @@ impl RollingFileWriterBuilder {
             suffix: None,
             max_size: usize::MAX,
             max_files: None,
+            max_total_size: None,
+            omit_first_index: false,
+            filename_pattern: None,
+            path_vars: BTreeMap::new(),
+            on_rotate: None,
+            make_writer: None,
+            max_staged_bytes: None,
+            #[cfg(any(feature = "gzip", feature = "zstd"))]
+            compression: None,
             clock: Clock::DefaultClock,
         }
     }
@@ -141,6 +258,18 @@ impl RollingFileWriterBuilder {
         self
     }
 
+    /// Sets the maximum total size in bytes of the log files to keep.
+    ///
+    /// Whenever a log file is created, the oldest log files are deleted until the total size of
+    /// the remaining ones fits in the budget, regardless of [`max_log_files`](Self::max_log_files).
+    /// Compressed log files count with their compressed size. The file being written is never
+    /// deleted, so it may exceed the budget on its own until it is rotated.
+    #[must_use]
+    pub fn max_total_size(mut self, n: u64) -> Self {
+        self.max_total_size = Some(n);
+        self
+    }
+
     /// Sets the maximum size of a log file in bytes.
     #[must_use]
     pub fn max_file_size(mut self, n: usize) -> Self {
@@ -148,30 +277,226 @@ impl RollingFileWriterBuilder {
         self
     }
 
-    #[cfg(test)]
-    fn clock(mut self, clock: Clock) -> Self {
-        self.clock = clock;
+    /// Sets whether to omit the index of the first file of each period for time-based rotation.
+    ///
+    /// By default, every file name carries an index, e.g., `app.2024-08-10.0.log`. When enabled,
+    /// the first file of a period is named `app.2024-08-10.log`, and indexes are only appended
+    /// when the file is split by size within the same period, e.g., `app.2024-08-10.1.log`.
+    ///
+    /// No effect with [`Rotation::Never`].
+    #[must_use]
+    pub fn omit_first_index(mut self, omit_first_index: bool) -> Self {
+        self.omit_first_index = omit_first_index;
+        self
+    }
+
+    /// Sets the pattern of the log file names, e.g., `app-%Y%m%d-%N.log`.
+    ///
+    /// The pattern is formatted with [`strftime`](jiff::fmt::strtime) at the start of each
+    /// period, with `%N` being the index of the file within the period. It replaces the default
+    /// `prefix.date.index.suffix` naming, so [`filename_prefix`](Self::filename_prefix),
+    /// [`filename_suffix`](Self::filename_suffix) and
+    /// [`omit_first_index`](Self::omit_first_index) have no effect.
+    ///
+    /// The text before the first and after the last conversion specifier identifies the log
+    /// files to delete for [`max_log_files`](Self::max_log_files), so at least one of them must
+    /// be non-empty. Include `%N` when files are rotated by size, otherwise the files of a period
+    /// are written to the same file.
+    #[must_use]
+    pub fn filename_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.filename_pattern = Some(pattern.into());
+        self
+    }
+
+    /// Sets the value of the placeholder `${name}` in the directory and the file names, e.g.,
+    /// `logs/${POD_NAME}`.
+    ///
+    /// Placeholders without a value set here are resolved from the environment variable of the
+    /// same name. [`build`](Self::build) fails if a placeholder is unresolved or empty.
+    #[must_use]
+    pub fn path_var(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.path_vars.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sets a callback called with the paths of the previous and the new log file whenever the
+    /// writer moves on to a new file, e.g., to ship the previous one elsewhere.
+    ///
+    /// The callback is called on the thread writing the records, so it should hand off slow work.
+    /// With [`compress`](Self::compress) enabled, it's called from the compression thread with
+    /// the path of the compressed file once the previous one is compressed, unless the previous
+    /// file is deleted for [`max_log_files`](Self::max_log_files) before.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::append::rolling_file::RollingFileWriter;
+    ///
+    /// let builder = RollingFileWriter::builder().on_rotate(|previous, new| {
+    ///     println!("rotated {} to {}", previous.display(), new.display());
+    /// });
+    /// ```
+    #[must_use]
+    pub fn on_rotate<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Path, &Path) + Send + Sync + 'static,
+    {
+        self.on_rotate = Some(OnRotate(Arc::new(callback)));
+        self
+    }
+
+    /// Sets how the writers of log files are opened. Default to opening them with
+    /// [`std::fs::File`] in append mode.
+    ///
+    /// Deleting old log files and compressing rotated ones still operate on the files in the log
+    /// directory. See [`MakeWriter`] for details.
+    #[must_use]
+    pub fn make_writer(mut self, make_writer: impl MakeWriter) -> Self {
+        self.make_writer = Some(Box::new(make_writer));
+        self
+    }
+
+    /// Enables the resilient mode for slow or occasionally unavailable file systems, such as NFS
+    /// or SMB mounts, with a staging buffer of at most `max_staged_bytes`.
+    ///
+    /// Writes go to the in-memory staging buffer and return immediately, while a background
+    /// thread writes the staged records to the log files, retrying with backoff and reopening the
+    /// current file when the file system fails. Rotation is still computed on the logical size of
+    /// the written records. When the staging buffer is full, new records are dropped.
+    ///
+    /// Dropping the writer, e.g., with the [`WorkerGuard`](crate::non_blocking::WorkerGuard),
+    /// waits a few seconds at most for the staged records to be written and the background thread
+    /// to exit. The records left behind are reported to the
+    /// [error handler](crate::RuntimeConfig::error_handler).
+    #[must_use]
+    pub fn staging(mut self, max_staged_bytes: usize) -> Self {
+        self.max_staged_bytes = Some(max_staged_bytes);
+        self
+    }
+
+    /// Compresses log files once they are rotated.
+    ///
+    /// When the writer moves on to a new file, the previous one is compressed in a background
+    /// thread, e.g., `app.2024-08-10.0.log` into `app.2024-08-10.0.log.gz`, and removed once
+    /// compressed. Files are compressed one at a time under a hidden temporary name, so that an
+    /// interrupted compression leaves no partial archive behind. Compressed files count toward
+    /// [`max_log_files`](Self::max_log_files); a file deleted for that while waiting to be
+    /// compressed is not compressed.
+    ///
+    /// Dropping the writer waits for the pending compressions. With
+    /// [`non_blocking`](super::non_blocking), dropping the
+    /// [`WorkerGuard`](crate::non_blocking::WorkerGuard) does so for at most the shutdown timeout.
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    #[must_use]
+    pub fn compress(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Sets a [`ManualClock`] to compute the rotation from, for deterministic tests of rotation
+    /// boundaries.
+    #[cfg(any(test, feature = "test-util"))]
+    #[must_use]
+    pub fn clock(mut self, clock: ManualClock) -> Self {
+        self.clock = Clock::ManualClock(clock);
         self
     }
 
     /// Builds the [`RollingFileWriter`].
-    pub fn build(self, dir: impl AsRef<Path>) -> anyhow::Result<RollingFileWriter> {
-        let Self {
-            rotation,
-            prefix,
-            suffix,
-            max_size,
-            max_files,
-            clock,
-        } = self;
-        let directory = dir.as_ref().to_path_buf();
-        let (state, writer) = State::new(
-            rotation, directory, prefix, suffix, max_size, max_files, clock,
-        )?;
-        Ok(RollingFileWriter { state, writer })
+    ///
+    /// Writers of the same files rotate them independently and corrupt each other's rotation, so
+    /// building a writer of the files of a live writer prints a warning to stderr. To write the
+    /// same files from several dispatches, share one appender with `Arc` instead.
+    ///
+    /// The `${NAME}` placeholders of `dir` and the file names are resolved here; see
+    /// [`path_var`](Self::path_var).
+    pub fn build(mut self, dir: impl AsRef<Path>) -> anyhow::Result<RollingFileWriter> {
+        if let Rotation::DailyAt { hour } = self.rotation {
+            if hour > 23 {
+                anyhow::bail!("rotation hour must be within 0..=23: {hour}");
+            }
+        }
+
+        let vars = &self.path_vars;
+        let dir = match dir.as_ref().to_str() {
+            Some(dir) => PathBuf::from(path_vars::expand(dir, vars)?),
+            None => dir.as_ref().to_path_buf(),
+        };
+        for name in [
+            &mut self.prefix,
+            &mut self.suffix,
+            &mut self.filename_pattern,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *name = path_vars::expand(name, vars)?;
+        }
+
+        let max_staged_bytes = self.max_staged_bytes;
+        let (state, writer) = State::new(self, dir)?;
+        state.report(max_staged_bytes);
+        let writer = FileWriter { state, writer };
+        let sink = match max_staged_bytes {
+            Some(max_staged_bytes) => Sink::Staged(StagingWriter::new(writer, max_staged_bytes)?),
+            None => Sink::Direct(Box::new(writer)),
+        };
+        Ok(RollingFileWriter { sink })
+    }
+}
+
+type RotateCallback = Arc<dyn Fn(&Path, &Path) + Send + Sync>;
+
+/// A callback called with the previous and the new log file on rotation.
+#[derive(Clone)]
+struct OnRotate(RotateCallback);
+
+impl std::fmt::Debug for OnRotate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("OnRotate(..)")
     }
 }
 
+/// Replaces the `%N` specifiers of a file name pattern with the index of the file.
+fn expand_index(pattern: &str, cnt: usize) -> String {
+    let mut expanded = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('N') => expanded.push_str(&cnt.to_string()),
+            Some(c) => {
+                expanded.push('%');
+                expanded.push(c);
+            }
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+
+/// Returns the literal text before the first and after the last conversion specifier of a file
+/// name pattern.
+fn pattern_literals(pattern: &str) -> (Option<String>, Option<String>) {
+    let non_empty = |s: &str| Some(s.to_string()).filter(|s| !s.is_empty());
+    let Some(first) = pattern.find('%') else {
+        return (non_empty(pattern), None);
+    };
+    let last = pattern.rfind('%').unwrap_or(first);
+    // a specifier ends with its conversion character, after the optional flags and width
+    let suffix = pattern[last + 1..]
+        .char_indices()
+        .find(|(_, c)| c.is_ascii_alphabetic() || *c == '%')
+        .map_or("", |(i, c)| &pattern[last + 1 + i + c.len_utf8()..]);
+    (non_empty(&pattern[..first]), non_empty(suffix))
+}
+
+/// The extensions of compressed log files, which count toward the maximum number of log files.
+const COMPRESSED_EXTENSIONS: [&str; 2] = [".gz", ".zst"];
+
 #[derive(Debug)]
 struct State {
     log_dir: PathBuf,
@@ -184,28 +509,80 @@ struct State {
     next_date_timestamp: Option<usize>,
     max_size: usize,
     max_files: Option<usize>,
+    max_total_size: Option<u64>,
+    omit_first_index: bool,
+    filename_pattern: Option<String>,
+    on_rotate: Option<OnRotate>,
+    make_writer: Box<dyn MakeWriter>,
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    compressor: Option<Compressor>,
+    // the path of the file being written
+    current_path: PathBuf,
     clock: Clock,
+    _registration: Registration,
 }
 
 impl State {
     fn new(
-        rotation: Rotation,
+        builder: RollingFileWriterBuilder,
         dir: impl AsRef<Path>,
-        log_filename_prefix: Option<String>,
-        log_filename_suffix: Option<String>,
-        max_size: usize,
-        max_files: Option<usize>,
-        clock: Clock,
-    ) -> anyhow::Result<(Self, File)> {
+    ) -> anyhow::Result<(Self, Box<dyn Write + Send + Sync>)> {
+        let RollingFileWriterBuilder {
+            rotation,
+            prefix: log_filename_prefix,
+            suffix: log_filename_suffix,
+            max_size,
+            max_files,
+            max_total_size,
+            omit_first_index,
+            filename_pattern,
+            path_vars: _,
+            on_rotate,
+            make_writer,
+            max_staged_bytes: _,
+            #[cfg(any(feature = "gzip", feature = "zstd"))]
+            compression,
+            clock,
+        } = builder;
+        let make_writer = make_writer.unwrap_or_else(|| Box::new(AppendFile));
         let log_dir = dir.as_ref().to_path_buf();
-        let date_format = rotation.date_format();
         let now = clock.now();
+        // with a pattern, its literal text identifies the log files instead
+        let (log_filename_prefix, log_filename_suffix) = match &filename_pattern {
+            Some(pattern) => {
+                jiff::fmt::strtime::format(expand_index(pattern, 0).as_str(), &now)
+                    .with_context(|| format!("invalid filename pattern: {pattern}"))?;
+                let literals = pattern_literals(pattern);
+                if literals == (None, None) {
+                    anyhow::bail!(
+                        "filename pattern must start or end with literal text: {pattern}"
+                    );
+                }
+                literals
+            }
+            None => (log_filename_prefix, log_filename_suffix),
+        };
+        let (registration, others) = Registration::new(
+            &log_dir,
+            log_filename_prefix.as_deref(),
+            log_filename_suffix.as_deref(),
+        );
+        if others > 0 {
+            eprintln!(
+                "rolling files in {} are written by {} other writers, which rotate them \
+                independently; share one appender across dispatches with `Arc` instead",
+                log_dir.display(),
+                others,
+            );
+        }
+        let date_format = rotation.date_format();
         let next_date_timestamp = rotation.next_date_timestamp(&now);
 
         let current_count = 0;
         let current_filesize = 0;
 
-        let state = State {
+        let mut state = State {
+            current_path: log_dir.clone(),
             log_dir,
             log_filename_prefix,
             log_filename_suffix,
@@ -216,15 +593,73 @@ impl State {
             rotation,
             max_size,
             max_files,
+            max_total_size,
+            omit_first_index,
+            filename_pattern,
+            on_rotate,
+            make_writer,
+            #[cfg(any(feature = "gzip", feature = "zstd"))]
+            compressor: compression.map(Compressor::new),
             clock,
+            _registration: registration,
         };
 
         let file = state.create_log_writer(&now, 0)?;
         Ok((state, file))
     }
 
+    fn report(&self, max_staged_bytes: Option<usize>) {
+        fn or_none(value: Option<impl ToString>) -> String {
+            value.map_or_else(|| "none".to_string(), |value| value.to_string())
+        }
+
+        let log_dir = std::path::absolute(&self.log_dir).unwrap_or_else(|_| self.log_dir.clone());
+        let max_size = Some(self.max_size).filter(|&max_size| max_size != usize::MAX);
+        startup::report(
+            "rolling file writer",
+            vec![
+                ("log_dir", log_dir.display().to_string()),
+                (
+                    "filename_prefix",
+                    or_none(self.log_filename_prefix.as_ref()),
+                ),
+                (
+                    "filename_suffix",
+                    or_none(self.log_filename_suffix.as_ref()),
+                ),
+                ("rotation", format!("{:?}", self.rotation)),
+                ("filename_pattern", or_none(self.filename_pattern.as_ref())),
+                ("max_file_size", or_none(max_size)),
+                ("max_log_files", or_none(self.max_files)),
+                ("max_total_size", or_none(self.max_total_size)),
+                ("max_staged_bytes", or_none(max_staged_bytes)),
+                ("compression", self.compression_name()),
+            ],
+        );
+    }
+
+    fn compression_name(&self) -> String {
+        #[cfg(any(feature = "gzip", feature = "zstd"))]
+        if let Some(compressor) = &self.compressor {
+            return format!("{:?}", compressor.compression());
+        }
+        "none".to_string()
+    }
+
     fn join_date(&self, date: &Zoned, cnt: usize) -> String {
+        if let Some(pattern) = &self.filename_pattern {
+            // the pattern is validated when the writer is built
+            return date.strftime(&expand_index(pattern, cnt)).to_string();
+        }
         let date = date.strftime(self.date_format);
+        if self.omit_first_index && cnt == 0 && self.rotation != Rotation::Never {
+            return match (&self.log_filename_prefix, &self.log_filename_suffix) {
+                (Some(filename), Some(suffix)) => format!("{filename}.{date}.{suffix}"),
+                (Some(filename), None) => format!("{filename}.{date}"),
+                (None, Some(suffix)) => format!("{date}.{suffix}"),
+                (None, None) => date.to_string(),
+            };
+        }
         match (
             &self.rotation,
             &self.log_filename_prefix,
@@ -242,22 +677,33 @@ impl State {
         }
     }
 
-    fn create_log_writer(&self, now: &Zoned, cnt: usize) -> anyhow::Result<File> {
+    fn create_log_writer(
+        &mut self,
+        now: &Zoned,
+        cnt: usize,
+    ) -> anyhow::Result<Box<dyn Write + Send + Sync>> {
         fs::create_dir_all(&self.log_dir).context("failed to create log directory")?;
-        let filename = self.join_date(now, cnt);
-        if let Some(max_files) = self.max_files {
-            if let Err(err) = self.delete_oldest_logs(max_files) {
+        let path = self.log_dir.join(self.join_date(now, cnt));
+        if self.max_files.is_some() || self.max_total_size.is_some() {
+            if let Err(err) = self.delete_oldest_logs(&path) {
                 eprintln!("failed to delete oldest logs: {err}");
             }
         }
-        OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(self.log_dir.join(filename))
-            .context("failed to create log file")
+        let file = self
+            .make_writer
+            .make_writer(&path)
+            .context("failed to create log file")?;
+        self.current_path = path;
+        Ok(file)
     }
 
-    fn delete_oldest_logs(&self, max_files: usize) -> anyhow::Result<()> {
+    /// Deletes the oldest log files before `opening` is opened, to stay within the maximum number
+    /// and the maximum total size of log files.
+    fn delete_oldest_logs(&self, opening: &Path) -> anyhow::Result<()> {
+        // files being compressed are not replaced by their archives while the directory is listed
+        #[cfg(any(feature = "gzip", feature = "zstd"))]
+        let mut in_progress = self.compressor.as_ref().map(Compressor::in_progress);
+
         let read_dir = fs::read_dir(&self.log_dir)
             .with_context(|| format!("failed to read log dir: {}", self.log_dir.display()))?;
 
@@ -275,6 +721,11 @@ impl State {
                 let filename = entry.file_name();
                 // if the filename is not a UTF-8 string, skip it.
                 let filename = filename.to_str()?;
+                // compressed log files count as the log files they were compressed from
+                let filename = COMPRESSED_EXTENSIONS
+                    .iter()
+                    .find_map(|extension| filename.strip_suffix(extension))
+                    .unwrap_or(filename);
                 if let Some(prefix) = &self.log_filename_prefix {
                     if !filename.starts_with(prefix) {
                         return None;
@@ -294,40 +745,85 @@ impl State {
                     return None;
                 }
 
-                let created = metadata.created().ok()?;
-                Some((entry, created))
+                let modified = metadata.modified().ok()?;
+                Some((entry.path(), modified, metadata.len()))
             })
+            .filter(|(path, _, _)| path != opening)
             .collect::<Vec<_>>();
 
-        if files.len() < max_files {
+        // delete files, so that (n-1) files remain, because we will create another log file
+        let mut excess_files = self
+            .max_files
+            .map_or(0, |max_files| (files.len() + 1).saturating_sub(max_files));
+        let mut total_size = files.iter().map(|(_, _, len)| len).sum::<u64>();
+        let over_budget = |total_size: u64| {
+            self.max_total_size
+                .is_some_and(|max_total_size| total_size > max_total_size)
+        };
+        if excess_files == 0 && !over_budget(total_size) {
             return Ok(());
         }
 
-        // sort the files by their creation timestamps.
-        files.sort_by_key(|(_, created_at)| *created_at);
+        // sort the files by their modification timestamps, which archives keep from their logs
+        files.sort_by_key(|(_, modified_at, _)| *modified_at);
 
-        // delete files, so that (n-1) files remain, because we will create another log file
-        for (file, _) in files.iter().take(files.len() - (max_files - 1)) {
-            fs::remove_file(file.path()).with_context(|| {
-                format!("Failed to remove old log file {}", file.path().display())
-            })?;
+        for (path, _, len) in files {
+            if excess_files == 0 && !over_budget(total_size) {
+                break;
+            }
+            excess_files = excess_files.saturating_sub(1);
+            total_size -= len;
+            // the compressor deletes the file instead of compressing it
+            #[cfg(any(feature = "gzip", feature = "zstd"))]
+            if let Some(deleted) = in_progress
+                .as_mut()
+                .and_then(|in_progress| in_progress.get_mut(&path))
+            {
+                *deleted = true;
+                continue;
+            }
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove old log file {}", path.display()))?;
         }
 
         Ok(())
     }
 
-    fn refresh_writer(&self, now: &Zoned, cnt: usize, file: &mut File) {
+    fn refresh_writer(&mut self, now: &Zoned, cnt: usize, file: &mut Box<dyn Write + Send + Sync>) {
+        let previous_path = self.current_path.clone();
         match self.create_log_writer(now, cnt) {
             Ok(new_file) => {
                 if let Err(err) = file.flush() {
                     eprintln!("failed to flush previous writer: {err}");
                 }
                 *file = new_file;
+                if previous_path != self.current_path {
+                    self.on_rotated(previous_path);
+                }
             }
             Err(err) => eprintln!("failed to create writer for logs: {err}"),
         }
     }
 
+    /// Compresses the previous log file, if enabled, and calls the rotation callback.
+    fn on_rotated(&mut self, previous_path: PathBuf) {
+        #[cfg(any(feature = "gzip", feature = "zstd"))]
+        if let Some(compressor) = &mut self.compressor {
+            let on_rotate = self.on_rotate.clone();
+            let current_path = self.current_path.clone();
+            compressor.compress(previous_path, move |archive: &Path| {
+                if let Some(OnRotate(callback)) = on_rotate {
+                    callback(archive, &current_path);
+                }
+            });
+            return;
+        }
+
+        if let Some(OnRotate(callback)) = &self.on_rotate {
+            callback(&previous_path, &self.current_path);
+        }
+    }
+
     fn should_rollover_on_date(&self, date: &Zoned) -> bool {
         self.next_date_timestamp
             .is_some_and(|ts| date.timestamp().as_millisecond() as usize >= ts)
@@ -354,6 +850,7 @@ impl State {
 mod tests {
     use std::cmp::min;
     use std::fs;
+    use std::io;
     use std::io::Write;
     use std::ops::Add;
     use std::str::FromStr;
@@ -364,10 +861,9 @@ mod tests {
     use rand::Rng;
     use tempfile::TempDir;
 
-    use crate::append::rolling_file::clock::Clock;
-    use crate::append::rolling_file::clock::ManualClock;
     use crate::append::rolling_file::RollingFileWriterBuilder;
     use crate::append::rolling_file::Rotation;
+    use crate::clock::ManualClock;
 
     #[test]
     fn test_file_rolling_via_file_size() {
@@ -396,17 +892,44 @@ mod tests {
                 let rand_str = generate_random_string();
                 expected_file_size += rand_str.len();
                 assert_eq!(writer.write(rand_str.as_bytes()).unwrap(), rand_str.len());
-                assert_eq!(writer.state.current_filesize, expected_file_size);
+                assert_eq!(writer.direct().state.current_filesize, expected_file_size);
             }
 
             writer.flush().unwrap();
             assert_eq!(
-                fs::read_dir(&writer.state.log_dir).unwrap().count(),
+                fs::read_dir(&writer.direct().state.log_dir)
+                    .unwrap()
+                    .count(),
                 min(i, max_files)
             );
         }
     }
 
+    #[test]
+    fn test_file_rolling_via_file_size_in_batch() {
+        let temp_dir = TempDir::new().expect("failed to create a temporary directory");
+
+        let mut writer = RollingFileWriterBuilder::new()
+            .rotation(Rotation::Never)
+            .filename_prefix("test_prefix")
+            .filename_suffix("log")
+            .max_file_size(10)
+            .build(&temp_dir)
+            .unwrap();
+
+        let records = vec![b"record".to_vec(); 5];
+        crate::non_blocking::Writer::write_all_batch(&mut writer, &records).unwrap();
+        writer.flush().unwrap();
+
+        // each file takes records until it reaches the size limit
+        let mut sizes = fs::read_dir(&temp_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().metadata().unwrap().len())
+            .collect::<Vec<_>>();
+        sizes.sort_unstable();
+        assert_eq!(sizes, [6, 12, 12]);
+    }
+
     #[test]
     fn test_file_rolling_via_time_rotation() {
         test_file_rolling_for_specific_time_rotation(
@@ -435,13 +958,14 @@ mod tests {
         let max_files = 10;
 
         let start_time = Zoned::from_str("2024-08-10T00:00:00[UTC]").unwrap();
+        let clock = ManualClock::new(start_time.clone());
         let mut writer = RollingFileWriterBuilder::new()
             .rotation(rotation)
             .filename_prefix("test_prefix")
             .filename_suffix("log")
             .max_log_files(max_files)
             .max_file_size(usize::MAX)
-            .clock(Clock::ManualClock(ManualClock::new(start_time.clone())))
+            .clock(clock.clone())
             .build(&temp_dir)
             .unwrap();
 
@@ -451,20 +975,22 @@ mod tests {
             let mut expected_file_size = 0;
             let end_time = cur_time.add(rotation_duration);
             while cur_time < end_time {
-                writer.state.clock.set_now(cur_time.clone());
+                clock.set_now(cur_time.clone());
 
                 let rand_str = generate_random_string();
                 expected_file_size += rand_str.len();
 
                 assert_eq!(writer.write(rand_str.as_bytes()).unwrap(), rand_str.len());
-                assert_eq!(writer.state.current_filesize, expected_file_size);
+                assert_eq!(writer.direct().state.current_filesize, expected_file_size);
 
                 cur_time = cur_time.add(write_interval);
             }
 
             writer.flush().unwrap();
             assert_eq!(
-                fs::read_dir(&writer.state.log_dir).unwrap().count(),
+                fs::read_dir(&writer.direct().state.log_dir)
+                    .unwrap()
+                    .count(),
                 min(i, max_files)
             );
         }
@@ -502,13 +1028,14 @@ mod tests {
         let file_size = 500;
 
         let start_time = Zoned::from_str("2024-08-10T00:00:00[UTC]").unwrap();
+        let clock = ManualClock::new(start_time.clone());
         let mut writer = RollingFileWriterBuilder::new()
             .rotation(rotation)
             .filename_prefix("test_prefix")
             .filename_suffix("log")
             .max_log_files(max_files)
             .max_file_size(file_size)
-            .clock(Clock::ManualClock(ManualClock::new(start_time.clone())))
+            .clock(clock.clone())
             .build(&temp_dir)
             .unwrap();
 
@@ -520,13 +1047,13 @@ mod tests {
         for i in 1..=total_files {
             let mut expected_file_size = 0;
             loop {
-                writer.state.clock.set_now(cur_time.clone());
+                clock.set_now(cur_time.clone());
 
                 let rand_str = generate_random_string();
                 expected_file_size += rand_str.len();
 
                 assert_eq!(writer.write(rand_str.as_bytes()).unwrap(), rand_str.len());
-                assert_eq!(writer.state.current_filesize, expected_file_size);
+                assert_eq!(writer.direct().state.current_filesize, expected_file_size);
 
                 cur_time = cur_time.add(write_interval);
 
@@ -543,7 +1070,9 @@ mod tests {
 
             writer.flush().unwrap();
             assert_eq!(
-                fs::read_dir(&writer.state.log_dir).unwrap().count(),
+                fs::read_dir(&writer.direct().state.log_dir)
+                    .unwrap()
+                    .count(),
                 min(i, max_files)
             );
         }
@@ -551,6 +1080,291 @@ mod tests {
         assert!(time_rotation_trigger);
     }
 
+    #[test]
+    fn test_omit_first_index() {
+        let temp_dir = TempDir::new().expect("failed to create a temporary directory");
+
+        let start_time = Zoned::from_str("2024-08-10T00:00:00[UTC]").unwrap();
+        let clock = ManualClock::new(start_time.clone());
+        let mut writer = RollingFileWriterBuilder::new()
+            .rotation(Rotation::Daily)
+            .filename_prefix("app")
+            .filename_suffix("log")
+            .max_file_size(10)
+            .omit_first_index(true)
+            .clock(clock.clone())
+            .build(&temp_dir)
+            .unwrap();
+
+        writer.write_all(b"0123456789").unwrap();
+        writer.write_all(b"0123456789").unwrap();
+        clock.set_now(start_time.add(Span::new().days(1)));
+        writer.write_all(b"0123456789").unwrap();
+        writer.flush().unwrap();
+
+        let mut filenames = fs::read_dir(&temp_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        filenames.sort();
+        assert_eq!(
+            filenames,
+            [
+                "app.2024-08-10.1.log",
+                "app.2024-08-10.log",
+                "app.2024-08-11.log"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filename_pattern() {
+        use std::sync::Arc;
+        use std::sync::Mutex;
+
+        let temp_dir = TempDir::new().expect("failed to create a temporary directory");
+        let start_time = Zoned::from_str("2024-08-10T17:12:52[UTC]").unwrap();
+        let clock = ManualClock::new(start_time.clone());
+        let rotations = Arc::new(Mutex::new(vec![]));
+        let mut writer = RollingFileWriterBuilder::new()
+            .rotation(Rotation::Daily)
+            .filename_pattern("app-%Y%m%d-%N.log")
+            .max_file_size(10)
+            .on_rotate({
+                let rotations = rotations.clone();
+                move |previous, new| {
+                    let name = |path: &std::path::Path| {
+                        path.file_name().unwrap().to_str().unwrap().to_string()
+                    };
+                    rotations.lock().unwrap().push((name(previous), name(new)));
+                }
+            })
+            .clock(clock.clone())
+            .build(&temp_dir)
+            .unwrap();
+
+        writer.write_all(b"0123456789").unwrap();
+        writer.write_all(b"0123456789").unwrap();
+        clock.set_now(start_time.add(Span::new().days(1)));
+        writer.write_all(b"0123456789").unwrap();
+        writer.flush().unwrap();
+
+        let mut filenames = fs::read_dir(&temp_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        filenames.sort();
+        assert_eq!(
+            filenames,
+            [
+                "app-20240810-0.log",
+                "app-20240810-1.log",
+                "app-20240811-0.log"
+            ]
+        );
+        assert_eq!(
+            *rotations.lock().unwrap(),
+            [
+                (
+                    "app-20240810-0.log".to_string(),
+                    "app-20240810-1.log".to_string()
+                ),
+                (
+                    "app-20240810-1.log".to_string(),
+                    "app-20240811-0.log".to_string()
+                ),
+            ]
+        );
+
+        assert!(RollingFileWriterBuilder::new()
+            .filename_pattern("%Y%m%d")
+            .build(&temp_dir)
+            .is_err());
+    }
+
+    #[test]
+    fn test_invalid_rotation_hour() {
+        let temp_dir = TempDir::new().expect("failed to create a temporary directory");
+        for hour in [24, u8::MAX] {
+            let err = RollingFileWriterBuilder::new()
+                .rotation(Rotation::DailyAt { hour })
+                .build(&temp_dir)
+                .unwrap_err();
+            assert!(err.to_string().contains("rotation hour"));
+        }
+    }
+
+    #[test]
+    fn test_pattern_literals() {
+        use super::pattern_literals;
+
+        let some = |s: &str| Some(s.to_string());
+        assert_eq!(
+            pattern_literals("app-%Y%m%d-%N.log"),
+            (some("app-"), some(".log"))
+        );
+        assert_eq!(pattern_literals("%Y-%-m-%3f.log"), (None, some(".log")));
+        assert_eq!(pattern_literals("app.%Y"), (some("app."), None));
+        assert_eq!(pattern_literals("app.log"), (some("app.log"), None));
+    }
+
+    #[test]
+    fn test_path_vars() {
+        let temp_dir = TempDir::new().expect("failed to create a temporary directory");
+        let dir = temp_dir.path().join("${POD_NAME}");
+        let mut writer = RollingFileWriterBuilder::new()
+            .filename_prefix("${SERVICE}")
+            .filename_suffix("log")
+            .path_var("POD_NAME", "web-0")
+            .path_var("SERVICE", "checkout")
+            .build(&dir)
+            .unwrap();
+        writer.write_all(b"hello").unwrap();
+        writer.flush().unwrap();
+        let content = fs::read_to_string(temp_dir.path().join("web-0/checkout.0.log")).unwrap();
+        assert_eq!(content, "hello");
+
+        // unresolved placeholders fail at build time
+        let err = RollingFileWriterBuilder::new()
+            .build(temp_dir.path().join("${LOGFORTH_TEST_UNSET_VAR}"))
+            .unwrap_err();
+        assert!(err.to_string().contains("unresolved placeholder"));
+    }
+
+    #[test]
+    fn test_make_writer() {
+        use std::collections::BTreeMap;
+        use std::path::Path;
+        use std::sync::Arc;
+        use std::sync::Mutex;
+
+        use crate::append::rolling_file::MakeWriter;
+
+        type Files = Arc<Mutex<BTreeMap<String, Vec<u8>>>>;
+
+        #[derive(Debug, Default)]
+        struct InMemory(Files);
+
+        #[derive(Debug)]
+        struct InMemoryFile(Files, String);
+
+        impl MakeWriter for InMemory {
+            fn make_writer(&self, path: &Path) -> io::Result<Box<dyn Write + Send + Sync>> {
+                let name = path.file_name().unwrap().to_str().unwrap().to_string();
+                self.0.lock().unwrap().entry(name.clone()).or_default();
+                Ok(Box::new(InMemoryFile(self.0.clone(), name)))
+            }
+        }
+
+        impl Write for InMemoryFile {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let mut files = self.0.lock().unwrap();
+                files.get_mut(&self.1).unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let temp_dir = TempDir::new().expect("failed to create a temporary directory");
+        let make_writer = InMemory::default();
+        let files = make_writer.0.clone();
+        let mut writer = RollingFileWriterBuilder::new()
+            .filename_prefix("app")
+            .filename_suffix("log")
+            .max_file_size(10)
+            .make_writer(make_writer)
+            .build(&temp_dir)
+            .unwrap();
+
+        writer.write_all(b"0123456789").unwrap();
+        writer.write_all(b"abc").unwrap();
+        writer.flush().unwrap();
+
+        let files = files.lock().unwrap();
+        assert_eq!(files["app.0.log"], b"0123456789");
+        assert_eq!(files["app.1.log"], b"abc");
+        assert_eq!(fs::read_dir(&temp_dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_max_total_size() {
+        let temp_dir = TempDir::new().expect("failed to create a temporary directory");
+        let mut writer = RollingFileWriterBuilder::new()
+            .rotation(Rotation::Never)
+            .filename_prefix("app")
+            .filename_suffix("log")
+            .max_log_files(10)
+            .max_file_size(10)
+            .max_total_size(45)
+            .build(&temp_dir)
+            .unwrap();
+
+        // records of 10, 30, 10 and 10 bytes
+        for record in ["0".repeat(9), "1".repeat(29), "2".repeat(9), "3".repeat(9)] {
+            writer.write_all(format!("{record}\n").as_bytes()).unwrap();
+            writer.flush().unwrap();
+        }
+        writer.write_all(b"4\n").unwrap();
+        writer.flush().unwrap();
+
+        let mut filenames = fs::read_dir(&temp_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        filenames.sort();
+        // the oldest files are deleted until the others fit in 45 bytes, then the new one opens
+        assert_eq!(filenames, ["app.2.log", "app.3.log", "app.4.log"]);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_compress_rotated_files() {
+        use std::io::Read;
+
+        use crate::append::rolling_file::Compression;
+
+        let temp_dir = TempDir::new().expect("failed to create a temporary directory");
+        let mut writer = RollingFileWriterBuilder::new()
+            .rotation(Rotation::Never)
+            .filename_prefix("app")
+            .filename_suffix("log")
+            .max_log_files(2)
+            .max_file_size(10)
+            .compress(Compression::Gzip)
+            .build(&temp_dir)
+            .unwrap();
+
+        for i in 0..3 {
+            writer
+                .write_all(format!("file {i:04}\n").as_bytes())
+                .unwrap();
+            writer.flush().unwrap();
+        }
+
+        let list_files = || {
+            let mut filenames = fs::read_dir(&temp_dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect::<Vec<_>>();
+            filenames.sort();
+            filenames
+        };
+        // dropping the writer waits for the compression
+        drop(writer);
+
+        // the oldest archive is deleted as archives count toward the maximum number of files
+        assert_eq!(list_files(), ["app.1.log.gz", "app.2.log"]);
+
+        let mut content = String::new();
+        flate2::read::GzDecoder::new(fs::File::open(temp_dir.path().join("app.1.log.gz")).unwrap())
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "file 0001\n");
+    }
+
     fn generate_random_string() -> String {
         let mut rng = rand::thread_rng();
         let len = rng.gen_range(50..=100);
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use jiff::civil::Weekday;
 use jiff::RoundMode;
 use jiff::ToSpan;
 use jiff::Unit;
@@ -27,11 +28,30 @@ pub enum Rotation {
     Hourly,
     /// Rotate files every day.
     Daily,
+    /// Rotate files every day at the given hour (`0..=23`) of the clock's timezone.
+    ///
+    /// For example, `Rotation::DailyAt { hour: 3 }` rotates files at 03:00 every day. The hour is
+    /// the local wall clock time, so it stays the same across DST transitions; an hour skipped by
+    /// a transition rotates files at the first instant after the gap.
+    ///
+    /// Building a writer with an hour out of range fails, and such a rotation never rotates
+    /// files.
+    DailyAt {
+        /// The hour of the day to rotate files.
+        hour: u8,
+    },
+    /// Rotate files every week at the start of the given day of the week.
+    Weekly {
+        /// The first day of the week.
+        start: Weekday,
+    },
     /// Never rotate files.
     Never,
 }
 
 impl Rotation {
+    /// Returns the timestamp in milliseconds of the next rotation after `current_date`, or `None`
+    /// if files are never rotated, including for a [`Rotation::DailyAt`] hour out of range.
     pub fn next_date_timestamp(&self, current_date: &Zoned) -> Option<usize> {
         let timestamp_round = ZonedRound::new().mode(RoundMode::Trunc);
 
@@ -44,6 +64,28 @@ impl Rotation {
                 (current_date + 1.hour()).round(timestamp_round.smallest(Unit::Hour))
             }
             Rotation::Daily => (current_date + 1.day()).round(timestamp_round.smallest(Unit::Day)),
+            Rotation::DailyAt { hour } => {
+                let hour = i8::try_from(hour).ok().filter(|hour| *hour <= 23)?;
+                // resolve the hour on the wall clock of each day, so that the boundary keeps its
+                // local time across DST transitions
+                let tz = current_date.time_zone();
+                let at = |date: jiff::civil::Date| date.at(hour, 0, 0, 0).to_zoned(tz.clone());
+                at(current_date.date()).and_then(|today| {
+                    if &today > current_date {
+                        Ok(today)
+                    } else {
+                        at(current_date.date().tomorrow()?)
+                    }
+                })
+            }
+            Rotation::Weekly { start } => current_date
+                .round(timestamp_round.smallest(Unit::Day))
+                .map(|start_of_day| {
+                    let elapsed_days = (start_of_day.weekday().to_monday_zero_offset()
+                        - start.to_monday_zero_offset())
+                    .rem_euclid(7);
+                    &start_of_day + (7 - elapsed_days).days()
+                }),
         };
         let next_date =
             next_date.expect("invalid time; this is a bug in logforth rolling file appender");
@@ -55,6 +97,8 @@ impl Rotation {
             Rotation::Minutely => "%F-%H-%M",
             Rotation::Hourly => "%F-%H",
             Rotation::Daily => "%F",
+            Rotation::DailyAt { .. } => "%F",
+            Rotation::Weekly { .. } => "%F",
             Rotation::Never => "%F",
         }
     }
@@ -64,6 +108,7 @@ impl Rotation {
 mod tests {
     use std::str::FromStr;
 
+    use jiff::civil::Weekday;
     use jiff::Timestamp;
     use jiff::Zoned;
 
@@ -92,5 +137,74 @@ mod tests {
             Rotation::Daily.next_date_timestamp(&current_date),
             Some(expected_date.as_millisecond() as usize)
         );
+
+        let expected_date = "2024-08-11T03:00:00+08".parse::<Timestamp>().unwrap();
+        assert_eq!(
+            Rotation::DailyAt { hour: 3 }.next_date_timestamp(&current_date),
+            Some(expected_date.as_millisecond() as usize)
+        );
+
+        let expected_date = "2024-08-10T18:00:00+08".parse::<Timestamp>().unwrap();
+        assert_eq!(
+            Rotation::DailyAt { hour: 18 }.next_date_timestamp(&current_date),
+            Some(expected_date.as_millisecond() as usize)
+        );
+        assert_eq!(
+            Rotation::DailyAt { hour: 24 }.next_date_timestamp(&current_date),
+            None
+        );
+
+        // 2024-08-10 is a Saturday
+        let expected_date = "2024-08-12T00:00:00+08".parse::<Timestamp>().unwrap();
+        assert_eq!(
+            Rotation::Weekly {
+                start: Weekday::Monday
+            }
+            .next_date_timestamp(&current_date),
+            Some(expected_date.as_millisecond() as usize)
+        );
+
+        let expected_date = "2024-08-17T00:00:00+08".parse::<Timestamp>().unwrap();
+        assert_eq!(
+            Rotation::Weekly {
+                start: Weekday::Saturday
+            }
+            .next_date_timestamp(&current_date),
+            Some(expected_date.as_millisecond() as usize)
+        );
+    }
+
+    #[test]
+    fn test_daily_at_dst_transitions() {
+        let next = |hour: u8, current_date: &str| {
+            let current_date = Zoned::from_str(current_date).unwrap();
+            Rotation::DailyAt { hour }.next_date_timestamp(&current_date)
+        };
+        let timestamp =
+            |date: &str| Some(date.parse::<Timestamp>().unwrap().as_millisecond() as usize);
+
+        // spring forward: 2024-03-10 02:00 EST jumps to 03:00 EDT
+        assert_eq!(
+            next(3, "2024-03-09T12:00:00-05[America/New_York]"),
+            timestamp("2024-03-10T03:00:00-04")
+        );
+        assert_eq!(
+            next(2, "2024-03-09T12:00:00-05[America/New_York]"),
+            timestamp("2024-03-10T03:00:00-04")
+        );
+        assert_eq!(
+            next(3, "2024-03-10T03:00:00-04[America/New_York]"),
+            timestamp("2024-03-11T03:00:00-04")
+        );
+
+        // fall back: 2024-11-03 02:00 EDT goes back to 01:00 EST
+        assert_eq!(
+            next(3, "2024-11-02T12:00:00-04[America/New_York]"),
+            timestamp("2024-11-03T03:00:00-05")
+        );
+        assert_eq!(
+            next(3, "2024-11-02T03:00:00-04[America/New_York]"),
+            timestamp("2024-11-03T03:00:00-05")
+        );
     }
 }
@@ -0,0 +1,374 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use log::Level;
+use log::Record;
+
+use crate::append::rolling_file::rolling::FileWriter;
+
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Stages the written records in memory, and writes them to the log files in a background thread
+/// so that a hanging or failing file system does not stall the writer.
+#[derive(Debug)]
+pub(super) struct StagingWriter {
+    shared: Arc<Shared>,
+    max_staged_bytes: usize,
+    // joined on drop once the staged records are written
+    thread: Option<JoinHandle<()>>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    cond: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    chunks: VecDeque<Vec<u8>>,
+    // including the chunk being written
+    staged_bytes: usize,
+    writing: bool,
+    dropping: bool,
+    shutdown: bool,
+    // set by the background thread when it exits
+    stopped: bool,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl StagingWriter {
+    pub(super) fn new(writer: FileWriter, max_staged_bytes: usize) -> io::Result<Self> {
+        let shared = Arc::new(Shared::default());
+        let worker = shared.clone();
+        let thread = std::thread::Builder::new()
+            .name("logforth-rolling-file-sync".to_string())
+            .spawn(move || sync(&worker, writer))?;
+        Ok(StagingWriter {
+            shared,
+            max_staged_bytes,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Write for StagingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut queue = self.shared.lock();
+        if queue.staged_bytes + buf.len() > self.max_staged_bytes {
+            if !queue.dropping {
+                queue.dropping = true;
+                eprintln!("rolling file staging buffer is full, dropping records");
+            }
+        } else {
+            queue.dropping = false;
+            queue.staged_bytes += buf.len();
+            queue.chunks.push_back(buf.to_vec());
+            self.shared.cond.notify_all();
+        }
+        // dropped records are reported above rather than stalling or failing the worker
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.shared.cond.notify_all();
+        Ok(())
+    }
+}
+
+impl Drop for StagingWriter {
+    fn drop(&mut self) {
+        let mut queue = self.shared.lock();
+        queue.shutdown = true;
+        self.shared.cond.notify_all();
+        let (queue, _) = self
+            .shared
+            .cond
+            .wait_timeout_while(queue, DRAIN_TIMEOUT, |queue| !queue.stopped)
+            .unwrap_or_else(|err| err.into_inner());
+        let (stopped, lost) = (queue.stopped, queue.staged_bytes);
+        drop(queue);
+
+        // a thread stuck in a hanging write is left behind rather than blocking the shutdown
+        if stopped {
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+        if lost > 0 {
+            crate::logger::report_error(
+                &Record::builder()
+                    .level(Level::Error)
+                    .target("logforth::rolling_file")
+                    .args(format_args!(
+                        "staged records were not written to the log files"
+                    ))
+                    .build(),
+                anyhow::anyhow!(
+                    "rolling file staging buffer was not drained in time, {lost} bytes are lost"
+                ),
+            );
+        }
+    }
+}
+
+fn sync(shared: &Shared, writer: FileWriter) {
+    write_staged(shared, writer);
+    // the writer is dropped by now, so that joining the thread waits for its own cleanup
+    shared.lock().stopped = true;
+    shared.cond.notify_all();
+}
+
+fn write_staged(shared: &Shared, mut writer: FileWriter) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let chunk = {
+            let queue = shared.lock();
+            let mut queue = shared
+                .cond
+                .wait_while(queue, |queue| queue.chunks.is_empty() && !queue.shutdown)
+                .unwrap_or_else(|err| err.into_inner());
+            match queue.chunks.pop_front() {
+                Some(chunk) => {
+                    queue.writing = true;
+                    chunk
+                }
+                None => return,
+            }
+        };
+
+        let mut written = 0;
+        let result = write_from(&mut writer, &chunk, &mut written).and_then(|()| writer.flush());
+
+        let mut queue = shared.lock();
+        queue.writing = false;
+        queue.staged_bytes -= written;
+        match result {
+            Ok(()) => {
+                backoff = MIN_BACKOFF;
+                shared.cond.notify_all();
+            }
+            Err(err) => {
+                if queue.shutdown {
+                    // the owner stops waiting soon; give up instead of retrying forever
+                    eprintln!("failed to write staged logs on shutdown: {err}");
+                    queue.chunks.clear();
+                    shared.cond.notify_all();
+                    return;
+                }
+                // a record partially written before the failure is resumed where it stopped
+                if written < chunk.len() {
+                    queue.chunks.push_front(chunk[written..].to_vec());
+                }
+                drop(queue);
+
+                eprintln!("failed to write staged logs, retrying in {backoff:?}: {err}");
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                if let Err(err) = writer.reopen() {
+                    eprintln!("failed to reopen log file: {err}");
+                }
+            }
+        }
+    }
+}
+
+/// Writes `chunk` from `written` on, counting the bytes written so far even if it fails.
+fn write_from(writer: &mut impl Write, chunk: &[u8], written: &mut usize) -> io::Result<()> {
+    while *written < chunk.len() {
+        match writer.write(&chunk[*written..]) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => *written += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::fs::OpenOptions;
+    use std::io;
+    use std::io::Write;
+    use std::path::Path;
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use tempfile::TempDir;
+
+    use crate::append::rolling_file::MakeWriter;
+    use crate::append::rolling_file::RollingFileWriterBuilder;
+
+    #[test]
+    fn test_staged_writes() {
+        let temp_dir = TempDir::new().expect("failed to create a temporary directory");
+        let mut writer = RollingFileWriterBuilder::new()
+            .filename_prefix("test")
+            .max_file_size(10)
+            .staging(1024)
+            .build(&temp_dir)
+            .unwrap();
+
+        for _ in 0..3 {
+            writer.write_all(b"0123456789").unwrap();
+        }
+        drop(writer);
+
+        let mut contents = fs::read_dir(&temp_dir)
+            .unwrap()
+            .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect::<Vec<_>>();
+        contents.sort();
+        assert_eq!(contents, vec!["0123456789"; 3]);
+    }
+
+    #[test]
+    fn test_resume_partial_write() {
+        #[derive(Debug)]
+        struct Flaky {
+            path: PathBuf,
+            calls: Arc<AtomicUsize>,
+        }
+
+        impl MakeWriter for Flaky {
+            fn make_writer(&self, path: &Path) -> io::Result<Box<dyn Write + Send + Sync>> {
+                Ok(Box::new(Flaky {
+                    path: path.to_path_buf(),
+                    calls: self.calls.clone(),
+                }))
+            }
+        }
+
+        // writes 4 bytes, then fails once, then writes in full
+        impl Write for Flaky {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let buf = match self.calls.fetch_add(1, Ordering::Relaxed) {
+                    0 => &buf[..4],
+                    1 => return Err(io::ErrorKind::BrokenPipe.into()),
+                    _ => buf,
+                };
+                let mut file = OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(&self.path)?;
+                file.write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let temp_dir = TempDir::new().expect("failed to create a temporary directory");
+        let mut writer = RollingFileWriterBuilder::new()
+            .filename_prefix("test")
+            .max_file_size(12)
+            .make_writer(Flaky {
+                path: PathBuf::new(),
+                calls: Arc::default(),
+            })
+            .staging(1024)
+            .build(&temp_dir)
+            .unwrap();
+
+        writer.write_all(b"0123456789").unwrap();
+        writer.write_all(b"ab").unwrap();
+
+        // neither the file nor its size has the partially written bytes twice; the retry is
+        // awaited as a failure on shutdown is not retried
+        let contents = || {
+            fs::read_dir(&temp_dir)
+                .unwrap()
+                .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while contents() != ["0123456789ab"] && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        drop(writer);
+        assert_eq!(contents(), ["0123456789ab"]);
+    }
+
+    #[test]
+    fn test_join_on_drop() {
+        #[derive(Debug, Default)]
+        struct Tracked {
+            dropped: Arc<AtomicUsize>,
+        }
+
+        impl MakeWriter for Tracked {
+            fn make_writer(&self, _: &Path) -> io::Result<Box<dyn Write + Send + Sync>> {
+                Ok(Box::new(Tracked {
+                    dropped: self.dropped.clone(),
+                }))
+            }
+        }
+
+        impl Write for Tracked {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        impl Drop for Tracked {
+            fn drop(&mut self) {
+                self.dropped.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let temp_dir = TempDir::new().expect("failed to create a temporary directory");
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let mut writer = RollingFileWriterBuilder::new()
+            .filename_prefix("test")
+            .make_writer(Tracked {
+                dropped: dropped.clone(),
+            })
+            .staging(1024)
+            .build(&temp_dir)
+            .unwrap();
+        writer.write_all(b"0123456789").unwrap();
+
+        // the file writer of the background thread is gone once the writer is dropped
+        drop(writer);
+        assert!(dropped.load(Ordering::SeqCst) >= 1);
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use log::kv::Key;
+use log::Level;
+use log::Record;
+
+use crate::append::Append;
+use crate::Diagnostic;
+use crate::Layout;
+
+/// An appender that routes log records to child appenders by their level, target and key-values.
+///
+/// Routes are tried in the order they are added, and a record goes to the appender of the first
+/// matching [`Route`] only. Records matching no route go to the fallback appender if any, and are
+/// dropped otherwise.
+///
+/// # Examples
+///
+/// ```
+/// use log::Level;
+/// use logforth::append;
+/// use logforth::append::Route;
+/// use logforth::append::Router;
+///
+/// let router = Router::default()
+///     .route(Route::default().key("audit"), append::Stderr::default())
+///     .route(
+///         Route::default().target("my_crate::db").level(Level::Warn),
+///         append::Stderr::default(),
+///     )
+///     .fallback(append::Stdout::default());
+///
+/// logforth::builder().dispatch(|d| d.append(router)).apply();
+///
+/// log::info!(audit = true; "user deleted");
+/// ```
+#[derive(Debug, Default)]
+pub struct Router {
+    routes: Vec<(Route, Box<dyn Append>)>,
+    fallback: Option<Box<dyn Append>>,
+}
+
+/// The conditions of a route of a [`Router`], all of which a record must meet.
+///
+/// The default route matches every record.
+#[derive(Debug, Clone, Default)]
+pub struct Route {
+    level: Option<Level>,
+    target: Option<String>,
+    keys: Vec<String>,
+    kvs: Vec<(String, String)>,
+}
+
+impl Route {
+    /// Matches records at or above the given level, e.g., `Warn` matches `Warn` and `Error`.
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Matches records whose target is `target` or starts with `target::`.
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Matches records with the key-value `key`, whatever its value.
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.keys.push(key.into());
+        self
+    }
+
+    /// Matches records with the key-value `key` rendered as `value`.
+    pub fn kv(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.kvs.push((key.into(), value.into()));
+        self
+    }
+
+    fn matches(&self, record: &Record) -> bool {
+        if self.level.is_some_and(|level| record.level() > level) {
+            return false;
+        }
+        if let Some(target) = &self.target {
+            let matched = record
+                .target()
+                .strip_prefix(target.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"));
+            if !matched {
+                return false;
+            }
+        }
+        let kvs = record.key_values();
+        self.keys
+            .iter()
+            .all(|key| kvs.get(Key::from_str(key)).is_some())
+            && self.kvs.iter().all(|(key, value)| {
+                kvs.get(Key::from_str(key))
+                    .is_some_and(|v| v.to_string() == *value)
+            })
+    }
+}
+
+impl Router {
+    /// Adds a route sending the records it matches to `append`.
+    pub fn route(mut self, route: Route, append: impl Append) -> Self {
+        self.routes.push((route, Box::new(append)));
+        self
+    }
+
+    /// Sets the appender of the records matching no route.
+    pub fn fallback(mut self, append: impl Append) -> Self {
+        self.fallback = Some(Box::new(append));
+        self
+    }
+}
+
+impl Append for Router {
+    fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        let append = self
+            .routes
+            .iter()
+            .find(|(route, _)| route.matches(record))
+            .map(|(_, append)| append)
+            .or(self.fallback.as_ref());
+        match append {
+            Some(append) => append.append_with_layout(record, diagnostics, layout),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&self) {
+        self.flush_with_layout(None)
+    }
+
+    fn flush_with_layout(&self, layout: Option<&Layout>) {
+        for (_, append) in &self.routes {
+            append.flush_with_layout(layout);
+        }
+        if let Some(fallback) = &self.fallback {
+            fallback.flush_with_layout(layout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append::test_util;
+    use crate::append::Testing;
+    use crate::append::TestingHandle;
+
+    #[test]
+    fn test_router() {
+        let testing = || {
+            let testing = Testing::default();
+            let handle = testing.handle();
+            (testing, handle)
+        };
+        let (audit, audit_handle) = testing();
+        let (db, db_handle) = testing();
+        let (tenant, tenant_handle) = testing();
+        let (other, other_handle) = testing();
+        let router = Router::default()
+            .route(Route::default().key("audit"), audit)
+            .route(Route::default().target("app::db").level(Level::Warn), db)
+            .route(Route::default().kv("tenant", "acme"), tenant)
+            .fallback(other);
+
+        let append = |target: &str, level: Level, kvs: &[(&str, &str)]| {
+            router
+                .append(
+                    &Record::builder()
+                        .args(format_args!("{target}"))
+                        .level(level)
+                        .target(target)
+                        .key_values(&kvs)
+                        .build(),
+                    &[],
+                )
+                .unwrap();
+        };
+        append("app::db", Level::Error, &[("audit", "true")]);
+        append("app::db", Level::Error, &[]);
+        append("app::db::pool", Level::Warn, &[]);
+        append("app::dbx", Level::Error, &[]);
+        append("app::db", Level::Info, &[]);
+        append("app", Level::Info, &[("tenant", "acme")]);
+        append("app", Level::Info, &[("tenant", "other")]);
+
+        let targets = |handle: &TestingHandle| test_util::messages(handle.records());
+        assert_eq!(targets(&audit_handle), ["app::db"]);
+        assert_eq!(targets(&db_handle), ["app::db", "app::db::pool"]);
+        assert_eq!(targets(&tenant_handle), ["app"]);
+        assert_eq!(targets(&other_handle), ["app::dbx", "app::db", "app"]);
+    }
+}
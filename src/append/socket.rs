@@ -0,0 +1,439 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Appender for shipping log records over TCP or UDP, e.g., to Logstash, Vector or Fluent Bit.
+//!
+//! Each record is formatted by the layout of the appender and framed according to
+//! [`SocketFraming`]: newline-delimited by default, which suits the line-based inputs of most
+//! log collectors.
+//!
+//! # Examples
+//!
+//!```rust, no_run
+//! use logforth::append::socket;
+//! use logforth::append::socket::Socket;
+//! use logforth::append::socket::SocketWriter;
+//! use logforth::layout::JsonLayout;
+//!
+//! let socket_writer = SocketWriter::tcp("127.0.0.1:5170").unwrap();
+//! let (non_blocking, _guard) = socket::non_blocking(socket_writer)
+//!     .buffered_lines_limit(10_000)
+//!     .finish();
+//!
+//! logforth::builder()
+//!     .dispatch(|d| {
+//!         d.filter(log::LevelFilter::Trace)
+//!             .append(Socket::new(non_blocking).with_layout(JsonLayout::default()))
+//!     })
+//!     .apply();
+//!
+//! log::info!("This log will be shipped to the collector.");
+//! ```
+
+use std::io;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::net::ToSocketAddrs;
+use std::net::UdpSocket;
+
+use log::Record;
+
+use crate::append::reconnect;
+use crate::append::reconnect::Reconnecting;
+use crate::layout::TextLayout;
+use crate::non_blocking::NonBlocking;
+use crate::non_blocking::NonBlockingBuilder;
+use crate::non_blocking::Writer;
+use crate::startup;
+use crate::Append;
+use crate::Diagnostic;
+use crate::Layout;
+
+/// How [`Socket`] delimits the formatted records on the wire.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SocketFraming {
+    /// Terminate each record with a newline.
+    #[default]
+    Newline,
+    /// Prefix each record with its length in bytes as a big-endian `u32`.
+    LengthPrefixed,
+}
+
+/// An appender that ships log records over a TCP or UDP socket.
+///
+/// Backpressure is handled by the non-blocking worker: bound its queue with
+/// [`NonBlockingBuilder::buffered_lines_limit`] and choose what happens when it is full with
+/// [`NonBlockingBuilder::overflow_policy`]. Avoid [`NonBlockingBuilder::max_record_size`] with
+/// [`SocketFraming::LengthPrefixed`]: truncated records would break the framing.
+#[derive(Debug)]
+pub struct Socket {
+    layout: Option<Layout>,
+    framing: SocketFraming,
+    writer: NonBlocking<SocketWriter>,
+}
+
+impl Socket {
+    /// Creates a new [`Socket`] appender.
+    ///
+    /// This appender by default uses [`TextLayout`] without color to format log records.
+    pub fn new(writer: NonBlocking<SocketWriter>) -> Self {
+        Self {
+            layout: None,
+            framing: SocketFraming::default(),
+            writer,
+        }
+    }
+
+    /// Sets the layout used to format log records.
+    pub fn with_layout(mut self, layout: impl Into<Layout>) -> Self {
+        self.layout = Some(layout.into());
+        self
+    }
+
+    /// Sets how records are delimited. Default to [`SocketFraming::Newline`].
+    pub fn with_framing(mut self, framing: SocketFraming) -> Self {
+        self.framing = framing;
+        self
+    }
+}
+
+impl Append for Socket {
+    fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        let mut bytes = match self.layout.as_ref().or(layout) {
+            Some(layout) => layout.format(record, diagnostics)?,
+            None => TextLayout::default()
+                .no_color()
+                .format(record, diagnostics)?,
+        };
+        match self.framing {
+            SocketFraming::Newline => bytes.push(b'\n'),
+            SocketFraming::LengthPrefixed => {
+                let len = u32::try_from(bytes.len())?;
+                bytes.splice(0..0, len.to_be_bytes());
+            }
+        }
+        self.writer.send(bytes, record.level())?;
+        Ok(())
+    }
+
+    fn flush(&self) {
+        if let Err(err) = self.writer.flush() {
+            eprintln!("failed to flush socket appender: {err:?}");
+        }
+    }
+}
+
+/// Create a non-blocking builder for socket writers.
+pub fn non_blocking(writer: SocketWriter) -> NonBlockingBuilder<SocketWriter> {
+    NonBlockingBuilder::new("logforth-socket", writer)
+}
+
+/// A writer that sends formatted log records over a TCP or UDP socket.
+///
+/// Stream writers ([`SocketWriter::tcp`] and [`SocketWriter::rustls`]) connect on the first
+/// write. When the connection breaks or a write times out, e.g., because the collector restarts
+/// or stalls, they reconnect with exponential backoff, resolving the address again, and buffer
+/// the records meanwhile; see [`SocketWriter::with_reconnect_buffer`].
+///
+/// [`SocketWriter::udp`] sends each record in its own datagram; records larger than a datagram
+/// are lost.
+#[derive(Debug)]
+pub struct SocketWriter {
+    transport: Transport,
+}
+
+#[derive(Debug)]
+enum Transport {
+    Datagram(UdpSocket),
+    Stream(Reconnecting),
+}
+
+impl SocketWriter {
+    /// Create a writer that sends records to the given address over TCP.
+    ///
+    /// Fails if the address cannot be resolved.
+    pub fn tcp<A>(addr: A) -> io::Result<SocketWriter>
+    where
+        A: ToSocketAddrs + Send + Sync + 'static,
+    {
+        let endpoint = format_addrs(&resolve(&addr)?);
+        report("tcp", endpoint.clone());
+        let connection = Reconnecting::new(endpoint, move || {
+            Ok(Box::new(reconnect::connect_tcp(&addr)?))
+        });
+        Ok(Self::stream(connection))
+    }
+
+    /// Create a writer that sends records to the given address over TLS, trusting the Mozilla
+    /// root certificates bundled by `webpki-roots`.
+    ///
+    /// Fails if the address cannot be resolved or `domain` is not a valid server name.
+    #[cfg(feature = "rustls")]
+    pub fn rustls<A, S>(addr: A, domain: S) -> io::Result<SocketWriter>
+    where
+        A: ToSocketAddrs + Send + Sync + 'static,
+        S: AsRef<str>,
+    {
+        let roots =
+            rustls::RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let provider = std::sync::Arc::new(rustls::crypto::ring::default_provider());
+        let config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Self::rustls_with(addr, domain, std::sync::Arc::new(config))
+    }
+
+    /// Create a writer that sends records to the given address over TLS, with the given client
+    /// configuration, e.g., to trust a private certificate authority or present a client
+    /// certificate.
+    ///
+    /// Fails if the address cannot be resolved or `domain` is not a valid server name.
+    #[cfg(feature = "rustls")]
+    pub fn rustls_with<A, S>(
+        addr: A,
+        domain: S,
+        config: std::sync::Arc<rustls::ClientConfig>,
+    ) -> io::Result<SocketWriter>
+    where
+        A: ToSocketAddrs + Send + Sync + 'static,
+        S: AsRef<str>,
+    {
+        let domain = domain.as_ref().to_string();
+        let server_name = rustls::pki_types::ServerName::try_from(domain.clone())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let endpoint = format!("{} ({domain})", format_addrs(&resolve(&addr)?));
+        report("tls", endpoint.clone());
+        let connection = Reconnecting::new(endpoint, move || {
+            Ok(Box::new(reconnect::connect_rustls(
+                &addr,
+                &server_name,
+                &config,
+            )?))
+        });
+        Ok(Self::stream(connection))
+    }
+
+    /// Create a writer that sends records to the given address over UDP.
+    ///
+    /// Fails if the address cannot be resolved or the socket cannot be bound.
+    pub fn udp<A: ToSocketAddrs>(addr: A) -> io::Result<SocketWriter> {
+        let addrs = resolve(&addr)?;
+        let local = match addrs[0] {
+            SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(&addrs[..])?;
+        report("udp", format_addrs(&addrs));
+        Ok(SocketWriter {
+            transport: Transport::Datagram(socket),
+        })
+    }
+
+    /// Sets the maximum number of records buffered while a stream writer is disconnected.
+    ///
+    /// When the buffer is full, the oldest records are dropped. Zero disables buffering. Default
+    /// to 1024. No effect on UDP writers.
+    pub fn with_reconnect_buffer(mut self, max_pending: usize) -> Self {
+        if let Transport::Stream(connection) = &mut self.transport {
+            connection.set_max_pending(max_pending);
+        }
+        self
+    }
+
+    fn stream(connection: Reconnecting) -> Self {
+        SocketWriter {
+            transport: Transport::Stream(connection),
+        }
+    }
+}
+
+fn resolve<A: ToSocketAddrs>(addr: &A) -> io::Result<Vec<SocketAddr>> {
+    let addrs = addr.to_socket_addrs()?.collect::<Vec<_>>();
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve to any address",
+        ));
+    }
+    Ok(addrs)
+}
+
+fn format_addrs(addrs: &[SocketAddr]) -> String {
+    addrs
+        .iter()
+        .map(SocketAddr::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn report(transport: &str, endpoint: String) {
+    startup::report(
+        "socket writer",
+        vec![("transport", transport.to_string()), ("endpoint", endpoint)],
+    );
+}
+
+impl Writer for SocketWriter {
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match &mut self.transport {
+            Transport::Datagram(socket) => socket.send(buf).map(|_| ()),
+            Transport::Stream(connection) => connection.send(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.transport {
+            Transport::Datagram(_) => Ok(()),
+            Transport::Stream(connection) => connection.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+    use std::io::BufReader;
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    use super::*;
+
+    fn record(message: &str, f: impl FnOnce(&Record)) {
+        f(&Record::builder()
+            .args(format_args!("{message}"))
+            .level(log::Level::Info)
+            .target("app")
+            .build())
+    }
+
+    #[test]
+    fn test_tcp_newline_framing() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let writer = SocketWriter::tcp(listener.local_addr().unwrap()).unwrap();
+        let (non_blocking, guard) = non_blocking(writer).finish();
+        let socket =
+            Socket::new(non_blocking).with_layout(crate::layout::CustomLayout::new(|record, _| {
+                Ok(record.args().to_string().into_bytes())
+            }));
+        for message in ["first", "second"] {
+            record(message, |record| socket.append(record, &[]).unwrap());
+        }
+        drop(socket);
+        drop(guard);
+
+        let (stream, _) = listener.accept().unwrap();
+        let lines = BufReader::new(stream)
+            .lines()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(lines, ["first", "second"]);
+    }
+
+    #[test]
+    fn test_udp_length_prefixed_framing() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let writer = SocketWriter::udp(receiver.local_addr().unwrap()).unwrap();
+        let (non_blocking, guard) = non_blocking(writer).finish();
+        let socket = Socket::new(non_blocking)
+            .with_layout(crate::layout::CustomLayout::new(|record, _| {
+                Ok(record.args().to_string().into_bytes())
+            }))
+            .with_framing(SocketFraming::LengthPrefixed);
+        for message in ["first", "second"] {
+            record(message, |record| socket.append(record, &[]).unwrap());
+        }
+        drop(socket);
+        drop(guard);
+
+        // one datagram per record
+        let mut buf = [0; 64];
+        for message in ["first", "second"] {
+            let len = receiver.recv(&mut buf).unwrap();
+            assert_eq!(buf[..4], (message.len() as u32).to_be_bytes());
+            assert_eq!(&buf[4..len], message.as_bytes());
+        }
+    }
+
+    #[cfg(feature = "rustls")]
+    #[test]
+    fn test_rustls() {
+        use std::sync::Arc;
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let certified = rcgen::generate_simple_self_signed(["localhost".to_string()]).unwrap();
+        let cert = certified.cert.der().clone();
+        let key =
+            rustls::pki_types::PrivateKeyDer::Pkcs8(certified.key_pair.serialize_der().into());
+        let mut server_config = rustls::ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert.clone()], key)
+            .unwrap();
+        // the client never reads the session tickets, and closing a socket with unread data
+        // resets the connection, which may discard the records not read by the server yet
+        server_config.send_tls13_tickets = 0;
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let connection = rustls::ServerConnection::new(Arc::new(server_config)).unwrap();
+            // the client does not send close_notify, so stop at the unclean end of the stream
+            BufReader::new(rustls::StreamOwned::new(connection, stream))
+                .lines()
+                .map_while(Result::ok)
+                .collect::<Vec<_>>()
+        });
+
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(cert).unwrap();
+        let client_config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let writer = SocketWriter::rustls_with(addr, "localhost", Arc::new(client_config)).unwrap();
+        let (non_blocking, guard) = non_blocking(writer).finish();
+        let socket =
+            Socket::new(non_blocking).with_layout(crate::layout::CustomLayout::new(|record, _| {
+                Ok(record.args().to_string().into_bytes())
+            }));
+        for message in ["first", "second"] {
+            record(message, |record| socket.append(record, &[]).unwrap());
+        }
+        drop(socket);
+        drop(guard);
+
+        assert_eq!(server.join().unwrap(), ["first", "second"]);
+        let err = SocketWriter::rustls(addr, "not a server name").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
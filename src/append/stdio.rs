@@ -14,10 +14,12 @@
 
 use std::io::Write;
 
+use log::Level;
 use log::Record;
 
 use crate::append::Append;
 use crate::layout::TextLayout;
+use crate::pool;
 use crate::Diagnostic;
 use crate::Layout;
 
@@ -30,17 +32,9 @@ use crate::Layout;
 ///
 /// let stdout_appender = Stdout::default();
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Stdout {
-    layout: Layout,
-}
-
-impl Default for Stdout {
-    fn default() -> Self {
-        Self {
-            layout: TextLayout::default().into(),
-        }
-    }
+    layout: Option<Layout>,
 }
 
 impl Stdout {
@@ -55,17 +49,24 @@ impl Stdout {
     /// let stdout_appender = Stdout::default().with_layout(TextLayout::default());
     /// ```
     pub fn with_layout(mut self, layout: impl Into<Layout>) -> Self {
-        self.layout = layout.into();
+        self.layout = Some(layout.into());
         self
     }
 }
 
 impl Append for Stdout {
     fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
-        let mut bytes = self.layout.format(record, diagnostics)?;
-        bytes.push(b'\n');
-        std::io::stdout().write_all(&bytes)?;
-        Ok(())
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        let layout = self.layout.as_ref().or(layout);
+        write_record(std::io::stdout(), layout, record, diagnostics)
     }
 
     fn flush(&self) {
@@ -82,17 +83,9 @@ impl Append for Stdout {
 ///
 /// let stderr_appender = Stderr::default();
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Stderr {
-    layout: Layout,
-}
-
-impl Default for Stderr {
-    fn default() -> Self {
-        Self {
-            layout: TextLayout::default().into(),
-        }
-    }
+    layout: Option<Layout>,
 }
 
 impl Stderr {
@@ -109,21 +102,129 @@ impl Stderr {
     /// let stderr_appender = Stderr::default().with_layout(JsonLayout::default());
     /// # }
     /// ```
-    pub fn with_layout(mut self, encoder: impl Into<Layout>) -> Self {
-        self.layout = encoder.into();
+    pub fn with_layout(mut self, layout: impl Into<Layout>) -> Self {
+        self.layout = Some(layout.into());
         self
     }
 }
 
 impl Append for Stderr {
     fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
-        let mut bytes = self.layout.format(record, diagnostics)?;
-        bytes.push(b'\n');
-        std::io::stderr().write_all(&bytes)?;
-        Ok(())
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        let layout = self.layout.as_ref().or(layout);
+        write_record(std::io::stderr(), layout, record, diagnostics)
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+/// An appender that writes log records at or above a severity to standard error, and the others
+/// to standard output.
+///
+/// This replaces two dispatches with complementary level filters, where an off-by-one level
+/// duplicates or loses the records at the boundary.
+///
+/// # Examples
+///
+/// ```
+/// use log::Level;
+/// use logforth::append::SplitStdio;
+///
+/// // warnings and errors go to stderr, the rest to stdout
+/// logforth::builder()
+///     .dispatch(|d| d.append(SplitStdio::at(Level::Warn)))
+///     .apply();
+/// ```
+#[derive(Debug)]
+pub struct SplitStdio {
+    threshold: Level,
+    layout: Option<Layout>,
+}
+
+impl SplitStdio {
+    /// Creates an appender that writes the records at `threshold` or more severe to standard
+    /// error, and the others to standard output.
+    pub fn at(threshold: Level) -> Self {
+        Self {
+            threshold,
+            layout: None,
+        }
+    }
+
+    /// Sets the layout for both outputs.
+    pub fn with_layout(mut self, layout: impl Into<Layout>) -> Self {
+        self.layout = Some(layout.into());
+        self
+    }
+
+    fn is_stderr(&self, level: Level) -> bool {
+        level <= self.threshold
+    }
+}
+
+impl Append for SplitStdio {
+    fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        let layout = self.layout.as_ref().or(layout);
+        if self.is_stderr(record.level()) {
+            // keep the order of the records across both outputs when they share a terminal
+            let _ = std::io::stdout().flush();
+            write_record(std::io::stderr(), layout, record, diagnostics)
+        } else {
+            write_record(std::io::stdout(), layout, record, diagnostics)
+        }
     }
 
     fn flush(&self) {
+        let _ = std::io::stdout().flush();
         let _ = std::io::stderr().flush();
     }
 }
+
+fn write_record(
+    mut output: impl Write,
+    layout: Option<&Layout>,
+    record: &Record,
+    diagnostics: &[Diagnostic],
+) -> anyhow::Result<()> {
+    let mut bytes = match layout {
+        Some(layout) => layout.format(record, diagnostics)?,
+        None => TextLayout::default().format(record, diagnostics)?,
+    };
+    bytes.push(b'\n');
+    output.write_all(&bytes)?;
+    pool::recycle(bytes);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_stdio_boundary() {
+        let split = SplitStdio::at(Level::Warn);
+        assert!(split.is_stderr(Level::Error));
+        assert!(split.is_stderr(Level::Warn));
+        assert!(!split.is_stderr(Level::Info));
+        assert!(!split.is_stderr(Level::Trace));
+    }
+}
@@ -0,0 +1,420 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use log::kv::Value;
+use log::Level;
+use log::Record;
+
+use crate::append::repeats;
+use crate::append::repeats::DiagnosticSnapshot;
+use crate::append::Append;
+use crate::clock;
+use crate::Diagnostic;
+use crate::Layout;
+
+/// An appender that detects error storms and summarizes them.
+///
+/// When more than `threshold` records at or above the configured level (default to
+/// [`Level::Error`]) of the same target arrive within one `window`, the target switches to the
+/// summarized mode: further records of that target are suppressed, and one aggregate record with
+/// the number of suppressed records and one sampled exemplar is forwarded per window instead. The
+/// target reverts to the normal mode once a window passes with no more than `threshold` records.
+///
+/// The aggregate record of a window is forwarded when the next record of the same target arrives
+/// or when the appender is flushed, with the diagnostics of its exemplar.
+///
+/// At most [`with_max_targets`](ErrorStorm::with_max_targets) targets are tracked; when a new
+/// target arrives beyond that, the target seen least recently is forgotten along with its count of
+/// suppressed records.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use logforth::append;
+/// use logforth::append::ErrorStorm;
+///
+/// logforth::builder()
+///     .dispatch(|d| {
+///         d.append(ErrorStorm::new(
+///             append::Stderr::default(),
+///             100,
+///             Duration::from_secs(10),
+///         ))
+///     })
+///     .apply();
+/// ```
+#[derive(Debug)]
+pub struct ErrorStorm {
+    append: Box<dyn Append>,
+    level: Level,
+    threshold: usize,
+    window: Duration,
+    max_targets: usize,
+    states: Mutex<HashMap<String, StormState>>,
+}
+
+#[derive(Debug)]
+struct StormState {
+    window_start: Instant,
+    seen_at: Instant,
+    count: usize,
+    storming: bool,
+    suppressed: usize,
+    exemplar: Option<(String, DiagnosticSnapshot)>,
+}
+
+impl StormState {
+    fn new(now: Instant) -> Self {
+        Self {
+            window_start: now,
+            seen_at: now,
+            count: 0,
+            storming: false,
+            suppressed: 0,
+            exemplar: None,
+        }
+    }
+
+    // Returns the summary of the window to forward, if any.
+    fn roll_window(&mut self, now: Instant, threshold: usize) -> Option<Summary> {
+        let summary = (self.suppressed > 0).then(|| {
+            let (exemplar, diagnostics) = self.exemplar.take().unwrap_or_default();
+            Summary {
+                suppressed: self.suppressed,
+                exemplar,
+                diagnostics,
+            }
+        });
+        if self.count <= threshold {
+            self.storming = false;
+        }
+        self.window_start = now;
+        self.count = 0;
+        self.suppressed = 0;
+        summary
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Summary {
+    suppressed: usize,
+    exemplar: String,
+    diagnostics: DiagnosticSnapshot,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Decision {
+    Forward,
+    Suppress,
+}
+
+impl ErrorStorm {
+    const DEFAULT_MAX_TARGETS: usize = 1024;
+
+    /// Creates a new [`ErrorStorm`] that wraps the given appender.
+    ///
+    /// A target is considered in an error storm when more than `threshold` records of it arrive
+    /// within `window`.
+    pub fn new(append: impl Append, threshold: usize, window: Duration) -> Self {
+        Self {
+            append: Box::new(append),
+            level: Level::Error,
+            threshold,
+            window,
+            max_targets: Self::DEFAULT_MAX_TARGETS,
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets the least severe level of records counted for storm detection. Default to
+    /// [`Level::Error`].
+    ///
+    /// Records less severe than this level are always forwarded.
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets the maximum number of targets tracked at once. Default to 1024.
+    pub fn with_max_targets(mut self, max_targets: usize) -> Self {
+        self.max_targets = max_targets.max(1);
+        self
+    }
+
+    fn on_record(
+        &self,
+        target: &str,
+        message: impl FnOnce() -> String,
+        diagnostics: &[Diagnostic],
+        now: Instant,
+    ) -> (Option<Summary>, Decision) {
+        let mut states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+        if states.len() >= self.max_targets && !states.contains_key(target) {
+            let least_recent = states
+                .iter()
+                .min_by_key(|(_, state)| state.seen_at)
+                .map(|(target, _)| target.clone());
+            if let Some(least_recent) = least_recent {
+                states.remove(&least_recent);
+            }
+        }
+        let state = match states.get_mut(target) {
+            Some(state) => state,
+            None => states
+                .entry(target.to_string())
+                .or_insert_with(|| StormState::new(now)),
+        };
+
+        state.seen_at = now;
+        let mut summary = None;
+        if now.duration_since(state.window_start) >= self.window {
+            summary = state.roll_window(now, self.threshold);
+        }
+
+        state.count += 1;
+        if state.count > self.threshold {
+            state.storming = true;
+        }
+
+        let decision = if state.storming {
+            state.suppressed += 1;
+            if state.exemplar.is_none() {
+                state.exemplar = Some((message(), DiagnosticSnapshot::capture(diagnostics)));
+            }
+            Decision::Suppress
+        } else {
+            Decision::Forward
+        };
+        (summary, decision)
+    }
+
+    fn append_summary(
+        &self,
+        level: Level,
+        target: &str,
+        summary: Summary,
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        let Summary {
+            suppressed,
+            exemplar,
+            diagnostics,
+        } = summary;
+        let kvs = [
+            ("suppressed", Value::from(suppressed)),
+            ("exemplar", Value::from(exemplar.as_str())),
+        ];
+        self.append.append_with_layout(
+            &Record::builder()
+                .level(level)
+                .target(target)
+                .args(format_args!(
+                    "error storm: suppressed {suppressed} records in the last {:?}; exemplar: {exemplar}",
+                    self.window
+                ))
+                .key_values(&kvs)
+                .build(),
+            &diagnostics.diagnostics(),
+            layout,
+        )
+    }
+}
+
+impl Append for ErrorStorm {
+    fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        if record.level() > self.level {
+            return self.append.append_with_layout(record, diagnostics, layout);
+        }
+
+        let target = record.target();
+        let (summary, decision) = self.on_record(
+            target,
+            || record.args().to_string(),
+            diagnostics,
+            clock::instant(),
+        );
+        if let Some(summary) = summary {
+            self.append_summary(self.level, target, summary, layout)?;
+        }
+        match decision {
+            Decision::Forward => self.append.append_with_layout(record, diagnostics, layout),
+            Decision::Suppress => Ok(()),
+        }
+    }
+
+    fn flush(&self) {
+        self.flush_with_layout(None)
+    }
+
+    fn flush_with_layout(&self, layout: Option<&Layout>) {
+        let now = clock::instant();
+        let summaries = {
+            let mut states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+            states
+                .iter_mut()
+                .filter(|(_, state)| now.duration_since(state.window_start) >= self.window)
+                .filter_map(|(target, state)| {
+                    let summary = state.roll_window(now, self.threshold)?;
+                    Some((target.clone(), summary))
+                })
+                .collect::<Vec<_>>()
+        };
+        for (target, summary) in summaries {
+            if let Err(err) = self.append_summary(self.level, &target, summary, layout) {
+                repeats::report_flush_error(&self.append, "error storm summary", err);
+            }
+        }
+        self.append.flush_with_layout(layout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append::test_util;
+    use crate::append::Memory;
+    use crate::append::Stderr;
+    use crate::diagnostic::ThreadLocalContext;
+    use crate::diagnostic::ThreadLocalDiagnostic;
+    use crate::layout::TextLayout;
+
+    #[test]
+    fn test_storm_detection() {
+        let storm = ErrorStorm::new(Stderr::default(), 2, Duration::from_secs(10));
+        let start = Instant::now();
+        let message = |n: usize| move || format!("error {n}");
+
+        // below the threshold
+        for n in 0..2 {
+            let (summary, decision) = storm.on_record("app", message(n), &[], start);
+            assert_eq!(summary, None);
+            assert_eq!(decision, Decision::Forward);
+        }
+
+        // storm starts, other targets are unaffected
+        for n in 2..5 {
+            let (summary, decision) = storm.on_record("app", message(n), &[], start);
+            assert_eq!(summary, None);
+            assert_eq!(decision, Decision::Suppress);
+        }
+        let (_, decision) = storm.on_record("other", message(0), &[], start);
+        assert_eq!(decision, Decision::Forward);
+
+        // the next window summarizes the previous one and keeps storming
+        let next = start + Duration::from_secs(10);
+        for n in 0..3 {
+            let (summary, decision) = storm.on_record("app", message(n), &[], next);
+            if n == 0 {
+                let expected = Summary {
+                    suppressed: 3,
+                    exemplar: "error 2".to_string(),
+                    diagnostics: DiagnosticSnapshot::default(),
+                };
+                assert_eq!(summary, Some(expected));
+            } else {
+                assert_eq!(summary, None);
+            }
+            assert_eq!(decision, Decision::Suppress);
+        }
+
+        // a quiet window reverts the target to the normal mode
+        let quiet = next + Duration::from_secs(10);
+        let (summary, decision) = storm.on_record("app", message(0), &[], quiet);
+        assert_eq!(summary.map(|s| s.suppressed), Some(3));
+        assert_eq!(decision, Decision::Suppress);
+        let after = quiet + Duration::from_secs(10);
+        let (summary, decision) = storm.on_record("app", message(1), &[], after);
+        assert_eq!(summary.map(|s| s.suppressed), Some(1));
+        assert_eq!(decision, Decision::Forward);
+    }
+
+    #[test]
+    fn test_max_targets() {
+        let storm =
+            ErrorStorm::new(Stderr::default(), 1, Duration::from_secs(10)).with_max_targets(2);
+        let start = Instant::now();
+        let message = || String::new;
+        storm.on_record("a", message(), &[], start);
+        storm.on_record("b", message(), &[], start);
+        let (_, decision) = storm.on_record("a", message(), &[], start);
+        assert_eq!(decision, Decision::Suppress);
+
+        // a third target evicts the least recently seen one
+        let later = start + Duration::from_millis(1);
+        storm.on_record("a", message(), &[], later);
+        storm.on_record("c", message(), &[], later);
+        let states = storm.states.lock().unwrap();
+        assert_eq!(states.len(), 2);
+        assert!(!states.contains_key("b"));
+    }
+
+    #[test]
+    fn test_summary_context() {
+        let memory = Memory::new(10);
+        let handle = memory.handle();
+        let mut storm = ErrorStorm::new(memory, 1, Duration::from_secs(3600));
+        let layout = Layout::from(
+            TextLayout::default()
+                .no_color()
+                .no_timestamp()
+                .no_location(),
+        );
+        let append = |message: &str, request: &str| {
+            ThreadLocalContext::new()
+                .with("request", request)
+                .scope(|| {
+                    test_util::with_record(Level::Error, message, |record| {
+                        let diagnostics = [ThreadLocalDiagnostic::default().into()];
+                        storm.append_with_layout(record, &diagnostics, Some(&layout))
+                    })
+                })
+                .unwrap();
+        };
+
+        // the summary emitted on flush has the diagnostics of the exemplar and the layout of the
+        // dispatch
+        append("error 0", "1");
+        append("error 1", "2");
+        // the window is over
+        storm.window = Duration::ZERO;
+        ThreadLocalContext::new()
+            .with("request", "3")
+            .scope(|| storm.flush_with_layout(Some(&layout)));
+
+        assert_eq!(
+            test_util::messages(handle.snapshot()),
+            [
+                "ERROR app: error 0 request=1",
+                "ERROR app: error storm: suppressed 1 records in the last 0ns; exemplar: error 1 \
+                 suppressed=1 exemplar=error 1 request=2",
+            ]
+        );
+    }
+}
@@ -34,16 +34,22 @@
 //! log::info!("This log will be written to syslog.");
 //! ```
 
+use std::fmt;
 use std::io;
+use std::net::SocketAddr;
+use std::net::ToSocketAddrs;
 
 use fasyslog::format::SyslogContext;
 use fasyslog::sender::SyslogSender;
 use fasyslog::SDElement;
 use log::Record;
 
+use crate::append::reconnect;
+use crate::append::reconnect::Reconnecting;
 use crate::non_blocking::NonBlocking;
 use crate::non_blocking::NonBlockingBuilder;
 use crate::non_blocking::Writer;
+use crate::startup;
 use crate::Append;
 use crate::Diagnostic;
 use crate::Layout;
@@ -63,12 +69,55 @@ pub enum SyslogFormat {
     RFC5424,
 }
 
+type MsgIdFunction = dyn Fn(&Record) -> String + Send + Sync + 'static;
+
+/// The source of the RFC 5424 MSGID field.
+enum MsgId {
+    /// Use the target of the record.
+    Target,
+    /// Use a static value.
+    Static(String),
+    /// Compute the value from the record.
+    Custom(Box<MsgIdFunction>),
+}
+
+impl fmt::Debug for MsgId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MsgId::Target => write!(f, "Target"),
+            MsgId::Static(msgid) => f.debug_tuple("Static").field(msgid).finish(),
+            MsgId::Custom(_) => write!(f, "Custom {{ ... }}"),
+        }
+    }
+}
+
+impl MsgId {
+    fn resolve(&self, record: &Record) -> Option<String> {
+        let msgid = match self {
+            MsgId::Target => sanitize_msgid(record.target()),
+            MsgId::Static(msgid) => sanitize_msgid(msgid),
+            MsgId::Custom(f) => sanitize_msgid(&f(record)),
+        };
+        (!msgid.is_empty()).then_some(msgid)
+    }
+}
+
+// RFC 5424 restricts MSGID to at most 32 printable US-ASCII characters.
+fn sanitize_msgid(msgid: &str) -> String {
+    msgid
+        .chars()
+        .filter(|c| matches!(c, '!'..='~'))
+        .take(32)
+        .collect()
+}
+
 /// An appender that writes log records to syslog.
 #[derive(Debug)]
 pub struct Syslog {
     writer: NonBlocking<SyslogWriter>,
     format: SyslogFormat,
     context: SyslogContext,
+    msgid: MsgId,
     layout: Option<Layout>,
 }
 
@@ -79,6 +128,7 @@ impl Syslog {
             writer,
             format: SyslogFormat::RFC3164,
             context: SyslogContext::default(),
+            msgid: MsgId::Target,
             layout: None,
         }
     }
@@ -95,9 +145,56 @@ impl Syslog {
         self
     }
 
+    /// Set the PROCID of the [`Syslog`] appender.
+    ///
+    /// Default to the PID of the current process (see [`std::process::id()`]).
+    pub fn with_procid(mut self, procid: u32) -> Self {
+        self.context.procid(procid.to_string());
+        self
+    }
+
+    /// Set a static MSGID for the [`Syslog`] appender.
+    ///
+    /// Only used with [`SyslogFormat::RFC5424`]. Default to the target of the record.
+    ///
+    /// Non-printable characters are stripped and the value is capped to 32 characters as
+    /// required by RFC 5424. An empty MSGID is sent as the nil value `-`.
+    pub fn with_msgid(mut self, msgid: impl Into<String>) -> Self {
+        self.msgid = MsgId::Static(msgid.into());
+        self
+    }
+
+    /// Set a function to compute the MSGID of each record for the [`Syslog`] appender.
+    ///
+    /// Only used with [`SyslogFormat::RFC5424`]. See [`Syslog::with_msgid`] for the restrictions
+    /// on the value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// use logforth::append::syslog;
+    /// use logforth::append::syslog::Syslog;
+    /// use logforth::append::syslog::SyslogFormat;
+    /// use logforth::append::syslog::SyslogWriter;
+    ///
+    /// let syslog_writer = SyslogWriter::tcp_well_known().unwrap();
+    /// let (non_blocking, _guard) = syslog::non_blocking(syslog_writer).finish();
+    /// let syslog = Syslog::new(non_blocking)
+    ///     .with_format(SyslogFormat::RFC5424)
+    ///     .with_msgid_fn(|record| record.level().to_string());
+    /// ```
+    pub fn with_msgid_fn(
+        mut self,
+        msgid: impl Fn(&Record) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.msgid = MsgId::Custom(Box::new(msgid));
+        self
+    }
+
     /// Set the layout of the [`Syslog`] appender.
     ///
-    /// Default to `None`, only the args will be logged.
+    /// Default to `None`, the layout of the dispatch is used if any; otherwise, only the args will
+    /// be logged.
     pub fn with_layout(mut self, layout: impl Into<Layout>) -> Self {
         self.layout = Some(layout.into());
         self
@@ -116,41 +213,52 @@ fn log_level_to_otel_severity(level: log::Level) -> fasyslog::Severity {
 
 impl Append for Syslog {
     fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        let layout = self.layout.as_ref().or(layout);
         let severity = log_level_to_otel_severity(record.level());
         let message = match self.format {
-            SyslogFormat::RFC3164 => match self.layout {
+            SyslogFormat::RFC3164 => match layout {
                 None => format!(
                     "{}",
                     self.context.format_rfc3164(severity, Some(record.args()))
                 ),
-                Some(ref layout) => {
+                Some(layout) => {
                     let message = layout.format(record, diagnostics)?;
                     let message = String::from_utf8_lossy(&message);
                     format!("{}", self.context.format_rfc3164(severity, Some(message)))
                 }
             },
             SyslogFormat::RFC5424 => {
-                const EMPTY_MSGID: Option<&str> = None;
                 const EMPTY_STRUCTURED_DATA: Vec<SDElement> = Vec::new();
 
-                match self.layout {
+                let msgid = self.msgid.resolve(record);
+                let msgid = msgid.as_deref();
+                match layout {
                     None => format!(
                         "{}",
                         self.context.format_rfc5424(
                             severity,
-                            EMPTY_MSGID,
+                            msgid,
                             EMPTY_STRUCTURED_DATA,
                             Some(record.args())
                         )
                     ),
-                    Some(ref layout) => {
+                    Some(layout) => {
                         let message = layout.format(record, diagnostics)?;
                         let message = String::from_utf8_lossy(&message);
                         format!(
                             "{}",
                             self.context.format_rfc5424(
                                 severity,
-                                EMPTY_MSGID,
+                                msgid,
                                 EMPTY_STRUCTURED_DATA,
                                 Some(message)
                             )
@@ -159,9 +267,15 @@ impl Append for Syslog {
                 }
             }
         };
-        self.writer.send(message.into_bytes())?;
+        self.writer.send(message.into_bytes(), record.level())?;
         Ok(())
     }
+
+    fn flush(&self) {
+        if let Err(err) = self.writer.flush() {
+            eprintln!("failed to flush syslog appender: {err:?}");
+        }
+    }
 }
 
 /// Create a non-blocking builder for syslog writers.
@@ -170,36 +284,66 @@ pub fn non_blocking(writer: SyslogWriter) -> NonBlockingBuilder<SyslogWriter> {
 }
 
 /// A writer that writes formatted log records to syslog.
+///
+/// Writers created with [`SyslogWriter::tcp`], [`SyslogWriter::native_tls`] and their variants
+/// reconnect when the connection breaks or a write times out (e.g., the syslog server restarts).
+/// While disconnected, messages are buffered up to a limit (see
+/// [`SyslogWriter::with_reconnect_buffer`]) and reconnection is retried with exponential backoff.
+/// Reconnect events are reported to stderr.
 #[derive(Debug)]
 pub struct SyslogWriter {
-    sender: SyslogSender,
+    transport: Transport,
+}
+
+#[derive(Debug)]
+enum Transport {
+    Sender(SyslogSender),
+    // messages are framed with a trailing CRLF as fasyslog's stream senders do (RFC 6587)
+    Stream(Reconnecting),
 }
 
 impl SyslogWriter {
     /// Create a new syslog writer that sends messages to the given syslog sender.
     pub fn new(sender: SyslogSender) -> Self {
-        Self { sender }
+        Self {
+            transport: Transport::Sender(sender),
+        }
+    }
+
+    /// Sets the maximum number of messages buffered while reconnecting. Default to 1024.
+    ///
+    /// When the buffer is full, the oldest messages are dropped. No effect on writers that do not
+    /// reconnect.
+    pub fn with_reconnect_buffer(mut self, max_pending: usize) -> Self {
+        if let Transport::Stream(connection) = &mut self.transport {
+            connection.set_max_pending(max_pending);
+        }
+        self
     }
 
     /// Create a new syslog writer that sends messages to the well-known TCP port (514).
     pub fn tcp_well_known() -> io::Result<SyslogWriter> {
-        fasyslog::sender::tcp_well_known()
-            .map(SyslogSender::Tcp)
-            .map(Self::new)
+        Self::tcp("127.0.0.1:601")
     }
 
     /// Create a new syslog writer that sends messages to the given TCP address.
-    pub fn tcp<A: std::net::ToSocketAddrs>(addr: A) -> io::Result<SyslogWriter> {
-        fasyslog::sender::tcp(addr)
-            .map(SyslogSender::Tcp)
-            .map(Self::new)
+    ///
+    /// The address is resolved again on every reconnect.
+    pub fn tcp<A>(addr: A) -> io::Result<SyslogWriter>
+    where
+        A: ToSocketAddrs + Send + Sync + 'static,
+    {
+        let endpoint = format_addrs(&addr.to_socket_addrs()?.collect::<Vec<_>>());
+        let connection = Reconnecting::connect(endpoint.clone(), move || {
+            Ok(Box::new(reconnect::connect_tcp(&addr)?))
+        })?;
+        report("tcp", endpoint);
+        Ok(Self::stream(connection))
     }
 
     /// Create a new syslog writer that sends messages to the well-known UDP port (514).
     pub fn udp_well_known() -> io::Result<SyslogWriter> {
-        fasyslog::sender::udp_well_known()
-            .map(SyslogSender::Udp)
-            .map(Self::new)
+        Self::udp("0.0.0.0:0", "127.0.0.1:514")
     }
 
     /// Create a new syslog writer that sends messages to the given UDP address.
@@ -207,70 +351,93 @@ impl SyslogWriter {
         local: L,
         remote: R,
     ) -> io::Result<SyslogWriter> {
-        fasyslog::sender::udp(local, remote)
+        let endpoint = format_addrs(&remote.to_socket_addrs()?.collect::<Vec<_>>());
+        let writer = fasyslog::sender::udp(local, remote)
             .map(SyslogSender::Udp)
-            .map(Self::new)
+            .map(Self::new)?;
+        report("udp", endpoint);
+        Ok(writer)
     }
 
     /// Create a new syslog writer that broadcast messages to the well-known UDP port (514).
     pub fn broadcast_well_known() -> io::Result<SyslogWriter> {
-        fasyslog::sender::broadcast_well_known()
-            .map(SyslogSender::Udp)
-            .map(Self::new)
+        Self::broadcast(514)
     }
 
     /// Create a new syslog writer that broadcast messages to the given UDP address.
     pub fn broadcast(port: u16) -> io::Result<SyslogWriter> {
-        fasyslog::sender::broadcast(port)
+        let writer = fasyslog::sender::broadcast(port)
             .map(SyslogSender::Udp)
-            .map(Self::new)
+            .map(Self::new)?;
+        report("udp broadcast", format!("255.255.255.255:{port}"));
+        Ok(writer)
     }
 
     /// Create a TLS sender that sends messages to the well-known port (6514).
     #[cfg(feature = "native-tls")]
     pub fn native_tls_well_known<S: AsRef<str>>(domain: S) -> io::Result<SyslogWriter> {
-        fasyslog::sender::native_tls_well_known(domain)
-            .map(SyslogSender::NativeTlsSender)
-            .map(Self::new)
+        let domain = domain.as_ref().to_string();
+        Self::native_tls((domain.clone(), 6514), &domain)
     }
 
     /// Create a TLS sender that sends messages to the given address.
+    ///
+    /// The address is resolved again on every reconnect.
     #[cfg(feature = "native-tls")]
-    pub fn native_tls<A: std::net::ToSocketAddrs, S: AsRef<str>>(
-        addr: A,
-        domain: S,
-    ) -> io::Result<SyslogWriter> {
-        fasyslog::sender::native_tls(addr, domain)
-            .map(SyslogSender::NativeTlsSender)
-            .map(Self::new)
+    pub fn native_tls<A, S>(addr: A, domain: S) -> io::Result<SyslogWriter>
+    where
+        A: ToSocketAddrs + Send + Sync + 'static,
+        S: AsRef<str>,
+    {
+        Self::native_tls_with(addr, domain, native_tls::TlsConnector::builder())
     }
 
     /// Create a TLS sender that sends messages to the given address with certificate builder.
+    ///
+    /// The address is resolved again on every reconnect.
     #[cfg(feature = "native-tls")]
-    pub fn native_tls_with<A: std::net::ToSocketAddrs, S: AsRef<str>>(
+    pub fn native_tls_with<A, S>(
         addr: A,
         domain: S,
         builder: native_tls::TlsConnectorBuilder,
-    ) -> io::Result<SyslogWriter> {
-        fasyslog::sender::native_tls_with(addr, domain, builder)
-            .map(SyslogSender::NativeTlsSender)
-            .map(Self::new)
+    ) -> io::Result<SyslogWriter>
+    where
+        A: ToSocketAddrs + Send + Sync + 'static,
+        S: AsRef<str>,
+    {
+        let addrs = addr.to_socket_addrs()?.collect::<Vec<_>>();
+        let domain = domain.as_ref().to_string();
+        let endpoint = format!("{} ({domain})", format_addrs(&addrs));
+        let connector = builder.build().map_err(io::Error::other)?;
+        let connection = Reconnecting::connect(endpoint.clone(), move || {
+            Ok(Box::new(reconnect::connect_tls(
+                &addr, &domain, &connector,
+            )?))
+        })?;
+        report("tls", endpoint);
+        Ok(Self::stream(connection))
     }
 
     /// Create a new syslog writer that sends messages to the given Unix stream socket.
     #[cfg(unix)]
     pub fn unix_stream(path: impl AsRef<std::path::Path>) -> io::Result<SyslogWriter> {
-        fasyslog::sender::unix_stream(path)
+        let endpoint = path.as_ref().display().to_string();
+        let writer = fasyslog::sender::unix_stream(path)
             .map(SyslogSender::UnixStream)
-            .map(Self::new)
+            .map(Self::new)?;
+        report("unix stream", endpoint);
+        Ok(writer)
     }
 
     /// Create a new syslog writer that sends messages to the given Unix datagram socket.
     #[cfg(unix)]
     pub fn unix_datagram(path: impl AsRef<std::path::Path>) -> io::Result<SyslogWriter> {
-        fasyslog::sender::unix_datagram(path)
+        let endpoint = path.as_ref().display().to_string();
+        let writer = fasyslog::sender::unix_datagram(path)
             .map(SyslogSender::UnixDatagram)
-            .map(Self::new)
+            .map(Self::new)?;
+        report("unix datagram", endpoint);
+        Ok(writer)
     }
 
     /// Create a new syslog writer that sends messages to the given Unix socket.
@@ -279,16 +446,101 @@ impl SyslogWriter {
     /// path.
     #[cfg(unix)]
     pub fn unix(path: impl AsRef<std::path::Path>) -> io::Result<SyslogWriter> {
-        fasyslog::sender::unix(path).map(Self::new)
+        let endpoint = path.as_ref().display().to_string();
+        let sender = fasyslog::sender::unix(path)?;
+        let transport = match sender {
+            SyslogSender::UnixDatagram(_) => "unix datagram",
+            _ => "unix stream",
+        };
+        report(transport, endpoint);
+        Ok(Self::new(sender))
+    }
+
+    fn stream(connection: Reconnecting) -> Self {
+        Self {
+            transport: Transport::Stream(connection),
+        }
     }
 }
 
+fn format_addrs(addrs: &[SocketAddr]) -> String {
+    addrs
+        .iter()
+        .map(SocketAddr::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn report(transport: &str, endpoint: String) {
+    startup::report(
+        "syslog writer",
+        vec![("transport", transport.to_string()), ("endpoint", endpoint)],
+    );
+}
+
 impl Writer for SyslogWriter {
     fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
-        self.sender.send_formatted(buf)
+        match &mut self.transport {
+            Transport::Sender(sender) => sender.send_formatted(buf),
+            Transport::Stream(connection) => {
+                let mut message = Vec::with_capacity(buf.len() + 2);
+                message.extend_from_slice(buf);
+                message.extend_from_slice(b"\r\n");
+                connection.send(&message)
+            }
+        }
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        self.sender.flush()
+        match &mut self.transport {
+            Transport::Sender(sender) => sender.flush(),
+            Transport::Stream(connection) => connection.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::append::reconnect::tests::Peer;
+
+    fn writer(peer: &Peer) -> SyslogWriter {
+        let connect = {
+            let peer = peer.clone();
+            move || peer.connect()
+        };
+        SyslogWriter::stream(Reconnecting::connect("peer".to_string(), connect).unwrap())
+    }
+
+    #[test]
+    fn test_reconnect_on_broken_pipe() {
+        let peer = Peer::default();
+        let mut writer = writer(&peer);
+        writer.write_all(b"first").unwrap();
+        *peer.break_after.lock().unwrap() = Some(0);
+        // fails on the broken connection and is resent after reconnecting
+        writer.write_all(b"second").unwrap();
+        std::thread::sleep(Reconnecting::INITIAL_BACKOFF);
+        writer.write_all(b"third").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(peer.received(), "first\r\nsecond\r\nthird\r\n");
+    }
+
+    #[test]
+    fn test_reconnect_buffer_overflow() {
+        let peer = Peer::default();
+        let mut writer = writer(&peer).with_reconnect_buffer(2);
+        writer.write_all(b"first").unwrap();
+        peer.down.store(true, Ordering::Relaxed);
+        *peer.break_after.lock().unwrap() = Some(0);
+        for message in ["lost", "kept", "also kept"] {
+            writer.write_all(message.as_bytes()).unwrap();
+        }
+        peer.down.store(false, Ordering::Relaxed);
+        std::thread::sleep(Reconnecting::INITIAL_BACKOFF);
+        writer.write_all(b"last").unwrap();
+        assert_eq!(peer.received(), "first\r\nkept\r\nalso kept\r\nlast\r\n");
     }
 }
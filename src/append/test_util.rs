@@ -0,0 +1,58 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers shared by the tests of appenders.
+
+use log::Level;
+use log::Record;
+
+use crate::append::Append;
+use crate::append::MemoryRecord;
+use crate::append::TestingRecord;
+
+/// Calls `f` with a record of the `app` target with the given level and message.
+pub(crate) fn with_record<R>(level: Level, message: &str, f: impl FnOnce(&Record) -> R) -> R {
+    f(&Record::builder()
+        .args(format_args!("{message}"))
+        .level(level)
+        .target("app")
+        .build())
+}
+
+/// Appends a record of the `app` target with the given level and message.
+pub(crate) fn append(append: &impl Append, level: Level, message: &str) -> anyhow::Result<()> {
+    with_record(level, message, |record| append.append(record, &[]))
+}
+
+/// A record kept by an appender for tests.
+pub(crate) trait Kept {
+    fn message(self) -> String;
+}
+
+impl Kept for MemoryRecord {
+    fn message(self) -> String {
+        self.message
+    }
+}
+
+impl Kept for TestingRecord {
+    fn message(self) -> String {
+        self.message
+    }
+}
+
+/// Returns the messages of the records.
+pub(crate) fn messages<R: Kept>(records: Vec<R>) -> Vec<String> {
+    records.into_iter().map(Kept::message).collect()
+}
@@ -0,0 +1,293 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+use log::Level;
+use log::Record;
+
+use crate::append::Append;
+use crate::diagnostic::Visitor;
+use crate::Diagnostic;
+
+/// An appender that captures log records for assertions in tests.
+///
+/// Records are captured with their key-values and the key-values of the diagnostics, without
+/// formatting. Use [`Testing::handle`] to query them.
+///
+/// # Examples
+///
+/// ```
+/// use log::Level;
+/// use logforth::append::Testing;
+///
+/// let testing = Testing::default();
+/// let handle = testing.handle();
+///
+/// logforth::builder().dispatch(|d| d.append(testing)).apply();
+///
+/// log::error!(request_id = "42"; "request failed");
+/// assert!(handle.logs_contain("request failed"));
+/// assert_eq!(handle.records_with_level(Level::Error).len(), 1);
+/// handle.assert_kv("request_id", "42");
+/// ```
+#[derive(Debug, Default)]
+pub struct Testing {
+    records: Arc<Mutex<Vec<TestingRecord>>>,
+}
+
+/// A record captured by a [`Testing`] appender.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TestingRecord {
+    /// The level of the record.
+    pub level: Level,
+    /// The target of the record.
+    pub target: String,
+    /// The message of the record.
+    pub message: String,
+    /// The key-values of the record, in order.
+    pub kvs: Vec<(String, String)>,
+    /// The key-values of the diagnostics, in order.
+    pub diagnostics: Vec<(String, String)>,
+}
+
+impl TestingRecord {
+    /// Returns the value of the key-value `key` of the record, if any.
+    pub fn kv(&self, key: &str) -> Option<&str> {
+        find(&self.kvs, key)
+    }
+
+    /// Returns the value of the diagnostic `key` of the record, if any.
+    pub fn diagnostic(&self, key: &str) -> Option<&str> {
+        find(&self.diagnostics, key)
+    }
+}
+
+fn find<'a>(kvs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    kvs.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+impl Testing {
+    /// Returns a handle to query the captured records.
+    pub fn handle(&self) -> TestingHandle {
+        TestingHandle {
+            records: self.records.clone(),
+        }
+    }
+}
+
+impl Append for Testing {
+    fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        let mut collector = Collector::default();
+        record.key_values().visit(&mut collector)?;
+        let kvs = std::mem::take(&mut collector.kvs);
+        for d in diagnostics {
+            d.visit(&mut collector);
+        }
+
+        let record = TestingRecord {
+            level: record.level(),
+            target: record.target().to_owned(),
+            message: record.args().to_string(),
+            kvs,
+            diagnostics: collector.kvs,
+        };
+        lock(&self.records).push(record);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Collector {
+    kvs: Vec<(String, String)>,
+}
+
+impl<'kvs> log::kv::VisitSource<'kvs> for Collector {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        self.kvs.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+impl Visitor for Collector {
+    fn visit<'k, 'v, K, V>(&mut self, key: K, value: V)
+    where
+        K: Into<Cow<'k, str>>,
+        V: Into<Cow<'v, str>>,
+    {
+        self.kvs
+            .push((key.into().into_owned(), value.into().into_owned()));
+    }
+}
+
+fn lock(records: &Mutex<Vec<TestingRecord>>) -> MutexGuard<'_, Vec<TestingRecord>> {
+    // a failed assertion while holding the lock cannot leave the records inconsistent
+    records.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// A handle to query the records captured by a [`Testing`] appender.
+#[derive(Debug, Clone)]
+pub struct TestingHandle {
+    records: Arc<Mutex<Vec<TestingRecord>>>,
+}
+
+impl TestingHandle {
+    /// Returns the captured records, from the oldest to the newest.
+    pub fn records(&self) -> Vec<TestingRecord> {
+        lock(&self.records).clone()
+    }
+
+    /// Returns the captured records at the given level.
+    pub fn records_with_level(&self, level: Level) -> Vec<TestingRecord> {
+        self.records_matching(|record| record.level == level)
+    }
+
+    /// Returns the captured records for which `predicate` returns `true`.
+    pub fn records_matching<F>(&self, predicate: F) -> Vec<TestingRecord>
+    where
+        F: Fn(&TestingRecord) -> bool,
+    {
+        lock(&self.records)
+            .iter()
+            .filter(|record| predicate(record))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns `true` if the message of any captured record contains `text`.
+    pub fn logs_contain(&self, text: &str) -> bool {
+        lock(&self.records)
+            .iter()
+            .any(|record| record.message.contains(text))
+    }
+
+    /// Asserts that a captured record has the key-value `key` with `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the captured records if no record has the key-value.
+    #[track_caller]
+    pub fn assert_kv(&self, key: &str, value: &str) {
+        let records = lock(&self.records);
+        if !records.iter().any(|record| record.kv(key) == Some(value)) {
+            panic!(
+                "no record with key-value {key}={value}; captured records:\n{}",
+                dump(&records)
+            );
+        }
+    }
+
+    /// Asserts that a captured record has the diagnostic `key` with `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the captured records if no record has the diagnostic.
+    #[track_caller]
+    pub fn assert_diagnostic(&self, key: &str, value: &str) {
+        let records = lock(&self.records);
+        if !records
+            .iter()
+            .any(|record| record.diagnostic(key) == Some(value))
+        {
+            panic!(
+                "no record with diagnostic {key}={value}; captured records:\n{}",
+                dump(&records)
+            );
+        }
+    }
+
+    /// Removes the captured records.
+    pub fn clear(&self) {
+        lock(&self.records).clear();
+    }
+}
+
+fn dump(records: &[TestingRecord]) -> String {
+    let mut text = String::new();
+    for record in records {
+        write!(
+            text,
+            "  {} {}: {}",
+            record.level, record.target, record.message
+        )
+        .unwrap();
+        for (key, value) in record.kvs.iter().chain(&record.diagnostics) {
+            write!(text, " {key}={value}").unwrap();
+        }
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::LazyDiagnostic;
+
+    #[test]
+    fn test_testing_queries() {
+        let testing = Testing::default();
+        let handle = testing.handle();
+
+        let kvs = [("request_id", "42")];
+        let diagnostic = LazyDiagnostic::new("user", || Some("alice".to_string()));
+        testing
+            .append(
+                &Record::builder()
+                    .args(format_args!("request failed"))
+                    .level(Level::Error)
+                    .target("app")
+                    .key_values(&kvs)
+                    .build(),
+                &[diagnostic.into()],
+            )
+            .unwrap();
+        testing
+            .append(
+                &Record::builder()
+                    .args(format_args!("request started"))
+                    .level(Level::Info)
+                    .target("app")
+                    .build(),
+                &[],
+            )
+            .unwrap();
+
+        assert!(handle.logs_contain("failed"));
+        assert!(!handle.logs_contain("finished"));
+        let errors = handle.records_with_level(Level::Error);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kv("request_id"), Some("42"));
+        assert_eq!(errors[0].diagnostic("user"), Some("alice"));
+        assert_eq!(errors[0].kv("user"), None);
+        handle.assert_kv("request_id", "42");
+        handle.assert_diagnostic("user", "alice");
+
+        let panic = std::panic::catch_unwind(|| handle.assert_kv("request_id", "43"));
+        let message = *panic.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.contains("ERROR app: request failed request_id=42 user=alice"));
+
+        handle.clear();
+        assert!(handle.records().is_empty());
+    }
+}
@@ -0,0 +1,336 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use log::kv::Source;
+use log::kv::Value;
+use log::Level;
+use log::Record;
+
+use crate::append::repeats;
+use crate::append::repeats::DiagnosticSnapshot;
+use crate::append::repeats::Fingerprint;
+use crate::append::Append;
+use crate::clock;
+use crate::Diagnostic;
+use crate::Layout;
+
+/// The number of fingerprints kept before those out of their cooldown are evicted.
+const MAX_IDLE_FINGERPRINTS: usize = 1024;
+
+/// An appender that throttles repeated records, meant for alerting sinks such as webhooks.
+///
+/// Records at or above the configured level (default to [`Level::Error`]) are grouped by
+/// fingerprint, by default their target and message. The first record of a fingerprint is
+/// forwarded, and the same fingerprint is then suppressed for the `cooldown`. The first record
+/// after the cooldown is forwarded with the number of suppressed records appended to its message
+/// and as the `suppressed` key-value.
+///
+/// Suppressed records that are not followed by another record of the same fingerprint are
+/// reported with a summary record when the appender is flushed after the cooldown.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use logforth::append;
+/// use logforth::append::Throttle;
+///
+/// logforth::builder()
+///     .dispatch(|d| {
+///         d.filter(log::LevelFilter::Error).append(Throttle::new(
+///             append::Stderr::default(),
+///             Duration::from_secs(600),
+///         ))
+///     })
+///     .apply();
+/// ```
+pub struct Throttle {
+    append: Box<dyn Append>,
+    level: Level,
+    cooldown: Duration,
+    fingerprint: Fingerprint,
+    states: Mutex<HashMap<String, ThrottleState>>,
+}
+
+impl fmt::Debug for Throttle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Throttle")
+            .field("append", &self.append)
+            .field("level", &self.level)
+            .field("cooldown", &self.cooldown)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct ThrottleState {
+    last_forwarded: Instant,
+    suppressed: usize,
+    // the first suppressed record, to summarize on flush
+    exemplar: Option<Exemplar>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Exemplar {
+    level: Level,
+    target: String,
+    message: String,
+    diagnostics: DiagnosticSnapshot,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Decision {
+    Forward { suppressed: usize },
+    Suppress,
+}
+
+impl Throttle {
+    /// Creates a new [`Throttle`] that wraps the given appender, forwarding each fingerprint at
+    /// most once per `cooldown`.
+    pub fn new(append: impl Append, cooldown: Duration) -> Self {
+        Self {
+            append: Box::new(append),
+            level: Level::Error,
+            cooldown,
+            fingerprint: Fingerprint::default(),
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets the least severe level of throttled records. Default to [`Level::Error`].
+    ///
+    /// Records less severe than this level are always forwarded.
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets how records are grouped, e.g., by target and an `error_code` key-value. Default to
+    /// the target and the message of records.
+    pub fn with_fingerprint<F>(mut self, fingerprint: F) -> Self
+    where
+        F: Fn(&Record) -> String + Send + Sync + 'static,
+    {
+        self.fingerprint = Fingerprint::new(fingerprint);
+        self
+    }
+
+    fn on_record(
+        &self,
+        fingerprint: String,
+        exemplar: impl FnOnce() -> Exemplar,
+        now: Instant,
+    ) -> Decision {
+        let mut states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(state) = states.get_mut(&fingerprint) {
+            if now.duration_since(state.last_forwarded) < self.cooldown {
+                state.suppressed += 1;
+                if state.exemplar.is_none() {
+                    state.exemplar = Some(exemplar());
+                }
+                return Decision::Suppress;
+            }
+            state.last_forwarded = now;
+            state.exemplar = None;
+            let suppressed = std::mem::take(&mut state.suppressed);
+            return Decision::Forward { suppressed };
+        }
+
+        if states.len() >= MAX_IDLE_FINGERPRINTS {
+            // forget fingerprints out of their cooldown, unless they have records to report
+            states.retain(|_, state| {
+                state.suppressed > 0 || now.duration_since(state.last_forwarded) < self.cooldown
+            });
+        }
+        states.insert(
+            fingerprint,
+            ThrottleState {
+                last_forwarded: now,
+                suppressed: 0,
+                exemplar: None,
+            },
+        );
+        Decision::Forward { suppressed: 0 }
+    }
+
+    fn append_with_suppressed(
+        &self,
+        record: &Record,
+        suppressed: usize,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        let kv = ("suppressed", Value::from(suppressed));
+        let kvs: [&dyn Source; 2] = [record.key_values(), &kv];
+        self.append.append_with_layout(
+            &record
+                .to_builder()
+                .args(format_args!(
+                    "{} (suppressed {suppressed} similar records in the last {:?})",
+                    record.args(),
+                    self.cooldown
+                ))
+                .key_values(&kvs)
+                .build(),
+            diagnostics,
+            layout,
+        )
+    }
+}
+
+impl Append for Throttle {
+    fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        if record.level() > self.level {
+            return self.append.append_with_layout(record, diagnostics, layout);
+        }
+
+        let exemplar = || Exemplar {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+            diagnostics: DiagnosticSnapshot::capture(diagnostics),
+        };
+        match self.on_record(self.fingerprint.of(record), exemplar, clock::instant()) {
+            Decision::Forward { suppressed: 0 } => {
+                self.append.append_with_layout(record, diagnostics, layout)
+            }
+            Decision::Forward { suppressed } => {
+                self.append_with_suppressed(record, suppressed, diagnostics, layout)
+            }
+            Decision::Suppress => Ok(()),
+        }
+    }
+
+    fn flush(&self) {
+        self.flush_with_layout(None)
+    }
+
+    fn flush_with_layout(&self, layout: Option<&Layout>) {
+        let now = clock::instant();
+        let exemplars = {
+            let mut states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+            states
+                .values_mut()
+                .filter(|state| now.duration_since(state.last_forwarded) >= self.cooldown)
+                .filter_map(|state| {
+                    let exemplar = state.exemplar.take()?;
+                    state.last_forwarded = now;
+                    Some((exemplar, std::mem::take(&mut state.suppressed)))
+                })
+                .collect::<Vec<_>>()
+        };
+        for (exemplar, suppressed) in exemplars {
+            if let Err(err) = self.append_with_suppressed(
+                &Record::builder()
+                    .level(exemplar.level)
+                    .target(&exemplar.target)
+                    .args(format_args!("{}", exemplar.message))
+                    .build(),
+                suppressed,
+                &exemplar.diagnostics.diagnostics(),
+                layout,
+            ) {
+                repeats::report_flush_error(&self.append, "throttled records", err);
+            }
+        }
+        self.append.flush_with_layout(layout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append::test_util;
+    use crate::append::Memory;
+
+    fn exemplar(message: &str) -> impl FnOnce() -> Exemplar + '_ {
+        move || Exemplar {
+            level: Level::Error,
+            target: "app".to_string(),
+            message: message.to_string(),
+            diagnostics: DiagnosticSnapshot::default(),
+        }
+    }
+
+    #[test]
+    fn test_throttle_cooldown() {
+        let throttle = Throttle::new(Memory::new(0), Duration::from_secs(60));
+        let start = Instant::now();
+        let fingerprint = || "app\0disk full".to_string();
+
+        let decision = throttle.on_record(fingerprint(), exemplar("disk full"), start);
+        assert_eq!(decision, Decision::Forward { suppressed: 0 });
+        for n in 1..=3 {
+            let now = start + Duration::from_secs(n);
+            let decision = throttle.on_record(fingerprint(), exemplar("disk full"), now);
+            assert_eq!(decision, Decision::Suppress);
+        }
+
+        // other fingerprints are unaffected
+        let decision = throttle.on_record("other".to_string(), exemplar("other"), start);
+        assert_eq!(decision, Decision::Forward { suppressed: 0 });
+
+        // the next record after the cooldown reports the suppressed ones
+        let next = start + Duration::from_secs(60);
+        let decision = throttle.on_record(fingerprint(), exemplar("disk full"), next);
+        assert_eq!(decision, Decision::Forward { suppressed: 3 });
+        let decision = throttle.on_record(fingerprint(), exemplar("disk full"), next);
+        assert_eq!(decision, Decision::Suppress);
+    }
+
+    #[test]
+    fn test_throttle_append() {
+        let memory = Memory::new(10);
+        let handle = memory.handle();
+        let throttle = Throttle::new(memory, Duration::ZERO).with_level(Level::Warn);
+        let append =
+            |level: Level, message: &str| test_util::append(&throttle, level, message).unwrap();
+
+        // a zero cooldown forwards every record; less severe records are never throttled
+        append(Level::Warn, "slow");
+        append(Level::Warn, "slow");
+        append(Level::Info, "started");
+        append(Level::Info, "started");
+        assert_eq!(handle.len(), 4);
+
+        // the suppressed count is reported with the next forwarded record
+        {
+            let mut states = throttle.states.lock().unwrap();
+            let state = states.get_mut("app\0slow").unwrap();
+            state.suppressed = 2;
+        }
+        append(Level::Warn, "slow");
+        let record = handle.drain().pop().unwrap();
+        assert!(record
+            .message
+            .contains("slow (suppressed 2 similar records in the last 0ns)"));
+        assert!(record.message.contains("suppressed=2"));
+    }
+}
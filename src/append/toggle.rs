@@ -0,0 +1,118 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use log::Record;
+
+use crate::append::Append;
+use crate::Diagnostic;
+use crate::Layout;
+
+/// An appender that can be switched on and off at runtime.
+///
+/// Use [`Toggle::handle`] to obtain an [`AppendHandle`] before installing the appender. While
+/// disabled, records are dropped with a single atomic load, so an expensive sink can be switched
+/// off (e.g., during an incident) without rebuilding the logger.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::append;
+/// use logforth::append::Toggle;
+///
+/// let stdout = Toggle::new(append::Stdout::default());
+/// let handle = stdout.handle();
+///
+/// logforth::builder().dispatch(|d| d.append(stdout)).apply();
+///
+/// handle.set_enabled(false);
+/// log::info!("this record is dropped");
+/// handle.set_enabled(true);
+/// ```
+#[derive(Debug)]
+pub struct Toggle<A> {
+    append: A,
+    enabled: Arc<AtomicBool>,
+}
+
+impl<A: Append> Toggle<A> {
+    /// Creates a new [`Toggle`] wrapping the given appender, enabled initially.
+    pub fn new(append: A) -> Self {
+        Self {
+            append,
+            enabled: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    /// Sets whether the appender is enabled initially. Default to `true`.
+    pub fn with_enabled(self, enabled: bool) -> Self {
+        self.enabled.store(enabled, Ordering::Relaxed);
+        self
+    }
+
+    /// Returns a handle to switch the appender on and off.
+    pub fn handle(&self) -> AppendHandle {
+        AppendHandle {
+            enabled: self.enabled.clone(),
+        }
+    }
+}
+
+impl<A: Append> Append for Toggle<A> {
+    fn append(&self, record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        self.append_with_layout(record, diagnostics, None)
+    }
+
+    fn append_with_layout(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+        layout: Option<&Layout>,
+    ) -> anyhow::Result<()> {
+        if self.enabled.load(Ordering::Relaxed) {
+            self.append.append_with_layout(record, diagnostics, layout)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn flush(&self) {
+        self.flush_with_layout(None)
+    }
+
+    fn flush_with_layout(&self, layout: Option<&Layout>) {
+        self.append.flush_with_layout(layout);
+    }
+}
+
+/// A handle to switch a [`Toggle`] appender on and off.
+#[derive(Debug, Clone)]
+pub struct AppendHandle {
+    enabled: Arc<AtomicBool>,
+}
+
+impl AppendHandle {
+    /// Sets whether the appender is enabled.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether the appender is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+}
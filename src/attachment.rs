@@ -0,0 +1,154 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Binary attachments of log records.
+//!
+//! A record can carry a small binary payload, e.g., a serialized protobuf message or a digest,
+//! for the appenders that can store it. Since key-values of the `log` crate cannot hold bytes,
+//! the payload is attached under the reserved key [`ATTACHMENT_KEY`] as its base64 encoding with
+//! [`Attachment`], and [`attachment`] decodes it back.
+//!
+//! [`TextLayout`](crate::layout::TextLayout) omits the attachment. The OpenTelemetry appender
+//! forwards it as a bytes attribute, and other layouts and appenders treat it as a regular
+//! key-value holding the base64 text.
+//!
+//! # Examples
+//!
+//! ```
+//! use logforth::attachment::Attachment;
+//!
+//! logforth::stdout().apply();
+//!
+//! let digest = [0xde, 0xad, 0xbe, 0xef];
+//! log::error!("logforth.attachment" = Attachment::new(&digest); "checksum mismatch");
+//! ```
+
+use std::fmt;
+use std::fmt::Write;
+
+use log::kv::Key;
+use log::kv::ToValue;
+use log::kv::Value;
+use log::Record;
+
+/// The key under which the binary attachment is attached to a record.
+pub const ATTACHMENT_KEY: &str = "logforth.attachment";
+
+/// Returns the binary attachment of the record, if it has a valid one.
+pub fn attachment(record: &Record) -> Option<Vec<u8>> {
+    let value = record.key_values().get(Key::from_str(ATTACHMENT_KEY))?;
+    decode(&value.to_string())
+}
+
+pub(crate) fn is_attachment_key(key: &str) -> bool {
+    key == ATTACHMENT_KEY
+}
+
+/// A binary payload to attach to a record under [`ATTACHMENT_KEY`].
+///
+/// The payload is rendered as its base64 encoding.
+#[derive(Debug, Clone, Copy)]
+pub struct Attachment<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Attachment<'a> {
+    /// Creates a new [`Attachment`] of the given bytes.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+}
+
+impl fmt::Display for Attachment<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in self.bytes.chunks(3) {
+            let n = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |n, (i, &b)| n | (u32::from(b) << (16 - 8 * i)));
+            let mut quad = [b'='; 4];
+            for (i, c) in quad.iter_mut().enumerate().take(chunk.len() + 1) {
+                *c = ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize];
+            }
+            for c in quad {
+                f.write_char(char::from(c))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ToValue for Attachment<'_> {
+    fn to_value(&self) -> Value<'_> {
+        Value::from_display(self)
+    }
+}
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Decodes padded standard base64, returning `None` on malformed input.
+pub(crate) fn decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.as_bytes();
+    if text.len() % 4 != 0 {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    for (index, quad) in text.chunks(4).enumerate() {
+        let last = index == text.len() / 4 - 1;
+        let padding = quad.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return None;
+        }
+
+        let mut n = 0u32;
+        for (i, &c) in quad[..4 - padding].iter().enumerate() {
+            let sextet = ALPHABET.iter().position(|&a| a == c)? as u32;
+            n |= sextet << (18 - 6 * i);
+        }
+        bytes.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attachment_round_trip() {
+        for (bytes, text) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"\xde\xad\xbe\xef", "3q2+7w=="),
+        ] {
+            assert_eq!(Attachment::new(bytes).to_string(), text);
+            assert_eq!(decode(text).as_deref(), Some(bytes));
+        }
+        assert_eq!(decode("Zm9"), None);
+        assert_eq!(decode("Zg==Zg=="), None);
+        assert_eq!(decode("Z!=="), None);
+
+        let attachment = Attachment::new(b"\x00\x01\x02");
+        let kvs = [(ATTACHMENT_KEY, attachment.to_value())];
+        let record = Record::builder().key_values(&kvs).build();
+        assert_eq!(
+            super::attachment(&record).as_deref(),
+            Some(&b"\x00\x01\x02"[..])
+        );
+    }
+}
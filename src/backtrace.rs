@@ -0,0 +1,101 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Backtrace capture for log records.
+//!
+//! With [`Builder::backtrace_level`](crate::Builder::backtrace_level), the logger captures a
+//! [`Backtrace`](std::backtrace::Backtrace) for records at or above the given level and attaches
+//! it to the record as a key-value under [`BACKTRACE_KEY`]. Layouts render it on demand, e.g.,
+//! with [`TextLayout::backtrace`](crate::layout::TextLayout::backtrace).
+//!
+//! # Examples
+//!
+//! ```
+//! use logforth::append;
+//! use logforth::layout::TextLayout;
+//!
+//! logforth::builder()
+//!     .backtrace_level(log::Level::Error)
+//!     .dispatch(|d| {
+//!         let layout = TextLayout::default().backtrace(true);
+//!         d.append(append::Stderr::default().with_layout(layout))
+//!     })
+//!     .apply();
+//!
+//! log::error!("something went wrong");
+//! ```
+
+use log::kv::Error;
+use log::kv::Key;
+use log::kv::Source;
+use log::kv::Value;
+use log::kv::VisitSource;
+use log::Record;
+
+/// The key under which the captured backtrace is attached to a record.
+pub const BACKTRACE_KEY: &str = "logforth.backtrace";
+
+/// The name under which layouts and appenders write the captured backtrace.
+#[cfg(any(
+    feature = "json",
+    feature = "fastrace",
+    feature = "opentelemetry",
+    all(unix, feature = "journald")
+))]
+pub(crate) const BACKTRACE_FIELD: &str = "backtrace";
+
+/// Returns the captured backtrace of the record, if it has one.
+pub fn backtrace(record: &Record) -> Option<String> {
+    record
+        .key_values()
+        .get(Key::from_str(BACKTRACE_KEY))
+        .map(|value| value.to_string())
+}
+
+pub(crate) fn is_backtrace_key(key: &str) -> bool {
+    key == BACKTRACE_KEY
+}
+
+/// The key-values of a record followed by a captured backtrace.
+pub(crate) struct WithBacktrace<'a> {
+    pub(crate) source: &'a dyn Source,
+    pub(crate) backtrace: &'a str,
+}
+
+impl Source for WithBacktrace<'_> {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), Error> {
+        self.source.visit(visitor)?;
+        visitor.visit_pair(Key::from_str(BACKTRACE_KEY), Value::from(self.backtrace))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_backtrace() {
+        let kvs = [("user_id", 42)];
+        let record = Record::builder().key_values(&kvs).build();
+        assert_eq!(backtrace(&record), None);
+
+        let kvs = WithBacktrace {
+            source: record.key_values(),
+            backtrace: "0: main",
+        };
+        let record = record.to_builder().key_values(&kvs).build();
+        assert_eq!(backtrace(&record).as_deref(), Some("0: main"));
+        assert_eq!(record.key_values().count(), 2);
+    }
+}
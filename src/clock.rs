@@ -0,0 +1,221 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An optional coarse clock for record timestamps, and a manual clock for tests.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicI64;
+use std::sync::atomic::Ordering;
+#[cfg(any(test, feature = "test-util"))]
+use std::sync::Arc;
+#[cfg(any(test, feature = "test-util"))]
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use jiff::Timestamp;
+#[cfg(any(test, feature = "test-util"))]
+use jiff::Zoned;
+
+static COARSE_CLOCK_ENABLED: AtomicBool = AtomicBool::new(false);
+static COARSE_CLOCK_MILLIS: AtomicI64 = AtomicI64::new(0);
+
+/// Starts a background thread that updates the coarse clock at the given resolution.
+///
+/// Only the first call starts the thread; subsequent calls are no-ops.
+pub(crate) fn start_coarse_clock(resolution: Duration) {
+    if COARSE_CLOCK_ENABLED.load(Ordering::Acquire) {
+        return;
+    }
+
+    COARSE_CLOCK_MILLIS.store(Timestamp::now().as_millisecond(), Ordering::Relaxed);
+    let spawned = std::thread::Builder::new()
+        .name("logforth-clock".to_string())
+        .spawn(move || loop {
+            std::thread::sleep(resolution);
+            COARSE_CLOCK_MILLIS.store(Timestamp::now().as_millisecond(), Ordering::Relaxed);
+        });
+    match spawned {
+        Ok(_) => COARSE_CLOCK_ENABLED.store(true, Ordering::Release),
+        Err(err) => eprintln!("failed to start coarse clock thread: {err}"),
+    }
+}
+
+/// Returns the current time, read from the installed manual clock or the coarse clock if any.
+pub(crate) fn now() -> Timestamp {
+    #[cfg(feature = "test-util")]
+    if let Some(installed) = installed() {
+        return installed.clock.now().timestamp();
+    }
+    if COARSE_CLOCK_ENABLED.load(Ordering::Acquire) {
+        let millis = COARSE_CLOCK_MILLIS.load(Ordering::Relaxed);
+        if let Ok(timestamp) = Timestamp::from_millisecond(millis) {
+            return timestamp;
+        }
+    }
+    Timestamp::now()
+}
+
+/// Returns the current monotonic time, advanced by the installed manual clock if any.
+pub(crate) fn instant() -> Instant {
+    #[cfg(feature = "test-util")]
+    if let Some(installed) = installed() {
+        let elapsed = installed
+            .clock
+            .now()
+            .timestamp()
+            .duration_since(installed.anchor);
+        return installed.anchor_instant + Duration::try_from(elapsed).unwrap_or_default();
+    }
+    Instant::now()
+}
+
+#[cfg(feature = "test-util")]
+static MANUAL_CLOCK_INSTALLED: AtomicBool = AtomicBool::new(false);
+#[cfg(feature = "test-util")]
+static MANUAL_CLOCK: Mutex<Option<Installed>> = Mutex::new(None);
+
+#[cfg(feature = "test-util")]
+#[derive(Debug, Clone)]
+struct Installed {
+    clock: ManualClock,
+    // The manual time and the real monotonic time at installation, to derive monotonic time
+    // from the manual time.
+    anchor: Timestamp,
+    anchor_instant: Instant,
+}
+
+#[cfg(feature = "test-util")]
+fn installed() -> Option<Installed> {
+    if !MANUAL_CLOCK_INSTALLED.load(Ordering::Acquire) {
+        return None;
+    }
+    let installed = MANUAL_CLOCK.lock().unwrap_or_else(|e| e.into_inner());
+    installed.clone()
+}
+
+#[cfg(feature = "test-util")]
+fn replace_installed(installed: Option<Installed>) -> Option<Installed> {
+    let mut current = MANUAL_CLOCK.lock().unwrap_or_else(|e| e.into_inner());
+    MANUAL_CLOCK_INSTALLED.store(installed.is_some(), Ordering::Release);
+    std::mem::replace(&mut current, installed)
+}
+
+/// A clock that only moves when it is told to, for deterministic tests of time-based behavior.
+///
+/// Clones share the same time. Once [installed](ManualClock::install), the clock drives the
+/// timestamps of built-in layouts and the windows of [`ErrorStorm`](crate::append::ErrorStorm).
+/// A rolling file follows it with `RollingFileWriterBuilder::clock`.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use logforth::ManualClock;
+///
+/// let clock = ManualClock::new("2024-08-10T17:12:52+08[+08]".parse().unwrap());
+/// let _guard = clock.install();
+/// clock.advance(Duration::from_secs(60));
+/// assert_eq!(clock.now().to_string(), "2024-08-10T17:13:52+08:00[+08:00]");
+/// ```
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Zoned>>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl ManualClock {
+    /// Creates a new [`ManualClock`] set to the given time.
+    pub fn new(now: Zoned) -> ManualClock {
+        ManualClock {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    /// Returns the current time of the clock.
+    pub fn now(&self) -> Zoned {
+        self.now.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Sets the current time of the clock.
+    pub fn set_now(&self, now: Zoned) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = now;
+    }
+
+    /// Moves the current time of the clock forward by the given duration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resulting time is out of the supported range.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        *now = now.checked_add(duration).expect("manual clock overflow");
+    }
+
+    /// Installs the clock as the time source of the crate until the returned guard is dropped.
+    ///
+    /// The clock takes precedence over the coarse clock. Dropping the guard restores the
+    /// previously installed clock, if any. The clock is global to the process, so tests that
+    /// install different clocks should not run concurrently, e.g., each should be in its own
+    /// integration test binary.
+    #[cfg(feature = "test-util")]
+    #[must_use = "the clock is uninstalled when the guard is dropped"]
+    pub fn install(&self) -> ManualClockGuard {
+        let previous = replace_installed(Some(Installed {
+            clock: self.clone(),
+            anchor: self.now().timestamp(),
+            anchor_instant: Instant::now(),
+        }));
+        ManualClockGuard { previous }
+    }
+}
+
+/// A guard that uninstalls a [`ManualClock`] when dropped.
+#[cfg(feature = "test-util")]
+#[derive(Debug)]
+pub struct ManualClockGuard {
+    previous: Option<Installed>,
+}
+
+#[cfg(feature = "test-util")]
+impl Drop for ManualClockGuard {
+    fn drop(&mut self) {
+        replace_installed(self.previous.take());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_manual_clock() {
+        let start = Zoned::from_str("2024-08-10T17:12:52+08[+08]").unwrap();
+        let clock = ManualClock::new(start.clone());
+        let shared = clock.clone();
+        assert_eq!(clock.now(), start);
+
+        let later = Zoned::from_str("2024-08-10T17:13:52+08[+08]").unwrap();
+        shared.advance(Duration::from_secs(60));
+        assert_eq!(clock.now(), later);
+
+        let earlier = Zoned::from_str("2024-01-01T12:00:00+08[+08]").unwrap();
+        shared.set_now(earlier.clone());
+        assert_eq!(clock.now(), earlier);
+    }
+}
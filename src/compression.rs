@@ -0,0 +1,167 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compression algorithms shared by the appenders, e.g., for rotated log files and HTTP requests.
+//!
+//! Each algorithm is enabled by the feature of the same name, `gzip` or `zstd`. Appenders take
+//! an optional [`Compression`], where `None` means no compression.
+
+use std::fmt;
+use std::io;
+use std::io::Write;
+
+/// Compression algorithms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Compress with gzip.
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// Compress with zstd.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// Returns the file extension of the algorithm, e.g., `gz`.
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => "gz",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => "zst",
+        }
+    }
+
+    /// Returns the name of the algorithm in the HTTP `Content-Encoding` header, e.g., `gzip`.
+    pub fn content_encoding(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => "gzip",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    /// Creates an encoder compressing the bytes written to it into `writer`, with the default
+    /// level of the algorithm.
+    ///
+    /// Call [`Encoder::finish`] to complete the compressed stream.
+    pub fn encoder<W: Write>(&self, writer: W) -> io::Result<Encoder<W>> {
+        let inner = match *self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => EncoderInner::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::default(),
+            )),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => EncoderInner::Zstd(zstd::Encoder::new(writer, 0)?),
+        };
+        Ok(Encoder { inner })
+    }
+
+    /// Compresses `bytes` in memory.
+    pub fn compress(&self, bytes: &[u8]) -> io::Result<Vec<u8>> {
+        let mut encoder = self.encoder(Vec::with_capacity(bytes.len() / 4))?;
+        encoder.write_all(bytes)?;
+        encoder.finish()
+    }
+}
+
+/// A writer compressing the bytes written to it, created by [`Compression::encoder`].
+pub struct Encoder<W: Write> {
+    inner: EncoderInner<W>,
+}
+
+impl<W: Write> fmt::Debug for Encoder<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let compression = match self.inner {
+            #[cfg(feature = "gzip")]
+            EncoderInner::Gzip(_) => Compression::Gzip,
+            #[cfg(feature = "zstd")]
+            EncoderInner::Zstd(_) => Compression::Zstd,
+        };
+        f.debug_struct("Encoder")
+            .field("compression", &compression)
+            .finish_non_exhaustive()
+    }
+}
+
+enum EncoderInner<W: Write> {
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Completes the compressed stream and returns the underlying writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self.inner {
+            #[cfg(feature = "gzip")]
+            EncoderInner::Gzip(encoder) => encoder.finish(),
+            #[cfg(feature = "zstd")]
+            EncoderInner::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            #[cfg(feature = "gzip")]
+            EncoderInner::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "zstd")]
+            EncoderInner::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            #[cfg(feature = "gzip")]
+            EncoderInner::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "zstd")]
+            EncoderInner::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_round_trip() {
+        let compressed = Compression::Gzip.compress(b"hello hello hello").unwrap();
+        let mut decompressed = String::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, "hello hello hello");
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_round_trip() {
+        let compressed = Compression::Zstd.compress(b"hello hello hello").unwrap();
+        let mut decompressed = String::new();
+        zstd::Decoder::new(compressed.as_slice())
+            .unwrap()
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, "hello hello hello");
+    }
+}
@@ -0,0 +1,921 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Set up the logger from a configuration document.
+//!
+//! [`Config`] describes dispatches, their filters, appenders and layouts as data, and implements
+//! [`serde::Deserialize`], so that it can be read from TOML, YAML, JSON or any other format with
+//! a serde implementation. For example, in TOML:
+//!
+//! ```toml
+//! max_level = "debug"
+//!
+//! [[dispatches]]
+//! filter = "info,my_crate=debug"
+//! appenders = [{ kind = "stdout", layout = { kind = "text", no_color = true } }]
+//!
+//! [[dispatches]]
+//! filter = "warn"
+//!
+//! [[dispatches.appenders]]
+//! kind = "rolling_file"
+//! dir = "logs"
+//! filename_prefix = "app"
+//! rotation = { daily_at = { hour = 3 } }
+//! max_log_files = 7
+//! layout = { kind = "json" }
+//! ```
+//!
+//! Appenders are `stdout` and `stderr`, as well as `rolling_file` and `syslog` with the
+//! corresponding features. Layouts are `text` and `logfmt`, as well as `json`, `clef` and `ecs`
+//! with the `json` feature; appenders without a layout use the text layout.
+//!
+//! # Examples
+//!
+//! ```
+//! # #[cfg(not(feature = "json"))] fn main() {}
+//! # #[cfg(feature = "json")] fn main() {
+//! use logforth::config::Config;
+//!
+//! let config: Config = serde_json::from_str(
+//!     r#"{
+//!         "dispatches": [{
+//!             "filter": "info",
+//!             "appenders": [{ "kind": "stderr", "layout": { "kind": "json" } }]
+//!         }]
+//!     }"#,
+//! )
+//! .unwrap();
+//!
+//! // keep the guard alive as long as the logger is used
+//! let (builder, _guard) = config.build().unwrap();
+//! builder.apply();
+//! # }
+//! ```
+//!
+//! With the `json` feature, [`Config::json_schema`] describes the configuration document as a
+//! JSON Schema, so that editors and CI can validate configurations before deployment.
+
+use anyhow::Context;
+use jiff::tz::TimeZone;
+use log::LevelFilter;
+use serde::Deserialize;
+#[cfg(feature = "rolling-file")]
+use serde::Serialize;
+
+use crate::append;
+use crate::filter::env_filter::EnvFilterBuilder;
+use crate::filter::EnvFilter;
+use crate::layout::TextLayout;
+use crate::Append;
+use crate::Builder;
+use crate::Layout;
+
+/// The configuration of a logger.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The max level passed to `log::set_max_level()`. Default to `trace`.
+    #[serde(default)]
+    pub max_level: Option<LevelFilter>,
+    /// The dispatches of the logger.
+    #[serde(default)]
+    pub dispatches: Vec<DispatchConfig>,
+}
+
+/// The configuration of a dispatch.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DispatchConfig {
+    /// The env filter directives of the dispatch, e.g., `info,my_crate=debug`. Default to no
+    /// filter.
+    #[serde(default)]
+    pub filter: Option<String>,
+    /// The appenders of the dispatch; at least one is required.
+    pub appenders: Vec<AppenderConfig>,
+}
+
+/// The configuration of an appender, tagged by `kind`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum AppenderConfig {
+    /// The [`Stdout`](append::Stdout) appender.
+    Stdout {
+        /// The layout of the appender.
+        #[serde(default)]
+        layout: Option<LayoutConfig>,
+    },
+    /// The [`Stderr`](append::Stderr) appender.
+    Stderr {
+        /// The layout of the appender.
+        #[serde(default)]
+        layout: Option<LayoutConfig>,
+    },
+    /// The [`RollingFile`](append::rolling_file::RollingFile) appender.
+    #[cfg(feature = "rolling-file")]
+    RollingFile {
+        /// The directory of the log files, where `${NAME}` is replaced with the environment
+        /// variable `NAME`, e.g., `logs/${POD_NAME}`.
+        dir: std::path::PathBuf,
+        /// The prefix of the log file names.
+        #[serde(default)]
+        filename_prefix: Option<String>,
+        /// The suffix of the log file names.
+        #[serde(default)]
+        filename_suffix: Option<String>,
+        /// The pattern of the log file names, e.g., `app-%Y%m%d-%N.log`.
+        #[serde(default)]
+        filename_pattern: Option<String>,
+        /// The rotation policy. Default to `never`.
+        #[serde(default)]
+        rotation: Option<RotationConfig>,
+        /// The maximum number of log files to keep.
+        #[serde(default)]
+        max_log_files: Option<usize>,
+        /// The maximum total size in bytes of the log files to keep.
+        #[serde(default)]
+        max_total_size: Option<u64>,
+        /// The maximum size of a log file in bytes before it is rotated.
+        #[serde(default)]
+        max_file_size: Option<usize>,
+        /// The layout of the appender.
+        #[serde(default)]
+        layout: Option<LayoutConfig>,
+    },
+    /// The [`Syslog`](append::syslog::Syslog) appender.
+    #[cfg(feature = "syslog")]
+    Syslog {
+        /// The transport to the syslog server.
+        transport: SyslogTransport,
+        /// The address of the syslog server: a socket address for `tcp` and `udp`, or a path for
+        /// `unix`. Default to the well-known address of the transport.
+        #[serde(default)]
+        address: Option<String>,
+        /// The layout of the message part of syslog records.
+        #[serde(default)]
+        layout: Option<LayoutConfig>,
+    },
+}
+
+/// The rotation policy of a rolling file appender.
+///
+/// The rotations with parameters are objects, e.g., `{ "daily_at": { "hour": 3 } }` or
+/// `{ "weekly": { "start": "monday" } }`.
+#[cfg(feature = "rolling-file")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum RotationConfig {
+    /// Rotate files every minute.
+    Minutely,
+    /// Rotate files every hour.
+    Hourly,
+    /// Rotate files every day.
+    Daily,
+    /// Rotate files every day at the given hour (`0..=23`) of the local timezone.
+    DailyAt {
+        /// The hour of the day to rotate files.
+        hour: u8,
+    },
+    /// Rotate files every week at the start of the given day of the week.
+    Weekly {
+        /// The first day of the week.
+        start: WeekdayConfig,
+    },
+    /// Never rotate files.
+    Never,
+}
+
+#[cfg(feature = "rolling-file")]
+impl From<RotationConfig> for append::rolling_file::Rotation {
+    fn from(rotation: RotationConfig) -> Self {
+        use append::rolling_file::Rotation;
+
+        match rotation {
+            RotationConfig::Minutely => Rotation::Minutely,
+            RotationConfig::Hourly => Rotation::Hourly,
+            RotationConfig::Daily => Rotation::Daily,
+            RotationConfig::DailyAt { hour } => Rotation::DailyAt { hour },
+            RotationConfig::Weekly { start } => Rotation::Weekly {
+                start: start.into(),
+            },
+            RotationConfig::Never => Rotation::Never,
+        }
+    }
+}
+
+/// A day of the week.
+#[cfg(feature = "rolling-file")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WeekdayConfig {
+    /// Monday.
+    Monday,
+    /// Tuesday.
+    Tuesday,
+    /// Wednesday.
+    Wednesday,
+    /// Thursday.
+    Thursday,
+    /// Friday.
+    Friday,
+    /// Saturday.
+    Saturday,
+    /// Sunday.
+    Sunday,
+}
+
+#[cfg(feature = "rolling-file")]
+impl From<WeekdayConfig> for jiff::civil::Weekday {
+    fn from(weekday: WeekdayConfig) -> Self {
+        use jiff::civil::Weekday;
+
+        match weekday {
+            WeekdayConfig::Monday => Weekday::Monday,
+            WeekdayConfig::Tuesday => Weekday::Tuesday,
+            WeekdayConfig::Wednesday => Weekday::Wednesday,
+            WeekdayConfig::Thursday => Weekday::Thursday,
+            WeekdayConfig::Friday => Weekday::Friday,
+            WeekdayConfig::Saturday => Weekday::Saturday,
+            WeekdayConfig::Sunday => Weekday::Sunday,
+        }
+    }
+}
+
+/// The transport of a syslog appender.
+#[cfg(feature = "syslog")]
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyslogTransport {
+    /// TCP, to `127.0.0.1:601` by default.
+    Tcp,
+    /// UDP, to `127.0.0.1:514` by default.
+    Udp,
+    /// A Unix socket; the address is required.
+    Unix,
+}
+
+/// The configuration of a layout, tagged by `kind`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum LayoutConfig {
+    /// The [`TextLayout`].
+    Text {
+        /// Whether to disable colors.
+        #[serde(default)]
+        no_color: bool,
+        /// The IANA name of the timezone of timestamps. Default to the system timezone.
+        #[serde(default)]
+        timezone: Option<String>,
+        /// The strftime-like format of timestamps; see [`TextLayout::timestamp_format`].
+        #[serde(default)]
+        timestamp_format: Option<String>,
+        /// The pattern of the lines; see [`TextPattern`](crate::layout::TextPattern).
+        #[serde(default)]
+        pattern: Option<String>,
+    },
+    /// The [`LogfmtLayout`](crate::layout::LogfmtLayout).
+    Logfmt {
+        /// The IANA name of the timezone of timestamps. Default to the system timezone.
+        #[serde(default)]
+        timezone: Option<String>,
+        /// Whether to encode values exactly like the go-logfmt encoder.
+        #[serde(default)]
+        strict: bool,
+        /// The prefix of the keys of the diagnostics.
+        #[serde(default)]
+        diagnostic_prefix: Option<String>,
+    },
+    /// The [`JsonLayout`](crate::layout::JsonLayout).
+    #[cfg(feature = "json")]
+    Json {
+        /// The IANA name of the timezone of timestamps. Default to the system timezone.
+        #[serde(default)]
+        timezone: Option<String>,
+    },
+    /// The [`ClefLayout`](crate::layout::ClefLayout).
+    #[cfg(feature = "json")]
+    Clef,
+    /// The [`EcsLayout`](crate::layout::EcsLayout).
+    #[cfg(feature = "json")]
+    Ecs,
+}
+
+/// Keeps the workers of the configured non-blocking appenders alive.
+///
+/// Dropping the guard flushes and stops the workers; see
+/// [`WorkerGuard`](crate::non_blocking::WorkerGuard).
+#[derive(Debug, Default)]
+#[must_use]
+pub struct ConfigGuard {
+    #[cfg(any(feature = "rolling-file", feature = "syslog"))]
+    guards: Vec<crate::non_blocking::WorkerGuard>,
+}
+
+impl Config {
+    /// Builds the configured appenders into a [`Builder`], and returns it with the guard of the
+    /// non-blocking appenders.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a filter, a timezone or an appender fails to build, or if a dispatch
+    /// has no appenders.
+    pub fn build(self) -> anyhow::Result<(Builder, ConfigGuard)> {
+        let mut guard = ConfigGuard::default();
+        let mut builder = crate::builder();
+        if let Some(max_level) = self.max_level {
+            builder = builder.max_level(max_level);
+        }
+
+        for (index, dispatch) in self.dispatches.into_iter().enumerate() {
+            let filter = dispatch
+                .filter
+                .map(|filter| EnvFilterBuilder::new().try_parse(&filter))
+                .transpose()
+                .with_context(|| format!("invalid filter of dispatch {index}"))?
+                .map(EnvFilter::new);
+            let appenders = dispatch
+                .appenders
+                .into_iter()
+                .map(|appender| appender.build(&mut guard))
+                .collect::<anyhow::Result<Vec<_>>>()
+                .with_context(|| format!("failed to build appenders of dispatch {index}"))?;
+            if appenders.is_empty() {
+                anyhow::bail!("dispatch {index} has no appenders");
+            }
+
+            builder = builder.dispatch(|d| {
+                let d = match filter {
+                    Some(filter) => d.filter(filter),
+                    None => d,
+                };
+                let mut appenders = appenders.into_iter();
+                let first = appenders.next().expect("dispatch has appenders");
+                appenders.fold(d.append(first), |d, appender| d.append(appender))
+            });
+        }
+        Ok((builder, guard))
+    }
+}
+
+impl AppenderConfig {
+    #[cfg_attr(
+        not(any(feature = "rolling-file", feature = "syslog")),
+        allow(unused_variables)
+    )]
+    fn build(self, guard: &mut ConfigGuard) -> anyhow::Result<Box<dyn Append>> {
+        let appender: Box<dyn Append> = match self {
+            AppenderConfig::Stdout { layout } => {
+                Box::new(append::Stdout::default().with_layout(build_layout(layout)?))
+            }
+            AppenderConfig::Stderr { layout } => {
+                Box::new(append::Stderr::default().with_layout(build_layout(layout)?))
+            }
+            #[cfg(feature = "rolling-file")]
+            AppenderConfig::RollingFile {
+                dir,
+                filename_prefix,
+                filename_suffix,
+                filename_pattern,
+                rotation,
+                max_log_files,
+                max_total_size,
+                max_file_size,
+                layout,
+            } => {
+                use append::rolling_file;
+
+                let mut writer = rolling_file::RollingFileWriter::builder();
+                if let Some(prefix) = filename_prefix {
+                    writer = writer.filename_prefix(prefix);
+                }
+                if let Some(suffix) = filename_suffix {
+                    writer = writer.filename_suffix(suffix);
+                }
+                if let Some(pattern) = filename_pattern {
+                    writer = writer.filename_pattern(pattern);
+                }
+                if let Some(rotation) = rotation {
+                    writer = writer.rotation(rotation.into());
+                }
+                if let Some(n) = max_log_files {
+                    writer = writer.max_log_files(n);
+                }
+                if let Some(n) = max_total_size {
+                    writer = writer.max_total_size(n);
+                }
+                if let Some(n) = max_file_size {
+                    writer = writer.max_file_size(n);
+                }
+                let writer = writer.build(&dir)?;
+                let (non_blocking, worker) = rolling_file::non_blocking(writer).finish();
+                guard.guards.push(worker);
+                Box::new(
+                    rolling_file::RollingFile::new(non_blocking).with_layout(build_layout(layout)?),
+                )
+            }
+            #[cfg(feature = "syslog")]
+            AppenderConfig::Syslog {
+                transport,
+                address,
+                layout,
+            } => {
+                use append::syslog;
+                use append::syslog::SyslogWriter;
+
+                let writer = match (transport, address) {
+                    (SyslogTransport::Tcp, None) => SyslogWriter::tcp_well_known()?,
+                    (SyslogTransport::Tcp, Some(address)) => SyslogWriter::tcp(address.clone())?,
+                    (SyslogTransport::Udp, None) => SyslogWriter::udp_well_known()?,
+                    (SyslogTransport::Udp, Some(address)) => {
+                        SyslogWriter::udp("0.0.0.0:0", address.as_str())?
+                    }
+                    (SyslogTransport::Unix, None) => {
+                        anyhow::bail!("the unix syslog transport requires an address")
+                    }
+                    #[cfg(unix)]
+                    (SyslogTransport::Unix, Some(address)) => SyslogWriter::unix(address)?,
+                    #[cfg(not(unix))]
+                    (SyslogTransport::Unix, Some(_)) => {
+                        anyhow::bail!("unix sockets are not supported on this platform")
+                    }
+                };
+                let (non_blocking, worker) = syslog::non_blocking(writer).finish();
+                guard.guards.push(worker);
+                let mut appender = syslog::Syslog::new(non_blocking);
+                if let Some(layout) = layout {
+                    appender = appender.with_layout(build_layout(Some(layout))?);
+                }
+                Box::new(appender)
+            }
+        };
+        Ok(appender)
+    }
+}
+
+#[cfg(feature = "json")]
+impl Config {
+    /// Returns a [JSON Schema](https://json-schema.org) (draft 2020-12) of the configuration
+    /// document.
+    ///
+    /// The schema covers the appenders and layouts of the enabled features, like
+    /// [`Config`] itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::config::Config;
+    ///
+    /// let schema = serde_json::to_string_pretty(&Config::json_schema()).unwrap();
+    /// std::fs::write(std::env::temp_dir().join("logforth.schema.json"), schema).unwrap();
+    /// ```
+    pub fn json_schema() -> serde_json::Value {
+        use serde_json::json;
+
+        let layout = json!({ "$ref": "#/$defs/layout" });
+        let optional_string =
+            |description: &str| json!({ "type": "string", "description": description });
+        let kind =
+            |kind: &str, description: &str, properties: serde_json::Value, required: &[&str]| {
+                let mut properties = properties;
+                properties["kind"] = json!({ "const": kind });
+                let mut required = required.to_vec();
+                required.insert(0, "kind");
+                json!({
+                    "type": "object",
+                    "description": description,
+                    "properties": properties,
+                    "required": required,
+                    "additionalProperties": false,
+                })
+            };
+
+        #[allow(unused_mut)]
+        let mut appenders = vec![
+            kind(
+                "stdout",
+                "Writes records to the standard output.",
+                json!({ "layout": layout }),
+                &[],
+            ),
+            kind(
+                "stderr",
+                "Writes records to the standard error.",
+                json!({ "layout": layout }),
+                &[],
+            ),
+        ];
+        #[cfg(feature = "rolling-file")]
+        {
+            let count = |doc: &str| json!({ "type": "integer", "minimum": 0, "description": doc });
+            let rotation = |name: &str, doc: &str, properties: serde_json::Value, field: &str| {
+                json!({
+                    "type": "object",
+                    "description": doc,
+                    "properties": {
+                        name: {
+                            "type": "object",
+                            "properties": properties,
+                            "required": [field],
+                            "additionalProperties": false,
+                        },
+                    },
+                    "required": [name],
+                    "additionalProperties": false,
+                })
+            };
+            appenders.push(kind(
+                "rolling_file",
+                "Writes records to rolling files.",
+                json!({
+                    "dir": optional_string(
+                        "The directory of the log files, where `${NAME}` is replaced with the \
+                         environment variable `NAME`."
+                    ),
+                    "filename_prefix": optional_string("The prefix of the log file names."),
+                    "filename_suffix": optional_string("The suffix of the log file names."),
+                    "filename_pattern": optional_string(
+                        "The pattern of the log file names, e.g., `app-%Y%m%d-%N.log`."
+                    ),
+                    "rotation": {
+                        "description": "The rotation policy. Default to `never`.",
+                        "oneOf": [
+                            { "enum": ["minutely", "hourly", "daily", "never"] },
+                            rotation(
+                                "daily_at",
+                                "Rotate files every day at the given hour of the local timezone.",
+                                json!({
+                                    "hour": {
+                                        "type": "integer",
+                                        "minimum": 0,
+                                        "maximum": 23,
+                                        "description": "The hour of the day to rotate files.",
+                                    },
+                                }),
+                                "hour",
+                            ),
+                            rotation(
+                                "weekly",
+                                "Rotate files every week at the start of the given day.",
+                                json!({
+                                    "start": {
+                                        "enum": [
+                                            "monday", "tuesday", "wednesday", "thursday",
+                                            "friday", "saturday", "sunday",
+                                        ],
+                                        "description": "The first day of the week.",
+                                    },
+                                }),
+                                "start",
+                            ),
+                        ],
+                    },
+                    "max_log_files": count("The maximum number of log files to keep."),
+                    "max_total_size": count(
+                        "The maximum total size in bytes of the log files to keep."
+                    ),
+                    "max_file_size": count(
+                        "The maximum size of a log file in bytes before it is rotated."
+                    ),
+                    "layout": layout,
+                }),
+                &["dir"],
+            ));
+        }
+        #[cfg(feature = "syslog")]
+        appenders.push(kind(
+            "syslog",
+            "Sends records to a syslog server.",
+            json!({
+                "transport": {
+                    "enum": ["tcp", "udp", "unix"],
+                    "description": "The transport to the syslog server.",
+                },
+                "address": optional_string(
+                    "The address of the syslog server: a socket address for `tcp` and `udp`, \
+                     or a path for `unix`."
+                ),
+                "layout": layout,
+            }),
+            &["transport"],
+        ));
+
+        let timezone = optional_string(
+            "The IANA name of the timezone of timestamps. Default to the system timezone.",
+        );
+        let layouts = vec![
+            kind(
+                "text",
+                "Formats records as human-readable text.",
+                json!({
+                    "no_color": { "type": "boolean", "description": "Whether to disable colors." },
+                    "timezone": timezone,
+                    "timestamp_format": optional_string("The strftime-like format of timestamps."),
+                    "pattern": optional_string(
+                        "The pattern of the lines, e.g., `{timestamp} [{level}] {message} {kv}`."
+                    ),
+                }),
+                &[],
+            ),
+            kind(
+                "logfmt",
+                "Formats records as logfmt lines.",
+                json!({
+                    "timezone": timezone,
+                    "strict": {
+                        "type": "boolean",
+                        "description":
+                            "Whether to encode values exactly like the go-logfmt encoder.",
+                    },
+                    "diagnostic_prefix":
+                        optional_string("The prefix of the keys of the diagnostics, e.g., `ctx_`."),
+                }),
+                &[],
+            ),
+            kind(
+                "json",
+                "Formats records as JSON objects.",
+                json!({ "timezone": timezone }),
+                &[],
+            ),
+            kind(
+                "clef",
+                "Formats records as Compact Log Event Format.",
+                json!({}),
+                &[],
+            ),
+            kind(
+                "ecs",
+                "Formats records as Elastic Common Schema documents.",
+                json!({}),
+                &[],
+            ),
+        ];
+
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "logforth configuration",
+            "type": "object",
+            "properties": {
+                "max_level": {
+                    "enum": ["off", "error", "warn", "info", "debug", "trace"],
+                    "description":
+                        "The max level passed to `log::set_max_level()`. Default to `trace`.",
+                },
+                "dispatches": {
+                    "type": "array",
+                    "description": "The dispatches of the logger.",
+                    "items": { "$ref": "#/$defs/dispatch" },
+                },
+            },
+            "additionalProperties": false,
+            "$defs": {
+                "dispatch": {
+                    "type": "object",
+                    "properties": {
+                        "filter": optional_string(
+                            "The env filter directives of the dispatch, \
+                             e.g., `info,my_crate=debug`."
+                        ),
+                        "appenders": {
+                            "type": "array",
+                            "minItems": 1,
+                            "items": { "$ref": "#/$defs/appender" },
+                        },
+                    },
+                    "required": ["appenders"],
+                    "additionalProperties": false,
+                },
+                "appender": { "oneOf": appenders },
+                "layout": { "oneOf": layouts },
+            },
+        })
+    }
+}
+
+fn build_layout(layout: Option<LayoutConfig>) -> anyhow::Result<Layout> {
+    let layout = match layout {
+        None => TextLayout::default().into(),
+        Some(LayoutConfig::Text {
+            no_color,
+            timezone,
+            timestamp_format,
+            pattern,
+        }) => {
+            let mut layout = TextLayout::default();
+            if no_color {
+                layout = layout.no_color();
+            }
+            if let Some(timezone) = timezone {
+                layout = layout.timezone(parse_timezone(&timezone)?);
+            }
+            if let Some(format) = timestamp_format {
+                layout = layout.timestamp_format(format);
+            }
+            if let Some(pattern) = pattern {
+                layout = layout.pattern(pattern.parse()?);
+            }
+            layout.into()
+        }
+        Some(LayoutConfig::Logfmt {
+            timezone,
+            strict,
+            diagnostic_prefix,
+        }) => {
+            let mut layout = crate::layout::LogfmtLayout::default().strict(strict);
+            if let Some(timezone) = timezone {
+                layout = layout.timezone(parse_timezone(&timezone)?);
+            }
+            if let Some(prefix) = diagnostic_prefix {
+                layout = layout.diagnostic_prefix(prefix);
+            }
+            layout.into()
+        }
+        #[cfg(feature = "json")]
+        Some(LayoutConfig::Json { timezone }) => {
+            let mut layout = crate::layout::JsonLayout::default();
+            if let Some(timezone) = timezone {
+                layout = layout.timezone(parse_timezone(&timezone)?);
+            }
+            layout.into()
+        }
+        #[cfg(feature = "json")]
+        Some(LayoutConfig::Clef) => crate::layout::ClefLayout::default().into(),
+        #[cfg(feature = "json")]
+        Some(LayoutConfig::Ecs) => crate::layout::EcsLayout::default().into(),
+    };
+    Ok(layout)
+}
+
+fn parse_timezone(name: &str) -> anyhow::Result<TimeZone> {
+    TimeZone::get(name).with_context(|| format!("unknown timezone {name}"))
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        let config: Config = serde_json::from_str(
+            r#"{
+                "max_level": "debug",
+                "dispatches": [
+                    { "filter": "info", "appenders": [{ "kind": "stdout" }] },
+                    { "appenders": [
+                        { "kind": "stderr", "layout": { "kind": "text", "timezone": "UTC" } },
+                        { "kind": "stdout", "layout": { "kind": "clef" } }
+                    ] }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.max_level, Some(LevelFilter::Debug));
+        assert!(config.build().is_ok());
+
+        let invalid = |json: &str| {
+            let config: Config = serde_json::from_str(json).unwrap();
+            config.build().unwrap_err().to_string()
+        };
+        assert_eq!(
+            invalid(r#"{ "dispatches": [{ "appenders": [] }] }"#),
+            "dispatch 0 has no appenders"
+        );
+        assert_eq!(
+            invalid(r#"{ "dispatches": [{ "filter": "app=nope..", "appenders": [] }] }"#),
+            "invalid filter of dispatch 0"
+        );
+
+        let unknown = r#"{ "dispatches": [{ "appenders": [{ "kind": "stdout", "colour": 1 }] }] }"#;
+        assert!(serde_json::from_str::<Config>(unknown).is_err());
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let schema = Config::json_schema();
+        let kinds = |def: &str| {
+            schema["$defs"][def]["oneOf"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|kind| {
+                    kind["properties"]["kind"]["const"]
+                        .as_str()
+                        .unwrap()
+                        .to_owned()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // every kind in the schema is accepted by the config
+        for kind in kinds("layout") {
+            let layout = serde_json::json!({ "kind": kind });
+            serde_json::from_value::<LayoutConfig>(layout).unwrap();
+        }
+        let appenders = kinds("appender");
+        assert_eq!(appenders[..2], ["stdout", "stderr"]);
+        assert_eq!(
+            appenders.contains(&"rolling_file".to_owned()),
+            cfg!(feature = "rolling-file")
+        );
+        assert_eq!(
+            appenders.contains(&"syslog".to_owned()),
+            cfg!(feature = "syslog")
+        );
+        #[cfg(feature = "rolling-file")]
+        {
+            let properties = schema["$defs"]["appender"]["oneOf"][2]["properties"]
+                .as_object()
+                .unwrap();
+            let rolling_file = serde_json::Value::Object(
+                properties
+                    .keys()
+                    .filter(|key| *key != "kind" && *key != "layout")
+                    .map(|key| (key.clone(), serde_json::json!(null)))
+                    .chain([
+                        ("kind".to_owned(), serde_json::json!("rolling_file")),
+                        ("dir".to_owned(), serde_json::json!("logs")),
+                    ])
+                    .collect(),
+            );
+            serde_json::from_value::<AppenderConfig>(rolling_file).unwrap();
+
+            // every rotation in the schema is accepted by the config
+            let rotations = &properties["rotation"]["oneOf"];
+            for rotation in rotations[0]["enum"].as_array().unwrap() {
+                serde_json::from_value::<RotationConfig>(rotation.clone()).unwrap();
+            }
+            let daily_at = &rotations[1]["properties"]["daily_at"]["properties"]["hour"];
+            assert_eq!(daily_at["maximum"], 23);
+            let weekly = &rotations[2]["properties"]["weekly"]["properties"]["start"];
+            for start in weekly["enum"].as_array().unwrap() {
+                let rotation = serde_json::json!({ "weekly": { "start": start } });
+                serde_json::from_value::<RotationConfig>(rotation).unwrap();
+            }
+        }
+    }
+
+    #[cfg(feature = "rolling-file")]
+    #[test]
+    fn test_rotation() {
+        use jiff::civil::Weekday;
+
+        use crate::append::rolling_file::Rotation;
+
+        let rotation = |json: serde_json::Value| {
+            let config: RotationConfig = serde_json::from_value(json.clone()).unwrap();
+            // round-trip through the serialized form
+            assert_eq!(serde_json::to_value(config).unwrap(), json);
+            Rotation::from(config)
+        };
+        assert_eq!(rotation(serde_json::json!("daily")), Rotation::Daily);
+        assert_eq!(
+            rotation(serde_json::json!({ "daily_at": { "hour": 3 } })),
+            Rotation::DailyAt { hour: 3 }
+        );
+        assert_eq!(
+            rotation(serde_json::json!({ "weekly": { "start": "sunday" } })),
+            Rotation::Weekly {
+                start: Weekday::Sunday
+            }
+        );
+
+        let invalid = [
+            serde_json::json!("daily_at"),
+            serde_json::json!({ "daily_at": {} }),
+            serde_json::json!({ "daily_at": { "hour": 3, "minute": 0 } }),
+            serde_json::json!({ "weekly": { "start": "someday" } }),
+        ];
+        for json in invalid {
+            assert!(serde_json::from_value::<RotationConfig>(json).is_err());
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = |rotation: &str| {
+            let json = format!(
+                r#"{{ "dispatches": [{{ "appenders": [{{
+                    "kind": "rolling_file",
+                    "dir": {:?},
+                    "rotation": {rotation}
+                }}] }}] }}"#,
+                dir.path()
+            );
+            serde_json::from_str::<Config>(&json).unwrap()
+        };
+        assert!(config(r#"{ "daily_at": { "hour": 23 } }"#).build().is_ok());
+        assert!(config(r#"{ "weekly": { "start": "monday" } }"#)
+            .build()
+            .is_ok());
+        assert!(config(r#"{ "daily_at": { "hour": 24 } }"#).build().is_err());
+    }
+}
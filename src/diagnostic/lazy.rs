@@ -0,0 +1,83 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::diagnostic::Visitor;
+use crate::Diagnostic;
+
+type ValueFunction = dyn Fn() -> Option<String> + Send + Sync + 'static;
+
+/// A diagnostic that computes its value only when a record is formatted.
+///
+/// The function is called each time a layout visits the diagnostics of a record, so expensive
+/// context is only computed for the records accepted by the dispatch's filters. Filters created
+/// with [`CustomFilter::with_diagnostics`] visit the diagnostics too, and call the function
+/// accordingly. No key-value is emitted when the function returns `None`.
+///
+/// [`CustomFilter::with_diagnostics`]: crate::filter::CustomFilter::with_diagnostics
+///
+/// ## Example
+///
+/// ```
+/// use logforth::diagnostic::LazyDiagnostic;
+///
+/// let diagnostic = LazyDiagnostic::new("open_files", || {
+///     std::fs::read_dir("/proc/self/fd")
+///         .ok()
+///         .map(|entries| entries.count().to_string())
+/// });
+/// ```
+#[derive(Clone)]
+pub struct LazyDiagnostic {
+    key: Cow<'static, str>,
+    value: Arc<ValueFunction>,
+}
+
+impl fmt::Debug for LazyDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyDiagnostic")
+            .field("key", &self.key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LazyDiagnostic {
+    /// Creates a diagnostic emitting the value computed by `value` under `key`.
+    pub fn new<K, F>(key: K, value: F) -> Self
+    where
+        K: Into<Cow<'static, str>>,
+        F: Fn() -> Option<String> + Send + Sync + 'static,
+    {
+        LazyDiagnostic {
+            key: key.into(),
+            value: Arc::new(value),
+        }
+    }
+
+    /// Visits the key-value pair if the value is available.
+    pub fn visit<V: Visitor>(&self, visitor: &mut V) {
+        if let Some(value) = (self.value)() {
+            visitor.visit(self.key.as_ref(), value);
+        }
+    }
+}
+
+impl From<LazyDiagnostic> for Diagnostic {
+    fn from(diagnostic: LazyDiagnostic) -> Self {
+        Diagnostic::Lazy(diagnostic)
+    }
+}
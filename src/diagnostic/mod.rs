@@ -19,11 +19,23 @@ use std::borrow::Cow;
 
 #[cfg(feature = "fastrace")]
 pub use self::fastrace::FastraceDiagnostic;
+pub use self::lazy::LazyDiagnostic;
+pub use self::task_local::FutureExt;
+#[cfg(feature = "futures")]
+pub use self::task_local::StreamExt;
+pub use self::task_local::WithTaskLocalContext;
+pub use self::thread_local::spawn_with_context;
+pub use self::thread_local::ThreadBuilderExt;
+pub use self::thread_local::ThreadLocalContext;
 pub use self::thread_local::ThreadLocalDiagnostic;
+pub use self::trace_context::TraceContextDiagnostic;
 
 #[cfg(feature = "fastrace")]
 mod fastrace;
+mod lazy;
+mod task_local;
 mod thread_local;
+pub mod trace_context;
 
 /// A visitor to walk through diagnostic key-value pairs.
 pub trait Visitor {
@@ -35,11 +47,13 @@ pub trait Visitor {
 }
 
 /// Represent a Mapped Diagnostic Context (MDC) that provides diagnostic key-values.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Diagnostic {
     #[cfg(feature = "fastrace")]
     Fastrace(FastraceDiagnostic),
+    Lazy(LazyDiagnostic),
     ThreadLocal(ThreadLocalDiagnostic),
+    TraceContext(TraceContextDiagnostic),
 }
 
 impl Diagnostic {
@@ -48,7 +62,9 @@ impl Diagnostic {
         match self {
             #[cfg(feature = "fastrace")]
             Diagnostic::Fastrace(diagnostic) => diagnostic.visit(visitor),
+            Diagnostic::Lazy(diagnostic) => diagnostic.visit(visitor),
             Diagnostic::ThreadLocal(diagnostic) => diagnostic.visit(visitor),
+            Diagnostic::TraceContext(diagnostic) => diagnostic.visit(visitor),
         }
     }
 }
@@ -0,0 +1,218 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+
+use crate::diagnostic::ThreadLocalContext;
+
+pin_project_lite::pin_project! {
+    /// A future, stream or sink that installs a [`ThreadLocalContext`] whenever it is polled.
+    ///
+    /// Created by [`FutureExt::with_task_local_context`] and
+    /// [`StreamExt::with_task_local_context`].
+    #[derive(Debug)]
+    #[must_use = "futures and streams do nothing unless polled"]
+    pub struct WithTaskLocalContext<T> {
+        #[pin]
+        inner: T,
+        context: ThreadLocalContext,
+    }
+}
+
+impl<T> WithTaskLocalContext<T> {
+    /// Consumes this wrapper, returning the underlying future, stream or sink.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+/// An extension trait for futures to carry a [`ThreadLocalContext`] across poll boundaries.
+///
+/// Since an async task can be polled on different threads, key-value pairs inserted with
+/// [`ThreadLocalDiagnostic`](crate::diagnostic::ThreadLocalDiagnostic) are lost once the task
+/// yields. The returned future installs the context around every poll, so records logged by the
+/// task always carry it.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::diagnostic::FutureExt;
+/// use logforth::diagnostic::ThreadLocalContext;
+///
+/// let context = ThreadLocalContext::new().with("request_id", "42");
+/// let fut = async {
+///     log::info!("handling request");
+/// }
+/// .with_task_local_context(context);
+/// ```
+pub trait FutureExt: Future + Sized {
+    /// Installs the context whenever the future is polled.
+    fn with_task_local_context(self, context: ThreadLocalContext) -> WithTaskLocalContext<Self> {
+        WithTaskLocalContext {
+            inner: self,
+            context,
+        }
+    }
+}
+
+impl<F: Future> FutureExt for F {}
+
+impl<F: Future> Future for WithTaskLocalContext<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        this.context.scope(|| this.inner.poll(cx))
+    }
+}
+
+#[cfg(feature = "futures")]
+mod futures {
+    use futures_core::Stream;
+    use futures_sink::Sink;
+
+    use super::*;
+
+    /// An extension trait for streams to carry a [`ThreadLocalContext`] across items.
+    ///
+    /// The returned stream installs the context around every poll, so records logged while
+    /// producing each item carry it, not only those of the first poll.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use futures_core::Stream;
+    /// use logforth::diagnostic::StreamExt;
+    /// use logforth::diagnostic::ThreadLocalContext;
+    ///
+    /// fn instrument(stream: impl Stream<Item = u32>) -> impl Stream<Item = u32> {
+    ///     let context = ThreadLocalContext::new().with("request_id", "42");
+    ///     stream.with_task_local_context(context)
+    /// }
+    /// ```
+    pub trait StreamExt: Stream + Sized {
+        /// Installs the context whenever the stream is polled.
+        fn with_task_local_context(
+            self,
+            context: ThreadLocalContext,
+        ) -> WithTaskLocalContext<Self> {
+            WithTaskLocalContext {
+                inner: self,
+                context,
+            }
+        }
+    }
+
+    impl<S: Stream> StreamExt for S {}
+
+    impl<S: Stream> Stream for WithTaskLocalContext<S> {
+        type Item = S::Item;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let this = self.project();
+            this.context.scope(|| this.inner.poll_next(cx))
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            self.inner.size_hint()
+        }
+    }
+
+    impl<S: Sink<Item>, Item> Sink<Item> for WithTaskLocalContext<S> {
+        type Error = S::Error;
+
+        fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            let this = self.project();
+            this.context.scope(|| this.inner.poll_ready(cx))
+        }
+
+        fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+            let this = self.project();
+            this.context.scope(|| this.inner.start_send(item))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            let this = self.project();
+            this.context.scope(|| this.inner.poll_flush(cx))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            let this = self.project();
+            this.context.scope(|| this.inner.poll_close(cx))
+        }
+    }
+}
+
+#[cfg(feature = "futures")]
+pub use self::futures::StreamExt;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::diagnostic::ThreadLocalDiagnostic;
+    use crate::diagnostic::Visitor;
+
+    #[derive(Default)]
+    struct Collector(BTreeMap<String, String>);
+
+    impl Visitor for Collector {
+        fn visit<'k, 'v, K, V>(&mut self, key: K, value: V)
+        where
+            K: Into<std::borrow::Cow<'k, str>>,
+            V: Into<std::borrow::Cow<'v, str>>,
+        {
+            self.0
+                .insert(key.into().into_owned(), value.into().into_owned());
+        }
+    }
+
+    fn snapshot() -> BTreeMap<String, String> {
+        let mut collector = Collector::default();
+        ThreadLocalDiagnostic::default().visit(&mut collector);
+        collector.0
+    }
+
+    #[test]
+    fn test_future_keeps_context_across_yields() {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .build()
+            .unwrap();
+
+        let context = ThreadLocalContext::new().with("request_id", "42");
+        let seen = rt.block_on(async {
+            tokio::spawn(
+                async {
+                    let mut seen = vec![];
+                    for _ in 0..8 {
+                        seen.push(snapshot().get("request_id").cloned());
+                        tokio::task::yield_now().await;
+                    }
+                    seen
+                }
+                .with_task_local_context(context),
+            )
+            .await
+            .unwrap()
+        });
+
+        assert!(seen.iter().all(|v| v.as_deref() == Some("42")));
+        assert!(!snapshot().contains_key("request_id"));
+    }
+}
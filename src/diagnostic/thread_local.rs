@@ -62,8 +62,206 @@ impl ThreadLocalDiagnostic {
     }
 }
 
+/// A set of key-value pairs to install into the thread-local context of
+/// [`ThreadLocalDiagnostic`] for a scope.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::diagnostic::ThreadLocalContext;
+///
+/// let context = ThreadLocalContext::new().with("request_id", "42");
+/// context.scope(|| log::info!("handling request"));
+/// ```
+#[derive(Default, Debug, Clone)]
+pub struct ThreadLocalContext {
+    kvs: BTreeMap<String, String>,
+}
+
+impl ThreadLocalContext {
+    /// Creates an empty context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Captures a snapshot of the current thread-local context.
+    ///
+    /// Combined with [`ThreadLocalContext::bind`], the snapshot can be re-installed on another
+    /// thread.
+    pub fn current() -> Self {
+        let kvs = CONTEXT.with(|map| map.borrow().clone());
+        ThreadLocalContext { kvs }
+    }
+
+    /// Adds a key-value pair to the context.
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.kvs.insert(key.into(), value.into());
+        self
+    }
+
+    /// Runs `f` with the key-value pairs of this context installed into the thread-local
+    /// context, and restores the previous values afterward.
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _guard = ScopeGuard::install(&self.kvs);
+        f()
+    }
+
+    /// Wraps `f` to run with the key-value pairs of this context installed.
+    ///
+    /// This is useful to propagate the context to closures executed on other threads, such as
+    /// those passed to `tokio::task::spawn_blocking`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::diagnostic::ThreadLocalContext;
+    /// use logforth::diagnostic::ThreadLocalDiagnostic;
+    ///
+    /// ThreadLocalDiagnostic::insert("request_id", "42");
+    /// let f = ThreadLocalContext::current().bind(|| log::info!("running on another thread"));
+    /// std::thread::spawn(f).join().unwrap();
+    /// ```
+    pub fn bind<F, R>(self, f: F) -> impl FnOnce() -> R
+    where
+        F: FnOnce() -> R,
+    {
+        move || self.scope(f)
+    }
+}
+
+/// Spawns a new thread with a snapshot of the current thread-local context installed.
+///
+/// See [`std::thread::spawn`] for details.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::diagnostic::spawn_with_context;
+/// use logforth::diagnostic::ThreadLocalDiagnostic;
+///
+/// ThreadLocalDiagnostic::insert("request_id", "42");
+/// spawn_with_context(|| log::info!("carries request_id"))
+///     .join()
+///     .unwrap();
+/// ```
+pub fn spawn_with_context<F, T>(f: F) -> std::thread::JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    std::thread::spawn(ThreadLocalContext::current().bind(f))
+}
+
+/// An extension trait for [`std::thread::Builder`] to spawn threads with a snapshot of the
+/// current thread-local context installed.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::diagnostic::ThreadBuilderExt;
+///
+/// let handle = std::thread::Builder::new()
+///     .name("worker".to_string())
+///     .spawn_with_context(|| log::info!("carries the caller's context"))
+///     .unwrap();
+/// handle.join().unwrap();
+/// ```
+pub trait ThreadBuilderExt {
+    /// Spawns a new thread with a snapshot of the current thread-local context installed.
+    ///
+    /// See [`std::thread::Builder::spawn`] for details.
+    fn spawn_with_context<F, T>(self, f: F) -> std::io::Result<std::thread::JoinHandle<T>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static;
+}
+
+impl ThreadBuilderExt for std::thread::Builder {
+    fn spawn_with_context<F, T>(self, f: F) -> std::io::Result<std::thread::JoinHandle<T>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.spawn(ThreadLocalContext::current().bind(f))
+    }
+}
+
+struct ScopeGuard {
+    previous: Vec<(String, Option<String>)>,
+}
+
+impl ScopeGuard {
+    fn install(kvs: &BTreeMap<String, String>) -> Self {
+        CONTEXT.with(|map| {
+            let mut map = map.borrow_mut();
+            let previous = kvs
+                .iter()
+                .map(|(k, v)| (k.clone(), map.insert(k.clone(), v.clone())))
+                .collect();
+            ScopeGuard { previous }
+        })
+    }
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        CONTEXT.with(|map| {
+            let mut map = map.borrow_mut();
+            for (key, value) in self.previous.drain(..) {
+                match value {
+                    Some(value) => map.insert(key, value),
+                    None => map.remove(&key),
+                };
+            }
+        })
+    }
+}
+
 impl From<ThreadLocalDiagnostic> for Diagnostic {
     fn from(diagnostic: ThreadLocalDiagnostic) -> Self {
         Diagnostic::ThreadLocal(diagnostic)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn current(key: &str) -> Option<String> {
+        CONTEXT.with(|map| map.borrow().get(key).cloned())
+    }
+
+    #[test]
+    fn test_scope_restores_context() {
+        ThreadLocalDiagnostic::insert("user", "alice");
+
+        let context = ThreadLocalContext::new()
+            .with("user", "bob")
+            .with("request_id", "42");
+        context.scope(|| {
+            assert_eq!(current("user").as_deref(), Some("bob"));
+            assert_eq!(current("request_id").as_deref(), Some("42"));
+        });
+
+        assert_eq!(current("user").as_deref(), Some("alice"));
+        assert_eq!(current("request_id"), None);
+        ThreadLocalDiagnostic::remove("user");
+    }
+
+    #[test]
+    fn test_spawn_with_context() {
+        ThreadLocalDiagnostic::insert("request_id", "42");
+
+        let seen = spawn_with_context(|| current("request_id")).join().unwrap();
+        assert_eq!(seen.as_deref(), Some("42"));
+
+        let seen = std::thread::Builder::new()
+            .spawn_with_context(|| current("request_id"))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(seen.as_deref(), Some("42"));
+
+        ThreadLocalDiagnostic::remove("request_id");
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::ensure;
+
+use crate::diagnostic::Visitor;
+use crate::Diagnostic;
+
+/// The environment variable of the W3C `traceparent` header, used by batch jobs.
+pub const TRACEPARENT_ENV: &str = "TRACEPARENT";
+
+/// The environment variable of the W3C `tracestate` header, used by batch jobs.
+pub const TRACESTATE_ENV: &str = "TRACESTATE";
+
+/// A diagnostic that enriches log records with a [W3C Trace Context].
+///
+/// The trace context is parsed from a `traceparent` string, e.g., received in HTTP headers or
+/// passed to batch jobs in the `TRACEPARENT` environment variable. This correlates logs across
+/// services without a tracing SDK.
+///
+/// Output format:
+///
+/// ```text
+/// 2025-01-10T15:22:37.868815+08:00[Asia/Shanghai] ERROR job: job.rs:39 Hello error! trace_id=4bf92f3577b34da6a3ce929d0e0e4736 span_id=00f067aa0ba902b7 trace_flags=01
+/// ```
+///
+/// [W3C Trace Context]: https://www.w3.org/TR/trace-context/
+///
+/// ## Example
+///
+/// ```
+/// use logforth::diagnostic::TraceContextDiagnostic;
+///
+/// let diagnostic = TraceContextDiagnostic::from_traceparent(
+///     "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+/// )
+/// .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct TraceContextDiagnostic {
+    trace_id: String,
+    span_id: String,
+    trace_flags: String,
+    tracestate: Option<String>,
+}
+
+impl TraceContextDiagnostic {
+    /// Parses the trace context from a `traceparent` string.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the string is not a valid `traceparent`.
+    pub fn from_traceparent(traceparent: &str) -> anyhow::Result<Self> {
+        let traceparent = traceparent.trim();
+        let mut parts = traceparent.split('-');
+        let (Some(version), Some(trace_id), Some(span_id), Some(trace_flags)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            anyhow::bail!("malformed traceparent: {traceparent}");
+        };
+
+        ensure!(
+            is_lower_hex(version, 2) && version != "ff",
+            "invalid traceparent version: {version}"
+        );
+        // version 00 has exactly four fields; future versions may append more
+        ensure!(
+            version != "00" || parts.next().is_none(),
+            "malformed traceparent: {traceparent}"
+        );
+        ensure!(
+            is_lower_hex(trace_id, 32) && trace_id.bytes().any(|b| b != b'0'),
+            "invalid trace id: {trace_id}"
+        );
+        ensure!(
+            is_lower_hex(span_id, 16) && span_id.bytes().any(|b| b != b'0'),
+            "invalid span id: {span_id}"
+        );
+        ensure!(
+            is_lower_hex(trace_flags, 2),
+            "invalid trace flags: {trace_flags}"
+        );
+
+        Ok(Self {
+            trace_id: trace_id.to_string(),
+            span_id: span_id.to_string(),
+            trace_flags: trace_flags.to_string(),
+            tracestate: None,
+        })
+    }
+
+    /// Parses the trace context from the `TRACEPARENT` and `TRACESTATE` environment variables.
+    ///
+    /// Returns `None` if `TRACEPARENT` is not set or invalid.
+    pub fn from_env() -> Option<Self> {
+        let traceparent = std::env::var(TRACEPARENT_ENV).ok()?;
+        let diagnostic = Self::from_traceparent(&traceparent).ok()?;
+        match std::env::var(TRACESTATE_ENV) {
+            Ok(tracestate) => Some(diagnostic.with_tracestate(tracestate)),
+            Err(_) => Some(diagnostic),
+        }
+    }
+
+    /// Sets the vendor-specific `tracestate`, which is emitted as is.
+    pub fn with_tracestate(mut self, tracestate: impl Into<String>) -> Self {
+        let tracestate = tracestate.into();
+        self.tracestate = (!tracestate.trim().is_empty()).then_some(tracestate);
+        self
+    }
+
+    /// Returns the trace ID.
+    pub fn trace_id(&self) -> &str {
+        &self.trace_id
+    }
+
+    /// Returns the span ID of the parent.
+    pub fn span_id(&self) -> &str {
+        &self.span_id
+    }
+
+    /// Returns whether the sampled flag is set.
+    pub fn sampled(&self) -> bool {
+        u8::from_str_radix(&self.trace_flags, 16).is_ok_and(|flags| flags & 0x01 != 0)
+    }
+
+    pub fn visit<V: Visitor>(&self, visitor: &mut V) {
+        visitor.visit("trace_id", &self.trace_id);
+        visitor.visit("span_id", &self.span_id);
+        visitor.visit("trace_flags", &self.trace_flags);
+        if let Some(tracestate) = &self.tracestate {
+            visitor.visit("tracestate", tracestate);
+        }
+    }
+}
+
+impl From<TraceContextDiagnostic> for Diagnostic {
+    fn from(diagnostic: TraceContextDiagnostic) -> Self {
+        Diagnostic::TraceContext(diagnostic)
+    }
+}
+
+fn is_lower_hex(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_traceparent() {
+        let diagnostic = TraceContextDiagnostic::from_traceparent(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        )
+        .unwrap();
+        assert_eq!(diagnostic.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(diagnostic.span_id(), "00f067aa0ba902b7");
+        assert!(diagnostic.sampled());
+
+        // future versions may carry more fields
+        assert!(TraceContextDiagnostic::from_traceparent(
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra",
+        )
+        .is_ok_and(|diagnostic| !diagnostic.sampled()));
+
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+        ] {
+            assert!(
+                TraceContextDiagnostic::from_traceparent(invalid).is_err(),
+                "{invalid}"
+            );
+        }
+    }
+}
@@ -0,0 +1,231 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A drop-in replacement for the common [`env_logger`](https://crates.io/crates/env_logger)
+//! entry points.
+//!
+//! [`init`] and [`Builder`] mirror the API surface of `env_logger` that most binaries use,
+//! including the `format` closure, so migrating is mostly a matter of changing the import:
+//!
+//! ```
+//! use std::io::Write;
+//!
+//! use logforth::env_logger_compat::Builder;
+//!
+//! Builder::from_default_env()
+//!     .format(|buf, record| writeln!(buf, "{} - {}", record.level(), record.args()))
+//!     .init();
+//! ```
+//!
+//! The records are filtered by an [`EnvFilter`] and written by the [`Stdout`] or [`Stderr`]
+//! appender, so the installed logger is a regular logforth logger.
+
+use std::fmt;
+use std::io;
+
+use log::LevelFilter;
+use log::Record;
+
+use crate::append::Stderr;
+use crate::append::Stdout;
+use crate::filter::env_filter::EnvFilterBuilder;
+use crate::filter::env_filter::DEFAULT_FILTER_ENV;
+use crate::filter::EnvFilter;
+use crate::layout::CustomLayout;
+
+type FormatFn = dyn Fn(&mut Formatter, &Record) -> io::Result<()> + Send + Sync + 'static;
+
+/// Initializes the global logger with the filters of the `RUST_LOG` environment variable.
+///
+/// Like `env_logger::init`, this panics if the global logger has already been set.
+pub fn init() {
+    try_init()
+        .expect("env_logger_compat::init should not be called after the global logger initialized");
+}
+
+/// Initializes the global logger with the filters of the `RUST_LOG` environment variable.
+///
+/// Returns an error if the global logger has already been set.
+pub fn try_init() -> Result<(), log::SetLoggerError> {
+    Builder::from_default_env().try_init()
+}
+
+/// The destination of the records, defaulting to [`Target::Stderr`] like `env_logger`.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Target {
+    /// Write to the standard output.
+    Stdout,
+    /// Write to the standard error.
+    #[default]
+    Stderr,
+}
+
+/// The buffer a record is formatted into by the closure passed to [`Builder::format`].
+///
+/// Write the formatted record with [`std::io::Write`], e.g. with the `write!` or `writeln!`
+/// macros. A trailing newline is optional.
+pub struct Formatter {
+    buf: Vec<u8>,
+}
+
+impl io::Write for Formatter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Formatter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Formatter").finish_non_exhaustive()
+    }
+}
+
+/// A builder compatible with `env_logger::Builder`.
+#[must_use = "call `init` to set the global logger"]
+pub struct Builder {
+    filter: EnvFilterBuilder,
+    format: Option<Box<FormatFn>>,
+    target: Target,
+}
+
+impl fmt::Debug for Builder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Builder")
+            .field("filter", &self.filter)
+            .field("format", &self.format.as_ref().map(|_| ".."))
+            .field("target", &self.target)
+            .finish()
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder::new()
+    }
+}
+
+impl Builder {
+    /// Creates a builder without any filter, which only enables error records.
+    pub fn new() -> Self {
+        Builder {
+            filter: EnvFilterBuilder::new(),
+            format: None,
+            target: Target::default(),
+        }
+    }
+
+    /// Creates a builder with the filters of the `RUST_LOG` environment variable.
+    pub fn from_default_env() -> Self {
+        Builder::from_env(DEFAULT_FILTER_ENV)
+    }
+
+    /// Creates a builder with the filters of the given environment variable.
+    pub fn from_env(env: &str) -> Self {
+        let mut builder = Builder::new();
+        builder.filter = builder.filter.merge_from_env(env);
+        builder
+    }
+
+    /// Adds a directive to the filter for a specific module.
+    pub fn filter_module(&mut self, module: &str, level: LevelFilter) -> &mut Self {
+        self.filter(Some(module), level)
+    }
+
+    /// Adds a directive to the filter for all modules.
+    pub fn filter_level(&mut self, level: LevelFilter) -> &mut Self {
+        self.filter(None, level)
+    }
+
+    /// Adds a directive to the filter.
+    pub fn filter(&mut self, module: Option<&str>, level: LevelFilter) -> &mut Self {
+        self.filter = std::mem::take(&mut self.filter).filter(module, level);
+        self
+    }
+
+    /// Parses the directives string, in the format of the `RUST_LOG` environment variable.
+    pub fn parse_filters(&mut self, filters: &str) -> &mut Self {
+        self.filter = std::mem::take(&mut self.filter).parse(filters);
+        self
+    }
+
+    /// Sets the closure formatting each record.
+    ///
+    /// Without a custom format, records are formatted with the default
+    /// [`TextLayout`](crate::layout::TextLayout).
+    pub fn format<F>(&mut self, format: F) -> &mut Self
+    where
+        F: Fn(&mut Formatter, &Record) -> io::Result<()> + Send + Sync + 'static,
+    {
+        self.format = Some(Box::new(format));
+        self
+    }
+
+    /// Sets the destination of the records.
+    pub fn target(&mut self, target: Target) -> &mut Self {
+        self.target = target;
+        self
+    }
+
+    /// Initializes the global logger with this builder's configuration.
+    ///
+    /// Panics if the global logger has already been set.
+    pub fn init(&mut self) {
+        self.try_init()
+            .expect("Builder::init should not be called after the global logger initialized");
+    }
+
+    /// Initializes the global logger with this builder's configuration.
+    ///
+    /// Like `env_logger`, the max level of the `log` crate is set to the most verbose level of
+    /// the filter directives, so that disabled records are skipped before reaching the logger.
+    ///
+    /// Returns an error if the global logger has already been set.
+    pub fn try_init(&mut self) -> Result<(), log::SetLoggerError> {
+        let filter = EnvFilter::new(std::mem::take(&mut self.filter));
+        let max_level = filter.max_level();
+        let layout = self.format.take().map(|format| {
+            CustomLayout::new(move |record, _| {
+                let mut formatter = Formatter { buf: vec![] };
+                format(&mut formatter, record)?;
+                // the appender terminates each record with a newline
+                if formatter.buf.last() == Some(&b'\n') {
+                    formatter.buf.pop();
+                }
+                Ok(formatter.buf)
+            })
+        });
+
+        crate::builder()
+            .max_level(max_level)
+            .dispatch(|d| {
+                let d = d.filter(filter);
+                match (self.target, layout) {
+                    (Target::Stdout, Some(layout)) => {
+                        d.append(Stdout::default().with_layout(layout))
+                    }
+                    (Target::Stdout, None) => d.append(Stdout::default()),
+                    (Target::Stderr, Some(layout)) => {
+                        d.append(Stderr::default().with_layout(layout))
+                    }
+                    (Target::Stderr, None) => d.append(Stderr::default()),
+                }
+            })
+            .try_apply()
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fatal records.
+//!
+//! The `log` crate has no fatal level. The [`fatal!`](crate::fatal!) macro logs a record at the
+//! error level marked with [`FATAL_KEY`]. With [`Builder::on_fatal`](crate::Builder::on_fatal),
+//! the logger flushes all appenders synchronously once a fatal record is dispatched and then calls
+//! the handler, e.g., to dump state or abort the process. Without a handler, fatal records behave
+//! like other error records.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use logforth::append;
+//!
+//! logforth::builder()
+//!     .on_fatal(|_| std::process::abort())
+//!     .dispatch(|d| d.append(append::Stderr::default()))
+//!     .apply();
+//!
+//! logforth::fatal!("data directory is corrupted");
+//! ```
+
+use log::kv::Key;
+use log::Record;
+
+/// The key under which a record is marked as fatal.
+///
+/// The key is namespaced like [`ROUTE_KEY`](crate::route::ROUTE_KEY), so that an application's own
+/// `fatal` key-value does not trigger the handler.
+pub const FATAL_KEY: &str = "logforth.fatal";
+
+/// Returns whether the record is marked as fatal.
+pub fn is_fatal(record: &Record) -> bool {
+    record
+        .key_values()
+        .get(Key::from_str(FATAL_KEY))
+        .and_then(|value| value.to_bool())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    use super::*;
+
+    #[test]
+    fn test_is_fatal() {
+        let fatal = [(FATAL_KEY, true)];
+        let record = Record::builder()
+            .level(Level::Error)
+            .key_values(&fatal)
+            .build();
+        assert!(is_fatal(&record));
+
+        let not_fatal = [(FATAL_KEY, false)];
+        let record = Record::builder()
+            .level(Level::Error)
+            .key_values(&not_fatal)
+            .build();
+        assert!(!is_fatal(&record));
+        assert!(!is_fatal(&Record::builder().level(Level::Error).build()));
+    }
+}
@@ -15,9 +15,14 @@
 use std::fmt::Debug;
 
 use log::Metadata;
+use log::Record;
 
 use crate::filter::Filter;
 use crate::filter::FilterResult;
+use crate::Diagnostic;
+
+type MetadataFilterFunction = dyn Fn(&Metadata) -> FilterResult + Send + Sync + 'static;
+type RecordFilterFunction = dyn Fn(&Record, &[Diagnostic]) -> FilterResult + Send + Sync + 'static;
 
 /// A custom filter using a user-defined function.
 ///
@@ -36,7 +41,12 @@ use crate::filter::FilterResult;
 /// });
 /// ```
 pub struct CustomFilter {
-    f: Box<dyn Fn(&Metadata) -> FilterResult + Send + Sync + 'static>,
+    f: FilterFunction,
+}
+
+enum FilterFunction {
+    Metadata(Box<MetadataFilterFunction>),
+    Record(Box<RecordFilterFunction>),
 }
 
 impl Debug for CustomFilter {
@@ -49,12 +59,50 @@ impl CustomFilter {
     /// Creates a new [`CustomFilter`].
     pub fn new(filter: impl Fn(&Metadata) -> FilterResult + Send + Sync + 'static) -> Self {
         CustomFilter {
-            f: Box::new(filter),
+            f: FilterFunction::Metadata(Box::new(filter)),
+        }
+    }
+
+    /// Creates a new [`CustomFilter`] that decides on the full record and the diagnostics of the
+    /// dispatch, e.g., to filter by key-values.
+    ///
+    /// Since the record is not available when the logger checks whether a level and target are
+    /// enabled, such a filter reports [`FilterResult::Unknown`] in that check and makes its
+    /// decision once the record is logged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::filter::CustomFilter;
+    /// use logforth::filter::FilterResult;
+    ///
+    /// let custom_filter = CustomFilter::with_diagnostics(|record, _diagnostics| {
+    ///     match record.key_values().get("audit".into()) {
+    ///         Some(_) => FilterResult::Accept,
+    ///         None => FilterResult::Neutral,
+    ///     }
+    /// });
+    /// ```
+    pub fn with_diagnostics(
+        filter: impl Fn(&Record, &[Diagnostic]) -> FilterResult + Send + Sync + 'static,
+    ) -> Self {
+        CustomFilter {
+            f: FilterFunction::Record(Box::new(filter)),
         }
     }
 
     pub(crate) fn enabled(&self, metadata: &Metadata) -> FilterResult {
-        (self.f)(metadata)
+        match &self.f {
+            FilterFunction::Metadata(f) => f(metadata),
+            FilterFunction::Record(_) => FilterResult::Unknown,
+        }
+    }
+
+    pub(crate) fn matches(&self, record: &Record, diagnostics: &[Diagnostic]) -> FilterResult {
+        match &self.f {
+            FilterFunction::Metadata(f) => f(record.metadata()),
+            FilterFunction::Record(f) => f(record, diagnostics),
+        }
     }
 }
 
@@ -63,3 +111,30 @@ impl From<CustomFilter> for Filter {
         Filter::Custom(filter)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    use super::*;
+
+    #[test]
+    fn test_filter_with_diagnostics() {
+        let filter = CustomFilter::with_diagnostics(|record, _| {
+            match record.key_values().get("audit".into()) {
+                Some(_) => FilterResult::Accept,
+                None => FilterResult::Reject,
+            }
+        });
+
+        let metadata = Metadata::builder().level(Level::Info).build();
+        assert_eq!(filter.enabled(&metadata), FilterResult::Unknown);
+
+        let kvs = [("audit", true)];
+        let record = Record::builder().key_values(&kvs).build();
+        assert_eq!(filter.matches(&record, &[]), FilterResult::Accept);
+
+        let record = Record::builder().build();
+        assert_eq!(filter.matches(&record, &[]), FilterResult::Reject);
+    }
+}
@@ -16,11 +16,20 @@
 
 use std::borrow::Cow;
 use std::str::FromStr;
+#[cfg(feature = "env-filter-stats")]
+use std::sync::atomic::AtomicU64;
+#[cfg(feature = "env-filter-stats")]
+use std::sync::atomic::Ordering;
+#[cfg(feature = "env-filter-stats")]
+use std::sync::Arc;
 
+use log::Level;
 use log::LevelFilter;
 use log::Metadata;
 
 use crate::filter::FilterResult;
+use crate::filter::KvFilter;
+use crate::Diagnostic;
 use crate::Filter;
 
 /// The default environment variable for filtering logs.
@@ -36,13 +45,76 @@ pub const DEFAULT_FILTER_ENV: &str = "RUST_LOG";
 ///
 /// The directive syntax is similar to that of [`env_logger`](https://crates.io/crates/env_logger)'s.
 /// Read more from [the `env_logger` documentation](https://docs.rs/env_logger/#enabling-logging)
+///
+/// In addition, a directive can specify a range of levels like `my_crate=debug..warn`, which
+/// enables the records from `debug` to `warn` and rejects the more severe ones.
+///
+/// A directive can also be constrained to the records with some key-values, either of the record
+/// or of the diagnostics, like `my_crate[tenant=acme,debug_id]=debug`: a key without a value
+/// matches any value. Such a directive applies to the matching records in place of the directives
+/// without constraints, including the message filter (`/regex`). Since key-values are not available
+/// when the logger checks whether a level and target are enabled, the filter then reports
+/// [`FilterResult::Unknown`] for the levels that only a constrained directive enables.
+///
+/// With the `env-filter-stats` feature, the filter counts the records each directive applies to;
+/// see [`EnvFilter::stats`].
 #[derive(Debug)]
-pub struct EnvFilter(env_filter::Filter);
+pub struct EnvFilter {
+    filter: env_filter::Filter,
+    // empty unless some directive specifies a level range
+    floors: Vec<LevelFloor>,
+    kv_directives: Vec<KvDirective>,
+    #[cfg(feature = "env-filter-stats")]
+    stats: EnvFilterStats,
+}
 
 impl EnvFilter {
     /// Initializes the filter builder from the [EnvFilterBuilder].
     pub fn new(mut builder: EnvFilterBuilder) -> Self {
-        EnvFilter(builder.0.build())
+        #[cfg(feature = "env-filter-stats")]
+        let stats = EnvFilterStats::new(&builder.floors, &builder.kv_directives);
+
+        let floors = if builder.floors.iter().any(|floor| floor.level.is_some()) {
+            builder.floors
+        } else {
+            vec![]
+        };
+
+        EnvFilter {
+            filter: builder.builder.build(),
+            floors,
+            kv_directives: builder.kv_directives,
+            #[cfg(feature = "env-filter-stats")]
+            stats,
+        }
+    }
+
+    /// Returns a handle to the number of records each directive of this filter applied to.
+    ///
+    /// The handle stays valid after the filter is moved into a logger, so it can be kept to find
+    /// the directives that never match, which are dead configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::filter::EnvFilter;
+    ///
+    /// let filter = EnvFilter::from("info,hyper=warn,my_crate::db=trace");
+    /// let stats = filter.stats();
+    ///
+    /// logforth::builder()
+    ///     .dispatch(|d| d.filter(filter).append(logforth::append::Stdout::default()))
+    ///     .apply();
+    ///
+    /// for directive in stats.snapshot() {
+    ///     if directive.hits == 0 {
+    ///         println!("unused directive: {}", directive.directive);
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "env-filter-stats")]
+    pub fn stats(&self) -> EnvFilterStats {
+        self.stats.clone()
     }
 
     /// Initializes the filter builder from the environment using default variable name `RUST_LOG`.
@@ -130,21 +202,71 @@ impl EnvFilter {
         }
     }
 
+    /// Returns the most verbose level enabled by any directive, e.g., to set the max level of
+    /// the logger.
+    pub(crate) fn max_level(&self) -> LevelFilter {
+        self.kv_directives
+            .iter()
+            .map(|directive| directive.level)
+            .fold(self.filter.filter(), Ord::max)
+    }
+
     pub(crate) fn enabled(&self, metadata: &Metadata) -> FilterResult {
-        if self.0.enabled(metadata) {
+        if self.filter.enabled(metadata) && self.above_floor(metadata) {
             FilterResult::Neutral
+        } else if self
+            .kv_directives
+            .iter()
+            .any(|d| d.matches_target(metadata.target()) && d.enables(metadata.level()))
+        {
+            FilterResult::Unknown
         } else {
             FilterResult::Reject
         }
     }
 
-    pub(crate) fn matches(&self, record: &log::Record) -> FilterResult {
-        if self.0.matches(record) {
+    pub(crate) fn matches(&self, record: &log::Record, diagnostics: &[Diagnostic]) -> FilterResult {
+        // like the other directives, the one with the longest name applies
+        let kv_directive = self
+            .kv_directives
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| d.matches_target(record.target()))
+            .filter(|(_, d)| d.kvs.matches(record, diagnostics) == FilterResult::Neutral)
+            .max_by_key(|(_, d)| d.name.as_ref().map_or(0, String::len));
+
+        #[cfg(feature = "env-filter-stats")]
+        self.stats
+            .record(record.target(), kv_directive.map(|(i, _)| i));
+
+        let enabled = match kv_directive {
+            Some((_, directive)) => directive.enables(record.level()),
+            None => self.filter.matches(record) && self.above_floor(record.metadata()),
+        };
+        if enabled {
             FilterResult::Neutral
         } else {
             FilterResult::Reject
         }
     }
+
+    // Like `env_filter`, the directive with the longest name matching the target applies.
+    fn above_floor(&self, metadata: &Metadata) -> bool {
+        let target = metadata.target();
+        let floor = self
+            .floors
+            .iter()
+            .filter(|floor| match &floor.name {
+                Some(name) => target.starts_with(name.as_str()),
+                None => true,
+            })
+            .max_by_key(|floor| floor.name.as_ref().map_or(0, String::len));
+
+        match floor.and_then(|floor| floor.level) {
+            Some(level) => metadata.level() >= level,
+            None => true,
+        }
+    }
 }
 
 impl From<EnvFilter> for Filter {
@@ -173,38 +295,173 @@ impl FromStr for EnvFilter {
     }
 }
 
+/// A handle to the number of records each directive of an [`EnvFilter`] applied to.
+///
+/// A directive applies to a record when it is the most specific directive for the record's
+/// target, whether it enables the record or not. Records rejected by the maximum level of the
+/// logger before reaching the filter are not counted.
+///
+/// See [`EnvFilter::stats`].
+#[cfg(feature = "env-filter-stats")]
+#[derive(Debug, Clone)]
+pub struct EnvFilterStats {
+    inner: Arc<StatsInner>,
+}
+
+#[cfg(feature = "env-filter-stats")]
+#[derive(Debug)]
+struct StatsInner {
+    // the names of the directives without key-values, in order
+    names: Vec<Option<String>>,
+    // the directives without key-values, then the ones with key-values
+    directives: Vec<String>,
+    hits: Vec<AtomicU64>,
+}
+
+/// The number of records a directive of an [`EnvFilter`] applied to.
+#[cfg(feature = "env-filter-stats")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DirectiveStats {
+    /// The directive, without its level, e.g. `my_crate::db` or `my_crate[tenant=acme]`; empty
+    /// for the default directive.
+    pub directive: String,
+    /// The number of records the directive applied to.
+    pub hits: u64,
+}
+
+#[cfg(feature = "env-filter-stats")]
+impl EnvFilterStats {
+    fn new(floors: &[LevelFloor], kv_directives: &[KvDirective]) -> Self {
+        let names = floors.iter().map(|floor| floor.name.clone()).collect();
+        let directives: Vec<_> = floors
+            .iter()
+            .map(|floor| floor.name.clone().unwrap_or_default())
+            .chain(
+                kv_directives
+                    .iter()
+                    .map(|d| format!("{}[{}]", d.name.as_deref().unwrap_or_default(), d.selector)),
+            )
+            .collect();
+        let hits = directives.iter().map(|_| AtomicU64::new(0)).collect();
+        EnvFilterStats {
+            inner: Arc::new(StatsInner {
+                names,
+                directives,
+                hits,
+            }),
+        }
+    }
+
+    // Counts a hit for the constrained directive at `kv_index` if any, or else for the most
+    // specific directive matching `target`.
+    fn record(&self, target: &str, kv_index: Option<usize>) {
+        let inner = &*self.inner;
+        let index = match kv_index {
+            Some(i) => Some(inner.names.len() + i),
+            None => inner
+                .names
+                .iter()
+                .enumerate()
+                .filter(|(_, name)| {
+                    name.as_ref()
+                        .map_or(true, |n| target.starts_with(n.as_str()))
+                })
+                .max_by_key(|(_, name)| name.as_ref().map_or(0, String::len))
+                .map(|(i, _)| i),
+        };
+        if let Some(index) = index {
+            inner.hits[index].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the number of records each directive applied to so far, in the order the
+    /// directives were configured; the directives with key-values come last.
+    pub fn snapshot(&self) -> Vec<DirectiveStats> {
+        let inner = &*self.inner;
+        inner
+            .directives
+            .iter()
+            .zip(&inner.hits)
+            .map(|(directive, hits)| DirectiveStats {
+                directive: directive.clone(),
+                hits: hits.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    /// Resets the counters of all directives to zero.
+    pub fn reset(&self) {
+        for hits in &self.inner.hits {
+            hits.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The least verbose level enabled by the directive for `name`, if it specifies a level range.
+#[derive(Debug)]
+struct LevelFloor {
+    name: Option<String>,
+    level: Option<Level>,
+}
+
+/// A directive constrained to the records with some key-values.
+#[derive(Debug)]
+struct KvDirective {
+    name: Option<String>,
+    // the key-values as written, to replace the directive for the same name and key-values
+    selector: String,
+    kvs: KvFilter,
+    level: LevelFilter,
+    floor: Option<Level>,
+}
+
+impl KvDirective {
+    fn matches_target(&self, target: &str) -> bool {
+        match &self.name {
+            Some(name) => target.starts_with(name.as_str()),
+            None => true,
+        }
+    }
+
+    fn enables(&self, level: Level) -> bool {
+        level <= self.level && self.floor.map_or(true, |floor| level >= floor)
+    }
+}
+
 /// A builder for the env log filter.
 ///
 /// It can be used to parse a set of directives from a string before building a [EnvFilter]
 /// instance.
 #[derive(Default, Debug)]
-pub struct EnvFilterBuilder(env_filter::Builder);
+pub struct EnvFilterBuilder {
+    builder: env_filter::Builder,
+    // one entry per directive, to find the one that applies to a target
+    floors: Vec<LevelFloor>,
+    kv_directives: Vec<KvDirective>,
+}
 
 impl EnvFilterBuilder {
     /// Initializes the filter builder with defaults.
     pub fn new() -> Self {
-        EnvFilterBuilder(env_filter::Builder::new())
+        EnvFilterBuilder::default()
     }
 
     /// Try to initialize the filter builder from an environment; return `None` if the environment
     /// variable is not set or invalid.
     pub fn try_from_env(env: &str) -> Option<Self> {
-        let mut builder = env_filter::Builder::new();
         let config = std::env::var(env).ok()?;
-        builder.try_parse(&config).ok()?;
-        Some(EnvFilterBuilder(builder))
+        EnvFilterBuilder::new().try_parse(&config).ok()
     }
 
     /// Adds a directive to the filter for a specific module.
-    pub fn filter_module(mut self, module: &str, level: LevelFilter) -> Self {
-        self.0.filter_module(module, level);
-        self
+    pub fn filter_module(self, module: &str, level: LevelFilter) -> Self {
+        self.filter(Some(module), level)
     }
 
     /// Adds a directive to the filter for all modules.
-    pub fn filter_level(mut self, level: LevelFilter) -> Self {
-        self.0.filter_level(level);
-        self
+    pub fn filter_level(self, level: LevelFilter) -> Self {
+        self.filter(None, level)
     }
 
     /// Adds a directive to the filter.
@@ -212,7 +469,8 @@ impl EnvFilterBuilder {
     /// The given module (if any) will log at most the specified level provided. If no module is
     /// provided then the filter will apply to all log messages.
     pub fn filter(mut self, module: Option<&str>, level: LevelFilter) -> Self {
-        self.0.filter(module, level);
+        self.builder.filter(module, level);
+        self.set_floor(module, None);
         self
     }
 
@@ -220,15 +478,372 @@ impl EnvFilterBuilder {
     ///
     /// See [the `env_logger` documentation](https://docs.rs/env_logger/#enabling-logging) for more details.
     pub fn try_parse(mut self, filters: &str) -> anyhow::Result<Self> {
-        self.0.try_parse(filters)?;
-        Ok(self)
+        let parsed = parse_spec(filters).map_err(anyhow::Error::msg)?;
+        self.builder.try_parse(&parsed.spec)?;
+        Ok(self.apply_parsed(parsed))
     }
 
     /// Parses the directives string.
     ///
     /// See [the `env_logger` documentation](https://docs.rs/env_logger/#enabling-logging) for more details.
     pub fn parse(mut self, filters: &str) -> Self {
-        self.0.parse(filters);
+        let parsed = match parse_spec(filters) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                eprintln!("warning: {err}, ignoring it");
+                return self;
+            }
+        };
+        self.builder.parse(&parsed.spec);
+        self.apply_parsed(parsed)
+    }
+
+    /// Merges the directives of the `RUST_LOG` environment variable, if set, on top of the
+    /// directives configured so far.
+    ///
+    /// This allows safe defaults in code with operator overrides layered on top: a directive from
+    /// the environment replaces the one for the same module, while the directives for other
+    /// modules are kept. The message filter (`/regex`) of the environment replaces the one in code.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::filter::env_filter::EnvFilterBuilder;
+    /// use logforth::filter::EnvFilter;
+    ///
+    /// let filter = EnvFilter::new(EnvFilterBuilder::new().parse("info,hyper=warn").merge_env());
+    /// ```
+    pub fn merge_env(self) -> Self {
+        self.merge_from_env(DEFAULT_FILTER_ENV)
+    }
+
+    /// Merges the directives of the given environment variable, if set, on top of the directives
+    /// configured so far.
+    ///
+    /// See [`EnvFilterBuilder::merge_env`] for details.
+    pub fn merge_from_env(self, env: &str) -> Self {
+        match std::env::var(env) {
+            Ok(filters) => self.parse(&filters),
+            Err(_) => self,
+        }
+    }
+
+    fn apply_parsed(mut self, parsed: ParsedSpec) -> Self {
+        for (name, level) in parsed.floors {
+            self.set_floor(name.as_deref(), level);
+        }
+        for directive in parsed.kv_directives {
+            let existing = self
+                .kv_directives
+                .iter_mut()
+                .find(|d| d.name == directive.name && d.selector == directive.selector);
+            match existing {
+                Some(existing) => *existing = directive,
+                None => self.kv_directives.push(directive),
+            }
+        }
         self
     }
+
+    fn set_floor(&mut self, name: Option<&str>, level: Option<Level>) {
+        let floor = LevelFloor {
+            name: name.map(ToOwned::to_owned),
+            level,
+        };
+        match self.floors.iter_mut().find(|f| f.name == floor.name) {
+            Some(existing) => *existing = floor,
+            None => self.floors.push(floor),
+        }
+    }
+}
+
+struct ParsedSpec {
+    // the directives understood by `env_filter`
+    spec: String,
+    floors: Vec<(Option<String>, Option<Level>)>,
+    kv_directives: Vec<KvDirective>,
+}
+
+/// Rewrites the level ranges of the directives to the most verbose level understood by
+/// `env_filter`, and returns the least verbose level of each directive. Directives constrained
+/// by key-values are taken out of the spec.
+fn parse_spec(spec: &str) -> Result<ParsedSpec, String> {
+    let (mods, filter) = match split_filter(spec) {
+        Some((mods, filter)) => (mods, Some(filter)),
+        None => (spec, None),
+    };
+
+    let mut directives = vec![];
+    let mut floors = vec![];
+    let mut kv_directives = vec![];
+    for directive in split_directives(mods) {
+        if directive.is_empty() {
+            continue;
+        }
+
+        if let Some((name, rest)) = directive.split_once('[') {
+            let invalid = || format!("invalid key-values '{directive}' in logging spec '{spec}'");
+            let (selector, level) = rest.split_once(']').ok_or_else(invalid)?;
+            let level = match level.trim() {
+                "" => None,
+                level => Some(level.strip_prefix('=').ok_or_else(invalid)?.trim()),
+            };
+            let (level, floor) = parse_level(level, directive, spec)?;
+            let mut kvs = KvFilter::new();
+            for kv in selector.split(',').map(str::trim) {
+                kvs = match kv.split_once('=') {
+                    Some((key, value)) => kvs.require(key.trim(), value.trim()),
+                    None if !kv.is_empty() => kvs.require_key(kv),
+                    None => return Err(invalid()),
+                };
+            }
+            let name = name.trim();
+            kv_directives.push(KvDirective {
+                name: (!name.is_empty()).then(|| name.to_owned()),
+                selector: selector.trim().to_owned(),
+                kvs,
+                level,
+                floor,
+            });
+            continue;
+        }
+
+        let (name, level) = match directive.split_once('=') {
+            Some((name, level)) => (Some(name.trim()), Some(level.trim())),
+            None if directive.parse::<LevelFilter>().is_ok() || directive.contains("..") => {
+                (None, Some(directive))
+            }
+            None => (Some(directive), None),
+        };
+
+        if level.is_some_and(|level| level.contains("..")) {
+            let (most_verbose, least_verbose) = parse_level(level, directive, spec)?;
+            directives.push(match name {
+                Some(name) => format!("{name}={most_verbose}"),
+                None => most_verbose.to_string(),
+            });
+            floors.push((name.map(ToOwned::to_owned), least_verbose));
+        } else {
+            directives.push(directive.to_owned());
+            floors.push((name.map(ToOwned::to_owned), None));
+        }
+    }
+
+    let mut spec = directives.join(",");
+    if let Some(filter) = filter {
+        spec.push('/');
+        spec.push_str(filter);
+    }
+    Ok(ParsedSpec {
+        spec,
+        floors,
+        kv_directives,
+    })
+}
+
+/// Parses a level or a level range of a directive into the most verbose level and, for a range,
+/// the least verbose level. No level enables all levels.
+fn parse_level(
+    level: Option<&str>,
+    directive: &str,
+    spec: &str,
+) -> Result<(LevelFilter, Option<Level>), String> {
+    let Some(level) = level else {
+        return Ok((LevelFilter::Trace, None));
+    };
+
+    match level.split_once("..") {
+        Some((from, to)) => {
+            let parse = |level: &str| {
+                level.trim().parse::<Level>().map_err(|_| {
+                    format!("invalid level range '{directive}' in logging spec '{spec}'")
+                })
+            };
+            let (from, to) = (parse(from)?, parse(to)?);
+            Ok((from.max(to).to_level_filter(), Some(from.min(to))))
+        }
+        None => level
+            .parse::<LevelFilter>()
+            .map(|level| (level, None))
+            .map_err(|_| format!("invalid level '{directive}' in logging spec '{spec}'")),
+    }
+}
+
+/// Splits the message filter (`/regex`) off the spec, ignoring slashes in key-values.
+fn split_filter(spec: &str) -> Option<(&str, &str)> {
+    let mut depth = 0;
+    for (i, c) in spec.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            '/' if depth == 0 => return Some((&spec[..i], &spec[i + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits the directives on commas, ignoring commas in key-values.
+fn split_directives(mods: &str) -> Vec<&str> {
+    let mut directives = vec![];
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in mods.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                directives.push(mods[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    directives.push(mods[start..].trim());
+    directives
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled(filter: &EnvFilter, target: &str, level: Level) -> bool {
+        let metadata = Metadata::builder().target(target).level(level).build();
+        filter.enabled(&metadata) == FilterResult::Neutral
+    }
+
+    #[test]
+    fn test_level_range() {
+        let filter = EnvFilter::new(EnvFilterBuilder::new().parse("warn,app=debug..warn,app::db"));
+
+        assert!(!enabled(&filter, "app", Level::Error));
+        assert!(enabled(&filter, "app", Level::Warn));
+        assert!(enabled(&filter, "app", Level::Debug));
+        assert!(!enabled(&filter, "app", Level::Trace));
+        // the more specific directive applies
+        assert!(enabled(&filter, "app::db", Level::Error));
+        assert!(enabled(&filter, "app::db", Level::Trace));
+        assert!(enabled(&filter, "other", Level::Error));
+        assert!(!enabled(&filter, "other", Level::Info));
+
+        assert!(EnvFilterBuilder::new()
+            .try_parse("app=debug..nope")
+            .is_err());
+    }
+
+    #[test]
+    fn test_kv_directives() {
+        let filter = EnvFilter::new(
+            EnvFilterBuilder::new()
+                .parse("warn,app[tenant=acme,debug_id]=debug,[audit]=info..warn"),
+        );
+
+        assert_eq!(
+            filter.enabled(
+                &Metadata::builder()
+                    .target("app")
+                    .level(Level::Debug)
+                    .build()
+            ),
+            FilterResult::Unknown
+        );
+        assert!(!enabled(&filter, "app", Level::Trace));
+
+        let matches = |target: &str, level: Level, kvs: &[(&str, &str)]| {
+            let record = log::Record::builder()
+                .target(target)
+                .level(level)
+                .key_values(&kvs)
+                .build();
+            filter.matches(&record, &[]) == FilterResult::Neutral
+        };
+        let kvs = [("tenant", "acme"), ("debug_id", "1")];
+        assert!(matches("app::db", Level::Debug, &kvs));
+        assert!(!matches("app::db", Level::Debug, &kvs[..1]));
+        assert!(!matches(
+            "app",
+            Level::Debug,
+            &[("tenant", "other"), ("debug_id", "1")]
+        ));
+        assert!(!matches("other", Level::Debug, &kvs));
+        assert!(matches("other", Level::Warn, &[]));
+
+        // the constrained directive applies in place of the others
+        assert!(matches("other", Level::Info, &[("audit", "true")]));
+        assert!(!matches("other", Level::Error, &[("audit", "true")]));
+
+        assert!(EnvFilterBuilder::new()
+            .try_parse("app[tenant=acme")
+            .is_err());
+        assert!(EnvFilterBuilder::new()
+            .try_parse("app[tenant]debug")
+            .is_err());
+    }
+
+    #[test]
+    fn test_max_level() {
+        let max_level =
+            |filters: &str| EnvFilter::new(EnvFilterBuilder::new().parse(filters)).max_level();
+
+        assert_eq!(max_level(""), LevelFilter::Error);
+        assert_eq!(max_level("warn,app=debug..warn"), LevelFilter::Debug);
+        assert_eq!(max_level("info,app[tenant=acme]=trace"), LevelFilter::Trace);
+        assert_eq!(max_level("off"), LevelFilter::Off);
+    }
+
+    #[test]
+    fn test_merge() {
+        let filter = EnvFilter::new(
+            EnvFilterBuilder::new()
+                .parse("info,app=debug..warn")
+                .parse("app=error"),
+        );
+
+        assert!(enabled(&filter, "app", Level::Error));
+        assert!(!enabled(&filter, "app", Level::Warn));
+        assert!(enabled(&filter, "other", Level::Info));
+    }
+
+    #[cfg(feature = "env-filter-stats")]
+    #[test]
+    fn test_directive_stats() {
+        let filter = EnvFilter::new(
+            EnvFilterBuilder::new()
+                .parse("info,app=debug,app::db=trace,app[tenant=acme]=trace,gone"),
+        );
+        let stats = filter.stats();
+        let log = |target: &str, tenant: Option<&str>| {
+            let kvs = tenant.map(|tenant| [("tenant", tenant)]);
+            let record = log::Record::builder()
+                .target(target)
+                .level(Level::Debug)
+                .key_values(&kvs)
+                .build();
+            filter.matches(&record, &[]);
+        };
+
+        log("other", None);
+        log("app::http", None);
+        log("app::db::pool", None);
+        log("app::db", None);
+        log("app", Some("acme"));
+
+        let hits: Vec<_> = stats
+            .snapshot()
+            .into_iter()
+            .map(|d| (d.directive, d.hits))
+            .collect();
+        assert_eq!(
+            hits,
+            [
+                ("".to_owned(), 1),
+                ("app".to_owned(), 1),
+                ("app::db".to_owned(), 2),
+                ("gone".to_owned(), 0),
+                ("app[tenant=acme]".to_owned(), 1),
+            ]
+        );
+
+        stats.reset();
+        assert!(stats.snapshot().iter().all(|d| d.hits == 0));
+    }
 }
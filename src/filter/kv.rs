@@ -0,0 +1,221 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+
+use log::kv::Error;
+use log::kv::Key;
+use log::kv::Value;
+use log::kv::VisitSource;
+use log::Metadata;
+use log::Record;
+
+use crate::diagnostic::Visitor;
+use crate::filter::Filter;
+use crate::filter::FilterResult;
+use crate::Diagnostic;
+
+/// A filter that rejects records by their key-values and the diagnostics of the dispatch.
+///
+/// Records that lack a required key-value, or carry a rejected one, are rejected; the others are
+/// passed on to the next filters. A key-value matches if either the record or a diagnostic has
+/// it, and values are compared by their string representation.
+///
+/// Since the record is not available when the logger checks whether a level and target are
+/// enabled, this filter reports [`FilterResult::Unknown`] in that check and makes its decision
+/// once the record is logged.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::append;
+/// use logforth::filter::KvFilter;
+///
+/// logforth::builder()
+///     .dispatch(|d| {
+///         d.filter(KvFilter::new().require("tenant", "acme").reject_key("internal"))
+///             .append(append::Stdout::default())
+///     })
+///     .apply();
+///
+/// log::error!(tenant = "acme"; "this record is logged");
+/// log::error!(tenant = "acme", internal = true; "this record is rejected");
+/// ```
+#[derive(Debug, Default)]
+pub struct KvFilter {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    key: String,
+    // `None` matches any value
+    value: Option<String>,
+    required: bool,
+}
+
+impl KvFilter {
+    /// Creates a new [`KvFilter`] that passes on all records.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rejects records that do not have the key with the given value.
+    pub fn require(self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.rule(key.into(), Some(value.into()), true)
+    }
+
+    /// Rejects records that do not have the key, with any value.
+    pub fn require_key(self, key: impl Into<String>) -> Self {
+        self.rule(key.into(), None, true)
+    }
+
+    /// Rejects records that have the key with the given value.
+    pub fn reject(self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.rule(key.into(), Some(value.into()), false)
+    }
+
+    /// Rejects records that have the key, with any value.
+    pub fn reject_key(self, key: impl Into<String>) -> Self {
+        self.rule(key.into(), None, false)
+    }
+
+    fn rule(mut self, key: String, value: Option<String>, required: bool) -> Self {
+        self.rules.push(Rule {
+            key,
+            value,
+            required,
+        });
+        self
+    }
+
+    pub(crate) fn enabled(&self, _: &Metadata) -> FilterResult {
+        if self.rules.is_empty() {
+            FilterResult::Neutral
+        } else {
+            FilterResult::Unknown
+        }
+    }
+
+    pub(crate) fn matches(&self, record: &Record, diagnostics: &[Diagnostic]) -> FilterResult {
+        if self.rules.is_empty() {
+            return FilterResult::Neutral;
+        }
+
+        let mut matcher = Matcher {
+            rules: &self.rules,
+            matched: vec![false; self.rules.len()],
+        };
+        let _ = record.key_values().visit(&mut matcher);
+        for d in diagnostics {
+            d.visit(&mut matcher);
+        }
+
+        let rejected = self
+            .rules
+            .iter()
+            .zip(matcher.matched)
+            .any(|(rule, matched)| rule.required != matched);
+        if rejected {
+            FilterResult::Reject
+        } else {
+            FilterResult::Neutral
+        }
+    }
+}
+
+impl From<KvFilter> for Filter {
+    fn from(filter: KvFilter) -> Self {
+        Filter::Kv(filter)
+    }
+}
+
+struct Matcher<'a> {
+    rules: &'a [Rule],
+    matched: Vec<bool>,
+}
+
+impl Matcher<'_> {
+    fn on_pair<'v>(&mut self, key: &str, value: impl FnOnce() -> Cow<'v, str>) {
+        let pending = |(rule, matched): (&Rule, &bool)| !*matched && rule.key == key;
+        if !self.rules.iter().zip(&self.matched).any(pending) {
+            return;
+        }
+
+        let value = value();
+        for (rule, matched) in self.rules.iter().zip(self.matched.iter_mut()) {
+            if !*matched && rule.key == key {
+                *matched = rule
+                    .value
+                    .as_ref()
+                    .map_or(true, |expected| *expected == value);
+            }
+        }
+    }
+}
+
+impl<'kvs> VisitSource<'kvs> for Matcher<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+        self.on_pair(key.as_str(), || match value.to_borrowed_str() {
+            Some(value) => Cow::Borrowed(value),
+            None => Cow::Owned(value.to_string()),
+        });
+        Ok(())
+    }
+}
+
+impl Visitor for Matcher<'_> {
+    fn visit<'k, 'v, K, V>(&mut self, key: K, value: V)
+    where
+        K: Into<Cow<'k, str>>,
+        V: Into<Cow<'v, str>>,
+    {
+        self.on_pair(&key.into(), || value.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::ThreadLocalDiagnostic;
+
+    #[test]
+    fn test_kv_filter() {
+        let filter = KvFilter::new()
+            .require("tenant", "acme")
+            .require_key("request_id")
+            .reject_key("internal");
+
+        let metadata = Metadata::builder().build();
+        assert_eq!(filter.enabled(&metadata), FilterResult::Unknown);
+
+        let matches = |kvs: &[(&str, &str)]| {
+            ThreadLocalDiagnostic::insert("request_id", "abc");
+            let record = Record::builder().key_values(&kvs).build();
+            let result = filter.matches(&record, &[ThreadLocalDiagnostic::default().into()]);
+            ThreadLocalDiagnostic::remove("request_id");
+            result
+        };
+        assert_eq!(matches(&[("tenant", "acme")]), FilterResult::Neutral);
+        assert_eq!(matches(&[("tenant", "other")]), FilterResult::Reject);
+        assert_eq!(matches(&[]), FilterResult::Reject);
+        assert_eq!(
+            matches(&[("tenant", "acme"), ("internal", "true")]),
+            FilterResult::Reject
+        );
+
+        let record = Record::builder().key_values(&[("tenant", "acme")]).build();
+        assert_eq!(filter.matches(&record, &[]), FilterResult::Reject);
+    }
+}
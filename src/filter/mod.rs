@@ -15,24 +15,64 @@
 //! Filters for log records.
 
 use std::str::FromStr;
+use std::sync::Arc;
 
 use log::LevelFilter;
 
 pub use self::custom::CustomFilter;
 pub use self::env_filter::EnvFilter;
+pub use self::kv::KvFilter;
+pub use self::rate_limit::RateLimitFilter;
+pub(crate) use self::rate_limit::RateLimitSummary;
+pub use self::rate_limit::RATE_LIMIT_TARGET;
+pub use self::remap::LevelRemap;
+pub use self::sampling::SamplingFilter;
+use crate::Diagnostic;
 
 mod custom;
 pub mod env_filter;
+mod kv;
+mod rate_limit;
+mod remap;
+mod sampling;
 
 /// The result of a filter check.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FilterResult {
     /// The record will be processed without further filtering.
     Accept,
+    /// The record will be processed without further filtering, but only by the appenders whose
+    /// name is listed.
+    ///
+    /// Appenders are named with [`DispatchBuilder::append_named`]; unnamed appenders never
+    /// receive such a record. Treated as [`FilterResult::Accept`] when checking whether a
+    /// record's metadata is enabled.
+    ///
+    /// [`DispatchBuilder::append_named`]: crate::DispatchBuilder::append_named
+    #[cfg_attr(feature = "serde", serde(skip))]
+    AcceptOnly(Arc<[String]>),
     /// The record should not be processed.
     Reject,
     /// No decision could be made, further filtering should occur.
     Neutral,
+    /// No decision could be made without the full record.
+    ///
+    /// Only meaningful when checking whether a record's metadata is enabled: the record is then
+    /// considered enabled and filtered once it is logged. Treated as [`FilterResult::Neutral`]
+    /// when matching a record.
+    Unknown,
+}
+
+impl FilterResult {
+    /// Creates a [`FilterResult::AcceptOnly`] routing records to the appenders with the given
+    /// names.
+    ///
+    /// The names can come from runtime configuration. A filter that returns the same routes for
+    /// many records should create the result once and clone it, which does not copy the names.
+    pub fn accept_only<S: Into<String>>(names: impl IntoIterator<Item = S>) -> Self {
+        FilterResult::AcceptOnly(names.into_iter().map(Into::into).collect())
+    }
 }
 
 /// Represents a filter that can be applied to log records.
@@ -42,6 +82,12 @@ pub enum Filter {
     Env(EnvFilter),
     /// A custom filter.
     Custom(CustomFilter),
+    /// A rate limiting filter.
+    RateLimit(RateLimitFilter),
+    /// A sampling filter.
+    Sampling(SamplingFilter),
+    /// A key-value filter.
+    Kv(KvFilter),
 }
 
 impl Filter {
@@ -49,13 +95,32 @@ impl Filter {
         match self {
             Filter::Env(filter) => filter.enabled(metadata),
             Filter::Custom(filter) => filter.enabled(metadata),
+            Filter::RateLimit(filter) => filter.enabled(metadata),
+            Filter::Sampling(filter) => filter.enabled(metadata),
+            Filter::Kv(filter) => filter.enabled(metadata),
+        }
+    }
+
+    pub(crate) fn matches(&self, record: &log::Record, diagnostics: &[Diagnostic]) -> FilterResult {
+        match self {
+            Filter::Env(filter) => filter.matches(record, diagnostics),
+            Filter::Custom(filter) => filter.matches(record, diagnostics),
+            Filter::RateLimit(filter) => filter.matches(record).0,
+            Filter::Sampling(filter) => filter.matches(record),
+            Filter::Kv(filter) => filter.matches(record, diagnostics),
         }
     }
 
-    pub(crate) fn matches(&self, record: &log::Record) -> FilterResult {
+    /// Like [`Filter::matches`], also returning the summary a rate limit filter writes after the
+    /// record.
+    pub(crate) fn matches_summarized(
+        &self,
+        record: &log::Record,
+        diagnostics: &[Diagnostic],
+    ) -> (FilterResult, Option<RateLimitSummary>) {
         match self {
-            Filter::Env(filter) => filter.matches(record),
-            Filter::Custom(filter) => filter.enabled(record.metadata()),
+            Filter::RateLimit(filter) => filter.matches(record),
+            filter => (filter.matches(record, diagnostics), None),
         }
     }
 }
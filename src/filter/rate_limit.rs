@@ -0,0 +1,293 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use log::kv::Value;
+use log::Level;
+use log::Metadata;
+use log::Record;
+
+use crate::clock;
+use crate::filter::Filter;
+use crate::filter::FilterResult;
+
+/// The target of the summary records emitted by [`RateLimitFilter`].
+///
+/// Records of this target are never rate limited.
+pub const RATE_LIMIT_TARGET: &str = "logforth::rate_limit";
+
+/// A filter that caps the rate of records, per target or globally.
+///
+/// Each target may log `limit` records per `window` on average, and up to
+/// [`burst`](RateLimitFilter::burst) records at once after being quiet. Records over the limit are
+/// rejected; the others are passed on to the next filters. Since the limit counts the records that
+/// reach this filter, place it after the filters that reject records by level or target.
+///
+/// By default, the first record of a target after a window in which records were rejected is
+/// followed by a summary record at the warn level under [`RATE_LIMIT_TARGET`], with the
+/// `suppressed` and `rate_limited_target` key-values. The summary is written to the appenders of
+/// the dispatch that owns the filter only, without going through its filters.
+///
+/// At most [`max_targets`](RateLimitFilter::max_targets) targets are tracked; when a new target
+/// arrives beyond that, the target seen least recently is forgotten along with its count of
+/// rejected records.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use logforth::append;
+/// use logforth::filter::RateLimitFilter;
+///
+/// logforth::builder()
+///     .dispatch(|d| {
+///         d.filter(log::LevelFilter::Info)
+///             .filter(RateLimitFilter::new(100, Duration::from_secs(1)).burst(200))
+///             .append(append::Stderr::default())
+///     })
+///     .apply();
+/// ```
+#[derive(Debug)]
+pub struct RateLimitFilter {
+    limit: usize,
+    window: Duration,
+    burst: usize,
+    global: bool,
+    summary: bool,
+    max_targets: usize,
+    states: Mutex<HashMap<String, RateState>>,
+}
+
+#[derive(Debug)]
+struct RateState {
+    tokens: f64,
+    refilled_at: Instant,
+    window_start: Instant,
+    suppressed: usize,
+}
+
+impl RateLimitFilter {
+    const DEFAULT_MAX_TARGETS: usize = 1024;
+
+    /// Creates a new [`RateLimitFilter`] that lets `limit` records per `window` of each target
+    /// through, with a burst of `limit` records.
+    pub fn new(limit: usize, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            burst: limit,
+            global: false,
+            summary: true,
+            max_targets: Self::DEFAULT_MAX_TARGETS,
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets the number of records that may be logged at once after a quiet period. Default to
+    /// the limit.
+    pub fn burst(mut self, burst: usize) -> Self {
+        self.burst = burst;
+        self
+    }
+
+    /// Sets whether the limit applies to all targets together rather than to each target.
+    /// Default to `false`.
+    pub fn global(mut self, global: bool) -> Self {
+        self.global = global;
+        self
+    }
+
+    /// Sets whether to log a summary of the records rejected in a window. Default to `true`.
+    pub fn summary(mut self, summary: bool) -> Self {
+        self.summary = summary;
+        self
+    }
+
+    /// Sets the maximum number of targets whose rate is tracked at once. Default to 1024.
+    pub fn max_targets(mut self, max_targets: usize) -> Self {
+        self.max_targets = max_targets.max(1);
+        self
+    }
+
+    pub(crate) fn enabled(&self, _: &Metadata) -> FilterResult {
+        FilterResult::Neutral
+    }
+
+    /// Returns the decision, and the summary to write after the record if a new window starts.
+    pub(crate) fn matches(&self, record: &Record) -> (FilterResult, Option<RateLimitSummary>) {
+        if record.target() == RATE_LIMIT_TARGET {
+            return (FilterResult::Neutral, None);
+        }
+
+        let key = if self.global { "" } else { record.target() };
+        let (result, suppressed) = self.on_record(key, clock::instant());
+        let summary = suppressed
+            .filter(|_| self.summary)
+            .map(|suppressed| RateLimitSummary {
+                target: key.to_string(),
+                global: self.global,
+                suppressed,
+                window: self.window,
+            });
+        (result, summary)
+    }
+
+    // Returns the decision, and the number of records rejected in the previous window when a new
+    // window starts.
+    fn on_record(&self, key: &str, now: Instant) -> (FilterResult, Option<usize>) {
+        let mut states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+        if states.len() >= self.max_targets && !states.contains_key(key) {
+            let least_recent = states
+                .iter()
+                .min_by_key(|(_, state)| state.refilled_at)
+                .map(|(key, _)| key.clone());
+            if let Some(least_recent) = least_recent {
+                states.remove(&least_recent);
+            }
+        }
+        let state = match states.get_mut(key) {
+            Some(state) => state,
+            None => states.entry(key.to_string()).or_insert_with(|| RateState {
+                tokens: self.burst as f64,
+                refilled_at: now,
+                window_start: now,
+                suppressed: 0,
+            }),
+        };
+
+        let rate = self.limit as f64 / self.window.as_secs_f64();
+        let elapsed = now.saturating_duration_since(state.refilled_at);
+        state.tokens = (state.tokens + elapsed.as_secs_f64() * rate).min(self.burst as f64);
+        state.refilled_at = now;
+
+        let mut suppressed = None;
+        if now.saturating_duration_since(state.window_start) >= self.window {
+            suppressed = Some(std::mem::take(&mut state.suppressed)).filter(|&n| n > 0);
+            state.window_start = now;
+        }
+
+        let result = if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            FilterResult::Neutral
+        } else {
+            state.suppressed += 1;
+            FilterResult::Reject
+        };
+        (result, suppressed)
+    }
+}
+
+/// A summary of the records rejected by a [`RateLimitFilter`] in a window.
+#[derive(Debug)]
+pub(crate) struct RateLimitSummary {
+    target: String,
+    global: bool,
+    suppressed: usize,
+    window: Duration,
+}
+
+impl RateLimitSummary {
+    /// Calls `f` with the summary as a record.
+    pub(crate) fn with_record(&self, f: impl FnOnce(&Record)) {
+        let suppressed = self.suppressed;
+        let kvs = [
+            ("suppressed", Value::from(suppressed)),
+            ("rate_limited_target", Value::from(self.target.as_str())),
+        ];
+        let scope = if self.global {
+            "all targets"
+        } else {
+            &self.target
+        };
+        f(&Record::builder()
+            .level(Level::Warn)
+            .target(RATE_LIMIT_TARGET)
+            .args(format_args!(
+                "rate limit: suppressed {suppressed} records of {scope} in the last {:?}",
+                self.window
+            ))
+            .key_values(&kvs)
+            .build());
+    }
+}
+
+impl From<RateLimitFilter> for Filter {
+    fn from(filter: RateLimitFilter) -> Self {
+        Filter::RateLimit(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit() {
+        let filter = RateLimitFilter::new(2, Duration::from_secs(1)).burst(3);
+        let start = Instant::now();
+
+        // the burst goes through, then records are rejected until tokens refill
+        for _ in 0..3 {
+            assert_eq!(
+                filter.on_record("app", start),
+                (FilterResult::Neutral, None)
+            );
+        }
+        assert_eq!(filter.on_record("app", start), (FilterResult::Reject, None));
+        assert_eq!(
+            filter.on_record("other", start),
+            (FilterResult::Neutral, None)
+        );
+
+        let later = start + Duration::from_millis(500);
+        assert_eq!(
+            filter.on_record("app", later),
+            (FilterResult::Neutral, None)
+        );
+        assert_eq!(filter.on_record("app", later), (FilterResult::Reject, None));
+
+        // the next window reports the rejected records of the previous one
+        let next = start + Duration::from_secs(1);
+        assert_eq!(
+            filter.on_record("app", next),
+            (FilterResult::Neutral, Some(2))
+        );
+        let quiet = next + Duration::from_secs(1);
+        assert_eq!(
+            filter.on_record("app", quiet),
+            (FilterResult::Neutral, None)
+        );
+    }
+
+    #[test]
+    fn test_max_targets() {
+        let filter = RateLimitFilter::new(1, Duration::from_secs(1)).max_targets(2);
+        let start = Instant::now();
+        assert_eq!(filter.on_record("a", start).0, FilterResult::Neutral);
+        assert_eq!(filter.on_record("b", start).0, FilterResult::Neutral);
+        assert_eq!(filter.on_record("a", start).0, FilterResult::Reject);
+
+        // a third target evicts the least recently seen one
+        let later = start + Duration::from_millis(1);
+        assert_eq!(filter.on_record("a", later).0, FilterResult::Reject);
+        assert_eq!(filter.on_record("c", later).0, FilterResult::Neutral);
+        assert_eq!(filter.states.lock().unwrap().len(), 2);
+        assert!(!filter.states.lock().unwrap().contains_key("b"));
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use log::Level;
+
+/// Re-classifies the level of log records by target before they are filtered.
+///
+/// This is useful for third-party crates that chronically log at an inappropriate level. Since
+/// remapping happens before any filter, the remapped level affects filtering decisions as well as
+/// the level appenders see.
+///
+/// A rule applies to records whose target is the given target or one of its submodules. Rules are
+/// checked in the order they were added, and the first matching rule wins.
+///
+/// Note that records filtered out by [`log::max_level`] never reach the logger, so remapping
+/// cannot raise the level of such records.
+///
+/// # Examples
+///
+/// ```
+/// use log::Level;
+/// use logforth::filter::LevelRemap;
+///
+/// logforth::builder()
+///     .level_remap(LevelRemap::new().map_target("noisy_dep", Level::Error, Level::Warn))
+///     .dispatch(|d| d.append(logforth::append::Stdout::default()))
+///     .apply();
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct LevelRemap {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    target: String,
+    from: Level,
+    to: Level,
+}
+
+impl LevelRemap {
+    /// Creates an empty [`LevelRemap`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remaps records of the target (and its submodules) at level `from` to level `to`.
+    pub fn map_target(mut self, target: impl Into<String>, from: Level, to: Level) -> Self {
+        self.rules.push(Rule {
+            target: target.into(),
+            from,
+            to,
+        });
+        self
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub(crate) fn remap(&self, target: &str, level: Level) -> Level {
+        self.rules
+            .iter()
+            .find(|rule| rule.from == level && matches_target(&rule.target, target))
+            .map_or(level, |rule| rule.to)
+    }
+}
+
+fn matches_target(rule: &str, target: &str) -> bool {
+    match target.strip_prefix(rule) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_remap() {
+        let remap = LevelRemap::new()
+            .map_target("noisy_dep", Level::Error, Level::Warn)
+            .map_target("noisy_dep::io", Level::Info, Level::Debug);
+
+        assert_eq!(remap.remap("noisy_dep", Level::Error), Level::Warn);
+        assert_eq!(remap.remap("noisy_dep::io", Level::Error), Level::Warn);
+        assert_eq!(remap.remap("noisy_dep::io", Level::Info), Level::Debug);
+        assert_eq!(remap.remap("noisy_dep", Level::Info), Level::Info);
+        assert_eq!(remap.remap("noisy_dependency", Level::Error), Level::Error);
+        assert_eq!(remap.remap("my_crate", Level::Error), Level::Error);
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use log::Level;
+use log::Metadata;
+use log::Record;
+
+use crate::filter::Filter;
+use crate::filter::FilterResult;
+
+/// A filter that keeps a sample of the records, configured per level.
+///
+/// Each level keeps all records unless configured otherwise, either deterministically with
+/// [`every`](SamplingFilter::every) (the first of every `n` records) or randomly with
+/// [`probability`](SamplingFilter::probability). Sampled out records are rejected; the others are
+/// passed on to the next filters. Since the samples are drawn from the records that reach this
+/// filter, place it after the filters that reject records by level or target.
+///
+/// # Examples
+///
+/// ```
+/// use log::Level;
+/// use logforth::append;
+/// use logforth::filter::SamplingFilter;
+///
+/// logforth::builder()
+///     .dispatch(|d| {
+///         d.filter(log::LevelFilter::Debug)
+///             .filter(
+///                 SamplingFilter::new()
+///                     .probability(Level::Debug, 0.01)
+///                     .every(Level::Info, 10),
+///             )
+///             .append(append::Stderr::default())
+///     })
+///     .apply();
+/// ```
+#[derive(Debug)]
+pub struct SamplingFilter {
+    // indexed by `level as usize - 1`
+    samplers: [Sampler; 5],
+    // the state of the splitmix64 generator
+    seed: AtomicU64,
+}
+
+#[derive(Debug)]
+enum Sampler {
+    All,
+    Every { n: u64, count: AtomicU64 },
+    Probability(f64),
+}
+
+impl Default for SamplingFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SamplingFilter {
+    /// Creates a new [`SamplingFilter`] that keeps all records.
+    pub fn new() -> Self {
+        Self {
+            samplers: [
+                Sampler::All,
+                Sampler::All,
+                Sampler::All,
+                Sampler::All,
+                Sampler::All,
+            ],
+            seed: AtomicU64::new(RandomState::new().hash_one(0u64)),
+        }
+    }
+
+    /// Keeps the first of every `n` records of the given level. An `n` of 0 is treated as 1.
+    pub fn every(mut self, level: Level, n: u64) -> Self {
+        self.samplers[level as usize - 1] = Sampler::Every {
+            n: n.max(1),
+            count: AtomicU64::new(0),
+        };
+        self
+    }
+
+    /// Keeps each record of the given level with the given probability, clamped to `0.0..=1.0`.
+    pub fn probability(mut self, level: Level, probability: f64) -> Self {
+        self.samplers[level as usize - 1] = Sampler::Probability(probability.clamp(0.0, 1.0));
+        self
+    }
+
+    pub(crate) fn enabled(&self, _: &Metadata) -> FilterResult {
+        FilterResult::Neutral
+    }
+
+    pub(crate) fn matches(&self, record: &Record) -> FilterResult {
+        let keep = match &self.samplers[record.level() as usize - 1] {
+            Sampler::All => true,
+            Sampler::Every { n, count } => count.fetch_add(1, Ordering::Relaxed) % n == 0,
+            Sampler::Probability(probability) => self.next_f64() < *probability,
+        };
+        if keep {
+            FilterResult::Neutral
+        } else {
+            FilterResult::Reject
+        }
+    }
+
+    // Returns a uniformly distributed number in `0.0..1.0`.
+    fn next_f64(&self) -> f64 {
+        let mut z = self
+            .seed
+            .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed)
+            .wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl From<SamplingFilter> for Filter {
+    fn from(filter: SamplingFilter) -> Self {
+        Filter::Sampling(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_kept(filter: &SamplingFilter, level: Level, records: usize) -> usize {
+        let record = Record::builder().level(level).build();
+        (0..records)
+            .filter(|_| filter.matches(&record) == FilterResult::Neutral)
+            .count()
+    }
+
+    #[test]
+    fn test_sampling() {
+        let filter = SamplingFilter::new()
+            .every(Level::Info, 10)
+            .probability(Level::Debug, 0.1)
+            .probability(Level::Trace, 0.0);
+
+        assert_eq!(count_kept(&filter, Level::Error, 100), 100);
+        assert_eq!(count_kept(&filter, Level::Info, 100), 10);
+        assert_eq!(count_kept(&filter, Level::Trace, 100), 0);
+
+        let kept = count_kept(&filter, Level::Debug, 10_000);
+        assert!((700..1300).contains(&kept), "kept {kept} records");
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Route output written to [`std::io::Write`] sinks into log records.
+//!
+//! Some libraries only accept a [`Write`] sink for their output, e.g., wrappers of C libraries
+//! or helpers capturing the output of child processes. [`writer_for`] returns a sink that turns
+//! each written line into a record of the given level and target, so that the output goes
+//! through the filters and appenders of the installed logger. [`ChildOutput`] does so for the
+//! output pipes of a child process.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::io::Write;
+//!
+//! logforth::stdout().apply();
+//!
+//! let mut writer = logforth::io::writer_for(log::Level::Warn, "ffi");
+//! writeln!(writer, "first line\nsecond line").unwrap();
+//! ```
+
+use std::io::Write;
+use std::process::Child;
+use std::thread::JoinHandle;
+
+use log::Level;
+use log::Record;
+
+use crate::diagnostic::ThreadBuilderExt;
+
+/// The maximum length in bytes of a line held by a [`LogWriter`] before it is logged.
+pub const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// Creates a [`LogWriter`] that logs each written line at the given level and target.
+pub fn writer_for(level: Level, target: impl Into<String>) -> LogWriter {
+    LogWriter {
+        level,
+        target: target.into(),
+        buffer: Vec::new(),
+    }
+}
+
+/// A [`Write`] sink that logs each written line as a record.
+///
+/// Lines are split on `\n`, with a trailing `\r` removed, and decoded as UTF-8 lossily. A
+/// trailing incomplete line is held until the line is completed, the writer is flushed, or the
+/// writer is dropped. A line longer than [`MAX_LINE_LEN`] is logged in parts of at most that
+/// many bytes, without splitting a UTF-8 encoded character, so that output without newlines
+/// cannot grow the buffer without bound.
+#[derive(Debug)]
+pub struct LogWriter {
+    level: Level,
+    target: String,
+    buffer: Vec<u8>,
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let (level, target) = (self.level, &self.target);
+        write_lines(&mut self.buffer, buf, MAX_LINE_LEN, |line| {
+            log_line(level, target, line)
+        });
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let (level, target) = (self.level, &self.target);
+        flush_line(&mut self.buffer, |line| log_line(level, target, line));
+        Ok(())
+    }
+}
+
+impl Drop for LogWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Logs the output of a child process, line by line.
+///
+/// [`ChildOutput::capture`] takes the piped stdout and stderr of a child process and spawns a
+/// thread per pipe that logs each line under the given target, at the info level for stdout and
+/// the warn level for stderr by default. The threads carry a snapshot of the caller's
+/// [thread-local context](crate::diagnostic::ThreadLocalContext), so that the lines are logged
+/// with the same diagnostics as the caller's records.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(not(unix))] fn main() {}
+/// # #[cfg(unix)] fn main() {
+/// use std::process::Command;
+/// use std::process::Stdio;
+///
+/// use logforth::io::ChildOutput;
+///
+/// logforth::stdout().apply();
+///
+/// let mut child = Command::new("sh")
+///     .args(["-c", "echo hello; echo oops >&2"])
+///     .stdout(Stdio::piped())
+///     .stderr(Stdio::piped())
+///     .spawn()
+///     .unwrap();
+/// let capture = ChildOutput::new("child").capture(&mut child).unwrap();
+/// child.wait().unwrap();
+/// capture.join();
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChildOutput {
+    target: String,
+    stdout_level: Level,
+    stderr_level: Level,
+}
+
+impl ChildOutput {
+    /// Creates a new [`ChildOutput`] logging under the given target.
+    pub fn new(target: impl Into<String>) -> Self {
+        Self {
+            target: target.into(),
+            stdout_level: Level::Info,
+            stderr_level: Level::Warn,
+        }
+    }
+
+    /// Sets the level of the lines written to stdout. Default to [`Level::Info`].
+    pub fn stdout_level(mut self, level: Level) -> Self {
+        self.stdout_level = level;
+        self
+    }
+
+    /// Sets the level of the lines written to stderr. Default to [`Level::Warn`].
+    pub fn stderr_level(mut self, level: Level) -> Self {
+        self.stderr_level = level;
+        self
+    }
+
+    /// Takes the piped stdout and stderr of the child and logs their lines until they are closed.
+    ///
+    /// Pipes that are not piped, or already taken, are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a thread cannot be spawned.
+    pub fn capture(&self, child: &mut Child) -> std::io::Result<CaptureHandle> {
+        let mut threads = vec![];
+        if let Some(stdout) = child.stdout.take() {
+            threads.push(self.spawn("stdout", stdout, self.stdout_level)?);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            threads.push(self.spawn("stderr", stderr, self.stderr_level)?);
+        }
+        Ok(CaptureHandle { threads })
+    }
+
+    fn spawn(
+        &self,
+        name: &str,
+        mut pipe: impl std::io::Read + Send + 'static,
+        level: Level,
+    ) -> std::io::Result<JoinHandle<()>> {
+        let mut writer = writer_for(level, self.target.clone());
+        std::thread::Builder::new()
+            .name(format!("logforth-child-{name}"))
+            .spawn_with_context(move || {
+                if let Err(err) = std::io::copy(&mut pipe, &mut writer) {
+                    eprintln!("logforth: failed to read the output of a child process: {err}");
+                }
+            })
+    }
+}
+
+/// A handle to the threads logging the output of a child process.
+///
+/// Dropping the handle detaches the threads; they exit once the pipes are closed.
+#[derive(Debug)]
+pub struct CaptureHandle {
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl CaptureHandle {
+    /// Waits until all the output of the child process has been logged.
+    pub fn join(self) {
+        for thread in self.threads {
+            let _ = thread.join();
+        }
+    }
+}
+
+// Emits the complete lines of the buffer after appending `buf` to it, and then the leading
+// parts of an incomplete line as long as it has at least `max_len` bytes.
+fn write_lines(buffer: &mut Vec<u8>, buf: &[u8], max_len: usize, mut emit: impl FnMut(&str)) {
+    buffer.extend_from_slice(buf);
+    if let Some(end) = buffer.iter().rposition(|&b| b == b'\n') {
+        for line in buffer[..end].split(|&b| b == b'\n') {
+            emit_line(line, &mut emit);
+        }
+        buffer.drain(..=end);
+    }
+    while buffer.len() >= max_len {
+        let mut cut = max_len;
+        // back off to the start of a UTF-8 encoded character split by the cut
+        while cut > 1 && max_len - cut < 3 && buffer[cut] & 0xC0 == 0x80 {
+            cut -= 1;
+        }
+        emit(&String::from_utf8_lossy(&buffer[..cut]));
+        buffer.drain(..cut);
+    }
+}
+
+// Emits the incomplete line left in the buffer, if any.
+fn flush_line(buffer: &mut Vec<u8>, emit: impl FnMut(&str)) {
+    if !buffer.is_empty() {
+        emit_line(buffer, emit);
+        buffer.clear();
+    }
+}
+
+// Logs a line without a source location, which would point to this module.
+fn log_line(level: Level, target: &str, line: &str) {
+    if level <= log::max_level() {
+        log::logger().log(
+            &Record::builder()
+                .level(level)
+                .target(target)
+                .args(format_args!("{line}"))
+                .build(),
+        );
+    }
+}
+
+fn emit_line(line: &[u8], mut emit: impl FnMut(&str)) {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    emit(&String::from_utf8_lossy(line));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_lines() {
+        let mut buffer = vec![];
+        let mut lines = vec![];
+
+        write_lines(&mut buffer, b"first\r\nsec", 16, |line| {
+            lines.push(line.to_string())
+        });
+        write_lines(&mut buffer, b"ond\n\nthird", 16, |line| {
+            lines.push(line.to_string())
+        });
+        assert_eq!(lines, ["first", "second", ""]);
+
+        flush_line(&mut buffer, |line| lines.push(line.to_string()));
+        flush_line(&mut buffer, |line| lines.push(line.to_string()));
+        assert_eq!(lines, ["first", "second", "", "third"]);
+    }
+
+    #[test]
+    fn test_write_long_line() {
+        let mut buffer = vec![];
+        let mut lines = vec![];
+
+        write_lines(&mut buffer, b"abcdefgh", 3, |line| {
+            lines.push(line.to_string())
+        });
+        assert_eq!(lines, ["abc", "def"]);
+        assert_eq!(buffer, b"gh");
+
+        // a character split by the cut is kept whole for the next part
+        write_lines(&mut buffer, "é".as_bytes(), 3, |line| {
+            lines.push(line.to_string())
+        });
+        assert_eq!(lines, ["abc", "def", "gh"]);
+        write_lines(&mut buffer, b"ij\nk", 3, |line| {
+            lines.push(line.to_string())
+        });
+        assert_eq!(lines, ["abc", "def", "gh", "éij"]);
+        assert_eq!(buffer, b"k");
+    }
+}
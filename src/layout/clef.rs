@@ -0,0 +1,245 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+
+use log::Level;
+use log::Record;
+use serde_json::Map;
+use serde_json::Value;
+
+use crate::backtrace::is_backtrace_key;
+use crate::backtrace::BACKTRACE_FIELD;
+use crate::clock;
+use crate::diagnostic::Visitor;
+use crate::layout::Layout;
+use crate::pool;
+use crate::secret::secret_key;
+use crate::secret::MASK;
+use crate::template::is_message_template_key;
+use crate::template::template_hash;
+use crate::Diagnostic;
+
+/// A layout that formats log records in the [Compact Log Event Format (CLEF)][clef].
+///
+/// CLEF is the newline-delimited JSON format ingested by [Seq](https://datalust.co/seq) and
+/// compatible tooling. Output format:
+///
+/// ```json
+/// {"@i":"76111a66","@l":"Information","@m":"User 42 logged in","@mt":"User {user_id} logged in","@t":"2024-08-11T14:44:57.172051Z","SourceContext":"app","user_id":"42"}
+/// {"@l":"Warning","@m":"Hello warn!","@t":"2024-08-11T14:44:57.172187Z","SourceContext":"app"}
+/// ```
+///
+/// The fields are filled as follows:
+///
+/// - `@t`: The timestamp in UTC.
+/// - `@m`: The rendered message.
+/// - `@mt`: The [message template](crate::template), if the record has one.
+/// - `@i`: The hex-encoded [hash of the message template](crate::template::template_hash), if the
+///   record has one.
+/// - `@l`: The level, mapped to `Verbose`, `Debug`, `Information`, `Warning`, or `Error`.
+/// - `@x`: The value of the `error` key-value, if present.
+/// - `SourceContext`: The target of the record.
+/// - `backtrace`: The [backtrace](crate::backtrace) captured for the record, if
+///   [`ClefLayout::backtrace`] is enabled.
+///
+/// Key-values of the record and the diagnostics are added as top-level properties. Keys starting
+/// with `@` are escaped by doubling the leading `@`, as required by CLEF.
+///
+/// [clef]: https://clef-json.org/
+///
+/// # Examples
+///
+/// ```
+/// use logforth::layout::ClefLayout;
+///
+/// let clef_layout = ClefLayout::default();
+/// ```
+#[derive(Default, Debug, Clone)]
+pub struct ClefLayout {
+    backtrace: bool,
+}
+
+fn clef_level(level: Level) -> &'static str {
+    match level {
+        Level::Error => "Error",
+        Level::Warn => "Warning",
+        Level::Info => "Information",
+        Level::Debug => "Debug",
+        Level::Trace => "Verbose",
+    }
+}
+
+struct KvCollector {
+    properties: Map<String, Value>,
+    message_template: Option<String>,
+    exception: Option<String>,
+    backtrace: bool,
+    captured_backtrace: Option<String>,
+}
+
+impl KvCollector {
+    fn insert(&mut self, key: &str, value: String) {
+        let (key, value) = match secret_key(key) {
+            Some(key) => (key, MASK.to_string()),
+            None => (key, value),
+        };
+        if is_backtrace_key(key) {
+            if self.backtrace {
+                self.captured_backtrace = Some(value);
+            }
+            return;
+        }
+        if is_message_template_key(key) {
+            self.message_template = Some(value);
+        } else if key == "error" {
+            self.exception = Some(value);
+        } else if key.starts_with('@') {
+            self.properties.insert(format!("@{key}"), value.into());
+        } else {
+            self.properties.insert(key.to_string(), value.into());
+        }
+    }
+}
+
+impl<'kvs> log::kv::VisitSource<'kvs> for KvCollector {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        self.insert(key.as_str(), value.to_string());
+        Ok(())
+    }
+}
+
+impl Visitor for KvCollector {
+    fn visit<'k, 'v, K, V>(&mut self, key: K, value: V)
+    where
+        K: Into<Cow<'k, str>>,
+        V: Into<Cow<'v, str>>,
+    {
+        self.insert(&key.into(), value.into().into_owned());
+    }
+}
+
+impl ClefLayout {
+    /// Sets whether to emit the backtrace captured for the record. Default to `false`.
+    ///
+    /// See [`Builder::backtrace_level`](crate::Builder::backtrace_level) to capture backtraces.
+    pub fn backtrace(mut self, backtrace: bool) -> Self {
+        self.backtrace = backtrace;
+        self
+    }
+
+    pub(crate) fn format(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut collector = KvCollector {
+            properties: Map::new(),
+            message_template: None,
+            exception: None,
+            backtrace: self.backtrace,
+            captured_backtrace: None,
+        };
+        record.key_values().visit(&mut collector)?;
+        for d in diagnostics {
+            d.visit(&mut collector);
+        }
+
+        let mut event = Map::new();
+        event.insert("@t".into(), format!("{:.6}", clock::now()).into());
+        event.insert("@m".into(), record.args().to_string().into());
+        if let Some(template) = collector.message_template {
+            let hash = template_hash(&template);
+            event.insert("@mt".into(), template.into());
+            event.insert("@i".into(), format!("{hash:08x}").into());
+        }
+        event.insert("@l".into(), clef_level(record.level()).into());
+        if let Some(exception) = collector.exception {
+            event.insert("@x".into(), exception.into());
+        }
+        event.insert("SourceContext".into(), record.target().into());
+        if let Some(backtrace) = collector.captured_backtrace {
+            event.insert(BACKTRACE_FIELD.into(), backtrace.into());
+        }
+        for (key, value) in collector.properties {
+            event.entry(key).or_insert(value);
+        }
+
+        let mut bytes = pool::take();
+        serde_json::to_writer(&mut bytes, &event)?;
+        Ok(bytes)
+    }
+}
+
+impl From<ClefLayout> for Layout {
+    fn from(layout: ClefLayout) -> Self {
+        Layout::Clef(layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtrace::BACKTRACE_KEY;
+    use crate::template::MESSAGE_TEMPLATE_KEY;
+
+    #[test]
+    fn test_clef_format() {
+        let kvs = [
+            (MESSAGE_TEMPLATE_KEY, "User {user_id} logged in"),
+            ("user_id", "42"),
+            ("@t", "escaped"),
+            ("error", "connection reset"),
+            ("secret_token", "s3cr3t"),
+            (BACKTRACE_KEY, "0: main"),
+            ("backtrace", "user value"),
+        ];
+        let record = Record::builder()
+            .args(format_args!("User 42 logged in"))
+            .level(Level::Warn)
+            .target("app")
+            .key_values(&kvs)
+            .build();
+
+        let bytes = ClefLayout::default().format(&record, &[]).unwrap();
+        let event: Map<String, Value> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(event["@m"], "User 42 logged in");
+        assert_eq!(event["@mt"], "User {user_id} logged in");
+        assert_eq!(
+            event["@i"],
+            format!("{:08x}", template_hash("User {user_id} logged in"))
+        );
+        assert_eq!(event["@l"], "Warning");
+        assert_eq!(event["@x"], "connection reset");
+        assert_eq!(event["SourceContext"], "app");
+        assert_eq!(event["user_id"], "42");
+        assert_eq!(event["@@t"], "escaped");
+        assert_eq!(event["token"], crate::secret::MASK);
+        assert!(!event.contains_key("secret_token"));
+        assert!(!event.contains_key("message_template"));
+        assert!(!event.contains_key("error"));
+        assert_eq!(event["backtrace"], "user value");
+
+        let bytes = ClefLayout::default()
+            .backtrace(true)
+            .format(&record, &[])
+            .unwrap();
+        let event: Map<String, Value> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(event["backtrace"], "0: main");
+    }
+}
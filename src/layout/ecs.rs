@@ -0,0 +1,272 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+
+use log::Record;
+use serde_json::Map;
+use serde_json::Value;
+
+use crate::attachment::is_attachment_key;
+use crate::backtrace::is_backtrace_key;
+use crate::clock;
+use crate::diagnostic::Visitor;
+use crate::layout::Layout;
+use crate::pool;
+use crate::secret::secret_key;
+use crate::secret::MASK;
+use crate::template::is_message_template_key;
+use crate::Diagnostic;
+
+/// The version of the Elastic Common Schema the output conforms to.
+const ECS_VERSION: &str = "8.11.0";
+
+/// A layout that formats log records as [Elastic Common Schema (ECS)][ecs] JSON documents.
+///
+/// The output can be shipped by Filebeat or Elastic Agent to Elasticsearch as is. Output format:
+///
+/// ```json
+/// {"@timestamp":"2024-08-11T14:44:57.172051Z","ecs":{"version":"8.11.0"},"error":{"message":"connection reset"},"labels":{"user_id":"42"},"log":{"level":"error","logger":"app::db","origin":{"file":{"line":51,"name":"src/db.rs"}}},"message":"query failed","trace":{"id":"4bf92f3577b34da6a3ce929d0e0e4736"}}
+/// ```
+///
+/// The fields are filled as follows:
+///
+/// - `@timestamp`: The timestamp in UTC.
+/// - `message`: The rendered message.
+/// - `log.level`: The level in lower case, e.g., `warn`.
+/// - `log.logger`: The target of the record.
+/// - `log.origin.file.name` and `log.origin.file.line`: The file and the line of the record, if
+///   present. The module path is not written, as `log.origin.function` expects a function name.
+/// - `error.message`: The value of the `error` key-value, if present.
+/// - `error.stack_trace`: The [backtrace](crate::backtrace) captured for the record, if
+///   [`EcsLayout::backtrace`] is enabled.
+/// - `trace.id`, `span.id` and `transaction.id`: The values of the `trace_id`, `span_id` and
+///   `transaction_id` key-values or diagnostics, if present.
+/// - `labels`: The other key-values and diagnostics, with dots in keys replaced by underscores as
+///   ECS requires.
+///
+/// [ecs]: https://www.elastic.co/guide/en/ecs/current/index.html
+///
+/// # Examples
+///
+/// ```
+/// use logforth::layout::EcsLayout;
+///
+/// let ecs_layout = EcsLayout::default();
+/// ```
+#[derive(Default, Debug, Clone)]
+pub struct EcsLayout {
+    backtrace: bool,
+}
+
+#[derive(Default)]
+struct KvCollector {
+    labels: Map<String, Value>,
+    error: Option<String>,
+    stack_trace: Option<String>,
+    trace_id: Option<String>,
+    span_id: Option<String>,
+    transaction_id: Option<String>,
+}
+
+impl KvCollector {
+    fn insert(&mut self, key: &str, value: String) {
+        let (key, value) = match secret_key(key) {
+            Some(key) => (key, MASK.to_string()),
+            None => (key, value),
+        };
+        match key {
+            // the rendered message is written already
+            _ if is_message_template_key(key) => {}
+            // binary attachments are left to the appenders that can store them
+            _ if is_attachment_key(key) => {}
+            _ if is_backtrace_key(key) => self.stack_trace = Some(value),
+            "error" => self.error = Some(value),
+            "trace_id" => self.trace_id = Some(value),
+            "span_id" => self.span_id = Some(value),
+            "transaction_id" => self.transaction_id = Some(value),
+            _ => {
+                self.labels.insert(key.replace('.', "_"), value.into());
+            }
+        }
+    }
+}
+
+impl<'kvs> log::kv::VisitSource<'kvs> for KvCollector {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        self.insert(key.as_str(), value.to_string());
+        Ok(())
+    }
+}
+
+impl Visitor for KvCollector {
+    fn visit<'k, 'v, K, V>(&mut self, key: K, value: V)
+    where
+        K: Into<Cow<'k, str>>,
+        V: Into<Cow<'v, str>>,
+    {
+        self.insert(&key.into(), value.into().into_owned());
+    }
+}
+
+/// Returns `{ key: value }`.
+fn object(key: &str, value: impl Into<Value>) -> Value {
+    let mut object = Map::new();
+    object.insert(key.to_string(), value.into());
+    Value::Object(object)
+}
+
+impl EcsLayout {
+    /// Sets whether to emit the backtrace captured for the record as `error.stack_trace`.
+    /// Default to `false`.
+    ///
+    /// See [`Builder::backtrace_level`](crate::Builder::backtrace_level) to capture backtraces.
+    pub fn backtrace(mut self, backtrace: bool) -> Self {
+        self.backtrace = backtrace;
+        self
+    }
+
+    pub(crate) fn format(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut collector = KvCollector::default();
+        record.key_values().visit(&mut collector)?;
+        for d in diagnostics {
+            d.visit(&mut collector);
+        }
+
+        let mut origin = Map::new();
+        if let Some(file) = record.file() {
+            let mut file = object("name", file);
+            if let Some(line) = record.line() {
+                file["line"] = line.into();
+            }
+            origin.insert("file".into(), file);
+        }
+        let mut log = Map::new();
+        log.insert(
+            "level".into(),
+            record.level().as_str().to_ascii_lowercase().into(),
+        );
+        log.insert("logger".into(), record.target().into());
+        if !origin.is_empty() {
+            log.insert("origin".into(), origin.into());
+        }
+
+        let mut event = Map::new();
+        event.insert("@timestamp".into(), format!("{:.6}", clock::now()).into());
+        event.insert("message".into(), record.args().to_string().into());
+        event.insert("log".into(), log.into());
+        event.insert("ecs".into(), object("version", ECS_VERSION));
+
+        let mut error = Map::new();
+        if let Some(message) = collector.error {
+            error.insert("message".into(), message.into());
+        }
+        if let Some(stack_trace) = collector.stack_trace.filter(|_| self.backtrace) {
+            error.insert("stack_trace".into(), stack_trace.into());
+        }
+        if !error.is_empty() {
+            event.insert("error".into(), error.into());
+        }
+        if let Some(trace_id) = collector.trace_id {
+            event.insert("trace".into(), object("id", trace_id));
+        }
+        if let Some(span_id) = collector.span_id {
+            event.insert("span".into(), object("id", span_id));
+        }
+        if let Some(transaction_id) = collector.transaction_id {
+            event.insert("transaction".into(), object("id", transaction_id));
+        }
+        if !collector.labels.is_empty() {
+            event.insert("labels".into(), collector.labels.into());
+        }
+
+        let mut bytes = pool::take();
+        serde_json::to_writer(&mut bytes, &event)?;
+        Ok(bytes)
+    }
+}
+
+impl From<EcsLayout> for Layout {
+    fn from(layout: EcsLayout) -> Self {
+        Layout::Ecs(layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+    use serde_json::json;
+
+    use super::*;
+    use crate::backtrace::BACKTRACE_KEY;
+    use crate::diagnostic::LazyDiagnostic;
+    use crate::template::MESSAGE_TEMPLATE_KEY;
+
+    #[test]
+    fn test_ecs_format() {
+        let kvs = [
+            (MESSAGE_TEMPLATE_KEY, "query failed"),
+            ("error", "connection reset"),
+            ("user_id", "42"),
+            ("http.method", "GET"),
+            ("secret_token", "s3cr3t"),
+            (BACKTRACE_KEY, "0: main"),
+        ];
+        let record = Record::builder()
+            .args(format_args!("query failed"))
+            .level(Level::Error)
+            .target("app::db")
+            .module_path(Some("app::db"))
+            .file(Some("src/db.rs"))
+            .line(Some(51))
+            .key_values(&kvs)
+            .build();
+        let diagnostics = [LazyDiagnostic::new("trace_id", || Some("4bf92f".to_string())).into()];
+
+        let bytes = EcsLayout::default().format(&record, &diagnostics).unwrap();
+        let mut event: Map<String, Value> = serde_json::from_slice(&bytes).unwrap();
+        let timestamp = event.remove("@timestamp").unwrap();
+        assert!(timestamp.as_str().unwrap().ends_with('Z'));
+        assert_eq!(
+            Value::Object(event),
+            json!({
+                "ecs": { "version": ECS_VERSION },
+                "error": { "message": "connection reset" },
+                "labels": { "user_id": "42", "http_method": "GET", "token": MASK },
+                "log": {
+                    "level": "error",
+                    "logger": "app::db",
+                    "origin": { "file": { "name": "src/db.rs", "line": 51 } },
+                },
+                "message": "query failed",
+                "trace": { "id": "4bf92f" },
+            })
+        );
+
+        let bytes = EcsLayout::default()
+            .backtrace(true)
+            .format(&record, &diagnostics)
+            .unwrap();
+        let event: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(event["error"]["stack_trace"], "0: main");
+    }
+}
@@ -15,16 +15,26 @@
 use std::borrow::Cow;
 use std::fmt::Arguments;
 
+use anyhow::Context;
 use jiff::tz::TimeZone;
-use jiff::Timestamp;
 use jiff::Zoned;
+use log::Level;
 use log::Record;
+use serde::ser::SerializeMap;
 use serde::Serialize;
+use serde::Serializer;
 use serde_json::Map;
 use serde_json::Value;
 
+use crate::backtrace::is_backtrace_key;
+use crate::clock;
 use crate::diagnostic::Visitor;
 use crate::layout::Layout;
+use crate::pool;
+use crate::secret::secret_key;
+use crate::secret::MASK;
+use crate::template::is_message_template_key;
+use crate::template::template_hash;
 use crate::Diagnostic;
 
 /// A JSON layout for formatting log records.
@@ -39,6 +49,15 @@ use crate::Diagnostic;
 /// {"timestamp":"2024-08-11T22:44:57.172353+08:00","level":"TRACE","module_path":"rolling_file","file":"examples/rolling_file.rs","line":55,"message":"Hello trace!","kvs":{}}
 /// ```
 ///
+/// Records logged with a [message template](crate::template) additionally have the
+/// `message_template` and `message_template_hash` fields. Records with a captured
+/// [backtrace](crate::backtrace) additionally have the `backtrace` field if
+/// [`JsonLayout::backtrace`] is enabled.
+///
+/// Fields can be renamed with [`JsonLayout::rename`], key-values can be moved to the top level
+/// with [`JsonLayout::flatten`], and fields with the same value for every record, e.g., the
+/// service name, can be added with [`JsonLayout::static_field`].
+///
 /// # Examples
 ///
 /// ```
@@ -49,6 +68,83 @@ use crate::Diagnostic;
 #[derive(Default, Debug, Clone)]
 pub struct JsonLayout {
     tz: Option<TimeZone>,
+    backtrace: bool,
+    framing: JsonFraming,
+    pretty: bool,
+    timestamp_format: JsonTimestamp,
+    level_format: JsonLevel,
+    location_key: Option<String>,
+    renames: Vec<(String, String)>,
+    flatten: bool,
+    static_fields: Vec<(String, String)>,
+}
+
+/// How [`JsonLayout`] formats the level of records.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum JsonLevel {
+    /// The name of the level, e.g., `"WARN"`.
+    #[default]
+    Name,
+    /// The [Datadog status][status]: `"error"`, `"warning"`, `"info"`, or `"debug"` for debug
+    /// and trace records.
+    ///
+    /// [status]: https://docs.datadoghq.com/logs/log_configuration/processors/#log-status-remapper
+    Datadog,
+    /// The severity of the Splunk Common Information Model: `"high"` for error, `"medium"` for
+    /// warn, `"low"` for info, and `"informational"` for debug and trace records.
+    Splunk,
+}
+
+impl JsonLevel {
+    fn format(self, level: Level) -> &'static str {
+        match self {
+            JsonLevel::Name => level.as_str(),
+            JsonLevel::Datadog => match level {
+                Level::Error => "error",
+                Level::Warn => "warning",
+                Level::Info => "info",
+                Level::Debug | Level::Trace => "debug",
+            },
+            JsonLevel::Splunk => match level {
+                Level::Error => "high",
+                Level::Warn => "medium",
+                Level::Info => "low",
+                Level::Debug | Level::Trace => "informational",
+            },
+        }
+    }
+}
+
+/// How [`JsonLayout`] formats the timestamp of records.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum JsonTimestamp {
+    /// An RFC 3339 string with microseconds and the time zone offset, e.g.,
+    /// `"2024-08-11T22:44:57.172051+08:00"`.
+    #[default]
+    Rfc3339,
+    /// The number of milliseconds since the Unix epoch.
+    EpochMillis,
+    /// The number of nanoseconds since the Unix epoch.
+    EpochNanos,
+}
+
+/// How [`JsonLayout`] delimits the formatted records.
+///
+/// Built-in appenders such as [`Stdout`](crate::append::Stdout) and the rolling file appender
+/// terminate each record with a newline themselves, so the other framings are meant for custom
+/// appenders that write the formatted bytes as-is, e.g., to a socket or a pipe.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum JsonFraming {
+    /// Emit the JSON object without any delimiter.
+    #[default]
+    None,
+    /// Terminate the JSON object with a newline, i.e., JSON Lines.
+    Newline,
+    /// Prefix the JSON object with its length in bytes as a big-endian `u32`.
+    LengthPrefixed,
 }
 
 impl JsonLayout {
@@ -66,10 +162,170 @@ impl JsonLayout {
         self.tz = Some(tz);
         self
     }
+
+    /// Sets whether to emit the backtrace captured for the record. Default to `false`.
+    ///
+    /// See [`Builder::backtrace_level`](crate::Builder::backtrace_level) to capture backtraces.
+    pub fn backtrace(mut self, backtrace: bool) -> Self {
+        self.backtrace = backtrace;
+        self
+    }
+
+    /// Sets how formatted records are delimited. Default to [`JsonFraming::None`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::layout::JsonFraming;
+    /// use logforth::layout::JsonLayout;
+    ///
+    /// let json_layout = JsonLayout::default().framing(JsonFraming::LengthPrefixed);
+    /// ```
+    pub fn framing(mut self, framing: JsonFraming) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Sets whether to format each record as indented multi-line JSON. Default to `false`.
+    ///
+    /// Pretty records are followed by an empty line to separate them from each other, which is
+    /// handy when reading logs with `less` or `jq -C` during local debugging. Do not enable it
+    /// when shipping logs with line-oriented collectors, which expect one record per line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::layout::JsonLayout;
+    ///
+    /// let json_layout = JsonLayout::default().pretty(true);
+    /// ```
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Sets how timestamps are formatted. Default to [`JsonTimestamp::Rfc3339`].
+    pub fn timestamp_format(mut self, timestamp_format: JsonTimestamp) -> Self {
+        self.timestamp_format = timestamp_format;
+        self
+    }
+
+    /// Sets how levels are formatted. Default to [`JsonLevel::Name`].
+    ///
+    /// Log pipelines such as Datadog and Splunk expect their own severity names; combine with
+    /// [`JsonLayout::rename`] to write them under the expected field, e.g., `status`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::layout::JsonLayout;
+    /// use logforth::layout::JsonLevel;
+    ///
+    /// let json_layout = JsonLayout::default()
+    ///     .level_format(JsonLevel::Datadog)
+    ///     .rename("level", "status")
+    ///     .location_key("source")
+    ///     .flatten(true);
+    /// ```
+    pub fn level_format(mut self, level_format: JsonLevel) -> Self {
+        self.level_format = level_format;
+        self
+    }
+
+    /// Writes the `file` and `line` fields within an object under `key`, e.g.,
+    /// `"source":{"file":"src/main.rs","line":42}`, instead of at the top level.
+    ///
+    /// The key is ignored if another field or a static field has the same name.
+    pub fn location_key(mut self, key: impl Into<String>) -> Self {
+        let key = key.into();
+        if !self.is_taken(&key, None) {
+            self.location_key = Some(key);
+        }
+        self
+    }
+
+    /// Renames a field, e.g., `message` to `msg`.
+    ///
+    /// The fields are `timestamp`, `level`, `target`, `file`, `line`, `message`,
+    /// `message_template`, `message_template_hash`, `backtrace` and `kvs`; renaming other fields
+    /// has no effect. The rename is ignored if another field, the location key or a static field
+    /// has the same name, so that the output has no duplicate fields.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::layout::JsonLayout;
+    ///
+    /// let json_layout = JsonLayout::default()
+    ///     .rename("timestamp", "ts")
+    ///     .rename("message", "msg");
+    /// ```
+    pub fn rename(mut self, field: impl Into<String>, name: impl Into<String>) -> Self {
+        let field = field.into();
+        let name = name.into();
+        if !self.is_taken(&name, Some(&field)) {
+            self.renames.retain(|(f, _)| *f != field);
+            self.renames.push((field, name));
+        }
+        self
+    }
+
+    /// Sets whether to write key-values and diagnostics as top-level fields instead of within
+    /// the `kvs` object. Default to `false`.
+    ///
+    /// Key-values named like another field are kept within the `kvs` object, so that the output
+    /// has no duplicate fields.
+    pub fn flatten(mut self, flatten: bool) -> Self {
+        self.flatten = flatten;
+        self
+    }
+
+    /// Adds a field with the same value for every record, e.g., the service name or the
+    /// environment.
+    ///
+    /// The field is ignored if another field or the location key has the same name, so that the
+    /// output has no duplicate fields. Adding a static field again replaces its value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::layout::JsonLayout;
+    ///
+    /// let json_layout = JsonLayout::default()
+    ///     .static_field("service", "checkout")
+    ///     .static_field("env", "production");
+    /// ```
+    pub fn static_field(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        self.static_fields.retain(|(k, _)| *k != key);
+        if !self.is_taken(&key, None) {
+            self.static_fields.push((key, value.into()));
+        }
+        self
+    }
+
+    fn field_name<'a>(&'a self, field: &'a str) -> &'a str {
+        self.renames
+            .iter()
+            .find(|(f, _)| f == field)
+            .map_or(field, |(_, name)| name)
+    }
+
+    /// Returns whether `name` is used by a field other than `except`, the location key, or a
+    /// static field.
+    fn is_taken(&self, name: &str, except: Option<&str>) -> bool {
+        FIELDS
+            .into_iter()
+            .any(|field| Some(field) != except && self.field_name(field) == name)
+            || self.location_key.as_deref() == Some(name)
+            || self.static_fields.iter().any(|(key, _)| key == name)
+    }
 }
 
 struct KvCollector<'a> {
     kvs: &'a mut Map<String, Value>,
+    message_template: Option<String>,
+    backtrace: Option<String>,
 }
 
 impl<'kvs> log::kv::VisitSource<'kvs> for KvCollector<'_> {
@@ -80,7 +336,13 @@ impl<'kvs> log::kv::VisitSource<'kvs> for KvCollector<'_> {
     ) -> Result<(), log::kv::Error> {
         let k = key.to_string();
         let v = value.to_string();
-        self.kvs.insert(k, v.into());
+        if is_message_template_key(&k) {
+            self.message_template = Some(v);
+        } else if is_backtrace_key(&k) {
+            self.backtrace = Some(v);
+        } else {
+            self.insert(k, v);
+        }
         Ok(())
     }
 }
@@ -93,35 +355,131 @@ impl Visitor for KvCollector<'_> {
     {
         let key = key.into().into_owned();
         let value = value.into().into_owned();
-        self.kvs.insert(key, value.into());
+        self.insert(key, value);
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
-pub(crate) struct RecordLine<'a> {
-    #[serde(serialize_with = "serialize_time_zone")]
+impl KvCollector<'_> {
+    fn insert(&mut self, key: String, value: String) {
+        match secret_key(&key) {
+            Some(key) => self.kvs.insert(key.to_string(), MASK.into()),
+            None => self.kvs.insert(key, value.into()),
+        };
+    }
+}
+
+const FIELDS: [&str; 10] = [
+    "timestamp",
+    "level",
+    "target",
+    "file",
+    "line",
+    "message",
+    "message_template",
+    "message_template_hash",
+    "backtrace",
+    "kvs",
+];
+
+struct RecordLine<'a> {
+    layout: &'a JsonLayout,
     timestamp: Zoned,
     level: &'a str,
     target: &'a str,
     file: &'a str,
     line: u32,
-    #[serde(serialize_with = "serialize_args")]
     message: &'a Arguments<'a>,
+    message_template: Option<String>,
+    message_template_hash: Option<u32>,
+    backtrace: Option<String>,
     kvs: Map<String, Value>,
 }
 
-fn serialize_time_zone<S>(timestamp: &Zoned, serializer: S) -> Result<S::Ok, S::Error>
-where
-    S: serde::Serializer,
-{
-    serializer.collect_str(&format_args!("{timestamp:.6}"))
+impl Serialize for RecordLine<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let layout = self.layout;
+        let name = |field| layout.field_name(field);
+        let mut map = serializer.serialize_map(None)?;
+        match layout.timestamp_format {
+            JsonTimestamp::Rfc3339 => map.serialize_entry(
+                name("timestamp"),
+                &Args(&format_args!("{:.6}", self.timestamp)),
+            )?,
+            JsonTimestamp::EpochMillis => map.serialize_entry(
+                name("timestamp"),
+                &self.timestamp.timestamp().as_millisecond(),
+            )?,
+            JsonTimestamp::EpochNanos => map.serialize_entry(
+                name("timestamp"),
+                &self.timestamp.timestamp().as_nanosecond(),
+            )?,
+        }
+        map.serialize_entry(name("level"), self.level)?;
+        map.serialize_entry(name("target"), self.target)?;
+        match &layout.location_key {
+            Some(key) => map.serialize_entry(key, &Location(self))?,
+            None => {
+                map.serialize_entry(name("file"), self.file)?;
+                map.serialize_entry(name("line"), &self.line)?;
+            }
+        }
+        map.serialize_entry(name("message"), &Args(self.message))?;
+        if let Some(message_template) = &self.message_template {
+            map.serialize_entry(name("message_template"), message_template)?;
+        }
+        if let Some(message_template_hash) = &self.message_template_hash {
+            map.serialize_entry(name("message_template_hash"), message_template_hash)?;
+        }
+        if let Some(backtrace) = &self.backtrace {
+            map.serialize_entry(name("backtrace"), backtrace)?;
+        }
+        for (key, value) in &layout.static_fields {
+            map.serialize_entry(key, value)?;
+        }
+        if !layout.flatten {
+            map.serialize_entry(name("kvs"), &self.kvs)?;
+            return map.end();
+        }
+
+        // key-values named like another field stay within the kvs object
+        let taken = |key: &str| {
+            FIELDS.into_iter().any(|field| name(field) == key)
+                || layout.location_key.as_deref() == Some(key)
+                || layout.static_fields.iter().any(|(k, _)| k == key)
+        };
+        let mut nested = Map::new();
+        for (key, value) in &self.kvs {
+            if taken(key) {
+                nested.insert(key.clone(), value.clone());
+            } else {
+                map.serialize_entry(key, value)?;
+            }
+        }
+        if !nested.is_empty() {
+            map.serialize_entry(name("kvs"), &nested)?;
+        }
+        map.end()
+    }
 }
 
-fn serialize_args<S>(args: &Arguments, serializer: S) -> Result<S::Ok, S::Error>
-where
-    S: serde::Serializer,
-{
-    serializer.collect_str(args)
+struct Location<'a>(&'a RecordLine<'a>);
+
+impl Serialize for Location<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let name = |field| self.0.layout.field_name(field);
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry(name("file"), self.0.file)?;
+        map.serialize_entry(name("line"), &self.0.line)?;
+        map.end()
+    }
+}
+
+struct Args<'a>(&'a Arguments<'a>);
+
+impl Serialize for Args<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self.0)
+    }
 }
 
 impl JsonLayout {
@@ -131,26 +489,63 @@ impl JsonLayout {
         diagnostics: &[Diagnostic],
     ) -> anyhow::Result<Vec<u8>> {
         let mut kvs = Map::new();
-        let mut visitor = KvCollector { kvs: &mut kvs };
+        let mut visitor = KvCollector {
+            kvs: &mut kvs,
+            message_template: None,
+            backtrace: None,
+        };
         record.key_values().visit(&mut visitor)?;
         for d in diagnostics {
             d.visit(&mut visitor);
         }
+        let message_template = visitor.message_template;
+        let message_template_hash = message_template.as_deref().map(template_hash);
+        let backtrace = visitor.backtrace.filter(|_| self.backtrace);
 
         let record_line = RecordLine {
+            layout: self,
             timestamp: match self.tz.clone() {
-                Some(tz) => Timestamp::now().to_zoned(tz),
-                None => Zoned::now(),
+                Some(tz) => clock::now().to_zoned(tz),
+                None => clock::now().to_zoned(TimeZone::system()),
             },
-            level: record.level().as_str(),
+            level: self.level_format.format(record.level()),
             target: record.target(),
             file: record.file().unwrap_or_default(),
             line: record.line().unwrap_or_default(),
             message: record.args(),
+            message_template,
+            message_template_hash,
+            backtrace,
             kvs,
         };
 
-        Ok(serde_json::to_vec(&record_line)?)
+        let write_json = |bytes: &mut Vec<u8>| {
+            if self.pretty {
+                serde_json::to_writer_pretty(&mut *bytes, &record_line)?;
+                // separate multi-line records with an empty line
+                bytes.push(b'\n');
+            } else {
+                serde_json::to_writer(&mut *bytes, &record_line)?;
+            }
+            Ok::<_, serde_json::Error>(())
+        };
+
+        let mut bytes = pool::take();
+        match self.framing {
+            JsonFraming::None => write_json(&mut bytes)?,
+            JsonFraming::Newline => {
+                write_json(&mut bytes)?;
+                bytes.push(b'\n');
+            }
+            JsonFraming::LengthPrefixed => {
+                bytes.extend_from_slice(&[0; 4]);
+                write_json(&mut bytes)?;
+                let len = u32::try_from(bytes.len() - 4)
+                    .context("JSON record is too large for a length-prefixed frame")?;
+                bytes[..4].copy_from_slice(&len.to_be_bytes());
+            }
+        }
+        Ok(bytes)
     }
 }
 
@@ -159,3 +554,154 @@ impl From<JsonLayout> for Layout {
         Layout::Json(layout)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    use super::*;
+
+    #[test]
+    fn test_json_framing() {
+        let record = Record::builder()
+            .args(format_args!("Hello"))
+            .level(Level::Info)
+            .target("app")
+            .build();
+
+        let plain = JsonLayout::default().format(&record, &[]).unwrap();
+        assert_eq!(plain.first(), Some(&b'{'));
+        assert_eq!(plain.last(), Some(&b'}'));
+
+        let lines = JsonLayout::default()
+            .framing(JsonFraming::Newline)
+            .format(&record, &[])
+            .unwrap();
+        assert_eq!(lines.last(), Some(&b'\n'));
+        let event: Map<String, Value> = serde_json::from_slice(&lines).unwrap();
+        assert_eq!(event["message"], "Hello");
+
+        let framed = JsonLayout::default()
+            .framing(JsonFraming::LengthPrefixed)
+            .format(&record, &[])
+            .unwrap();
+        let (len, payload) = framed.split_at(4);
+        assert_eq!(
+            u32::from_be_bytes(len.try_into().unwrap()) as usize,
+            payload.len()
+        );
+        let event: Map<String, Value> = serde_json::from_slice(payload).unwrap();
+        assert_eq!(event["message"], "Hello");
+    }
+
+    #[test]
+    fn test_json_pretty() {
+        let record = Record::builder()
+            .args(format_args!("Hello"))
+            .level(Level::Info)
+            .target("app")
+            .build();
+
+        let pretty = JsonLayout::default()
+            .pretty(true)
+            .format(&record, &[])
+            .unwrap();
+        let pretty = String::from_utf8(pretty).unwrap();
+        assert!(pretty.starts_with("{\n  \"timestamp\": "));
+        assert!(pretty.ends_with("\n}\n"));
+        let event: Map<String, Value> = serde_json::from_str(&pretty).unwrap();
+        assert_eq!(event["message"], "Hello");
+    }
+
+    #[test]
+    fn test_json_fields() {
+        let kvs = [("user", "alice"), ("msg", "shadowed")];
+        let record = Record::builder()
+            .args(format_args!("Hello"))
+            .level(Level::Info)
+            .target("app")
+            .key_values(&kvs)
+            .build();
+
+        let layout = JsonLayout::default()
+            .rename("message", "msg")
+            .rename("timestamp", "ts")
+            .timestamp_format(JsonTimestamp::EpochMillis)
+            .flatten(true)
+            .static_field("service", "checkout");
+        let event: Map<String, Value> =
+            serde_json::from_slice(&layout.format(&record, &[]).unwrap()).unwrap();
+        assert!(event["ts"].is_i64());
+        assert_eq!(event["msg"], "Hello");
+        assert_eq!(event["user"], "alice");
+        assert_eq!(event["service"], "checkout");
+        assert_eq!(event["kvs"], serde_json::json!({ "msg": "shadowed" }));
+        assert!(!event.contains_key("message"));
+        assert!(!event.contains_key("timestamp"));
+
+        let layout = JsonLayout::default().timestamp_format(JsonTimestamp::EpochNanos);
+        let line = String::from_utf8(layout.format(&record, &[]).unwrap()).unwrap();
+        assert!(line.starts_with("{\"timestamp\":1"), "{line}");
+        assert!(line.ends_with(r#""kvs":{"msg":"shadowed","user":"alice"}}"#));
+    }
+
+    #[test]
+    fn test_json_level_and_location() {
+        let record = Record::builder()
+            .args(format_args!("Hello"))
+            .level(Level::Warn)
+            .target("app")
+            .file(Some("src/main.rs"))
+            .line(Some(42))
+            .build();
+
+        let layout = JsonLayout::default()
+            .level_format(JsonLevel::Datadog)
+            .rename("level", "status")
+            .location_key("source");
+        let event: Map<String, Value> =
+            serde_json::from_slice(&layout.format(&record, &[]).unwrap()).unwrap();
+        assert_eq!(event["status"], "warning");
+        assert_eq!(
+            event["source"],
+            serde_json::json!({ "file": "src/main.rs", "line": 42 })
+        );
+        assert!(!event.contains_key("file"));
+        assert!(!event.contains_key("line"));
+
+        let layout = JsonLayout::default().level_format(JsonLevel::Splunk);
+        let event: Map<String, Value> =
+            serde_json::from_slice(&layout.format(&record, &[]).unwrap()).unwrap();
+        assert_eq!(event["level"], "medium");
+        assert_eq!(event["file"], "src/main.rs");
+    }
+
+    #[test]
+    fn test_json_colliding_names() {
+        let record = Record::builder()
+            .args(format_args!("Hello"))
+            .level(Level::Info)
+            .target("app")
+            .build();
+
+        let layout = JsonLayout::default()
+            .rename("message", "level")
+            .static_field("target", "shadowed")
+            .static_field("service", "checkout")
+            .rename("timestamp", "service")
+            .location_key("message")
+            .rename("target", "service_target")
+            .static_field("target", "checkout");
+        let line = String::from_utf8(layout.format(&record, &[]).unwrap()).unwrap();
+        for key in ["level", "message", "service", "target", "timestamp"] {
+            assert_eq!(line.matches(&format!("\"{key}\":")).count(), 1, "{line}");
+        }
+        let event: Map<String, Value> = serde_json::from_str(&line).unwrap();
+        assert_eq!(event["level"], "INFO");
+        assert_eq!(event["message"], "Hello");
+        assert_eq!(event["service_target"], "app");
+        assert_eq!(event["target"], "checkout");
+        assert_eq!(event["service"], "checkout");
+        assert!(event["timestamp"].is_string());
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use jiff::Zoned;
+use log::Level;
+
+/// Names of levels, weekdays and months used by [`TextLayout`](crate::layout::TextLayout).
+///
+/// Names that are not set fall back to the defaults: upper-case level names, and English weekday
+/// and month names in timestamps. Weekday and month names apply to the `%A`, `%a`, `%B`, `%b` and
+/// `%h` directives of a [custom timestamp format](crate::layout::TextLayout::timestamp_format);
+/// directives with flags, e.g., `%^a`, keep the English names.
+///
+/// # Examples
+///
+/// ```
+/// use log::Level;
+/// use logforth::layout::TextLayout;
+/// use logforth::layout::TextLocale;
+///
+/// let locale = TextLocale::new()
+///     .level(Level::Warn, "WRN")
+///     .level(Level::Error, "ERR")
+///     .weekdays_abbr(["lun", "mar", "mer", "jeu", "ven", "sam", "dim"]);
+/// let layout = TextLayout::default()
+///     .locale(locale)
+///     .timestamp_format("%a %d %H:%M:%S");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TextLocale {
+    levels: [Option<String>; 5],
+    weekdays: Option<[String; 7]>,
+    weekdays_abbr: Option<[String; 7]>,
+    months: Option<[String; 12]>,
+    months_abbr: Option<[String; 12]>,
+}
+
+impl TextLocale {
+    /// Creates a new [`TextLocale`] with the default names.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the name of a level.
+    pub fn level(mut self, level: Level, name: impl Into<String>) -> Self {
+        self.levels[level as usize - 1] = Some(name.into());
+        self
+    }
+
+    /// Sets the full weekday names, from Monday to Sunday, for the `%A` directive.
+    pub fn weekdays(mut self, names: [&str; 7]) -> Self {
+        self.weekdays = Some(names.map(str::to_string));
+        self
+    }
+
+    /// Sets the abbreviated weekday names, from Monday to Sunday, for the `%a` directive.
+    pub fn weekdays_abbr(mut self, names: [&str; 7]) -> Self {
+        self.weekdays_abbr = Some(names.map(str::to_string));
+        self
+    }
+
+    /// Sets the full month names, from January to December, for the `%B` directive.
+    pub fn months(mut self, names: [&str; 12]) -> Self {
+        self.months = Some(names.map(str::to_string));
+        self
+    }
+
+    /// Sets the abbreviated month names, from January to December, for the `%b` and `%h`
+    /// directives.
+    pub fn months_abbr(mut self, names: [&str; 12]) -> Self {
+        self.months_abbr = Some(names.map(str::to_string));
+        self
+    }
+
+    pub(crate) fn level_name(&self, level: Level) -> Option<&str> {
+        self.levels[level as usize - 1].as_deref()
+    }
+
+    /// Replaces the name directives of `format` with the names of `time` set in this locale,
+    /// leaving the other directives to `strftime`.
+    pub(crate) fn localize_format(&self, time: &Zoned, format: &str) -> String {
+        let weekday = time.weekday().to_monday_zero_offset() as usize;
+        let month = time.month() as usize - 1;
+
+        let mut localized = String::with_capacity(format.len());
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                localized.push(c);
+                continue;
+            }
+            let Some(directive) = chars.next() else {
+                localized.push(c);
+                break;
+            };
+            let name = match directive {
+                'A' => self.weekdays.as_ref().map(|names| &names[weekday]),
+                'a' => self.weekdays_abbr.as_ref().map(|names| &names[weekday]),
+                'B' => self.months.as_ref().map(|names| &names[month]),
+                'b' | 'h' => self.months_abbr.as_ref().map(|names| &names[month]),
+                _ => None,
+            };
+            match name {
+                Some(name) => localized.push_str(&name.replace('%', "%%")),
+                None => {
+                    localized.push(c);
+                    localized.push(directive);
+                }
+            }
+        }
+        localized
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_localize_format() {
+        let time: Zoned = "2024-08-10T17:12:52+08[+08]".parse().unwrap();
+        let locale = TextLocale::new()
+            .weekdays(["L", "Ma", "Me", "J", "V", "Samedi", "D"])
+            .months_abbr([
+                "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.",
+                "nov.", "déc.",
+            ]);
+
+        let format = locale.localize_format(&time, "%A %d %b %Y, %a %B %%a");
+        assert_eq!(format, "Samedi %d août %Y, %a %B %%a");
+        assert_eq!(
+            time.strftime(&format).to_string(),
+            "Samedi 10 août 2024, Sat August %a"
+        );
+        assert_eq!(TextLocale::new().localize_format(&time, "%A"), "%A");
+    }
+
+    #[test]
+    fn test_level_name() {
+        let locale = TextLocale::new().level(Level::Warn, "WRN");
+        assert_eq!(locale.level_name(Level::Warn), Some("WRN"));
+        assert_eq!(locale.level_name(Level::Error), None);
+    }
+}
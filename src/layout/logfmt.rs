@@ -0,0 +1,290 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+use std::fmt::Write;
+
+use jiff::tz::TimeZone;
+
+use crate::attachment::is_attachment_key;
+use crate::backtrace::is_backtrace_key;
+use crate::clock;
+use crate::diagnostic::Visitor;
+use crate::layout::Layout;
+use crate::pool;
+use crate::secret::secret_key;
+use crate::secret::MASK;
+use crate::template::is_message_template_key;
+use crate::Diagnostic;
+
+/// A layout that formats log records as [logfmt](https://brandur.org/logfmt) lines.
+///
+/// Output format:
+///
+/// ```text
+/// time=2024-08-11T22:44:57.172105+08:00 level=info target=app msg="User logged in" user_id=42
+/// time=2024-08-11T22:44:57.172219+08:00 level=warn target=app msg="Disk almost full" free=3%
+/// ```
+///
+/// Key-values of the record follow the message, then those of the diagnostics, which can be
+/// told apart with [`LogfmtLayout::diagnostic_prefix`]. Characters not allowed in keys
+/// (spaces, `=`, `"` and control characters) are replaced with `_`.
+///
+/// By default, values are quoted when they are empty or contain spaces, `=` or `"`, and quotes,
+/// backslashes and line breaks are escaped in quoted values. See [`LogfmtLayout::strict`] to
+/// follow the rules of the [go-logfmt](https://github.com/go-logfmt/logfmt) encoder instead.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::layout::LogfmtLayout;
+///
+/// let logfmt_layout = LogfmtLayout::default()
+///     .strict(true)
+///     .diagnostic_prefix("ctx_");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LogfmtLayout {
+    tz: Option<TimeZone>,
+    strict: bool,
+    diagnostic_prefix: Option<String>,
+}
+
+impl LogfmtLayout {
+    /// Sets the timezone for timestamps.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jiff::tz::TimeZone;
+    /// use logforth::layout::LogfmtLayout;
+    ///
+    /// let logfmt_layout = LogfmtLayout::default().timezone(TimeZone::UTC);
+    /// ```
+    pub fn timezone(mut self, tz: TimeZone) -> Self {
+        self.tz = Some(tz);
+        self
+    }
+
+    /// Sets whether to encode values exactly like the go-logfmt encoder. Default to `false`.
+    ///
+    /// In strict mode:
+    ///
+    /// - Values are quoted when they contain any character up to and including the space, `=`, `"`,
+    ///   or the replacement character `U+FFFD`, and the value `null` is quoted.
+    /// - Empty values are written without quotes, e.g., `key=`.
+    /// - In quoted values, `\`, `"`, `\n`, `\r` and `\t` are escaped with a backslash, and the
+    ///   other control characters as `\u00XX`.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets a prefix for the keys of the diagnostics, e.g., `ctx_`, so that they do not collide
+    /// with the key-values of records. Default to no prefix.
+    pub fn diagnostic_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.diagnostic_prefix = Some(prefix.into());
+        self
+    }
+}
+
+impl LogfmtLayout {
+    pub(crate) fn format(
+        &self,
+        record: &log::Record,
+        diagnostics: &[Diagnostic],
+    ) -> anyhow::Result<Vec<u8>> {
+        let time = match self.tz.clone() {
+            Some(tz) => clock::now().to_zoned(tz),
+            None => clock::now().to_zoned(TimeZone::system()),
+        };
+
+        let mut writer = KvWriter {
+            text: pool::take_string(),
+            strict: self.strict,
+            prefix: "",
+        };
+        write!(&mut writer.text, "time={time:.6}")?;
+        writer.write_pair("level", &record.level().as_str().to_lowercase());
+        writer.write_pair("target", record.target());
+        writer.write_pair("msg", &record.args().to_string());
+        record.key_values().visit(&mut writer)?;
+        writer.prefix = self.diagnostic_prefix.as_deref().unwrap_or_default();
+        for d in diagnostics {
+            d.visit(&mut writer);
+        }
+        Ok(writer.text.into_bytes())
+    }
+}
+
+impl From<LogfmtLayout> for Layout {
+    fn from(layout: LogfmtLayout) -> Self {
+        Layout::Logfmt(layout)
+    }
+}
+
+struct KvWriter<'a> {
+    text: String,
+    strict: bool,
+    // prepended to the keys, e.g., of the diagnostics
+    prefix: &'a str,
+}
+
+impl KvWriter<'_> {
+    fn write_pair(&mut self, key: &str, value: &str) {
+        self.text.push(' ');
+        for c in self.prefix.chars().chain(key.chars()) {
+            self.text.push(if is_invalid_key_char(c) { '_' } else { c });
+        }
+        if self.prefix.is_empty() && key.is_empty() {
+            self.text.push('_');
+        }
+        self.text.push('=');
+        if self.needs_quotes(value) {
+            self.write_quoted(value);
+        } else {
+            self.text.push_str(value);
+        }
+    }
+
+    fn needs_quotes(&self, value: &str) -> bool {
+        if self.strict {
+            value == "null" || value.chars().any(|c| c <= ' ' || is_invalid_key_char(c))
+        } else {
+            value.is_empty()
+                || value
+                    .chars()
+                    .any(|c| c == ' ' || c == '=' || c == '"' || c.is_control())
+        }
+    }
+
+    fn write_quoted(&mut self, value: &str) {
+        self.text.push('"');
+        for c in value.chars() {
+            match c {
+                '\\' | '"' => {
+                    self.text.push('\\');
+                    self.text.push(c);
+                }
+                '\n' => self.text.push_str("\\n"),
+                '\r' => self.text.push_str("\\r"),
+                '\t' => self.text.push_str("\\t"),
+                c if c < ' ' && self.strict => {
+                    write!(&mut self.text, "\\u{:04x}", c as u32).unwrap();
+                }
+                c if c.is_control() => self.text.extend(c.escape_default()),
+                c => self.text.push(c),
+            }
+        }
+        self.text.push('"');
+    }
+}
+
+fn is_invalid_key_char(c: char) -> bool {
+    c <= ' ' || c == '=' || c == '"' || c == char::REPLACEMENT_CHARACTER
+}
+
+impl<'kvs> log::kv::VisitSource<'kvs> for KvWriter<'_> {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        let key = key.as_str();
+        // the rendered message is already written, and binary attachments and backtraces do not
+        // fit on a line
+        if is_message_template_key(key) || is_attachment_key(key) || is_backtrace_key(key) {
+            return Ok(());
+        }
+        match secret_key(key) {
+            Some(key) => self.write_pair(key, MASK),
+            None => self.write_pair(key, &value.to_string()),
+        }
+        Ok(())
+    }
+}
+
+impl Visitor for KvWriter<'_> {
+    fn visit<'k, 'v, K, V>(&mut self, key: K, value: V)
+    where
+        K: Into<Cow<'k, str>>,
+        V: Into<Cow<'v, str>>,
+    {
+        let key = key.into();
+        match secret_key(&key) {
+            Some(key) => self.write_pair(key, MASK),
+            None => self.write_pair(&key, &value.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    use super::*;
+    use crate::diagnostic::LazyDiagnostic;
+
+    fn format(layout: LogfmtLayout, kvs: &[(&str, &str)]) -> String {
+        let record = log::Record::builder()
+            .args(format_args!("hello world"))
+            .level(Level::Info)
+            .target("app")
+            .key_values(&kvs)
+            .build();
+        let diagnostic = LazyDiagnostic::new("user", || Some("alice".to_string()));
+        let bytes = layout
+            .timezone(TimeZone::UTC)
+            .format(&record, &[diagnostic.into()])
+            .unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        // skip the timestamp
+        text.split_once(' ').unwrap().1.to_owned()
+    }
+
+    #[test]
+    fn test_logfmt_format() {
+        let kvs = [
+            ("user", "bob"),
+            ("path", r"C:\tmp"),
+            ("quote", "say \"hi\"\n"),
+            ("empty", ""),
+            ("bad key", "null"),
+        ];
+        assert_eq!(
+            format(LogfmtLayout::default().diagnostic_prefix("ctx_"), &kvs),
+            r#"level=info target=app msg="hello world" user=bob path=C:\tmp quote="say \"hi\"\n" empty="" bad_key=null ctx_user=alice"#
+        );
+        assert_eq!(
+            format(LogfmtLayout::default(), &[("bell", "\u{7}")]),
+            r#"level=info target=app msg="hello world" bell="\u{7}" user=alice"#
+        );
+    }
+
+    #[test]
+    fn test_logfmt_strict() {
+        let kvs = [
+            ("path", r"C:\tmp"),
+            ("spaced", r"C:\my tmp"),
+            ("empty", ""),
+            ("null", "null"),
+            ("bell", "\u{7}"),
+            ("unicode", "héllo"),
+        ];
+        assert_eq!(
+            format(LogfmtLayout::default().strict(true), &kvs),
+            r#"level=info target=app msg="hello world" path=C:\tmp spaced="C:\\my tmp" empty= null="null" bell="\u0007" unicode=héllo user=alice"#
+        );
+    }
+}
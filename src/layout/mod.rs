@@ -14,17 +14,46 @@
 
 //! Layouts for formatting log records.
 
+#[cfg(feature = "json")]
+pub use clef::ClefLayout;
 pub use custom::CustomLayout;
 #[cfg(feature = "json")]
+pub use ecs::EcsLayout;
+#[cfg(feature = "json")]
+pub use json::JsonFraming;
+#[cfg(feature = "json")]
 pub use json::JsonLayout;
+#[cfg(feature = "json")]
+pub use json::JsonLevel;
+#[cfg(feature = "json")]
+pub use json::JsonTimestamp;
+pub use locale::TextLocale;
+pub use logfmt::LogfmtLayout;
+pub use pattern::TextPattern;
+pub use select::SelectLayout;
+pub use switchable::LayoutHandle;
+pub use switchable::SwitchableLayout;
+pub use text::KvStyle;
 pub use text::TextLayout;
+pub use truncate::truncate_str;
+pub use truncate::truncate_utf8;
 
 use crate::Diagnostic;
 
+#[cfg(feature = "json")]
+mod clef;
 mod custom;
 #[cfg(feature = "json")]
+mod ecs;
+#[cfg(feature = "json")]
 mod json;
+mod locale;
+mod logfmt;
+mod pattern;
+mod select;
+mod switchable;
 mod text;
+mod truncate;
 
 /// Represents a layout for formatting log records.
 #[derive(Debug)]
@@ -33,6 +62,13 @@ pub enum Layout {
     Text(TextLayout),
     #[cfg(feature = "json")]
     Json(JsonLayout),
+    #[cfg(feature = "json")]
+    Clef(ClefLayout),
+    #[cfg(feature = "json")]
+    Ecs(EcsLayout),
+    Logfmt(LogfmtLayout),
+    Select(SelectLayout),
+    Switchable(SwitchableLayout),
 }
 
 impl Layout {
@@ -46,6 +82,13 @@ impl Layout {
             Layout::Text(layout) => layout.format(record, diagnostics),
             #[cfg(feature = "json")]
             Layout::Json(layout) => layout.format(record, diagnostics),
+            #[cfg(feature = "json")]
+            Layout::Clef(layout) => layout.format(record, diagnostics),
+            #[cfg(feature = "json")]
+            Layout::Ecs(layout) => layout.format(record, diagnostics),
+            Layout::Logfmt(layout) => layout.format(record, diagnostics),
+            Layout::Select(layout) => layout.format(record, diagnostics),
+            Layout::Switchable(layout) => layout.format(record, diagnostics),
         }
     }
 }
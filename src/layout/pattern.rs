@@ -0,0 +1,396 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::fmt::Write;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::str::FromStr;
+
+use anyhow::Context;
+use log::Record;
+
+use crate::diagnostic::Visitor;
+use crate::secret::secret_key;
+use crate::secret::MASK;
+use crate::Diagnostic;
+
+/// The pattern of the lines written by [`TextLayout`](crate::layout::TextLayout).
+///
+/// A pattern is text with placeholders of the record fields in braces, e.g.,
+/// `{timestamp} [{level:>5}] {target} {file}:{line} {message} {kv}`. The fields are:
+///
+/// - `timestamp`: The timestamp, in the format of the layout.
+/// - `level`: The level, colored unless colors are disabled.
+/// - `target`, `module`: The target and the module path.
+/// - `file`, `path`, `line`: The file name, the full file path and the line.
+/// - `message`: The message.
+/// - `kv`: The key-values and diagnostics, as space-separated `key=value` pairs.
+/// - `kv.NAME`: The value of the key-value or diagnostic `NAME`, e.g., `kv.trace_id`.
+///
+/// A field can be aligned to a width: `{level:>5}` aligns it right, `{target:<20}` left and
+/// `{line:^4}` centered; the default is left. The width is at most `65535`.
+///
+/// A field that the record doesn't have is written empty. Wrap text in `{?` and `}` to write it
+/// only if the record has every field within, e.g., `{? ({file}:{line})}` or `{? {kv}}`. Write
+/// `{{` and `}}` for literal braces; a literal `}` cannot be written within an optional group.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::layout::TextLayout;
+/// use logforth::layout::TextPattern;
+///
+/// let pattern: TextPattern =
+///     "{timestamp} [{level:>5}] {target}{? ({file}:{line})} {message}{? {kv}}"
+///         .parse()
+///         .unwrap();
+/// let layout = TextLayout::default().pattern(pattern);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextPattern {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field(Field, Option<(Align, usize)>),
+    Optional(Vec<Part>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Field {
+    Timestamp,
+    Level,
+    Target,
+    Module,
+    File,
+    Path,
+    Line,
+    Message,
+    Kvs,
+    Kv(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+impl FromStr for TextPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = parse_parts(&mut s.chars().peekable(), false)
+            .with_context(|| format!("invalid text pattern `{s}`"))?;
+        Ok(Self { parts })
+    }
+}
+
+fn parse_parts(chars: &mut Peekable<Chars>, optional: bool) -> anyhow::Result<Vec<Part>> {
+    let mut parts = vec![];
+    let mut literal = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.next_if_eq(&'{').is_some() => literal.push('{'),
+            '{' => {
+                if !literal.is_empty() {
+                    parts.push(Part::Literal(std::mem::take(&mut literal)));
+                }
+                if chars.next_if_eq(&'?').is_some() {
+                    parts.push(Part::Optional(parse_parts(chars, true)?));
+                    continue;
+                }
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => anyhow::bail!("unclosed placeholder `{{{placeholder}`"),
+                    }
+                }
+                parts.push(parse_placeholder(&placeholder)?);
+            }
+            '}' if optional => {
+                if !literal.is_empty() {
+                    parts.push(Part::Literal(literal));
+                }
+                return Ok(parts);
+            }
+            '}' if chars.next_if_eq(&'}').is_some() => literal.push('}'),
+            '}' => anyhow::bail!("unmatched `}}`; write `}}}}` for a literal `}}`"),
+            c => literal.push(c),
+        }
+    }
+
+    if optional {
+        anyhow::bail!("unclosed optional group `{{?`");
+    }
+    if !literal.is_empty() {
+        parts.push(Part::Literal(literal));
+    }
+    Ok(parts)
+}
+
+fn parse_placeholder(placeholder: &str) -> anyhow::Result<Part> {
+    let (name, spec) = match placeholder.split_once(':') {
+        Some((name, spec)) => (name, Some(spec)),
+        None => (placeholder, None),
+    };
+    let field = match name {
+        "timestamp" => Field::Timestamp,
+        "level" => Field::Level,
+        "target" => Field::Target,
+        "module" => Field::Module,
+        "file" => Field::File,
+        "path" => Field::Path,
+        "line" => Field::Line,
+        "message" => Field::Message,
+        "kv" => Field::Kvs,
+        _ => match name.strip_prefix("kv.") {
+            Some(key) if !key.is_empty() => Field::Kv(key.to_string()),
+            _ => anyhow::bail!("unknown field `{name}`"),
+        },
+    };
+    let align = match spec {
+        None => None,
+        Some(spec) => {
+            let (align, width) = match spec.chars().next() {
+                Some('<') => (Align::Left, &spec[1..]),
+                Some('>') => (Align::Right, &spec[1..]),
+                Some('^') => (Align::Center, &spec[1..]),
+                _ => (Align::Left, spec),
+            };
+            // the formatting machinery panics on widths beyond `u16::MAX`
+            let width = width
+                .parse::<u16>()
+                .with_context(|| format!("invalid alignment `{spec}` of field `{name}`"))?;
+            Some((align, usize::from(width)))
+        }
+    };
+    Ok(Part::Field(field, align))
+}
+
+/// The fields of a record rendered by [`TextPattern::render`].
+pub(crate) struct PatternFields<'a> {
+    pub(crate) record: &'a Record<'a>,
+    pub(crate) diagnostics: &'a [Diagnostic],
+    pub(crate) timestamp: &'a str,
+    pub(crate) level: &'a dyn Display,
+    pub(crate) file: &'a str,
+    pub(crate) kvs: &'a str,
+}
+
+impl TextPattern {
+    pub(crate) fn render(&self, text: &mut String, fields: &PatternFields) -> std::fmt::Result {
+        render_parts(&self.parts, text, fields).map(|_| ())
+    }
+}
+
+/// Renders the parts and returns whether the record has every field within.
+fn render_parts(
+    parts: &[Part],
+    text: &mut String,
+    fields: &PatternFields,
+) -> Result<bool, std::fmt::Error> {
+    let mut complete = true;
+    for part in parts {
+        match part {
+            Part::Literal(literal) => text.push_str(literal),
+            Part::Field(Field::Level, align) => write_aligned(text, fields.level, *align)?,
+            Part::Field(field, align) => match field_value(field, fields) {
+                Some(value) if !value.is_empty() => write_aligned(text, &value, *align)?,
+                _ => {
+                    complete = false;
+                    write_aligned(text, &"", *align)?;
+                }
+            },
+            Part::Optional(parts) => {
+                let len = text.len();
+                if !render_parts(parts, text, fields)? {
+                    text.truncate(len);
+                }
+            }
+        }
+    }
+    Ok(complete)
+}
+
+fn field_value<'a>(field: &'a Field, fields: &PatternFields<'a>) -> Option<Cow<'a, str>> {
+    let record = fields.record;
+    match field {
+        Field::Timestamp => Some(Cow::Borrowed(fields.timestamp)),
+        Field::Level => Some(Cow::Owned(fields.level.to_string())),
+        Field::Target => Some(Cow::Borrowed(record.target())),
+        Field::Module => record.module_path().map(Cow::Borrowed),
+        Field::File => Some(Cow::Borrowed(fields.file)),
+        Field::Path => record.file().map(Cow::Borrowed),
+        Field::Line => record.line().map(|line| Cow::Owned(line.to_string())),
+        Field::Message => Some(match record.args().as_str() {
+            Some(message) => Cow::Borrowed(message),
+            None => Cow::Owned(record.args().to_string()),
+        }),
+        Field::Kvs => Some(Cow::Borrowed(fields.kvs)),
+        Field::Kv(key) => {
+            let mut lookup = KvLookup { key, value: None };
+            record.key_values().visit(&mut lookup).ok()?;
+            for d in fields.diagnostics {
+                d.visit(&mut lookup);
+            }
+            lookup.value.map(Cow::Owned)
+        }
+    }
+}
+
+fn write_aligned(
+    text: &mut String,
+    value: &dyn Display,
+    align: Option<(Align, usize)>,
+) -> std::fmt::Result {
+    match align {
+        None => write!(text, "{value}"),
+        Some((Align::Left, width)) => write!(text, "{value:<width$}"),
+        Some((Align::Right, width)) => write!(text, "{value:>width$}"),
+        Some((Align::Center, width)) => write!(text, "{value:^width$}"),
+    }
+}
+
+/// Finds the value of a key-value or diagnostic, masking secrets.
+struct KvLookup<'a> {
+    key: &'a str,
+    value: Option<String>,
+}
+
+impl KvLookup<'_> {
+    fn visit_str(&mut self, key: &str, value: impl Display) {
+        if self.value.is_some() {
+            return;
+        }
+        match secret_key(key) {
+            Some(key) if key == self.key => self.value = Some(MASK.to_string()),
+            None if key == self.key => self.value = Some(value.to_string()),
+            _ => {}
+        }
+    }
+}
+
+impl<'kvs> log::kv::VisitSource<'kvs> for KvLookup<'_> {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        self.visit_str(key.as_str(), value);
+        Ok(())
+    }
+}
+
+impl Visitor for KvLookup<'_> {
+    fn visit<'k, 'v, K, V>(&mut self, key: K, value: V)
+    where
+        K: Into<Cow<'k, str>>,
+        V: Into<Cow<'v, str>>,
+    {
+        self.visit_str(&key.into(), value.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    use super::*;
+    use crate::diagnostic::LazyDiagnostic;
+    use crate::layout::TextLayout;
+
+    fn format(pattern: &str, record: &Record, diagnostics: &[Diagnostic]) -> String {
+        let layout = TextLayout::default()
+            .no_color()
+            .pattern(pattern.parse().unwrap());
+        String::from_utf8(layout.format(record, diagnostics).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_pattern() {
+        let kvs = [("user", "alice"), ("secret_token", "hunter2")];
+        let record = Record::builder()
+            .args(format_args!("hello"))
+            .level(Level::Warn)
+            .target("app::db")
+            .file(Some("src/db/mod.rs"))
+            .line(Some(42))
+            .key_values(&kvs)
+            .build();
+        let diagnostics = [LazyDiagnostic::new("trace_id", || Some("abc".to_string())).into()];
+
+        assert_eq!(
+            format(
+                "[{level:>5}] {target:<8}|{file}:{line:^4}| {message} {kv}",
+                &record,
+                &diagnostics
+            ),
+            format!("[ WARN] app::db |mod.rs: 42 | hello user=alice token={MASK} trace_id=abc")
+        );
+        assert_eq!(
+            format(
+                "{path} {kv.trace_id} {kv.token} {{{kv.user}}}",
+                &record,
+                &[]
+            ),
+            format!("src/db/mod.rs  {MASK} {{alice}}")
+        );
+    }
+
+    #[test]
+    fn test_pattern_optional() {
+        let pattern = "{level} {message}{? ({file}:{line})}{? {kv}}";
+        let record = Record::builder()
+            .args(format_args!("hello"))
+            .level(Level::Info)
+            .build();
+        assert_eq!(format(pattern, &record, &[]), "INFO hello");
+
+        let kvs = [("user", "alice")];
+        let record = Record::builder()
+            .args(format_args!("hello"))
+            .level(Level::Info)
+            .file(Some("main.rs"))
+            .line(Some(7))
+            .key_values(&kvs)
+            .build();
+        assert_eq!(
+            format(pattern, &record, &[]),
+            "INFO hello (main.rs:7) user=alice"
+        );
+    }
+
+    #[test]
+    fn test_pattern_errors() {
+        for pattern in [
+            "{level",
+            "{lvl}",
+            "{kv.}",
+            "{level:>x}",
+            "{level:>70000} {message}",
+            "{message}}",
+            "{? {file}",
+        ] {
+            assert!(pattern.parse::<TextPattern>().is_err(), "{pattern}");
+        }
+    }
+}
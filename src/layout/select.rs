@@ -0,0 +1,146 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::Debug;
+use std::fmt::Formatter;
+
+use log::Level;
+use log::Record;
+
+use crate::layout::Layout;
+use crate::Diagnostic;
+
+type PredicateFunction = dyn Fn(&Record) -> bool + Send + Sync + 'static;
+
+/// A layout that picks one of two layouts for each record.
+///
+/// For example, format errors verbosely with their key-values and diagnostics, and other records
+/// compactly:
+///
+/// ```
+/// use log::Level;
+/// use logforth::append;
+/// use logforth::layout::CustomLayout;
+/// use logforth::layout::SelectLayout;
+/// use logforth::layout::TextLayout;
+///
+/// let compact = CustomLayout::new(|record, _| {
+///     Ok(format!("{} {}", record.level(), record.args()).into_bytes())
+/// });
+/// let layout = SelectLayout::by_level(Level::Error, TextLayout::default(), compact);
+///
+/// logforth::builder()
+///     .dispatch(|d| d.append(append::Stdout::default().with_layout(layout)))
+///     .apply();
+/// ```
+pub struct SelectLayout {
+    predicate: Box<PredicateFunction>,
+    selected: Box<Layout>,
+    otherwise: Box<Layout>,
+}
+
+impl Debug for SelectLayout {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.debug_struct("SelectLayout")
+            .field("selected", &self.selected)
+            .field("otherwise", &self.otherwise)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SelectLayout {
+    /// Creates a new [`SelectLayout`] formatting the records for which `predicate` returns `true`
+    /// with `selected`, and other records with `otherwise`.
+    pub fn new(
+        predicate: impl Fn(&Record) -> bool + Send + Sync + 'static,
+        selected: impl Into<Layout>,
+        otherwise: impl Into<Layout>,
+    ) -> Self {
+        Self {
+            predicate: Box::new(predicate),
+            selected: Box::new(selected.into()),
+            otherwise: Box::new(otherwise.into()),
+        }
+    }
+
+    /// Creates a new [`SelectLayout`] formatting the records at or above `level` with
+    /// `selected`, e.g., `Warn` selects `Warn` and `Error` records, and other records with
+    /// `otherwise`.
+    pub fn by_level(
+        level: Level,
+        selected: impl Into<Layout>,
+        otherwise: impl Into<Layout>,
+    ) -> Self {
+        Self::new(move |record| record.level() <= level, selected, otherwise)
+    }
+
+    pub(crate) fn format(
+        &self,
+        record: &Record,
+        diagnostics: &[Diagnostic],
+    ) -> anyhow::Result<Vec<u8>> {
+        if (self.predicate)(record) {
+            self.selected.format(record, diagnostics)
+        } else {
+            self.otherwise.format(record, diagnostics)
+        }
+    }
+}
+
+impl From<SelectLayout> for Layout {
+    fn from(layout: SelectLayout) -> Self {
+        Layout::Select(layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::CustomLayout;
+
+    fn constant(text: &'static str) -> CustomLayout {
+        CustomLayout::new(move |_, _| Ok(text.as_bytes().to_vec()))
+    }
+
+    #[test]
+    fn test_select_by_level() {
+        let layout = SelectLayout::by_level(Level::Warn, constant("verbose"), constant("compact"));
+        let format = |level: Level| {
+            let record = Record::builder()
+                .args(format_args!("hello"))
+                .level(level)
+                .build();
+            layout.format(&record, &[]).unwrap()
+        };
+        assert_eq!(format(Level::Error), b"verbose");
+        assert_eq!(format(Level::Warn), b"verbose");
+        assert_eq!(format(Level::Info), b"compact");
+    }
+
+    #[test]
+    fn test_select_nested() {
+        let inner = SelectLayout::new(
+            |record| record.target() == "db",
+            constant("db"),
+            constant("other"),
+        );
+        let layout = SelectLayout::by_level(Level::Error, constant("error"), inner);
+        let record = Record::builder()
+            .args(format_args!("hello"))
+            .level(Level::Info)
+            .target("db")
+            .build();
+        assert_eq!(layout.format(&record, &[]).unwrap(), b"db");
+    }
+}
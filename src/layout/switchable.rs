@@ -0,0 +1,117 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::sync::RwLock;
+
+use crate::layout::Layout;
+use crate::Diagnostic;
+
+/// A layout that can be swapped at runtime.
+///
+/// Use [`SwitchableLayout::handle`] to obtain a [`LayoutHandle`] before passing the layout to an
+/// appender. Any appender that accepts a [`Layout`] can then have its output format changed
+/// (e.g., from text to JSON during an incident capture) without rebuilding the logger.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(not(feature = "json"))] fn main() {}
+/// # #[cfg(feature = "json")] fn main() {
+/// use logforth::append;
+/// use logforth::layout::JsonLayout;
+/// use logforth::layout::SwitchableLayout;
+/// use logforth::layout::TextLayout;
+///
+/// let layout = SwitchableLayout::new(TextLayout::default());
+/// let handle = layout.handle();
+///
+/// logforth::builder()
+///     .dispatch(|d| d.append(append::Stdout::default().with_layout(layout)))
+///     .apply();
+///
+/// handle.set(JsonLayout::default());
+/// log::info!("this record is formatted as JSON");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SwitchableLayout {
+    layout: Arc<RwLock<Layout>>,
+}
+
+impl SwitchableLayout {
+    /// Creates a new [`SwitchableLayout`] with the given initial layout.
+    pub fn new(layout: impl Into<Layout>) -> Self {
+        Self {
+            layout: Arc::new(RwLock::new(layout.into())),
+        }
+    }
+
+    /// Returns a handle to swap the layout.
+    pub fn handle(&self) -> LayoutHandle {
+        LayoutHandle {
+            layout: self.layout.clone(),
+        }
+    }
+
+    pub(crate) fn format(
+        &self,
+        record: &log::Record,
+        diagnostics: &[Diagnostic],
+    ) -> anyhow::Result<Vec<u8>> {
+        let layout = self.layout.read().unwrap_or_else(|e| e.into_inner());
+        layout.format(record, diagnostics)
+    }
+}
+
+impl From<SwitchableLayout> for Layout {
+    fn from(layout: SwitchableLayout) -> Self {
+        Layout::Switchable(layout)
+    }
+}
+
+/// A handle to swap the layout of a [`SwitchableLayout`].
+#[derive(Debug, Clone)]
+pub struct LayoutHandle {
+    layout: Arc<RwLock<Layout>>,
+}
+
+impl LayoutHandle {
+    /// Replaces the layout. Records formatted afterwards use the new layout.
+    pub fn set(&self, layout: impl Into<Layout>) {
+        let layout = layout.into();
+        *self.layout.write().unwrap_or_else(|e| e.into_inner()) = layout;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::CustomLayout;
+
+    fn constant(text: &'static str) -> CustomLayout {
+        CustomLayout::new(move |_, _| Ok(text.as_bytes().to_vec()))
+    }
+
+    #[test]
+    fn test_set_swaps_layout() {
+        let layout = SwitchableLayout::new(constant("before"));
+        let handle = layout.handle();
+        let record = log::Record::builder().args(format_args!("hello")).build();
+
+        assert_eq!(layout.format(&record, &[]).unwrap(), b"before");
+        handle.set(constant("after"));
+        assert_eq!(layout.format(&record, &[]).unwrap(), b"after");
+    }
+}
@@ -16,12 +16,20 @@ use std::borrow::Cow;
 use std::fmt::Write;
 
 use jiff::tz::TimeZone;
-use jiff::Timestamp;
-use jiff::Zoned;
 use log::Level;
 
+use crate::attachment::is_attachment_key;
+use crate::backtrace::is_backtrace_key;
+use crate::clock;
 use crate::diagnostic::Visitor;
+use crate::layout::pattern::PatternFields;
 use crate::layout::Layout;
+use crate::layout::TextLocale;
+use crate::layout::TextPattern;
+use crate::pool;
+use crate::secret::secret_key;
+use crate::secret::MASK;
+use crate::template::is_message_template_key;
 use crate::Diagnostic;
 
 #[cfg(feature = "colored")]
@@ -81,7 +89,11 @@ mod colored {
         }
 
         pub(crate) fn format_record_level(&self, level: Level) -> ColoredString {
-            self.colors.colorize_record_level(self.no_color, level)
+            let mut colored = self.colors.colorize_record_level(self.no_color, level);
+            if let Some(name) = self.level_name(level) {
+                colored.input = name.to_string();
+            }
+            colored
         }
     }
 }
@@ -107,6 +119,13 @@ mod colored {
 /// You can customize the timezone of the timestamp by setting the `tz` field with a [`TimeZone`]
 /// instance. Otherwise, the system timezone is used.
 ///
+/// You can customize the format of the timestamp with [`TextLayout::timestamp_format`], and the
+/// names of levels, weekdays and months with a [`TextLocale`].
+///
+/// You can hide the timestamp, the target and the location with [`TextLayout::no_timestamp`],
+/// [`TextLayout::no_target`] and [`TextLayout::no_location`], choose how key-values are written
+/// with a [`KvStyle`], or rearrange the fields with a [`TextPattern`].
+///
 /// # Examples
 ///
 /// ```
@@ -120,6 +139,27 @@ pub struct TextLayout {
     colors: crate::color::LevelColor,
     no_color: bool,
     tz: Option<TimeZone>,
+    timestamp_format: Option<String>,
+    locale: Option<Box<TextLocale>>,
+    pattern: Option<TextPattern>,
+    no_timestamp: bool,
+    no_target: bool,
+    no_location: bool,
+    kv_style: KvStyle,
+    backtrace: bool,
+}
+
+/// How [`TextLayout`] writes the key-values and diagnostics of a record.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KvStyle {
+    /// Write `key=value` pairs.
+    #[default]
+    Inline,
+    /// Write `[key=value]` pairs.
+    Bracketed,
+    /// Don't write key-values.
+    Hidden,
 }
 
 impl TextLayout {
@@ -129,6 +169,44 @@ impl TextLayout {
         self
     }
 
+    /// Hides the timestamp.
+    pub fn no_timestamp(mut self) -> Self {
+        self.no_timestamp = true;
+        self
+    }
+
+    /// Hides the target.
+    pub fn no_target(mut self) -> Self {
+        self.no_target = true;
+        self
+    }
+
+    /// Hides the file and the line, which are mostly noise on the console during development.
+    pub fn no_location(mut self) -> Self {
+        self.no_location = true;
+        self
+    }
+
+    /// Sets how key-values and diagnostics are written. Default to [`KvStyle::Inline`].
+    ///
+    /// This also applies to the `{kv}` field of a [pattern](TextLayout::pattern).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::layout::KvStyle;
+    /// use logforth::layout::TextLayout;
+    ///
+    /// let text_layout = TextLayout::default()
+    ///     .no_timestamp()
+    ///     .no_location()
+    ///     .kv_style(KvStyle::Bracketed);
+    /// ```
+    pub fn kv_style(mut self, kv_style: KvStyle) -> Self {
+        self.kv_style = kv_style;
+        self
+    }
+
     /// Sets the timezone for timestamps.
     ///
     /// # Examples
@@ -144,9 +222,70 @@ impl TextLayout {
         self
     }
 
+    /// Sets whether to render the backtrace captured for the record on the following lines.
+    /// Default to `false`.
+    ///
+    /// See [`Builder::backtrace_level`](crate::Builder::backtrace_level) to capture backtraces.
+    pub fn backtrace(mut self, backtrace: bool) -> Self {
+        self.backtrace = backtrace;
+        self
+    }
+
+    /// Sets the names of levels, weekdays and months.
+    ///
+    /// See [`TextLocale`] for details.
+    pub fn locale(mut self, locale: TextLocale) -> Self {
+        self.locale = Some(Box::new(locale));
+        self
+    }
+
+    /// Sets the [`strftime`](jiff::fmt::strftime) format of timestamps. Default to RFC 3339 with
+    /// microseconds and the time zone offset.
+    ///
+    /// Weekday and month names can be localized with [`TextLayout::locale`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::layout::TextLayout;
+    ///
+    /// let text_layout = TextLayout::default().timestamp_format("%Y-%m-%d %H:%M:%S%.3f");
+    /// ```
+    pub fn timestamp_format(mut self, format: impl Into<String>) -> Self {
+        self.timestamp_format = Some(format.into());
+        self
+    }
+
+    /// Sets the pattern of the lines. Default to the fields shown above.
+    ///
+    /// See [`TextPattern`] for the syntax. A pattern replaces the options that hide fields.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::layout::TextLayout;
+    ///
+    /// let text_layout = TextLayout::default().pattern(
+    ///     "{timestamp} [{level:>5}] {target} {file}:{line} {message} {kv}"
+    ///         .parse()
+    ///         .unwrap(),
+    /// );
+    /// ```
+    pub fn pattern(mut self, pattern: TextPattern) -> Self {
+        self.pattern = Some(pattern);
+        self
+    }
+
+    fn level_name(&self, level: Level) -> Option<&str> {
+        self.locale.as_ref()?.level_name(level)
+    }
+
     #[cfg(not(feature = "colored"))]
     pub(crate) fn format_record_level(&self, level: Level) -> String {
-        level.to_string()
+        match self.level_name(level) {
+            Some(name) => name.to_string(),
+            None => level.to_string(),
+        }
     }
 }
 
@@ -157,24 +296,87 @@ impl TextLayout {
         diagnostics: &[Diagnostic],
     ) -> anyhow::Result<Vec<u8>> {
         let time = match self.tz.clone() {
-            Some(tz) => Timestamp::now().to_zoned(tz),
-            None => Zoned::now(),
+            Some(tz) => clock::now().to_zoned(tz),
+            None => clock::now().to_zoned(TimeZone::system()),
         };
         let level = self.format_record_level(record.level());
-        let target = record.target();
         let file = filename(record);
-        let line = record.line().unwrap_or_default();
-        let message = record.args();
 
-        let mut visitor = KvWriter {
-            text: format!("{time:.6} {level:>5} {target}: {file}:{line} {message}"),
+        let mut text = pool::take_string();
+        let backtrace = match &self.pattern {
+            Some(pattern) => {
+                let mut timestamp = String::new();
+                self.format_timestamp(&mut timestamp, &time)?;
+                let mut visitor = KvWriter {
+                    text: String::new(),
+                    style: self.kv_style,
+                    backtrace: None,
+                };
+                record.key_values().visit(&mut visitor)?;
+                for d in diagnostics {
+                    d.visit(&mut visitor);
+                }
+                let fields = PatternFields {
+                    record,
+                    diagnostics,
+                    timestamp: &timestamp,
+                    level: &level,
+                    file: &file,
+                    kvs: visitor.text.trim_start(),
+                };
+                pattern.render(&mut text, &fields)?;
+                visitor.backtrace
+            }
+            None => {
+                let target = record.target();
+                let line = record.line().unwrap_or_default();
+                let message = record.args();
+                if !self.no_timestamp {
+                    self.format_timestamp(&mut text, &time)?;
+                    text.push(' ');
+                }
+                write!(&mut text, "{level:>5}")?;
+                if !self.no_target {
+                    write!(&mut text, " {target}:")?;
+                }
+                if !self.no_location {
+                    write!(&mut text, " {file}:{line}")?;
+                }
+                write!(&mut text, " {message}")?;
+                let mut visitor = KvWriter {
+                    text,
+                    style: self.kv_style,
+                    backtrace: None,
+                };
+                record.key_values().visit(&mut visitor)?;
+                for d in diagnostics {
+                    d.visit(&mut visitor);
+                }
+                text = visitor.text;
+                visitor.backtrace
+            }
         };
-        record.key_values().visit(&mut visitor)?;
-        for d in diagnostics {
-            d.visit(&mut visitor);
+
+        if let Some(backtrace) = backtrace.filter(|_| self.backtrace) {
+            text.push('\n');
+            text.push_str(backtrace.trim_end());
         }
+        Ok(text.into_bytes())
+    }
+}
 
-        Ok(visitor.text.into_bytes())
+impl TextLayout {
+    fn format_timestamp(&self, text: &mut String, time: &jiff::Zoned) -> std::fmt::Result {
+        match &self.timestamp_format {
+            Some(format) => {
+                let format = match &self.locale {
+                    Some(locale) => Cow::Owned(locale.localize_format(time, format)),
+                    None => Cow::Borrowed(format.as_str()),
+                };
+                write!(text, "{}", time.strftime(format.as_ref()))
+            }
+            None => write!(text, "{time:.6}"),
+        }
     }
 }
 
@@ -197,6 +399,18 @@ fn filename<'a>(record: &'a log::Record<'a>) -> Cow<'a, str> {
 
 struct KvWriter {
     text: String,
+    style: KvStyle,
+    backtrace: Option<String>,
+}
+
+impl KvWriter {
+    fn write(&mut self, key: &str, value: impl std::fmt::Display) -> std::fmt::Result {
+        match self.style {
+            KvStyle::Inline => write!(&mut self.text, " {key}={value}"),
+            KvStyle::Bracketed => write!(&mut self.text, " [{key}={value}]"),
+            KvStyle::Hidden => Ok(()),
+        }
+    }
 }
 
 impl<'kvs> log::kv::VisitSource<'kvs> for KvWriter {
@@ -205,7 +419,23 @@ impl<'kvs> log::kv::VisitSource<'kvs> for KvWriter {
         key: log::kv::Key<'kvs>,
         value: log::kv::Value<'kvs>,
     ) -> Result<(), log::kv::Error> {
-        write!(&mut self.text, " {key}={value}")?;
+        // the rendered message is already written
+        if is_message_template_key(key.as_str()) {
+            return Ok(());
+        }
+        // binary attachments are left to the appenders that can store them
+        if is_attachment_key(key.as_str()) {
+            return Ok(());
+        }
+        // the backtrace is written on its own lines
+        if is_backtrace_key(key.as_str()) {
+            self.backtrace = Some(value.to_string());
+            return Ok(());
+        }
+        match secret_key(key.as_str()) {
+            Some(key) => self.write(key, MASK)?,
+            None => self.write(key.as_str(), value)?,
+        }
         Ok(())
     }
 }
@@ -216,14 +446,122 @@ impl Visitor for KvWriter {
         K: Into<Cow<'k, str>>,
         V: Into<Cow<'v, str>>,
     {
+        let key = key.into();
+        let value = value.into();
+        let (key, value) = match secret_key(&key) {
+            Some(key) => (key, MASK),
+            None => (key.as_ref(), value.as_ref()),
+        };
         // SAFETY: no more than an allocate-less version
         //  self.text.push_str(&format!(" {key}={value}"))
-        write!(
-            &mut self.text,
-            " {key}={value}",
-            key = key.into(),
-            value = value.into()
-        )
-        .unwrap();
+        self.write(key, value).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Record;
+
+    use super::*;
+
+    #[test]
+    fn test_hidden_fields() {
+        let kvs = [("user", "alice"), ("secret_token", "hunter2")];
+        let record = Record::builder()
+            .args(format_args!("hello"))
+            .level(Level::Info)
+            .target("app")
+            .file(Some("src/main.rs"))
+            .line(Some(7))
+            .key_values(&kvs)
+            .build();
+        let format = |layout: TextLayout| {
+            let layout = layout.no_color().no_timestamp();
+            String::from_utf8(layout.format(&record, &[]).unwrap()).unwrap()
+        };
+
+        assert_eq!(
+            format(TextLayout::default()),
+            format!(" INFO app: main.rs:7 hello user=alice token={MASK}")
+        );
+        assert_eq!(
+            format(TextLayout::default().no_target().no_location()),
+            format!(" INFO hello user=alice token={MASK}")
+        );
+        assert_eq!(
+            format(TextLayout::default().kv_style(KvStyle::Bracketed)),
+            format!(" INFO app: main.rs:7 hello [user=alice] [token={MASK}]")
+        );
+        assert_eq!(
+            format(TextLayout::default().kv_style(KvStyle::Hidden)),
+            " INFO app: main.rs:7 hello"
+        );
+    }
+
+    #[test]
+    fn test_attachment_omitted() {
+        let kvs = [
+            ("attachment", "report.pdf"),
+            (crate::attachment::ATTACHMENT_KEY, "3q2+7w=="),
+        ];
+        let record = Record::builder()
+            .args(format_args!("hello"))
+            .level(Level::Info)
+            .key_values(&kvs)
+            .build();
+        let layout = TextLayout::default()
+            .no_color()
+            .no_timestamp()
+            .no_target()
+            .no_location();
+        assert_eq!(
+            String::from_utf8(layout.format(&record, &[]).unwrap()).unwrap(),
+            " INFO hello attachment=report.pdf"
+        );
+    }
+    #[test]
+    fn test_backtrace_key() {
+        let kvs = [
+            ("backtrace", "user value"),
+            (crate::backtrace::BACKTRACE_KEY, "0: main"),
+        ];
+        let record = Record::builder()
+            .args(format_args!("hello"))
+            .level(Level::Error)
+            .key_values(&kvs)
+            .build();
+        let layout = TextLayout::default()
+            .no_color()
+            .no_timestamp()
+            .no_target()
+            .no_location();
+        let format =
+            |layout: &TextLayout| String::from_utf8(layout.format(&record, &[]).unwrap()).unwrap();
+        assert_eq!(format(&layout), "ERROR hello backtrace=user value");
+        assert_eq!(
+            format(&layout.clone().backtrace(true)),
+            "ERROR hello backtrace=user value\n0: main"
+        );
+    }
+    #[test]
+    fn test_message_template_key() {
+        let kvs = [
+            ("message_template", "user value"),
+            (crate::template::MESSAGE_TEMPLATE_KEY, "hello"),
+        ];
+        let record = Record::builder()
+            .args(format_args!("hello"))
+            .level(Level::Info)
+            .key_values(&kvs)
+            .build();
+        let layout = TextLayout::default()
+            .no_color()
+            .no_timestamp()
+            .no_target()
+            .no_location();
+        assert_eq!(
+            String::from_utf8(layout.format(&record, &[]).unwrap()).unwrap(),
+            " INFO hello message_template=user value"
+        );
     }
 }
@@ -0,0 +1,116 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers to truncate formatted output on UTF-8 character boundaries.
+//!
+//! Layouts and writers that cap the size of a payload should use these helpers rather than
+//! slicing bytes directly: a cut in the middle of a multibyte character produces invalid UTF-8,
+//! which downstream JSON parsers reject.
+
+/// Returns the longest prefix of `s` that is at most `max_len` bytes long and ends on a
+/// character boundary.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::layout::truncate_str;
+///
+/// assert_eq!(truncate_str("héllo", 2), "h");
+/// assert_eq!(truncate_str("héllo", 3), "hé");
+/// assert_eq!(truncate_str("héllo", 16), "héllo");
+/// ```
+pub fn truncate_str(s: &str, max_len: usize) -> &str {
+    &s[..floor_char_boundary(s.as_bytes(), max_len)]
+}
+
+/// Truncates `bytes` to at most `max_len` bytes without splitting a UTF-8 encoded character,
+/// and returns whether anything was removed.
+///
+/// Bytes that are not valid UTF-8 are cut at `max_len` at worst, so that the result never
+/// exceeds the limit.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::layout::truncate_utf8;
+///
+/// let mut bytes = "héllo".as_bytes().to_vec();
+/// assert!(truncate_utf8(&mut bytes, 2));
+/// assert_eq!(bytes, b"h");
+/// ```
+pub fn truncate_utf8(bytes: &mut Vec<u8>, max_len: usize) -> bool {
+    if bytes.len() <= max_len {
+        return false;
+    }
+    bytes.truncate(floor_char_boundary(bytes, max_len));
+    true
+}
+
+// Returns the largest character boundary at or below `index`. A UTF-8 character spans at most
+// four bytes, so at most three continuation bytes are skipped.
+fn floor_char_boundary(bytes: &[u8], index: usize) -> usize {
+    if index >= bytes.len() {
+        return bytes.len();
+    }
+    (index.saturating_sub(3)..=index)
+        .rev()
+        .find(|&i| !is_continuation_byte(bytes[i]))
+        .unwrap_or(index)
+}
+
+fn is_continuation_byte(byte: u8) -> bool {
+    byte & 0b1100_0000 == 0b1000_0000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::truncate_str;
+    use super::truncate_utf8;
+
+    const SAMPLES: &[&str] = &[
+        "",
+        "plain ascii",
+        "héllo wörld",
+        "日本語のログ",
+        "emoji 🦀🔥 mixed ß and ✓",
+        "\u{10FFFF}\u{80}\u{800}\u{10000}",
+    ];
+
+    #[test]
+    fn test_truncate_on_char_boundaries() {
+        for sample in SAMPLES {
+            for max_len in 0..=sample.len() + 4 {
+                let truncated = truncate_str(sample, max_len);
+                assert!(truncated.len() <= max_len);
+                assert!(sample.starts_with(truncated));
+                // the next character would not have fit
+                if let Some(next) = sample[truncated.len()..].chars().next() {
+                    assert!(truncated.len() + next.len_utf8() > max_len);
+                }
+
+                let mut bytes = sample.as_bytes().to_vec();
+                let removed = truncate_utf8(&mut bytes, max_len);
+                assert_eq!(removed, sample.len() > max_len);
+                assert_eq!(std::str::from_utf8(&bytes).unwrap(), truncated);
+            }
+        }
+    }
+
+    #[test]
+    fn test_truncate_invalid_utf8() {
+        let mut bytes = vec![0x80; 8];
+        assert!(truncate_utf8(&mut bytes, 5));
+        assert_eq!(bytes.len(), 5);
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Key-values computed only when a record is formatted.
+//!
+//! A [`Lazy`] value wraps a function whose result is computed the first time a layout or an
+//! appender serializes the value, so expensive context is not computed for records rejected by
+//! filters. The result is computed at most once per record, and serialized as a string.
+//!
+//! # Examples
+//!
+//! ```
+//! use logforth::lazy::lazy;
+//!
+//! let state = vec![1, 2, 3];
+//! log::info!(state = lazy(|| format!("{state:?}")); "request handled");
+//! ```
+//!
+//! See [`LazyDiagnostic`](crate::diagnostic::LazyDiagnostic) for diagnostics computed lazily.
+
+use std::cell::OnceCell;
+use std::fmt;
+
+use log::kv::ToValue;
+use log::kv::Value;
+
+/// Creates a [`Lazy`] value computed by `f`.
+pub fn lazy<F, T>(f: F) -> Lazy<F, T>
+where
+    F: Fn() -> T,
+    T: fmt::Display,
+{
+    Lazy::new(f)
+}
+
+/// A key-value computed the first time it's serialized.
+pub struct Lazy<F, T> {
+    f: F,
+    value: OnceCell<T>,
+}
+
+impl<F, T> Lazy<F, T>
+where
+    F: Fn() -> T,
+    T: fmt::Display,
+{
+    /// Creates a value computed by `f`.
+    pub fn new(f: F) -> Self {
+        Lazy {
+            f,
+            value: OnceCell::new(),
+        }
+    }
+}
+
+impl<F, T> fmt::Display for Lazy<F, T>
+where
+    F: Fn() -> T,
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.get_or_init(|| (self.f)()).fmt(f)
+    }
+}
+
+impl<F, T: fmt::Debug> fmt::Debug for Lazy<F, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lazy")
+            .field("value", &self.value.get())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F, T> ToValue for Lazy<F, T>
+where
+    F: Fn() -> T,
+    T: fmt::Display,
+{
+    fn to_value(&self) -> Value<'_> {
+        Value::from_display(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn test_lazy_value() {
+        let calls = Cell::new(0);
+        let value = lazy(|| {
+            calls.set(calls.get() + 1);
+            42
+        });
+        assert_eq!(calls.get(), 0);
+
+        let value = value.to_value();
+        assert_eq!(value.to_string(), "42");
+        assert_eq!(value.to_string(), "42");
+        assert_eq!(calls.get(), 1);
+    }
+}
@@ -51,6 +51,21 @@
 //! log::error!("Error message.");
 //! log::info!("Info message.");
 //! ```
+//!
+//! # Compile-time filters
+//!
+//! Lower levels can be compiled out with the [static level filters](log#compile-time-filters) of
+//! the `log` crate, e.g., by enabling its `release_max_level_info` feature in the application:
+//!
+//! ```toml
+//! [dependencies]
+//! log = { version = "0.4", features = ["release_max_level_info"] }
+//! ```
+//!
+//! The macros of this crate expand to the `log` macros, so records at the compiled-out levels are
+//! never constructed and hot paths pay nothing for them. These features are not mirrored as
+//! features of this crate: enabling two of them at once fails to compile, which would break
+//! `--all-features` builds.
 
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
 
@@ -60,17 +75,71 @@ pub extern crate colored;
 pub mod color;
 
 pub mod append;
+pub mod attachment;
+pub mod backtrace;
+mod clock;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+pub mod compression;
+#[cfg(feature = "config")]
+pub mod config;
 pub mod diagnostic;
+pub mod env_logger_compat;
+pub mod fatal;
 pub mod filter;
+pub mod io;
 pub mod layout;
+pub mod lazy;
+pub mod lifecycle;
+pub mod panic;
+pub mod pool;
+#[cfg(feature = "json")]
+pub mod record;
+pub mod route;
+pub mod secret;
+pub mod span;
+pub mod template;
+pub mod wire;
 
 #[cfg(feature = "non-blocking")]
 pub mod non_blocking;
 
 pub use append::Append;
+#[cfg(feature = "test-util")]
+pub use clock::ManualClock;
+#[cfg(feature = "test-util")]
+pub use clock::ManualClockGuard;
 pub use diagnostic::Diagnostic;
 pub use filter::Filter;
 pub use layout::Layout;
+#[cfg(feature = "derive")]
+pub use logforth_derive::LogFields;
 
 mod logger;
 pub use logger::*;
+
+mod macros;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use log;
+
+    /// The key-values of a record logged with `fields:`, i.e., the message template followed by
+    /// the fields.
+    pub struct WithFields<'a, S: ?Sized> {
+        pub template: &'static str,
+        pub fields: &'a S,
+    }
+
+    impl<S: log::kv::Source + ?Sized> log::kv::Source for WithFields<'_, S> {
+        fn visit<'kvs>(
+            &'kvs self,
+            visitor: &mut dyn log::kv::VisitSource<'kvs>,
+        ) -> Result<(), log::kv::Error> {
+            visitor.visit_pair(
+                log::kv::Key::from_str(crate::template::MESSAGE_TEMPLATE_KEY),
+                log::kv::Value::from(self.template),
+            )?;
+            self.fields.visit(visitor)
+        }
+    }
+}
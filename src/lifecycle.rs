@@ -0,0 +1,147 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Standardized lifecycle events of a service.
+//!
+//! [`started`] logs a `service.start` record and returns a [`LifecycleGuard`] that logs a
+//! `service.stop` record with the exit code and uptime when dropped. Both records are logged at
+//! the info level under the reserved [`LIFECYCLE_TARGET`], with the key-values:
+//!
+//! - `event`: `service.start` or `service.stop`.
+//! - `pid`: the ID of the running process.
+//! - `version`: the version of the service, if set with [`Lifecycle::version`].
+//! - `config_hash`: the hash of the configuration, if set with [`Lifecycle::config_hash`].
+//! - `exit_code`: the exit code, only on `service.stop`.
+//! - `uptime_ms`: the uptime in milliseconds, only on `service.stop`.
+//!
+//! # Examples
+//!
+//! ```
+//! logforth::stdout().apply();
+//!
+//! let guard = logforth::lifecycle::Lifecycle::new()
+//!     .version(env!("CARGO_PKG_VERSION"))
+//!     .started();
+//!
+//! // run the service ...
+//!
+//! guard.stopped(0);
+//! ```
+
+use std::time::Instant;
+
+use log::Level;
+
+/// The target of lifecycle records.
+pub const LIFECYCLE_TARGET: &str = "logforth::lifecycle";
+
+/// Logs a `service.start` record without version and config hash.
+///
+/// See [`Lifecycle::started`] for details.
+pub fn started() -> LifecycleGuard {
+    Lifecycle::new().started()
+}
+
+/// A builder for the lifecycle records of a service.
+#[derive(Debug, Default, Clone)]
+pub struct Lifecycle {
+    version: Option<String>,
+    config_hash: Option<String>,
+}
+
+impl Lifecycle {
+    /// Creates a new [`Lifecycle`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the version of the service.
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Sets the hash of the configuration the service runs with.
+    pub fn config_hash(mut self, config_hash: impl Into<String>) -> Self {
+        self.config_hash = Some(config_hash.into());
+        self
+    }
+
+    /// Logs a `service.start` record, and returns a guard that logs a `service.stop` record.
+    ///
+    /// The guard should be kept alive until the service stops, e.g., by binding it in `main`.
+    pub fn started(self) -> LifecycleGuard {
+        log::log!(
+            target: LIFECYCLE_TARGET,
+            Level::Info,
+            event = "service.start",
+            pid = std::process::id(),
+            version = self.version.as_deref(),
+            config_hash = self.config_hash.as_deref();
+            "service started"
+        );
+
+        LifecycleGuard {
+            lifecycle: self,
+            start: Instant::now(),
+            exit_code: None,
+        }
+    }
+}
+
+/// A guard that logs a `service.stop` record when dropped.
+///
+/// The exit code defaults to `0`, or `101` if the thread is panicking (the exit code of a Rust
+/// program that panics in `main`). Use [`LifecycleGuard::set_exit_code`] or
+/// [`LifecycleGuard::stopped`] to report another exit code.
+#[derive(Debug)]
+#[must_use = "the service.stop record is logged when the guard is dropped"]
+pub struct LifecycleGuard {
+    lifecycle: Lifecycle,
+    start: Instant,
+    exit_code: Option<i32>,
+}
+
+impl LifecycleGuard {
+    /// Sets the exit code reported in the `service.stop` record.
+    pub fn set_exit_code(&mut self, exit_code: i32) {
+        self.exit_code = Some(exit_code);
+    }
+
+    /// Logs the `service.stop` record with the given exit code.
+    pub fn stopped(mut self, exit_code: i32) {
+        self.set_exit_code(exit_code);
+    }
+}
+
+impl Drop for LifecycleGuard {
+    fn drop(&mut self) {
+        let exit_code = self
+            .exit_code
+            .unwrap_or(if std::thread::panicking() { 101 } else { 0 });
+        let uptime_ms = self.start.elapsed().as_millis() as u64;
+
+        log::log!(
+            target: LIFECYCLE_TARGET,
+            Level::Info,
+            event = "service.stop",
+            pid = std::process::id(),
+            version = self.lifecycle.version.as_deref(),
+            config_hash = self.lifecycle.config_hash.as_deref(),
+            exit_code = exit_code,
+            uptime_ms = uptime_ms;
+            "service stopped"
+        );
+    }
+}
@@ -12,15 +12,28 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::Level;
 use log::LevelFilter;
+use log::Record;
 
+use super::early;
+use super::log_impl;
 use super::log_impl::Dispatch;
+use super::log_impl::DispatchAppend;
 use super::log_impl::Logger;
+use super::startup;
+use super::RuntimeConfig;
 use crate::append;
+use crate::clock;
 use crate::filter::EnvFilter;
+use crate::filter::LevelRemap;
 use crate::Append;
 use crate::Diagnostic;
 use crate::Filter;
+use crate::Layout;
 
 /// Creates a new empty [`Builder`] instance for configuring log dispatching.
 ///
@@ -86,15 +99,31 @@ pub struct Builder {
     // stashed dispatches
     dispatches: Vec<Dispatch>,
 
+    // diagnostics shared by all dispatches
+    diagnostics: Vec<Diagnostic>,
+
     // default to trace - we need this because the global default is OFF
     max_level: LevelFilter,
+
+    // remap levels of records before filtering
+    level_remap: LevelRemap,
+
+    // capture backtraces for records at or above this level
+    backtrace_level: Option<Level>,
+
+    // runtime settings, including the coarse clock and the error handler
+    runtime: RuntimeConfig,
 }
 
 impl Builder {
     fn new() -> Self {
         Builder {
             dispatches: vec![],
+            diagnostics: vec![],
             max_level: LevelFilter::Trace,
+            level_remap: LevelRemap::default(),
+            backtrace_level: None,
+            runtime: RuntimeConfig::default(),
         }
     }
 
@@ -117,6 +146,29 @@ impl Builder {
         self
     }
 
+    /// Add a diagnostic that applies to all dispatches.
+    ///
+    /// Records logged through the `log` crate pick up the key-values of global diagnostics
+    /// without each dispatch declaring them. Global diagnostics are visited before the
+    /// dispatch's own diagnostics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::append;
+    /// use logforth::diagnostic;
+    ///
+    /// logforth::builder()
+    ///     .diagnostic(diagnostic::ThreadLocalDiagnostic::default())
+    ///     .dispatch(|d| d.append(append::Stdout::default()))
+    ///     .dispatch(|d| d.append(append::Stderr::default()))
+    ///     .apply();
+    /// ```
+    pub fn diagnostic(mut self, diagnostic: impl Into<Diagnostic>) -> Self {
+        self.diagnostics.push(diagnostic.into());
+        self
+    }
+
     /// Sets the global maximum log level. Default to [`LevelFilter::Trace`].
     ///
     /// This will be passed to `log::set_max_level()`.
@@ -133,10 +185,185 @@ impl Builder {
         self
     }
 
+    /// Sets the level remapping applied to records before they are filtered.
+    ///
+    /// See [`LevelRemap`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use log::Level;
+    /// use logforth::filter::LevelRemap;
+    ///
+    /// logforth::builder()
+    ///     .level_remap(LevelRemap::new().map_target("noisy_dep", Level::Info, Level::Debug))
+    ///     .apply();
+    /// ```
+    pub fn level_remap(mut self, level_remap: LevelRemap) -> Self {
+        self.level_remap = level_remap;
+        self
+    }
+
+    /// Captures a backtrace for records at or above the given level.
+    ///
+    /// The backtrace is attached to the record as a key-value; see the [`backtrace`] module for
+    /// details. It is captured only once a dispatch accepts the record, so filters do not see it.
+    /// Capturing a backtrace is expensive, so this is meant for rare records like errors.
+    ///
+    /// [`backtrace`]: crate::backtrace
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// logforth::builder()
+    ///     .backtrace_level(log::Level::Error)
+    ///     .apply();
+    /// ```
+    pub fn backtrace_level(mut self, level: Level) -> Self {
+        self.backtrace_level = Some(level);
+        self
+    }
+
+    /// Enables a coarse clock for the timestamps of built-in layouts.
+    ///
+    /// A background thread reads the system clock once per `resolution` and layouts use that
+    /// value instead of reading the clock for every record. This trades timestamp precision for
+    /// throughput under extreme log rates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// logforth::builder()
+    ///     .coarse_clock(Duration::from_millis(10))
+    ///     .apply();
+    /// ```
+    pub fn coarse_clock(mut self, resolution: Duration) -> Self {
+        self.runtime.coarse_clock = Some(resolution);
+        self
+    }
+
+    /// Sets the handler called after a [fatal](crate::fatal) record is dispatched.
+    ///
+    /// Once a record logged with [`fatal!`](crate::fatal!) has been passed to the appenders, the
+    /// logger flushes all appenders synchronously, including the worker threads of non-blocking
+    /// appenders, and then calls `handler` with the record. The handler may abort the process or
+    /// dump state; the record is on disk by then, unless a worker thread does not finish within
+    /// its shutdown timeout, which bounds how long each non-blocking appender delays the handler
+    /// (see [`NonBlocking::flush`](crate::non_blocking::NonBlocking::flush)).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// logforth::builder()
+    ///     .on_fatal(|record| eprintln!("fatal: {}", record.args()))
+    ///     .apply();
+    /// ```
+    pub fn on_fatal<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Record) + Send + Sync + 'static,
+    {
+        self.runtime = self.runtime.on_fatal(handler);
+        self
+    }
+
+    /// Sets whether the logger parses panic messages into structured key-values. Default to
+    /// `false`.
+    ///
+    /// When enabled, error records whose message follows one of the conventional panic formats
+    /// are logged with the panic message alone, and the panicking thread and location as
+    /// key-values; see the [`panic`] module for details.
+    ///
+    /// [`panic`]: crate::panic
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// logforth::builder().normalize_panics(true).apply();
+    /// ```
+    pub fn normalize_panics(mut self, normalize_panics: bool) -> Self {
+        self.runtime = self.runtime.normalize_panics(normalize_panics);
+        self
+    }
+
+    /// Coalesces the flushes of each appender within the given window. Disabled by default.
+    ///
+    /// When enabled, [`log::Log::flush`] skips an appender that was flushed by the logger within
+    /// the last `window` and has not been appended to since, so that many threads flushing at
+    /// once don't trigger a redundant fsync or export each time. An appender with records
+    /// appended since its last flush is always flushed, so a final flush, e.g., at shutdown,
+    /// never leaves records behind. Flushes before the [fatal handler](Self::on_fatal) and when
+    /// the dispatches are replaced are never coalesced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// logforth::builder()
+    ///     .coalesce_flushes(Duration::from_millis(50))
+    ///     .apply();
+    /// ```
+    pub fn coalesce_flushes(mut self, window: Duration) -> Self {
+        self.runtime = self.runtime.coalesce_flushes(window);
+        self
+    }
+
+    /// Sets whether error records rejected by every dispatch are written to stderr. Default to
+    /// `false`.
+    ///
+    /// When enabled, an error record, including a [fatal](mod@crate::fatal) one, that no dispatch
+    /// accepts is written to stderr instead of being dropped, so that misconfigured filters never
+    /// hide serious errors. The first time this happens, the logger also reports it on stderr,
+    /// so that the misconfiguration is discoverable. Use [`RuntimeConfig::last_resort_append`]
+    /// to write such records to another appender.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::append;
+    ///
+    /// // errors of `my_app::db` are still written to stderr
+    /// logforth::builder()
+    ///     .last_resort(true)
+    ///     .dispatch(|d| {
+    ///         d.filter("info,my_app::db=off")
+    ///             .append(append::Stdout::default())
+    ///     })
+    ///     .apply();
+    /// ```
+    pub fn last_resort(mut self, enabled: bool) -> Self {
+        self.runtime = self.runtime.last_resort(enabled);
+        self
+    }
+
+    /// Sets the runtime settings of the logger.
+    ///
+    /// This replaces the runtime settings configured before, such as the coarse clock. Appenders
+    /// that opt in inherit the same settings from a clone of `runtime`; see [`RuntimeConfig`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use logforth::RuntimeConfig;
+    ///
+    /// logforth::builder()
+    ///     .runtime(RuntimeConfig::new().coarse_clock(Duration::from_millis(10)))
+    ///     .apply();
+    /// ```
+    pub fn runtime(mut self, runtime: RuntimeConfig) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
     /// Sets up the global logger with all the configured dispatches.
     ///
     /// This should be called early in the execution of a Rust program. Any log events that occur
-    /// before initialization will be ignored.
+    /// before initialization will be ignored, unless they are held by
+    /// [`buffer_early_records`](crate::buffer_early_records).
     ///
     /// # Errors
     ///
@@ -151,8 +378,68 @@ impl Builder {
     /// }
     /// ```
     pub fn try_apply(self) -> Result<(), log::SetLoggerError> {
-        let logger = Logger::new(self.dispatches);
-        log::set_boxed_logger(Box::new(logger))?;
+        let dispatches = build_dispatches(self.dispatches, &self.diagnostics);
+        let coarse_clock = self.runtime.coarse_clock;
+        let error_handler = self.runtime.error_handler.clone();
+        let logger = Logger::new(
+            dispatches,
+            self.level_remap,
+            self.backtrace_level,
+            self.runtime,
+        );
+        let installed = logger.dispatches();
+        if let Some(logger) = early::install(logger) {
+            log::set_boxed_logger(Box::new(logger))?;
+        }
+        log_impl::install(installed, error_handler);
+        log::set_max_level(self.max_level);
+        if let Some(resolution) = coarse_clock {
+            clock::start_coarse_clock(resolution);
+        }
+        startup::install();
+        Ok(())
+    }
+
+    /// Replaces the dispatches of the installed logger with the ones of this builder, without
+    /// re-initializing the global logger.
+    ///
+    /// This allows changing filters and appenders at runtime, e.g., to raise the verbosity of a
+    /// service from an admin endpoint. The dispatches, the global diagnostics and the max level
+    /// are replaced; the other settings of the installed logger are kept. Records being logged
+    /// while reloading are delivered to either the previous or the new dispatches, and the
+    /// previous dispatches are flushed once replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the global logger was not installed by [`Builder::apply`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use log::LevelFilter;
+    /// use logforth::append;
+    ///
+    /// logforth::builder()
+    ///     .dispatch(|d| {
+    ///         d.filter(LevelFilter::Info)
+    ///             .append(append::Stdout::default())
+    ///     })
+    ///     .apply();
+    ///
+    /// logforth::builder()
+    ///     .dispatch(|d| {
+    ///         d.filter(LevelFilter::Debug)
+    ///             .append(append::Stdout::default())
+    ///     })
+    ///     .reload()
+    ///     .unwrap();
+    /// log::debug!("this record is logged");
+    /// ```
+    pub fn reload(self) -> anyhow::Result<()> {
+        let Some(installed) = log_impl::installed() else {
+            anyhow::bail!("the global logger was not installed by Builder::apply");
+        };
+        installed.replace(build_dispatches(self.dispatches, &self.diagnostics));
         log::set_max_level(self.max_level);
         Ok(())
     }
@@ -177,6 +464,13 @@ impl Builder {
     }
 }
 
+fn build_dispatches(dispatches: Vec<Dispatch>, diagnostics: &[Diagnostic]) -> Arc<[Dispatch]> {
+    dispatches
+        .into_iter()
+        .map(|dispatch| dispatch.with_global_diagnostics(diagnostics))
+        .collect()
+}
+
 /// A builder for configuring a log dispatch, including filters and appenders.
 ///
 /// # Examples
@@ -195,7 +489,9 @@ impl Builder {
 pub struct DispatchBuilder<const APPEND: bool> {
     filters: Vec<Filter>,
     diagnostics: Vec<Diagnostic>,
-    appends: Vec<Box<dyn Append>>,
+    layout: Option<Layout>,
+    routes: Vec<String>,
+    appends: Vec<DispatchAppend>,
 }
 
 impl DispatchBuilder<false> {
@@ -203,6 +499,8 @@ impl DispatchBuilder<false> {
         DispatchBuilder {
             filters: vec![],
             diagnostics: vec![],
+            layout: None,
+            routes: vec![],
             appends: vec![],
         }
     }
@@ -246,11 +544,68 @@ impl DispatchBuilder<false> {
         self.diagnostics.push(diagnostic.into());
         self
     }
+
+    /// Set the layout of this dispatch.
+    ///
+    /// The layout is used by appenders that are not configured with a layout of their own. This
+    /// allows one appender instance to be shared across dispatches with different layouts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    ///
+    /// use logforth::append;
+    /// use logforth::layout::CustomLayout;
+    /// use logforth::layout::TextLayout;
+    ///
+    /// let stdout = Arc::new(append::Stdout::default());
+    ///
+    /// logforth::builder()
+    ///     .dispatch(|d| {
+    ///         d.filter(log::LevelFilter::Error)
+    ///             .layout(CustomLayout::new(|record, _| {
+    ///                 Ok(format!("[Alert] {}", record.args()).into_bytes())
+    ///             }))
+    ///             .append(stdout.clone())
+    ///     })
+    ///     .dispatch(|d| d.layout(TextLayout::default().no_color()).append(stdout))
+    ///     .apply();
+    /// ```
+    pub fn layout(mut self, layout: impl Into<Layout>) -> Self {
+        self.layout = Some(layout.into());
+        self
+    }
+
+    /// Tag this dispatch with a route.
+    ///
+    /// Records with the key-value [`ROUTE_KEY`](crate::route::ROUTE_KEY) set to `route` are
+    /// delivered only to the dispatches tagged with it, bypassing their filters. See the
+    /// [`route`](crate::route) module.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::append;
+    ///
+    /// logforth::builder()
+    ///     .dispatch(|d| {
+    ///         d.filter(log::LevelFilter::Off)
+    ///             .route("audit")
+    ///             .append(append::Stderr::default())
+    ///     })
+    ///     .apply();
+    /// ```
+    pub fn route(mut self, route: impl Into<String>) -> Self {
+        self.routes.push(route.into());
+        self
+    }
 }
 
 impl DispatchBuilder<true> {
     fn build(self) -> Dispatch {
-        Dispatch::new(self.filters, self.diagnostics, self.appends)
+        Dispatch::new(self.filters, self.diagnostics, self.layout, self.appends)
+            .with_routes(self.routes)
     }
 }
 
@@ -266,11 +621,57 @@ impl<const APPEND: bool> DispatchBuilder<APPEND> {
     ///     .dispatch(|d| d.append(append::Stdout::default()))
     ///     .apply();
     /// ```
-    pub fn append(mut self, append: impl Append) -> DispatchBuilder<true> {
-        self.appends.push(Box::new(append));
+    pub fn append(self, append: impl Append) -> DispatchBuilder<true> {
+        self.push_append(None, append)
+    }
+
+    /// Add a named appender to this dispatch.
+    ///
+    /// A filter returning [`FilterResult::AcceptOnly`] routes the record to the named appenders
+    /// it lists only, so a single dispatch can send different records to different sinks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use logforth::append;
+    /// use logforth::filter::CustomFilter;
+    /// use logforth::filter::FilterResult;
+    ///
+    /// let errors = FilterResult::accept_only(["stderr"]);
+    /// let others = FilterResult::accept_only(["stdout"]);
+    ///
+    /// logforth::builder()
+    ///     .dispatch(|d| {
+    ///         d.filter(CustomFilter::new(move |metadata| {
+    ///             if metadata.level() == log::Level::Error {
+    ///                 errors.clone()
+    ///             } else {
+    ///                 others.clone()
+    ///             }
+    ///         }))
+    ///         .append_named("stdout", append::Stdout::default())
+    ///         .append_named("stderr", append::Stderr::default())
+    ///     })
+    ///     .apply();
+    /// ```
+    ///
+    /// [`FilterResult::AcceptOnly`]: crate::filter::FilterResult::AcceptOnly
+    pub fn append_named(
+        self,
+        name: impl Into<String>,
+        append: impl Append,
+    ) -> DispatchBuilder<true> {
+        self.push_append(Some(name.into()), append)
+    }
+
+    fn push_append(mut self, name: Option<String>, append: impl Append) -> DispatchBuilder<true> {
+        self.appends
+            .push(DispatchAppend::new(name, Box::new(append)));
         DispatchBuilder {
             filters: self.filters,
             diagnostics: self.diagnostics,
+            layout: self.layout,
+            routes: self.routes,
             appends: self.appends,
         }
     }
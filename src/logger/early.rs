@@ -0,0 +1,368 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+use log::kv::Key;
+use log::kv::Value;
+use log::kv::VisitSource;
+use log::Level;
+use log::LevelFilter;
+use log::Metadata;
+use log::Record;
+
+use super::log_impl::Logger;
+
+static EARLY: EarlyLogger = EarlyLogger::new();
+
+// whether `EARLY` is the global logger
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Holds records logged before the logger is installed, and replays them once it is.
+///
+/// This installs a placeholder global logger that keeps up to `capacity` records; records beyond
+/// the capacity are dropped, and reported on standard error when the held records are replayed.
+/// [`Builder::apply`] then installs the configured logger behind the placeholder and replays the
+/// held records through it, so that they go through the filters and appenders like any other.
+/// Records logged during the replay are queued behind the held records, so that records are
+/// dispatched in the order they were logged.
+///
+/// Records are held regardless of their level. Call [`log::set_max_level`] afterward to hold
+/// only the records up to a level. Since records are formatted when they are replayed, their
+/// timestamps and diagnostics are those of the replay.
+///
+/// # Errors
+///
+/// Returns an error if a global logger has already been set.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::append;
+///
+/// logforth::buffer_early_records(1024).unwrap();
+/// log::info!("this record is replayed once the logger is installed");
+///
+/// logforth::builder()
+///     .dispatch(|d| d.append(append::Stdout::default()))
+///     .apply();
+/// ```
+///
+/// [`Builder::apply`]: crate::Builder::apply
+pub fn buffer_early_records(capacity: usize) -> Result<(), log::SetLoggerError> {
+    EARLY
+        .buffer
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .capacity = capacity;
+    log::set_logger(&EARLY)?;
+    log::set_max_level(LevelFilter::Trace);
+    INSTALLED.store(true, Ordering::Release);
+    Ok(())
+}
+
+/// Installs the logger behind the placeholder and replays the held records, or returns the
+/// logger back if the placeholder is not the global logger or already has a logger.
+pub(super) fn install(logger: Logger) -> Option<Logger> {
+    if !INSTALLED.load(Ordering::Acquire) {
+        return Some(logger);
+    }
+    EARLY.install(logger)
+}
+
+#[derive(Debug)]
+struct EarlyLogger {
+    logger: OnceLock<Logger>,
+    buffer: Mutex<Buffer>,
+    // whether the held records have been replayed, after which records bypass the buffer
+    replayed: AtomicBool,
+}
+
+impl EarlyLogger {
+    const fn new() -> Self {
+        Self {
+            logger: OnceLock::new(),
+            buffer: Mutex::new(Buffer {
+                capacity: 0,
+                records: Vec::new(),
+                dropped: 0,
+            }),
+            replayed: AtomicBool::new(false),
+        }
+    }
+
+    fn install(&self, logger: Logger) -> Option<Logger> {
+        {
+            let _buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(logger) = self.logger.set(logger) {
+                return Some(logger);
+            }
+        }
+
+        // Records logged during the replay, including those logged by appenders, are queued
+        // behind the held records rather than overtaking them. The replay drains the queue until
+        // it is empty, without holding the lock while the records are logged.
+        let logger = self.logger.get().expect("logger has just been set");
+        let mut dropped = 0;
+        loop {
+            let records = {
+                let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+                dropped += std::mem::take(&mut buffer.dropped);
+                let records = std::mem::take(&mut buffer.records);
+                if records.is_empty() {
+                    self.replayed.store(true, Ordering::Release);
+                    break;
+                }
+                records
+            };
+            for record in &records {
+                record.replay(logger);
+            }
+        }
+        if dropped > 0 {
+            eprintln!("logforth: dropped {dropped} records logged before the logger was installed");
+        }
+        None
+    }
+
+    fn replayed_logger(&self) -> Option<&Logger> {
+        if self.replayed.load(Ordering::Acquire) {
+            self.logger.get()
+        } else {
+            None
+        }
+    }
+}
+
+impl log::Log for EarlyLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        match self.logger.get() {
+            Some(logger) => logger.enabled(metadata),
+            None => true,
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if let Some(logger) = self.replayed_logger() {
+            logger.log(record);
+            return;
+        }
+
+        let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+        // the held records may have been replayed while waiting for the lock
+        match self.replayed_logger() {
+            Some(logger) => {
+                drop(buffer);
+                logger.log(record);
+            }
+            // records are not dropped once the logger is installed
+            None if self.logger.get().is_some() => buffer.records.push(EarlyRecord::new(record)),
+            None => buffer.push(record),
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(logger) = self.logger.get() {
+            logger.flush();
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Buffer {
+    capacity: usize,
+    records: Vec<EarlyRecord>,
+    dropped: usize,
+}
+
+impl Buffer {
+    fn push(&mut self, record: &Record) {
+        if self.records.len() < self.capacity {
+            self.records.push(EarlyRecord::new(record));
+        } else {
+            self.dropped += 1;
+        }
+    }
+}
+
+/// An owned copy of a record, with its message formatted and its key-values stringified.
+#[derive(Debug)]
+struct EarlyRecord {
+    level: Level,
+    target: String,
+    message: String,
+    module_path: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    kvs: Vec<(String, String)>,
+}
+
+impl EarlyRecord {
+    fn new(record: &Record) -> Self {
+        let mut kvs = KvCollector(Vec::new());
+        let _ = record.key_values().visit(&mut kvs);
+        Self {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+            module_path: record.module_path().map(ToOwned::to_owned),
+            file: record.file().map(ToOwned::to_owned),
+            line: record.line(),
+            kvs: kvs.0,
+        }
+    }
+
+    fn replay(&self, logger: &dyn log::Log) {
+        let kvs: Vec<(&str, &str)> = self
+            .kvs
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        logger.log(
+            &Record::builder()
+                .level(self.level)
+                .target(&self.target)
+                .args(format_args!("{}", self.message))
+                .module_path(self.module_path.as_deref())
+                .file(self.file.as_deref())
+                .line(self.line)
+                .key_values(&kvs)
+                .build(),
+        );
+    }
+}
+
+struct KvCollector(Vec<(String, String)>);
+
+impl<'kvs> VisitSource<'kvs> for KvCollector {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+        self.0.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use log::Log;
+
+    use super::*;
+    use crate::filter::LevelRemap;
+    use crate::logger::log_impl::Dispatch;
+    use crate::logger::log_impl::DispatchAppend;
+    use crate::Append;
+    use crate::Diagnostic;
+    use crate::RuntimeConfig;
+
+    #[derive(Default)]
+    struct Recording(Mutex<Vec<String>>);
+
+    impl log::Log for Recording {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            let mut kvs = KvCollector(Vec::new());
+            record.key_values().visit(&mut kvs).unwrap();
+            self.0.lock().unwrap().push(format!(
+                "{} {} {} {:?}",
+                record.level(),
+                record.target(),
+                record.args(),
+                kvs.0
+            ));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[derive(Debug)]
+    struct Nesting(&'static EarlyLogger, Arc<Mutex<Vec<String>>>);
+
+    impl Append for Nesting {
+        fn append(&self, record: &Record, _: &[Diagnostic]) -> anyhow::Result<()> {
+            let message = record.args().to_string();
+            if message == "first" {
+                self.0
+                    .log(&Record::builder().args(format_args!("nested")).build());
+            }
+            self.1.lock().unwrap().push(message);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_install_replays_in_order() {
+        let early: &'static EarlyLogger = Box::leak(Box::new(EarlyLogger::new()));
+        early.buffer.lock().unwrap().capacity = 2;
+        for message in ["first", "second"] {
+            early.log(&Record::builder().args(format_args!("{message}")).build());
+        }
+
+        let messages = Arc::new(Mutex::new(vec![]));
+        let append = DispatchAppend::new(None, Box::new(Nesting(early, messages.clone())));
+        let logger = Logger::new(
+            Arc::new([Dispatch::new(vec![], vec![], None, vec![append])]),
+            LevelRemap::default(),
+            None,
+            RuntimeConfig::default(),
+        );
+        assert!(early.install(logger).is_none());
+        early.log(&Record::builder().args(format_args!("third")).build());
+
+        // the record logged by the appender is queued behind the held records
+        assert_eq!(
+            *messages.lock().unwrap(),
+            ["first", "second", "nested", "third"]
+        );
+    }
+
+    #[test]
+    fn test_buffer_replay() {
+        let mut buffer = Buffer {
+            capacity: 2,
+            records: Vec::new(),
+            dropped: 0,
+        };
+        let kvs = [("user", "alice")];
+        for message in ["first", "second", "third"] {
+            buffer.push(
+                &Record::builder()
+                    .level(Level::Warn)
+                    .target("app")
+                    .args(format_args!("{message}"))
+                    .key_values(&kvs)
+                    .build(),
+            );
+        }
+        assert_eq!(buffer.dropped, 1);
+
+        let recording = Arc::new(Recording::default());
+        for record in &buffer.records {
+            record.replay(recording.as_ref());
+        }
+        assert_eq!(
+            *recording.0.lock().unwrap(),
+            [
+                r#"WARN app first [("user", "alice")]"#,
+                r#"WARN app second [("user", "alice")]"#,
+            ]
+        );
+    }
+}
@@ -12,15 +12,40 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
 use std::io::Write;
+use std::ops::ControlFlow;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::sync::RwLock;
+use std::time::Duration;
+use std::time::Instant;
 
+use log::Level;
 use log::Metadata;
 use log::Record;
 
+use super::runtime::ErrorHandler;
+use super::ErrorPolicy;
+use super::RuntimeConfig;
+use crate::backtrace::WithBacktrace;
+use crate::clock;
+use crate::fatal;
 use crate::filter::FilterResult;
+use crate::filter::LevelRemap;
+use crate::panic;
+use crate::panic::WithPanic;
+use crate::route;
 use crate::Append;
+use crate::AppendError;
 use crate::Diagnostic;
 use crate::Filter;
+use crate::Layout;
 
 /// A logger facade that dispatches log records to one or more [`Dispatch`] instances.
 ///
@@ -28,32 +53,293 @@ use crate::Filter;
 /// with the [`log`] crate.
 #[derive(Debug)]
 pub(super) struct Logger {
-    dispatches: Vec<Dispatch>,
+    dispatches: Arc<Dispatches>,
+    level_remap: LevelRemap,
+    backtrace_level: Option<Level>,
+    runtime: RuntimeConfig,
+    // whether a record was written to the last-resort appender
+    last_resort_used: AtomicBool,
 }
 
 impl Logger {
-    pub(super) fn new(dispatches: Vec<Dispatch>) -> Self {
-        Self { dispatches }
+    pub(super) fn new(
+        dispatches: Arc<[Dispatch]>,
+        level_remap: LevelRemap,
+        backtrace_level: Option<Level>,
+        runtime: RuntimeConfig,
+    ) -> Self {
+        Self {
+            dispatches: Arc::new(Dispatches::new(dispatches)),
+            level_remap,
+            backtrace_level,
+            runtime,
+            last_resort_used: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the dispatches, which may be replaced while the logger is installed.
+    pub(super) fn dispatches(&self) -> Arc<Dispatches> {
+        self.dispatches.clone()
+    }
+
+    fn log_normalized(&self, record: &Record) {
+        if !self.level_remap.is_empty() {
+            let level = self.level_remap.remap(record.target(), record.level());
+            if level != record.level() {
+                self.log_remapped(&record.to_builder().level(level).build());
+                return;
+            }
+        }
+
+        self.log_remapped(record);
+    }
+
+    fn log_remapped(&self, record: &Record) {
+        let capture = self
+            .backtrace_level
+            .is_some_and(|level| record.level() <= level);
+        self.dispatch(record, &mut LazyBacktrace::new(capture));
+
+        if let Some(handler) = &self.runtime.fatal_handler {
+            if fatal::is_fatal(record) {
+                self.dispatches.with(|dispatches| {
+                    for dispatch in dispatches {
+                        dispatch.flush();
+                    }
+                });
+                handler(record);
+            }
+        }
+    }
+
+    fn dispatch(&self, record: &Record, backtrace: &mut LazyBacktrace) {
+        self.dispatches
+            .with(|dispatches| self.dispatch_to(dispatches, record, backtrace));
+    }
+
+    fn dispatch_to(&self, dispatches: &[Dispatch], record: &Record, backtrace: &mut LazyBacktrace) {
+        let mut on_error = |err| match &self.runtime.error_handler {
+            Some(handler) => handler(record, err),
+            None => handle_error(record, err),
+        };
+        if let Some(route) = route::route(record) {
+            let mut routed = dispatches
+                .iter()
+                .filter(|dispatch| dispatch.routes.contains(&route))
+                .peekable();
+            if routed.peek().is_some() {
+                backtrace.with_record(record, |record| {
+                    for dispatch in routed {
+                        if dispatch
+                            .log_to_appends(record, None, self.runtime.error_policy, &mut on_error)
+                            .is_break()
+                        {
+                            return;
+                        }
+                    }
+                });
+                return;
+            }
+        }
+
+        let mut accepted = false;
+        for dispatch in dispatches.iter() {
+            match dispatch.log(record, backtrace, self.runtime.error_policy, &mut on_error) {
+                ControlFlow::Continue(delivered) => accepted |= delivered,
+                ControlFlow::Break(()) => return,
+            }
+        }
+
+        if !accepted && record.level() == Level::Error {
+            if let Some(append) = &self.runtime.last_resort {
+                backtrace.with_record(record, |record| {
+                    self.log_last_resort(append.as_ref(), record, &mut on_error)
+                });
+            }
+        }
+    }
+
+    /// Writes an error record rejected by every dispatch to the last-resort appender.
+    fn log_last_resort(
+        &self,
+        append: &dyn Append,
+        record: &Record,
+        on_error: &mut dyn FnMut(anyhow::Error),
+    ) {
+        if !self.last_resort_used.swap(true, Ordering::Relaxed) {
+            eprintln!(
+                "logforth: an error record of target `{}` was rejected by every dispatch and \
+                 written to the last-resort appender {}; check the filters",
+                record.target(),
+                append.name(),
+            );
+        }
+        if let Err(err) = append.append(record, &[]) {
+            on_error(AppendError::new(None, append, err).into());
+        }
     }
 }
 
 impl log::Log for Logger {
     fn enabled(&self, metadata: &Metadata) -> bool {
+        if !self.level_remap.is_empty() {
+            let level = self.level_remap.remap(metadata.target(), metadata.level());
+            if level != metadata.level() {
+                let metadata = Metadata::builder()
+                    .level(level)
+                    .target(metadata.target())
+                    .build();
+                return self.dispatches.with(|dispatches| {
+                    dispatches
+                        .iter()
+                        .any(|dispatch| dispatch.enabled(&metadata))
+                });
+            }
+        }
+
         self.dispatches
-            .iter()
-            .any(|dispatch| dispatch.enabled(metadata))
+            .with(|dispatches| dispatches.iter().any(|dispatch| dispatch.enabled(metadata)))
     }
 
     fn log(&self, record: &Record) {
-        for dispatch in &self.dispatches {
-            if let Err(err) = dispatch.log(record) {
-                handle_error(record, err);
+        if self.runtime.normalize_panics && record.level() == Level::Error {
+            let message = record.args().to_string();
+            if let Some(panic) = panic::parse(&message) {
+                let kvs = WithPanic {
+                    source: record.key_values(),
+                    panic: &panic,
+                };
+                self.log_normalized(
+                    &record
+                        .to_builder()
+                        .args(format_args!("{}", panic.message))
+                        .key_values(&kvs)
+                        .build(),
+                );
+                return;
             }
         }
+
+        self.log_normalized(record);
     }
 
     fn flush(&self) {
-        for dispatch in &self.dispatches {
+        self.dispatches.with(|dispatches| {
+            for dispatch in dispatches {
+                match self.runtime.flush_window {
+                    Some(window) => dispatch.flush_coalesced(window),
+                    None => dispatch.flush(),
+                }
+            }
+        });
+    }
+}
+
+// the dispatches of the logger installed by this crate
+static INSTALLED: OnceLock<Arc<Dispatches>> = OnceLock::new();
+
+// the error handler of the logger installed by this crate, if configured
+static ERROR_HANDLER: OnceLock<ErrorHandler> = OnceLock::new();
+
+pub(super) fn install(dispatches: Arc<Dispatches>, error_handler: Option<ErrorHandler>) {
+    if INSTALLED.set(dispatches).is_ok() {
+        if let Some(error_handler) = error_handler {
+            let _ = ERROR_HANDLER.set(error_handler);
+        }
+    }
+}
+
+/// Reports an error that does not come from dispatching a record, e.g., records an appender
+/// lost on shutdown, to the error handler of the installed logger, or to stderr by default.
+#[cfg(feature = "rolling-file")]
+pub(crate) fn report_error(record: &Record, error: anyhow::Error) {
+    match ERROR_HANDLER.get() {
+        Some(handler) => handler(record, error),
+        None => handle_error(record, error),
+    }
+}
+
+/// Returns the dispatches of the logger installed by this crate, if any.
+pub(super) fn installed() -> Option<&'static Dispatches> {
+    INSTALLED.get().map(AsRef::as_ref)
+}
+
+// the source of the generations of dispatches, unique across loggers
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // the dispatches last loaded by this thread, with their generation
+    static LOADED: RefCell<Option<(u64, Arc<[Dispatch]>)>> = const { RefCell::new(None) };
+}
+
+/// The dispatches of a [`Logger`], which can be replaced as a whole.
+///
+/// Records are dispatched to a snapshot of the dispatches, so that replacing them does not wait
+/// for appenders. Each thread keeps the snapshot it last loaded until the dispatches are
+/// replaced, so that loading them takes neither the lock nor a reference count in the steady
+/// state. A thread that does not log again keeps the previous dispatches alive until it exits;
+/// they are flushed on replacement.
+#[derive(Debug)]
+pub(super) struct Dispatches {
+    current: RwLock<Arc<[Dispatch]>>,
+    // the generation of `current`, renewed on every replacement
+    generation: AtomicU64,
+}
+
+impl Dispatches {
+    pub(super) fn new(dispatches: Arc<[Dispatch]>) -> Self {
+        Self {
+            current: RwLock::new(dispatches),
+            generation: AtomicU64::new(GENERATION.fetch_add(1, Ordering::Relaxed)),
+        }
+    }
+
+    /// Returns a snapshot of the dispatches.
+    pub(super) fn load(&self) -> Arc<[Dispatch]> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Calls `f` with the dispatches, loaded from the snapshot of this thread if it is current.
+    pub(super) fn with<R>(&self, f: impl FnOnce(&[Dispatch]) -> R) -> R {
+        // read before the snapshot, so that a snapshot is never tagged with a newer generation
+        let generation = self.generation.load(Ordering::Acquire);
+        let mut f = Some(f);
+        let loaded = LOADED.try_with(|loaded| {
+            let mut loaded = loaded.try_borrow_mut().ok()?;
+            let stale = match &*loaded {
+                Some((loaded, _)) if *loaded == generation => None,
+                _ => loaded.replace((generation, self.load())),
+            };
+            let (_, dispatches) = loaded.as_ref()?;
+            let result = f.take().map(|f| f(dispatches));
+            drop(loaded);
+            // appenders may log when dropped
+            drop(stale);
+            result
+        });
+        match loaded {
+            Ok(Some(result)) => result,
+            // the thread is exiting, or a record is logged while dispatching
+            _ => {
+                let f = f.expect("not called on the snapshot of this thread");
+                f(&self.load())
+            }
+        }
+    }
+
+    /// Replaces the dispatches, and flushes the previous ones.
+    pub(super) fn replace(&self, dispatches: Arc<[Dispatch]>) {
+        let previous = {
+            let mut current = self.current.write().unwrap_or_else(|e| e.into_inner());
+            let previous = std::mem::replace(&mut *current, dispatches);
+            let generation = GENERATION.fetch_add(1, Ordering::Relaxed);
+            self.generation.store(generation, Ordering::Release);
+            previous
+        };
+        for dispatch in previous.iter() {
             dispatch.flush();
         }
     }
@@ -65,19 +351,64 @@ impl log::Log for Logger {
 /// Each [`Dispatch`] instance contains a set of filters and appenders.
 ///
 /// `filters` are used to determine whether a log record should be passed to the appenders.
-/// `appends` are used to write log records to a destination.
+/// `appends` are used to write log records to a destination, optionally under a name that
+/// [`FilterResult::AcceptOnly`] can route to.
+/// `layout` is used by appenders that are not configured with a layout of their own.
 #[derive(Debug)]
 pub(super) struct Dispatch {
     filters: Vec<Filter>,
     diagnostics: Vec<Diagnostic>,
-    appends: Vec<Box<dyn Append>>,
+    layout: Option<Layout>,
+    appends: Vec<DispatchAppend>,
+    // the routes of records delivered to this dispatch regardless of the filters
+    routes: Vec<String>,
+}
+
+/// An appender of a [`Dispatch`], with the name it was registered under, if any.
+#[derive(Debug)]
+pub(super) struct DispatchAppend {
+    name: Option<String>,
+    append: Box<dyn Append>,
+    // when the appender was last flushed by the logger, to coalesce flushes
+    last_flush: Mutex<Option<Instant>>,
+    // whether records were appended since the last coalesced flush
+    appended: AtomicBool,
+}
+
+impl DispatchAppend {
+    pub(super) fn new(name: Option<String>, append: Box<dyn Append>) -> Self {
+        Self {
+            name,
+            append,
+            last_flush: Mutex::new(None),
+            appended: AtomicBool::new(false),
+        }
+    }
+
+    /// Flushes the appender unless it was flushed within `window` and nothing was appended
+    /// since, so that records are never left unflushed.
+    fn flush_coalesced(&self, window: Duration, layout: Option<&Layout>) {
+        let now = clock::instant();
+        {
+            let mut last_flush = self.last_flush.lock().unwrap_or_else(|e| e.into_inner());
+            let recent =
+                last_flush.is_some_and(|last_flush| now.duration_since(last_flush) < window);
+            if recent && !self.appended.load(Ordering::Acquire) {
+                return;
+            }
+            *last_flush = Some(now);
+            self.appended.store(false, Ordering::Release);
+        }
+        self.append.flush_with_layout(layout);
+    }
 }
 
 impl Dispatch {
     pub(super) fn new(
         filters: Vec<Filter>,
         diagnostics: Vec<Diagnostic>,
-        appends: Vec<Box<dyn Append>>,
+        layout: Option<Layout>,
+        appends: Vec<DispatchAppend>,
     ) -> Self {
         debug_assert!(
             !appends.is_empty(),
@@ -87,15 +418,34 @@ impl Dispatch {
         Self {
             filters,
             diagnostics,
+            layout,
             appends,
+            routes: vec![],
         }
     }
 
+    pub(super) fn with_routes(mut self, routes: Vec<String>) -> Self {
+        self.routes = routes;
+        self
+    }
+
+    pub(super) fn with_global_diagnostics(mut self, diagnostics: &[Diagnostic]) -> Self {
+        if !diagnostics.is_empty() {
+            let mut merged = diagnostics.to_vec();
+            merged.append(&mut self.diagnostics);
+            self.diagnostics = merged;
+        }
+        self
+    }
+
     fn enabled(&self, metadata: &Metadata) -> bool {
         for filter in &self.filters {
             match filter.enabled(metadata) {
                 FilterResult::Reject => return false,
-                FilterResult::Accept => return true,
+                // the decision is deferred until the record is logged
+                FilterResult::Accept | FilterResult::AcceptOnly(_) | FilterResult::Unknown => {
+                    return true
+                }
                 FilterResult::Neutral => {}
             }
         }
@@ -103,40 +453,167 @@ impl Dispatch {
         true
     }
 
-    fn log(&self, record: &Record) -> anyhow::Result<()> {
+    /// Writes the record to the appenders accepted by the filters.
+    ///
+    /// Errors are passed to `on_error`, and `policy` decides whether to continue. Returns
+    /// [`ControlFlow::Break`] if the record should not be delivered to other dispatches, or
+    /// whether the filters accepted the record otherwise.
+    fn log(
+        &self,
+        record: &Record,
+        backtrace: &mut LazyBacktrace,
+        policy: ErrorPolicy,
+        on_error: &mut dyn FnMut(anyhow::Error),
+    ) -> ControlFlow<(), bool> {
+        let diagnostics = &self.diagnostics;
+        let mut only = None;
+        let mut accepted = true;
+        let mut summaries = vec![];
         for filter in &self.filters {
-            match filter.matches(record) {
-                FilterResult::Reject => return Ok(()),
+            let (result, summary) = filter.matches_summarized(record, diagnostics);
+            summaries.extend(summary);
+            match result {
+                FilterResult::Reject => {
+                    accepted = false;
+                    break;
+                }
                 FilterResult::Accept => break,
-                FilterResult::Neutral => {}
+                FilterResult::AcceptOnly(names) => {
+                    only = Some(names);
+                    break;
+                }
+                FilterResult::Neutral | FilterResult::Unknown => {}
             }
         }
+        if accepted {
+            backtrace.with_record(record, |record| {
+                self.log_to_appends(record, only.as_deref(), policy, on_error)
+            })?;
+        }
+        // rate limit summaries follow the record that ended the window, whatever its fate
+        for summary in summaries {
+            let mut flow = ControlFlow::Continue(());
+            summary.with_record(|record| {
+                flow = self.log_to_appends(record, None, policy, on_error);
+            });
+            flow?;
+        }
+        ControlFlow::Continue(accepted)
+    }
 
+    /// Writes the record to the appenders, or to the named ones in `only` if set.
+    fn log_to_appends(
+        &self,
+        record: &Record,
+        only: Option<&[String]>,
+        policy: ErrorPolicy,
+        on_error: &mut dyn FnMut(anyhow::Error),
+    ) -> ControlFlow<()> {
         let diagnostics = &self.diagnostics;
-        for append in &self.appends {
-            append.append(record, diagnostics)?;
+        let layout = self.layout.as_ref();
+        for dispatch_append in &self.appends {
+            let DispatchAppend { name, append, .. } = dispatch_append;
+            if let Some(names) = only {
+                let routed = name.as_ref().is_some_and(|name| names.contains(name));
+                if !routed {
+                    continue;
+                }
+            }
+            dispatch_append.appended.store(true, Ordering::Release);
+            let mut result = append.append_with_layout(record, diagnostics, layout);
+            if result.is_err() && policy == ErrorPolicy::RetryOnce {
+                result = append.append_with_layout(record, diagnostics, layout);
+            }
+            if let Err(err) = result {
+                on_error(AppendError::new(name.as_deref(), append.as_ref(), err).into());
+                match policy {
+                    ErrorPolicy::Continue | ErrorPolicy::RetryOnce => {}
+                    ErrorPolicy::SkipDispatch => return ControlFlow::Continue(()),
+                    ErrorPolicy::Abort => return ControlFlow::Break(()),
+                }
+            }
         }
-        Ok(())
+        ControlFlow::Continue(())
+    }
+
+    /// Writes the record to every appender regardless of the filters, then flushes them.
+    pub(super) fn selftest<'a>(
+        &'a self,
+        record: &'a Record,
+    ) -> impl Iterator<Item = (Option<&'a str>, &'a str, anyhow::Result<()>)> + 'a {
+        let layout = self.layout.as_ref();
+        self.appends
+            .iter()
+            .map(move |DispatchAppend { name, append, .. }| {
+                let result = append.append_with_layout(record, &self.diagnostics, layout);
+                append.flush_with_layout(layout);
+                (name.as_deref(), append.name(), result)
+            })
     }
 
     fn flush(&self) {
+        for DispatchAppend { append, .. } in &self.appends {
+            append.flush_with_layout(self.layout.as_ref());
+        }
+    }
+
+    fn flush_coalesced(&self, window: Duration) {
         for append in &self.appends {
-            append.flush();
+            append.flush_coalesced(window, self.layout.as_ref());
+        }
+    }
+}
+
+/// A backtrace captured once a dispatch accepts the record, if the record's level asks for one.
+struct LazyBacktrace {
+    capture: bool,
+    captured: Option<String>,
+}
+
+impl LazyBacktrace {
+    fn new(capture: bool) -> Self {
+        Self {
+            capture,
+            captured: None,
+        }
+    }
+
+    /// Calls `f` with the record, with the backtrace attached if it is to be captured.
+    fn with_record<R>(&mut self, record: &Record, f: impl FnOnce(&Record) -> R) -> R {
+        if !self.capture {
+            return f(record);
         }
+        let backtrace = self
+            .captured
+            .get_or_insert_with(|| Backtrace::force_capture().to_string());
+        let kvs = WithBacktrace {
+            source: record.key_values(),
+            backtrace,
+        };
+        f(&record.to_builder().key_values(&kvs).build())
     }
 }
 
 fn handle_error(record: &Record, error: anyhow::Error) {
+    let appender = match error.downcast_ref::<AppendError>() {
+        Some(error) => match error.name() {
+            Some(name) => format!("`{name}` ({})", error.kind()),
+            None => error.kind().to_owned(),
+        },
+        None => "unknown".to_owned(),
+    };
     let Err(fallback_error) = write!(
         std::io::stderr(),
         r###"
 Error perform logging.
     Attempted to log: {args}
     Record: {record:?}
+    Appender: {appender}
     Error: {error:?}
 "###,
         args = record.args(),
         record = record,
+        appender = appender,
         error = error,
     ) else {
         return;
@@ -156,3 +633,454 @@ Error performing stderr logging after error occurred during regular logging.
         fallback_error = fallback_error,
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Mutex;
+
+    use log::Log;
+
+    use super::*;
+
+    /// Fails the first `failures` records, then counts the written ones.
+    #[derive(Debug)]
+    struct Flaky {
+        failures: AtomicUsize,
+        written: Arc<AtomicUsize>,
+    }
+
+    impl Append for Flaky {
+        fn append(&self, _: &Record, _: &[Diagnostic]) -> anyhow::Result<()> {
+            let failures = self.failures.load(Ordering::Relaxed);
+            if failures > 0 {
+                self.failures.store(failures - 1, Ordering::Relaxed);
+                anyhow::bail!("flaky appender failed");
+            }
+            self.written.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    fn flaky(failures: usize, written: &Arc<AtomicUsize>) -> DispatchAppend {
+        DispatchAppend::new(
+            None,
+            Box::new(Flaky {
+                failures: AtomicUsize::new(failures),
+                written: written.clone(),
+            }),
+        )
+    }
+
+    /// Logs a record to two dispatches, whose first appender fails once, and returns the number of
+    /// records written by the healthy appenders of each dispatch and the number of errors.
+    fn run(policy: ErrorPolicy) -> (usize, usize, usize) {
+        let first = Arc::new(AtomicUsize::new(0));
+        let second = Arc::new(AtomicUsize::new(0));
+        let errors = Arc::new(Mutex::new(vec![]));
+        let dispatches: Arc<[Dispatch]> = Arc::new([
+            Dispatch::new(
+                vec![],
+                vec![],
+                None,
+                vec![flaky(1, &first), flaky(0, &first)],
+            ),
+            Dispatch::new(vec![], vec![], None, vec![flaky(0, &second)]),
+        ]);
+        let runtime = RuntimeConfig::new().error_policy(policy).error_handler({
+            let errors = errors.clone();
+            move |record, err| {
+                let kind = err.downcast_ref::<AppendError>().unwrap().kind();
+                assert_eq!(kind, "logforth::logger::log_impl::tests::Flaky");
+                errors
+                    .lock()
+                    .unwrap()
+                    .push(format!("{}: {err}", record.args()))
+            }
+        });
+
+        let logger = Logger::new(dispatches, LevelRemap::default(), None, runtime);
+        logger.log(&Record::builder().args(format_args!("hello")).build());
+
+        let errors = errors.lock().unwrap();
+        assert!(errors
+            .iter()
+            .all(|err| err == "hello: flaky appender failed"));
+        (
+            first.load(Ordering::Relaxed),
+            second.load(Ordering::Relaxed),
+            errors.len(),
+        )
+    }
+
+    #[test]
+    fn test_route() {
+        let general = Arc::new(AtomicUsize::new(0));
+        let audit = Arc::new(AtomicUsize::new(0));
+        let dispatches: Arc<[Dispatch]> = Arc::new([
+            Dispatch::new(vec![], vec![], None, vec![flaky(0, &general)]),
+            Dispatch::new(
+                vec![log::LevelFilter::Off.into()],
+                vec![],
+                None,
+                vec![flaky(0, &audit)],
+            )
+            .with_routes(vec!["audit".to_string()]),
+        ]);
+        let logger = Logger::new(
+            dispatches,
+            LevelRemap::default(),
+            None,
+            RuntimeConfig::new(),
+        );
+        let log = |route: &str| {
+            let kvs = [(route::ROUTE_KEY, route)];
+            logger.log(
+                &Record::builder()
+                    .args(format_args!("hello"))
+                    .key_values(&kvs)
+                    .build(),
+            );
+        };
+
+        // routed records bypass the filters of the tagged dispatches only
+        log("audit");
+        assert_eq!(general.load(Ordering::Relaxed), 0);
+        assert_eq!(audit.load(Ordering::Relaxed), 1);
+
+        // unknown routes are dispatched as usual
+        log("security");
+        assert_eq!(general.load(Ordering::Relaxed), 1);
+        assert_eq!(audit.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_accept_only() {
+        let counters: Vec<_> = (0..3).map(|_| Arc::new(AtomicUsize::new(0))).collect();
+        let named = |name: &str, written: &Arc<AtomicUsize>| {
+            let append = Flaky {
+                failures: AtomicUsize::new(0),
+                written: written.clone(),
+            };
+            DispatchAppend::new(Some(name.to_string()), Box::new(append))
+        };
+        // routes read from runtime configuration
+        let config = String::from("db=stderr,audit;*=stdout");
+        let routes: Vec<(String, FilterResult)> = config
+            .split(';')
+            .map(|route| {
+                let (target, names) = route.split_once('=').unwrap();
+                (
+                    target.to_string(),
+                    FilterResult::accept_only(names.split(',')),
+                )
+            })
+            .collect();
+        let filter = crate::filter::CustomFilter::new(move |metadata| {
+            routes
+                .iter()
+                .find(|(target, _)| target == metadata.target() || target == "*")
+                .map_or(FilterResult::Neutral, |(_, result)| result.clone())
+        });
+        let dispatches: Arc<[Dispatch]> = Arc::new([Dispatch::new(
+            vec![filter.into()],
+            vec![],
+            None,
+            vec![
+                named("stdout", &counters[0]),
+                named("stderr", &counters[1]),
+                named("audit", &counters[2]),
+                flaky(0, &counters[2]),
+            ],
+        )]);
+        let logger = Logger::new(
+            dispatches,
+            LevelRemap::default(),
+            None,
+            RuntimeConfig::new(),
+        );
+        let log = |target| {
+            logger.log(
+                &Record::builder()
+                    .args(format_args!("hello"))
+                    .target(target)
+                    .build(),
+            );
+        };
+        let written = || {
+            counters
+                .iter()
+                .map(|written| written.load(Ordering::Relaxed))
+                .collect::<Vec<_>>()
+        };
+
+        // unnamed appenders never receive routed records
+        log("db");
+        assert_eq!(written(), [0, 1, 1]);
+        log("app");
+        assert_eq!(written(), [1, 1, 1]);
+    }
+
+    #[test]
+    fn test_last_resort() {
+        let written = Arc::new(AtomicUsize::new(0));
+        let last_resort = Arc::new(AtomicUsize::new(0));
+        let dispatches: Arc<[Dispatch]> = Arc::new([Dispatch::new(
+            vec!["info,db=off".into()],
+            vec![],
+            None,
+            vec![flaky(0, &written)],
+        )]);
+        let runtime = RuntimeConfig::new().last_resort_append(Flaky {
+            failures: AtomicUsize::new(0),
+            written: last_resort.clone(),
+        });
+        let logger = Logger::new(dispatches, LevelRemap::default(), None, runtime);
+        let log = |level, target| {
+            logger.log(
+                &Record::builder()
+                    .args(format_args!("hello"))
+                    .level(level)
+                    .target(target)
+                    .build(),
+            )
+        };
+
+        log(Level::Error, "app");
+        assert_eq!(written.load(Ordering::Relaxed), 1);
+        assert_eq!(last_resort.load(Ordering::Relaxed), 0);
+
+        log(Level::Error, "db");
+        log(Level::Error, "db");
+        assert_eq!(written.load(Ordering::Relaxed), 1);
+        assert_eq!(last_resort.load(Ordering::Relaxed), 2);
+
+        // only error records are rescued
+        log(Level::Warn, "db");
+        assert_eq!(last_resort.load(Ordering::Relaxed), 2);
+    }
+
+    /// Records the appended records, flushes and fatal handler calls in order.
+    #[derive(Debug)]
+    struct Recording(Arc<Mutex<Vec<String>>>);
+
+    impl Append for Recording {
+        fn append(&self, record: &Record, _: &[Diagnostic]) -> anyhow::Result<()> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("append {}", record.args()));
+            Ok(())
+        }
+
+        fn flush(&self) {
+            self.0.lock().unwrap().push("flush".to_string());
+        }
+    }
+
+    #[test]
+    fn test_on_fatal() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let append = DispatchAppend::new(None, Box::new(Recording(events.clone())));
+        let dispatches: Arc<[Dispatch]> =
+            Arc::new([Dispatch::new(vec![], vec![], None, vec![append])]);
+        let runtime = RuntimeConfig::new().on_fatal({
+            let events = events.clone();
+            move |record| {
+                events
+                    .lock()
+                    .unwrap()
+                    .push(format!("fatal {}", record.args()))
+            }
+        });
+        let logger = Logger::new(dispatches, LevelRemap::default(), None, runtime);
+
+        logger.log(&Record::builder().args(format_args!("error")).build());
+        let kvs = [(fatal::FATAL_KEY, true)];
+        logger.log(
+            &Record::builder()
+                .args(format_args!("boom"))
+                .key_values(&kvs)
+                .build(),
+        );
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["append error", "append boom", "flush", "fatal boom"]
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_summary() {
+        let window = Duration::from_millis(100);
+        let events = Arc::new(Mutex::new(vec![]));
+        let append = DispatchAppend::new(None, Box::new(Recording(events.clone())));
+        let filter = crate::filter::RateLimitFilter::new(1, window);
+        let dispatches: Arc<[Dispatch]> = Arc::new([Dispatch::new(
+            vec![filter.into()],
+            vec![],
+            None,
+            vec![append],
+        )]);
+        let logger = Logger::new(
+            dispatches,
+            LevelRemap::default(),
+            None,
+            RuntimeConfig::new(),
+        );
+        let log = |message: &str| {
+            logger.log(
+                &Record::builder()
+                    .args(format_args!("{message}"))
+                    .target("app")
+                    .build(),
+            )
+        };
+
+        log("first");
+        log("rejected");
+        std::thread::sleep(window);
+        log("second");
+
+        // the summary follows the record that starts the next window
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "append first",
+                "append second",
+                "append rate limit: suppressed 1 records of app in the last 100ms"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_backtrace_after_filters() {
+        /// Records whether the appended records carry a backtrace.
+        #[derive(Debug)]
+        struct Backtraces(Arc<Mutex<Vec<bool>>>);
+
+        impl Append for Backtraces {
+            fn append(&self, record: &Record, _: &[Diagnostic]) -> anyhow::Result<()> {
+                let captured = crate::backtrace::backtrace(record).is_some();
+                self.0.lock().unwrap().push(captured);
+                Ok(())
+            }
+        }
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let filter = crate::filter::CustomFilter::with_diagnostics(|record, _| {
+            match crate::backtrace::backtrace(record) {
+                None => FilterResult::Neutral,
+                Some(_) => FilterResult::Reject,
+            }
+        });
+        let append = DispatchAppend::new(None, Box::new(Backtraces(seen.clone())));
+        let dispatches: Arc<[Dispatch]> = Arc::new([Dispatch::new(
+            vec![filter.into()],
+            vec![],
+            None,
+            vec![append],
+        )]);
+        let logger = Logger::new(
+            dispatches,
+            LevelRemap::default(),
+            Some(Level::Error),
+            RuntimeConfig::new(),
+        );
+
+        for level in [Level::Error, Level::Warn] {
+            logger.log(
+                &Record::builder()
+                    .args(format_args!("hello"))
+                    .level(level)
+                    .build(),
+            );
+        }
+        assert_eq!(*seen.lock().unwrap(), [true, false]);
+    }
+
+    #[test]
+    fn test_error_policy() {
+        assert_eq!(run(ErrorPolicy::SkipDispatch), (0, 1, 1));
+        assert_eq!(run(ErrorPolicy::Continue), (1, 1, 1));
+        assert_eq!(run(ErrorPolicy::RetryOnce), (2, 1, 0));
+        assert_eq!(run(ErrorPolicy::Abort), (0, 0, 1));
+    }
+
+    #[test]
+    fn test_replace_dispatches() {
+        let recording = |events: &Arc<Mutex<Vec<String>>>| {
+            let append = DispatchAppend::new(None, Box::new(Recording(events.clone())));
+            Arc::new([Dispatch::new(vec![], vec![], None, vec![append])])
+        };
+        let previous = Arc::new(Mutex::new(vec![]));
+        let next = Arc::new(Mutex::new(vec![]));
+        let logger = Logger::new(
+            recording(&previous),
+            LevelRemap::default(),
+            None,
+            RuntimeConfig::default(),
+        );
+
+        logger.log(&Record::builder().args(format_args!("before")).build());
+        logger.dispatches().replace(recording(&next));
+        logger.log(&Record::builder().args(format_args!("after")).build());
+
+        assert_eq!(*previous.lock().unwrap(), ["append before", "flush"]);
+        assert_eq!(*next.lock().unwrap(), ["append after"]);
+    }
+
+    #[test]
+    fn test_load_dispatches() {
+        let recording = |events: &Arc<Mutex<Vec<String>>>| -> Arc<[Dispatch]> {
+            let append = DispatchAppend::new(None, Box::new(Recording(events.clone())));
+            Arc::new([Dispatch::new(vec![], vec![], None, vec![append])])
+        };
+        let previous = Arc::new(Mutex::new(vec![]));
+        let next = Arc::new(Mutex::new(vec![]));
+        let dispatches = Arc::new(Dispatches::new(recording(&previous)));
+        let snapshot = dispatches.load();
+        let loaded =
+            |dispatches: &Dispatches| dispatches.with(|loaded| std::ptr::eq(loaded, &*snapshot));
+
+        // nested loads fall back to a fresh snapshot
+        assert!(dispatches.with(|_| loaded(&dispatches)));
+        assert!(loaded(&dispatches));
+
+        // a replacement on another thread invalidates the snapshot of this thread
+        let replaced = dispatches.clone();
+        std::thread::spawn(move || {
+            replaced.with(|_| ());
+            replaced.replace(recording(&next));
+        })
+        .join()
+        .unwrap();
+        assert!(!loaded(&dispatches));
+        assert_eq!(*previous.lock().unwrap(), ["flush"]);
+    }
+
+    #[test]
+    fn test_coalesce_flushes() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let append = DispatchAppend::new(None, Box::new(Recording(events.clone())));
+        let dispatches: Arc<[Dispatch]> =
+            Arc::new([Dispatch::new(vec![], vec![], None, vec![append])]);
+        let runtime = RuntimeConfig::new().coalesce_flushes(Duration::from_secs(3600));
+        let logger = Logger::new(dispatches, LevelRemap::default(), None, runtime);
+
+        logger.log(&Record::builder().args(format_args!("first")).build());
+        logger.flush();
+        logger.flush();
+        assert_eq!(*events.lock().unwrap(), ["append first", "flush"]);
+
+        // records appended within the window are flushed by the next flush
+        logger.log(&Record::builder().args(format_args!("second")).build());
+        logger.flush();
+        logger.flush();
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["append first", "flush", "append second", "flush"]
+        );
+    }
+}
@@ -15,4 +15,17 @@
 mod builder;
 pub use builder::*;
 
+mod early;
+pub use early::buffer_early_records;
+
 mod log_impl;
+#[cfg(feature = "rolling-file")]
+pub(crate) use log_impl::report_error;
+
+mod runtime;
+pub use runtime::AppendError;
+pub use runtime::ErrorPolicy;
+pub use runtime::RuntimeConfig;
+
+pub mod selftest;
+pub mod startup;
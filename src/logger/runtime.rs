@@ -0,0 +1,272 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::Record;
+
+use crate::append::Stderr;
+use crate::Append;
+
+pub(super) type ErrorHandler = Arc<dyn Fn(&Record, anyhow::Error) + Send + Sync>;
+type FatalHandler = Arc<dyn Fn(&Record) + Send + Sync>;
+
+/// What happens to a record when an appender fails to write it.
+///
+/// In every case, the error is reported to the [error handler](RuntimeConfig::error_handler)
+/// along with the record.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorPolicy {
+    /// Skip the remaining appenders of the failing dispatch, and continue with the other
+    /// dispatches.
+    #[default]
+    SkipDispatch,
+    /// Continue with the remaining appenders and dispatches.
+    Continue,
+    /// Retry the failing appender once, then continue with the remaining appenders and dispatches.
+    RetryOnce,
+    /// Stop delivering the record, skipping the remaining appenders and dispatches.
+    Abort,
+}
+
+/// Runtime settings shared by the logger and the appenders that opt in.
+///
+/// The logger applies the coarse clock and the error handler when it's installed with
+/// [`Builder::runtime`]. Non-blocking appenders inherit the worker defaults with
+/// `NonBlockingBuilder::runtime`, so the same knobs are not repeated on every builder.
+///
+/// [`Builder::runtime`]: crate::Builder::runtime
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use logforth::append;
+/// use logforth::RuntimeConfig;
+///
+/// let runtime = RuntimeConfig::new()
+///     .shutdown_timeout(Duration::from_secs(5))
+///     .error_handler(|record, err| eprintln!("failed to log {:?}: {err}", record.args()));
+///
+/// logforth::builder()
+///     .runtime(runtime)
+///     .dispatch(|d| d.append(append::Stdout::default()))
+///     .apply();
+/// ```
+#[derive(Clone, Default)]
+pub struct RuntimeConfig {
+    pub(crate) buffered_lines_limit: Option<usize>,
+    pub(crate) shutdown_timeout: Option<Duration>,
+    pub(crate) coarse_clock: Option<Duration>,
+    pub(crate) error_handler: Option<ErrorHandler>,
+    pub(crate) error_policy: ErrorPolicy,
+    pub(crate) fatal_handler: Option<FatalHandler>,
+    pub(crate) normalize_panics: bool,
+    pub(crate) flush_window: Option<Duration>,
+    pub(crate) last_resort: Option<Arc<dyn Append>>,
+}
+
+impl fmt::Debug for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RuntimeConfig")
+            .field("buffered_lines_limit", &self.buffered_lines_limit)
+            .field("shutdown_timeout", &self.shutdown_timeout)
+            .field("coarse_clock", &self.coarse_clock)
+            .field("error_handler", &self.error_handler.as_ref().map(|_| ".."))
+            .field("error_policy", &self.error_policy)
+            .field("fatal_handler", &self.fatal_handler.as_ref().map(|_| ".."))
+            .field("normalize_panics", &self.normalize_panics)
+            .field("flush_window", &self.flush_window)
+            .field("last_resort", &self.last_resort.as_ref().map(|a| a.name()))
+            .finish()
+    }
+}
+
+impl RuntimeConfig {
+    /// Creates a new [`RuntimeConfig`] with the default settings of each component.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of lines non-blocking appenders buffer before dropping logs or exerting
+    /// backpressure on senders.
+    pub fn buffered_lines_limit(mut self, buffered_lines_limit: usize) -> Self {
+        self.buffered_lines_limit = Some(buffered_lines_limit);
+        self
+    }
+
+    /// Sets how long dropping the worker guard of a non-blocking appender waits for pending
+    /// records to be written.
+    pub fn shutdown_timeout(mut self, shutdown_timeout: Duration) -> Self {
+        self.shutdown_timeout = Some(shutdown_timeout);
+        self
+    }
+
+    /// Enables the coarse clock with the given resolution.
+    ///
+    /// See [`Builder::coarse_clock`] for details.
+    ///
+    /// [`Builder::coarse_clock`]: crate::Builder::coarse_clock
+    pub fn coarse_clock(mut self, resolution: Duration) -> Self {
+        self.coarse_clock = Some(resolution);
+        self
+    }
+
+    /// Sets the handler called when an appender fails to write a record.
+    ///
+    /// The error is an [`AppendError`], which tells which appender failed. By default, the error
+    /// is reported to stderr.
+    pub fn error_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Record, anyhow::Error) + Send + Sync + 'static,
+    {
+        self.error_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets what happens to a record when an appender fails to write it. Default to
+    /// [`ErrorPolicy::SkipDispatch`].
+    pub fn error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    /// Sets the handler called after a [fatal](crate::fatal) record is dispatched.
+    ///
+    /// See [`Builder::on_fatal`] for details.
+    ///
+    /// [`Builder::on_fatal`]: crate::Builder::on_fatal
+    pub fn on_fatal<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Record) + Send + Sync + 'static,
+    {
+        self.fatal_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets whether the logger parses panic messages into structured key-values. Default to
+    /// `false`.
+    ///
+    /// See [`Builder::normalize_panics`] for details.
+    ///
+    /// [`Builder::normalize_panics`]: crate::Builder::normalize_panics
+    pub fn normalize_panics(mut self, normalize_panics: bool) -> Self {
+        self.normalize_panics = normalize_panics;
+        self
+    }
+
+    /// Coalesces the flushes of each appender within the given window.
+    ///
+    /// See [`Builder::coalesce_flushes`] for details.
+    ///
+    /// [`Builder::coalesce_flushes`]: crate::Builder::coalesce_flushes
+    pub fn coalesce_flushes(mut self, window: Duration) -> Self {
+        self.flush_window = Some(window);
+        self
+    }
+
+    /// Sets whether error records rejected by every dispatch are written to stderr. Default to
+    /// `false`.
+    ///
+    /// See [`Builder::last_resort`] for details.
+    ///
+    /// [`Builder::last_resort`]: crate::Builder::last_resort
+    pub fn last_resort(mut self, enabled: bool) -> Self {
+        self.last_resort = match enabled {
+            true => Some(Arc::new(Stderr::default())),
+            false => None,
+        };
+        self
+    }
+
+    /// Writes error records rejected by every dispatch to `append`.
+    ///
+    /// See [`Builder::last_resort`] for details.
+    ///
+    /// [`Builder::last_resort`]: crate::Builder::last_resort
+    pub fn last_resort_append(mut self, append: impl Append) -> Self {
+        self.last_resort = Some(Arc::new(append));
+        self
+    }
+}
+
+/// The error passed to the [error handler](RuntimeConfig::error_handler) when an appender fails
+/// to write a record.
+///
+/// It displays as the error returned by the appender, and tells which appender failed. Downcast
+/// the [`anyhow::Error`] to read it:
+///
+/// ```
+/// use logforth::AppendError;
+/// use logforth::RuntimeConfig;
+///
+/// let runtime =
+///     RuntimeConfig::new().error_handler(|_, err| match err.downcast_ref::<AppendError>() {
+///         Some(append_err) => eprintln!("{} failed: {err:#}", append_err.kind()),
+///         None => eprintln!("failed to log: {err:#}"),
+///     });
+/// ```
+#[derive(Debug)]
+pub struct AppendError {
+    name: Option<String>,
+    kind: String,
+    error: anyhow::Error,
+}
+
+impl AppendError {
+    pub(crate) fn new(name: Option<&str>, append: &dyn Append, error: anyhow::Error) -> Self {
+        Self {
+            name: name.map(ToOwned::to_owned),
+            kind: append.name().to_owned(),
+            error,
+        }
+    }
+
+    /// The name of the appender in its dispatch, if named with
+    /// [`DispatchBuilder::append_named`](crate::DispatchBuilder::append_named).
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The kind of the appender, as returned by [`Append::name`].
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// The error returned by the appender, e.g., to downcast it.
+    pub fn error(&self) -> &anyhow::Error {
+        &self.error
+    }
+
+    /// Consumes the [`AppendError`] and returns the error returned by the appender.
+    pub fn into_error(self) -> anyhow::Error {
+        self.error
+    }
+}
+
+impl fmt::Display for AppendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl std::error::Error for AppendError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
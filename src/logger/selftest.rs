@@ -0,0 +1,143 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verify end-to-end delivery of the installed logger.
+//!
+//! [`run`] injects a synthetic record through every dispatch of the logger installed with
+//! [`Builder::apply`] and reports which appenders accepted it. The record is logged at the info
+//! level under the reserved [`SELFTEST_TARGET`], with the [`SELFTEST_KEY`] key holding an ID
+//! unique to the run, so that sinks and their consumers can recognize and acknowledge it.
+//!
+//! Filters and level remapping are skipped, so that every appender receives the record, while
+//! dispatch diagnostics and layouts are applied as usual. Each appender is flushed after the
+//! record is written. Non-blocking appenders report success once the record is queued.
+//!
+//! [`Builder::apply`]: crate::Builder::apply
+//!
+//! # Examples
+//!
+//! ```
+//! logforth::stdout().apply();
+//!
+//! let report = logforth::selftest::run();
+//! assert!(report.is_ok(), "{report:?}");
+//! ```
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use log::Level;
+use log::Record;
+
+use super::log_impl;
+
+/// The target of selftest records.
+pub const SELFTEST_TARGET: &str = "logforth::selftest";
+
+/// The key under which the ID of the selftest run is attached to a selftest record.
+pub const SELFTEST_KEY: &str = "logforth.selftest";
+
+/// Logs a selftest record to every appender of the installed logger.
+///
+/// The report has no sinks if the global logger was not installed by this crate.
+pub fn run() -> SelftestReport {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let mut sinks = vec![];
+    if let Some(dispatches) = log_impl::installed().map(|d| d.load()) {
+        let kvs = [(SELFTEST_KEY, id)];
+        let record = Record::builder()
+            .level(Level::Info)
+            .target(SELFTEST_TARGET)
+            .args(format_args!("logforth selftest"))
+            .key_values(&kvs)
+            .build();
+
+        for (dispatch_index, dispatch) in dispatches.iter().enumerate() {
+            for (name, kind, result) in dispatch.selftest(&record) {
+                sinks.push(SelftestSink {
+                    dispatch: dispatch_index,
+                    name: name.map(ToOwned::to_owned),
+                    kind: kind.to_owned(),
+                    error: result.err(),
+                });
+            }
+        }
+    }
+
+    SelftestReport { id, sinks }
+}
+
+/// The outcome of a selftest run.
+#[derive(Debug)]
+pub struct SelftestReport {
+    id: u64,
+    sinks: Vec<SelftestSink>,
+}
+
+impl SelftestReport {
+    /// The ID of the run, attached to the selftest record under [`SELFTEST_KEY`].
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The outcome of each appender, in the order of dispatches and appenders.
+    pub fn sinks(&self) -> &[SelftestSink] {
+        &self.sinks
+    }
+
+    /// Whether at least one appender received the record and all of them succeeded.
+    pub fn is_ok(&self) -> bool {
+        !self.sinks.is_empty() && self.sinks.iter().all(SelftestSink::is_ok)
+    }
+}
+
+/// The outcome of a selftest run for a single appender.
+#[derive(Debug)]
+pub struct SelftestSink {
+    dispatch: usize,
+    name: Option<String>,
+    kind: String,
+    error: Option<anyhow::Error>,
+}
+
+impl SelftestSink {
+    /// The index of the dispatch of the appender, in the order they were added to the builder.
+    pub fn dispatch(&self) -> usize {
+        self.dispatch
+    }
+
+    /// The name of the appender, if added with [`DispatchBuilder::append_named`].
+    ///
+    /// [`DispatchBuilder::append_named`]: crate::DispatchBuilder::append_named
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The kind of the appender, as returned by [`Append::name`](crate::Append::name).
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// The error returned by the appender, if it failed to write the record.
+    pub fn error(&self) -> Option<&anyhow::Error> {
+        self.error.as_ref()
+    }
+
+    /// Whether the appender wrote the record successfully.
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
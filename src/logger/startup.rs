@@ -0,0 +1,186 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Startup reports of the effective configuration of appenders.
+//!
+//! Appenders whose output depends on resolved paths and endpoints, i.e., rolling files, syslog
+//! and journald, report their effective configuration when they are constructed: one record at
+//! the info level under [`STARTUP_TARGET`] per appender, with the configuration as key-values,
+//! e.g., the absolute log directory or the socket address. This answers "why are my logs not
+//! where I expect" from the logs themselves.
+//!
+//! Appenders are usually constructed before the logger is installed, so the reports are held
+//! until [`Builder::apply`] installs it. Filter out the target to silence them.
+//!
+//! [`Builder::apply`]: crate::Builder::apply
+//!
+//! # Examples
+//!
+//! ```
+//! use logforth::append;
+//! use logforth::filter::CustomFilter;
+//! use logforth::filter::FilterResult;
+//!
+//! logforth::builder()
+//!     .dispatch(|d| {
+//!         d.filter(CustomFilter::new(|metadata| {
+//!             if metadata.target() == logforth::startup::STARTUP_TARGET {
+//!                 FilterResult::Reject
+//!             } else {
+//!                 FilterResult::Neutral
+//!             }
+//!         }))
+//!         .append(append::Stdout::default())
+//!     })
+//!     .apply();
+//! ```
+
+// reports are pushed by the rolling file, syslog and journald appenders, and only
+// journald amends them afterwards
+#![cfg_attr(not(feature = "journald"), allow(dead_code))]
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+use log::Level;
+use log::Record;
+
+/// The target of startup report records.
+pub const STARTUP_TARGET: &str = "logforth::startup";
+
+// reports beyond this number are dropped if the logger is never installed
+const MAX_PENDING_REPORTS: usize = 64;
+
+static PENDING: Mutex<Pending> = Mutex::new(Pending {
+    installed: false,
+    reports: Vec::new(),
+});
+
+#[derive(Debug)]
+struct Report {
+    id: u64,
+    message: &'static str,
+    kvs: Vec<(&'static str, String)>,
+}
+
+#[derive(Debug)]
+struct Pending {
+    installed: bool,
+    reports: Vec<Report>,
+}
+
+impl Pending {
+    // Returns the report back if it should be logged right away.
+    fn push(&mut self, report: Report) -> Option<Report> {
+        if self.installed {
+            return Some(report);
+        }
+        if self.reports.len() < MAX_PENDING_REPORTS {
+            self.reports.push(report);
+        }
+        None
+    }
+
+    fn amend(&mut self, id: u64, key: &'static str, value: String) {
+        let Some(report) = self.reports.iter_mut().find(|report| report.id == id) else {
+            return;
+        };
+        match report.kvs.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => report.kvs.push((key, value)),
+        }
+    }
+
+    fn install(&mut self) -> Vec<Report> {
+        self.installed = true;
+        std::mem::take(&mut self.reports)
+    }
+}
+
+/// Reports the effective configuration of an appender, returning an ID to amend it with.
+pub(crate) fn report(message: &'static str, kvs: Vec<(&'static str, String)>) -> u64 {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let report = Report { id, message, kvs };
+    let report = PENDING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(report);
+    if let Some(report) = report {
+        log_report(&report);
+    }
+    id
+}
+
+/// Updates a key-value of a report that has not been logged yet, e.g., when an appender is
+/// reconfigured after construction.
+pub(crate) fn amend(id: u64, key: &'static str, value: String) {
+    let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    pending.amend(id, key, value);
+}
+
+pub(super) fn install() {
+    let reports = PENDING.lock().unwrap_or_else(|e| e.into_inner()).install();
+    for report in &reports {
+        log_report(report);
+    }
+}
+
+fn log_report(report: &Report) {
+    let kvs = report
+        .kvs
+        .iter()
+        .map(|(key, value)| (*key, value.as_str()))
+        .collect::<Vec<_>>();
+    log::logger().log(
+        &Record::builder()
+            .level(Level::Info)
+            .target(STARTUP_TARGET)
+            .args(format_args!("{}", report.message))
+            .key_values(&kvs)
+            .build(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(id: u64, kvs: &[(&'static str, &str)]) -> Report {
+        Report {
+            id,
+            message: "test",
+            kvs: kvs.iter().map(|(k, v)| (*k, v.to_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn test_pending_reports() {
+        let mut pending = Pending {
+            installed: false,
+            reports: vec![],
+        };
+        assert!(pending.push(report(1, &[("path", "/a")])).is_none());
+        assert!(pending.push(report(2, &[])).is_none());
+        pending.amend(1, "path", "/b".to_string());
+        pending.amend(2, "identifier", "app".to_string());
+
+        let reports = pending.install();
+        assert_eq!(reports[0].kvs, [("path", "/b".to_string())]);
+        assert_eq!(reports[1].kvs, [("identifier", "app".to_string())]);
+        assert!(pending.push(report(3, &[])).is_some());
+    }
+}
@@ -0,0 +1,250 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Logging macros that capture the message template, a macro to build records with their source
+//! location, and a macro to start spans.
+
+/// Logs a message at the specified level and captures its message template.
+///
+/// This macro is a drop-in replacement of [`log::log!`] that requires the format string to be a
+/// literal. The literal is attached to the record as a key-value under
+/// [`MESSAGE_TEMPLATE_KEY`](crate::template::MESSAGE_TEMPLATE_KEY), so that layouts can emit the
+/// template besides the rendered message. See [`template`](crate::template) for details.
+///
+/// # Examples
+///
+/// ```
+/// use log::Level;
+///
+/// let user_id = 42;
+/// let ip = "127.0.0.1";
+/// logforth::log!(Level::Info, "User {user_id} logged in from {ip}");
+/// logforth::log!(target: "audit", Level::Info, attempts = 3; "User {user_id} logged in");
+/// ```
+///
+/// With `fields:`, the key-values are taken from a [`log::kv::Source`], such as a struct deriving
+/// [`LogFields`](crate::LogFields) with the `derive` feature:
+///
+/// ```
+/// # #[cfg(not(feature = "derive"))] fn main() {}
+/// # #[cfg(feature = "derive")] fn main() {
+/// use logforth::LogFields;
+///
+/// #[derive(LogFields)]
+/// struct Request {
+///     method: &'static str,
+///     #[log(rename = "http.path", display)]
+///     path: String,
+///     #[log(skip)]
+///     body: Vec<u8>,
+/// }
+///
+/// let request = Request {
+///     method: "GET",
+///     path: "/users".to_string(),
+///     body: vec![],
+/// };
+/// logforth::info!(fields: &request; "handled {}", request.path);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! log {
+    // log!(target: "my_target", Level::Info, fields: &request; "a {} event", "log");
+    (target: $target:expr, $lvl:expr, fields: $fields:expr; $template:literal $($arg:tt)*) => {{
+        let lvl = $lvl;
+        if lvl <= $crate::__private::log::STATIC_MAX_LEVEL && lvl <= $crate::__private::log::max_level() {
+            $crate::__private::log::logger().log(
+                &$crate::record!()
+                    .target($target)
+                    .level(lvl)
+                    .args(::std::format_args!($template $($arg)*))
+                    .key_values(&$crate::__private::WithFields {
+                        template: $template,
+                        fields: $fields,
+                    })
+                    .build(),
+            );
+        }
+    }};
+
+    // log!(target: "my_target", Level::Info, key1 = 42, key2 = true; "a {} event", "log");
+    (target: $target:expr, $lvl:expr, $($key:tt $(:$capture:tt)? $(= $value:expr)?),+; $template:literal $($arg:tt)*) => {
+        $crate::__private::log::log!(
+            target: $target,
+            $lvl,
+            $($key $(:$capture)? $(= $value)?),+,
+            "logforth.message_template" = $template;
+            $template $($arg)*
+        )
+    };
+
+    // log!(target: "my_target", Level::Info, "a {} event", "log");
+    (target: $target:expr, $lvl:expr, $template:literal $($arg:tt)*) => {
+        $crate::__private::log::log!(
+            target: $target,
+            $lvl,
+            "logforth.message_template" = $template;
+            $template $($arg)*
+        )
+    };
+
+    // log!(Level::Info, "a log event")
+    ($lvl:expr, $($arg:tt)+) => {
+        $crate::log!(target: ::std::module_path!(), $lvl, $($arg)+)
+    };
+}
+
+/// Logs a fatal message at the error level and captures its message template.
+///
+/// The record is marked with [`FATAL_KEY`](crate::fatal::FATAL_KEY), which triggers the handler
+/// set with [`Builder::on_fatal`](crate::Builder::on_fatal). See [`fatal`](crate::fatal) for
+/// details, and [`log!`](crate::log!) for the syntax.
+///
+/// # Examples
+///
+/// ```
+/// logforth::fatal!("data directory is corrupted");
+/// logforth::fatal!(target: "storage", path = "/data"; "data directory is corrupted");
+/// ```
+#[macro_export]
+macro_rules! fatal {
+    (target: $target:expr, $($key:tt $(:$capture:tt)? $(= $value:expr)?),+; $template:literal $($arg:tt)*) => (
+        $crate::log!(
+            target: $target,
+            $crate::__private::log::Level::Error,
+            "logforth.fatal" = true,
+            $($key $(:$capture)? $(= $value)?),+;
+            $template $($arg)*
+        )
+    );
+    (target: $target:expr, $template:literal $($arg:tt)*) => (
+        $crate::log!(target: $target, $crate::__private::log::Level::Error, "logforth.fatal" = true; $template $($arg)*)
+    );
+    ($($arg:tt)+) => ($crate::fatal!(target: ::std::module_path!(), $($arg)+));
+}
+
+/// Logs a message at the error level and captures its message template.
+///
+/// See [`log!`](crate::log!) for details.
+#[macro_export]
+macro_rules! error {
+    (target: $target:expr, $($arg:tt)+) => ($crate::log!(target: $target, $crate::__private::log::Level::Error, $($arg)+));
+    ($($arg:tt)+) => ($crate::log!($crate::__private::log::Level::Error, $($arg)+));
+}
+
+/// Logs a message at the warn level and captures its message template.
+///
+/// See [`log!`](crate::log!) for details.
+#[macro_export]
+macro_rules! warn {
+    (target: $target:expr, $($arg:tt)+) => ($crate::log!(target: $target, $crate::__private::log::Level::Warn, $($arg)+));
+    ($($arg:tt)+) => ($crate::log!($crate::__private::log::Level::Warn, $($arg)+));
+}
+
+/// Logs a message at the info level and captures its message template.
+///
+/// See [`log!`](crate::log!) for details.
+#[macro_export]
+macro_rules! info {
+    (target: $target:expr, $($arg:tt)+) => ($crate::log!(target: $target, $crate::__private::log::Level::Info, $($arg)+));
+    ($($arg:tt)+) => ($crate::log!($crate::__private::log::Level::Info, $($arg)+));
+}
+
+/// Logs a message at the debug level and captures its message template.
+///
+/// See [`log!`](crate::log!) for details.
+#[macro_export]
+macro_rules! debug {
+    (target: $target:expr, $($arg:tt)+) => ($crate::log!(target: $target, $crate::__private::log::Level::Debug, $($arg)+));
+    ($($arg:tt)+) => ($crate::log!($crate::__private::log::Level::Debug, $($arg)+));
+}
+
+/// Logs a message at the trace level and captures its message template.
+///
+/// See [`log!`](crate::log!) for details.
+#[macro_export]
+macro_rules! trace {
+    (target: $target:expr, $($arg:tt)+) => ($crate::log!(target: $target, $crate::__private::log::Level::Trace, $($arg)+));
+    ($($arg:tt)+) => ($crate::log!($crate::__private::log::Level::Trace, $($arg)+));
+}
+
+/// Creates a [`log::RecordBuilder`] with the source location of the call site.
+///
+/// The module path, file and line are filled with [`module_path!`], [`file!`] and [`line!`], and
+/// the target with the module path, so that records emitted manually, e.g., to an appender or to
+/// [`log::logger`], are not silently missing their location. Any of them can be overridden on the
+/// returned builder.
+///
+/// # Examples
+///
+/// ```
+/// use log::Level;
+///
+/// let record = logforth::record!()
+///     .level(Level::Info)
+///     .args(format_args!("emitted manually"))
+///     .build();
+/// assert_eq!(record.module_path(), Some(module_path!()));
+/// assert_eq!(record.target(), module_path!());
+/// assert!(record.line().is_some());
+/// log::logger().log(&record);
+/// ```
+#[macro_export]
+macro_rules! record {
+    () => {{
+        let mut builder = $crate::__private::log::Record::builder();
+        builder
+            .target(::std::module_path!())
+            .module_path_static(::std::option::Option::Some(::std::module_path!()))
+            .file_static(::std::option::Option::Some(::std::file!()))
+            .line(::std::option::Option::Some(::std::line!()));
+        builder
+    }};
+}
+
+/// Starts a [`Span`](crate::span::Span) that logs its duration when dropped.
+///
+/// The level defaults to [`Level::Info`](log::Level::Info). Key-values are formatted with
+/// [`Display`](std::fmt::Display), and only if the level is enabled. See [`span`](crate::span)
+/// for details.
+///
+/// # Examples
+///
+/// ```
+/// use log::Level;
+///
+/// let _span = logforth::span!("db_query", query_id = 7);
+/// let _span = logforth::span!(level: Level::Debug, "parse", input = "a.json");
+/// ```
+#[macro_export]
+macro_rules! span {
+    (level: $lvl:expr, $name:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        $crate::span::Span::start(
+            $lvl,
+            ::std::module_path!(),
+            ::std::file!(),
+            ::std::line!(),
+            $name,
+            |_kvs| {
+                $(_kvs.push((
+                    ::std::stringify!($key),
+                    ::std::string::ToString::to_string(&$value),
+                ));)*
+            },
+        )
+    };
+    ($name:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        $crate::span!(level: $crate::__private::log::Level::Info, $name $(, $key = $value)*)
+    };
+}
@@ -12,18 +12,30 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::thread::JoinHandle;
 use std::time::Duration;
+use std::time::Instant;
 
 use anyhow::Context;
 use crossbeam_channel::bounded;
 use crossbeam_channel::unbounded;
+use crossbeam_channel::Receiver;
 use crossbeam_channel::SendTimeoutError;
 use crossbeam_channel::Sender;
+use crossbeam_channel::TrySendError;
+use log::Level;
 
+use super::worker::Buffering;
 use super::worker::Worker;
 use super::Message;
 use super::Writer;
+use crate::layout::truncate_utf8;
+use crate::pool;
+use crate::RuntimeConfig;
 
 /// A guard that flushes log records associated with a [`NonBlocking`] writer on drop.
 ///
@@ -44,6 +56,8 @@ pub struct WorkerGuard {
     sender: Sender<Message>,
     shutdown: Sender<()>,
     shutdown_timeout: Duration,
+    thread_name: String,
+    dropped: Arc<AtomicU64>,
 }
 
 impl WorkerGuard {
@@ -52,20 +66,32 @@ impl WorkerGuard {
         sender: Sender<Message>,
         shutdown: Sender<()>,
         shutdown_timeout: Option<Duration>,
+        thread_name: String,
+        dropped: Arc<AtomicU64>,
     ) -> Self {
-        const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(100);
-
         WorkerGuard {
             _guard: Some(handle),
             sender,
             shutdown,
             shutdown_timeout: shutdown_timeout.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT),
+            thread_name,
+            dropped,
         }
     }
 }
 
 impl Drop for WorkerGuard {
     fn drop(&mut self) {
+        let dropped = self.dropped.load(Ordering::Relaxed);
+        if dropped > 0 {
+            // reported to stderr rather than through the global logger, which would deliver it to
+            // every dispatch and may already be torn down
+            eprintln!(
+                "logging worker {} dropped {dropped} records because its queue was full",
+                self.thread_name,
+            );
+        }
+
         let shutdown_timeout = self.shutdown_timeout;
         match self
             .sender
@@ -85,43 +111,262 @@ impl Drop for WorkerGuard {
     }
 }
 
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(100);
+
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The suffix appended to records truncated by [`NonBlockingBuilder::max_record_size`].
+const TRUNCATED_SUFFIX: &[u8] = b"...[truncated]";
+
+/// What happens to a record sent when the queue of the worker thread is full.
+///
+/// The queue is bounded only with [`NonBlockingBuilder::buffered_lines_limit`].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OverflowPolicy {
+    /// Block the logging thread until the worker thread makes room for the record.
+    #[default]
+    Block,
+    /// Drop the record, and count it in [`NonBlocking::dropped_count`].
+    DropIncoming,
+    /// Drop the oldest record in the queue to make room for the record, and count it in
+    /// [`NonBlocking::dropped_count`].
+    ///
+    /// Pending flush requests are kept; if one is the oldest entry, the incoming record is
+    /// dropped instead.
+    DropOldest,
+    /// Block the logging thread for at most the given duration, then drop the record and count
+    /// it in [`NonBlocking::dropped_count`].
+    BlockWithTimeout(Duration),
+}
+
 /// A non-blocking writer for rolling files.
 #[derive(Clone, Debug)]
 pub struct NonBlocking<T: Writer + Send + 'static> {
     sender: Sender<Message>,
+    // only kept with `OverflowPolicy::DropOldest` to pop the oldest record
+    receiver: Option<Receiver<Message>>,
+    healthy: Arc<AtomicBool>,
+    flush_timeout: Duration,
+    max_record_size: Option<usize>,
+    truncated: Arc<AtomicU64>,
+    overflow_policy: OverflowPolicy,
+    dropped: Arc<AtomicU64>,
+    // records at or above this level are flushed without waiting for the buffer
+    flush_level: Option<Level>,
     marker: std::marker::PhantomData<T>,
 }
 
 impl<T: Writer + Send + 'static> NonBlocking<T> {
-    fn create(
-        writer: T,
-        thread_name: String,
-        buffered_lines_limit: Option<usize>,
-        shutdown_timeout: Option<Duration>,
-    ) -> (Self, WorkerGuard) {
+    fn create(builder: NonBlockingBuilder<T>) -> (Self, WorkerGuard) {
+        let NonBlockingBuilder {
+            thread_name,
+            buffered_lines_limit,
+            shutdown_timeout,
+            recover_from_panic,
+            max_record_size,
+            overflow_policy,
+            buffer_size,
+            flush_interval,
+            flush_level,
+            writer,
+        } = builder;
+        let buffering = buffer_size.map(|size| Buffering {
+            size,
+            interval: flush_interval.unwrap_or(DEFAULT_FLUSH_INTERVAL),
+        });
+
         let (sender, receiver) = match buffered_lines_limit {
             Some(cap) => bounded(cap),
             None => unbounded(),
         };
 
         let (shutdown_sender, shutdown_receiver) = bounded(0);
+        let flush_timeout = shutdown_timeout.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT);
 
-        let worker = Worker::new(writer, receiver, shutdown_receiver);
+        let healthy = Arc::new(AtomicBool::new(true));
+        let oldest_receiver =
+            (overflow_policy == OverflowPolicy::DropOldest).then(|| receiver.clone());
+        let worker = Worker::new(
+            writer,
+            receiver,
+            shutdown_receiver,
+            healthy.clone(),
+            recover_from_panic,
+            buffering,
+        );
+        let dropped = Arc::new(AtomicU64::new(0));
         let worker_guard = WorkerGuard::new(
-            worker.make_thread(thread_name),
+            worker.make_thread(thread_name.clone()),
             sender.clone(),
             shutdown_sender,
             shutdown_timeout,
+            thread_name,
+            dropped.clone(),
         );
 
         let marker = std::marker::PhantomData;
-        (Self { sender, marker }, worker_guard)
+        let non_blocking = Self {
+            sender,
+            receiver: oldest_receiver,
+            healthy,
+            flush_timeout,
+            max_record_size,
+            truncated: Arc::new(AtomicU64::new(0)),
+            overflow_policy,
+            dropped,
+            flush_level: flush_level.filter(|_| buffering.is_some()),
+            marker,
+        };
+        (non_blocking, worker_guard)
+    }
+
+    /// Returns whether the worker thread is still running and accepting log records.
+    ///
+    /// The worker stops after the [`WorkerGuard`] is dropped, or after the writer panics if
+    /// panic recovery is disabled (see [`NonBlockingBuilder::recover_from_panic`]).
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of records truncated because they exceeded the maximum record size
+    /// (see [`NonBlockingBuilder::max_record_size`]).
+    pub fn truncated_count(&self) -> u64 {
+        self.truncated.load(Ordering::Relaxed)
     }
 
-    pub(crate) fn send(&self, record: Vec<u8>) -> anyhow::Result<()> {
+    /// Returns the number of records dropped because the queue of the worker thread was full
+    /// (see [`OverflowPolicy`]).
+    ///
+    /// When the [`WorkerGuard`] is dropped, the number of dropped records is reported to stderr if
+    /// any.
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Waits for the worker thread to write and flush the records sent so far.
+    ///
+    /// This blocks the calling thread for at most the shutdown timeout in total (see
+    /// [`NonBlockingBuilder::shutdown_timeout`]), including the time waiting for room in a full
+    /// queue. The appenders built on a non-blocking writer call this from [`Append::flush`], so
+    /// flushing the logger may block as long.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the worker thread is stopped, fails to write the records, or does not
+    /// finish in time.
+    ///
+    /// [`Append::flush`]: crate::Append::flush
+    pub fn flush(&self) -> anyhow::Result<()> {
+        let deadline = Instant::now() + self.flush_timeout;
+        let (ack, done) = bounded(1);
         self.sender
-            .send(Message::Record(record))
-            .context("failed to send log message")
+            .send_deadline(Message::Flush(ack), deadline)
+            .map_err(|_| anyhow::anyhow!("failed to send flush request to logging worker"))?;
+        done.recv_deadline(deadline)
+            .context("failed to wait for logging worker to flush")
+    }
+
+    /// Sends a record of the given level, which is flushed right away if the level is at or
+    /// above the flush level (see [`NonBlockingBuilder::flush_level`]).
+    pub(crate) fn send(&self, record: Vec<u8>, level: Level) -> anyhow::Result<()> {
+        let record = self.truncate(record);
+        let message = match self.flush_level {
+            Some(flush_level) if level <= flush_level => Message::Urgent(record),
+            _ => Message::Record(record),
+        };
+        match self.overflow_policy {
+            OverflowPolicy::Block => self
+                .sender
+                .send(message)
+                .context("failed to send log message"),
+            OverflowPolicy::DropIncoming => match self.sender.try_send(message) {
+                Ok(()) => Ok(()),
+                Err(TrySendError::Full(message)) => {
+                    self.drop_message(message);
+                    Ok(())
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    Err(anyhow::anyhow!("failed to send log message"))
+                }
+            },
+            OverflowPolicy::DropOldest => self.send_dropping_oldest(message),
+            OverflowPolicy::BlockWithTimeout(timeout) => {
+                match self.sender.send_timeout(message, timeout) {
+                    Ok(()) => Ok(()),
+                    Err(SendTimeoutError::Timeout(message)) => {
+                        self.drop_message(message);
+                        Ok(())
+                    }
+                    Err(SendTimeoutError::Disconnected(_)) => {
+                        Err(anyhow::anyhow!("failed to send log message"))
+                    }
+                }
+            }
+        }
+    }
+
+    fn send_dropping_oldest(&self, mut message: Message) -> anyhow::Result<()> {
+        let receiver = self
+            .receiver
+            .as_ref()
+            .expect("receiver is kept with OverflowPolicy::DropOldest");
+        // the receiver kept here holds the channel open after the worker stops, so sends would
+        // keep succeeding into a queue that nobody drains
+        if !self.is_healthy() {
+            return Err(anyhow::anyhow!("failed to send log message"));
+        }
+        loop {
+            match self.sender.try_send(message) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Disconnected(_)) => {
+                    return Err(anyhow::anyhow!("failed to send log message"));
+                }
+                Err(TrySendError::Full(incoming)) => match receiver.try_recv() {
+                    Ok(oldest @ (Message::Record(_) | Message::Urgent(_))) => {
+                        self.drop_message(oldest);
+                        message = incoming;
+                    }
+                    Ok(control) => {
+                        // requeue flush and shutdown requests in the slot just freed, behind
+                        // the records already queued, and drop the incoming record instead
+                        self.drop_message(incoming);
+                        return self
+                            .sender
+                            .send_timeout(control, self.flush_timeout)
+                            .map_err(|_| anyhow::anyhow!("failed to requeue control message"));
+                    }
+                    // the worker drained the queue in the meantime
+                    Err(_) => message = incoming,
+                },
+            }
+        }
+    }
+
+    fn drop_message(&self, message: Message) {
+        if let Message::Record(record) | Message::Urgent(record) = message {
+            pool::recycle(record);
+        }
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn truncate(&self, mut record: Vec<u8>) -> Vec<u8> {
+        let Some(max_record_size) = self.max_record_size else {
+            return record;
+        };
+        if record.len() <= max_record_size {
+            return record;
+        }
+
+        let newline = record.last() == Some(&b'\n');
+        truncate_utf8(&mut record, max_record_size);
+        record.extend_from_slice(TRUNCATED_SUFFIX);
+        if newline {
+            record.push(b'\n');
+        }
+        record.shrink_to_fit();
+        self.truncated.fetch_add(1, Ordering::Relaxed);
+        record
     }
 }
 
@@ -131,6 +376,12 @@ pub struct NonBlockingBuilder<T: Writer + Send + 'static> {
     thread_name: String,
     buffered_lines_limit: Option<usize>,
     shutdown_timeout: Option<Duration>,
+    recover_from_panic: bool,
+    max_record_size: Option<usize>,
+    overflow_policy: OverflowPolicy,
+    buffer_size: Option<usize>,
+    flush_interval: Option<Duration>,
+    flush_level: Option<Level>,
     writer: T,
 }
 
@@ -140,22 +391,126 @@ impl<T: Writer + Send + 'static> NonBlockingBuilder<T> {
             thread_name: thread_name.into(),
             buffered_lines_limit: None,
             shutdown_timeout: None,
+            recover_from_panic: true,
+            max_record_size: None,
+            overflow_policy: OverflowPolicy::Block,
+            buffer_size: None,
+            flush_interval: None,
+            flush_level: Some(Level::Error),
             writer,
         }
     }
 
     /// Sets the number of lines to buffer before dropping logs or exerting backpressure on senders.
+    ///
+    /// See [`overflow_policy`](Self::overflow_policy) for what happens when the limit is reached.
     pub fn buffered_lines_limit(mut self, buffered_lines_limit: usize) -> Self {
         self.buffered_lines_limit = Some(buffered_lines_limit);
         self
     }
 
+    /// Sets what happens to records sent when the queue is full. Default to
+    /// [`OverflowPolicy::Block`].
+    ///
+    /// No effect without [`buffered_lines_limit`](Self::buffered_lines_limit).
+    pub fn overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.overflow_policy = overflow_policy;
+        self
+    }
+
     /// Sets the shutdown timeout before the worker guard dropped.
     pub fn shutdown_timeout(mut self, shutdown_timeout: Duration) -> Self {
         self.shutdown_timeout = Some(shutdown_timeout);
         self
     }
 
+    /// Inherits the worker defaults of the given [`RuntimeConfig`].
+    ///
+    /// Only the settings configured on `runtime` are applied, and calling the dedicated setters
+    /// afterwards overrides them.
+    pub fn runtime(mut self, runtime: &RuntimeConfig) -> Self {
+        if let Some(buffered_lines_limit) = runtime.buffered_lines_limit {
+            self.buffered_lines_limit = Some(buffered_lines_limit);
+        }
+        if let Some(shutdown_timeout) = runtime.shutdown_timeout {
+            self.shutdown_timeout = Some(shutdown_timeout);
+        }
+        self
+    }
+
+    /// Sets whether the worker thread keeps running after the writer panics. Default to `true`.
+    ///
+    /// When enabled, the panic is reported to stderr and the records being written at that time
+    /// are lost, but subsequent records are still processed. When disabled, the worker thread
+    /// stops on the first panic and all subsequent sends fail, whatever the
+    /// [`OverflowPolicy`]; records still queued at that time are lost.
+    pub fn recover_from_panic(mut self, recover_from_panic: bool) -> Self {
+        self.recover_from_panic = recover_from_panic;
+        self
+    }
+
+    /// Sets the maximum size in bytes of a record buffered for the worker thread.
+    ///
+    /// Larger records are truncated to this size, without splitting a UTF-8 encoded character,
+    /// and marked with a `...[truncated]` suffix, which bounds the memory held by a long queue
+    /// when huge payloads are logged by accident. Note that truncation can break structured
+    /// formats such as JSON. Use
+    /// [`NonBlocking::truncated_count`] to monitor how many records were truncated.
+    ///
+    /// Default to no limit.
+    pub fn max_record_size(mut self, max_record_size: usize) -> Self {
+        self.max_record_size = Some(max_record_size);
+        self
+    }
+
+    /// Buffers up to `buffer_size` bytes of records in the worker thread before writing them.
+    ///
+    /// By default, the worker writes and flushes the records as soon as it receives them, which
+    /// costs at least one write per batch of records. With buffering, records are written and
+    /// flushed once the buffer holds `buffer_size` bytes, the [flush interval] has passed since
+    /// the last write, a record at or above the [flush level] arrives, or the records are
+    /// flushed explicitly. This trades latency of visible records for throughput.
+    ///
+    /// [flush interval]: Self::flush_interval
+    /// [flush level]: Self::flush_level
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use logforth::append::rolling_file;
+    /// use logforth::append::rolling_file::RollingFileWriter;
+    ///
+    /// let writer = RollingFileWriter::builder().build("logs").unwrap();
+    /// let (non_blocking, _guard) = rolling_file::non_blocking(writer)
+    ///     .buffer_size(64 * 1024)
+    ///     .flush_interval(Duration::from_millis(200))
+    ///     .finish();
+    /// ```
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    /// Sets the longest time buffered records wait before they are written. Default to 1 second.
+    ///
+    /// No effect without [`buffer_size`](Self::buffer_size).
+    pub fn flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = Some(flush_interval);
+        self
+    }
+
+    /// Sets the level at or above which records are written and flushed right away along with
+    /// the buffered ones. Default to [`Level::Error`].
+    ///
+    /// No effect without [`buffer_size`](Self::buffer_size). Only appenders that know the level
+    /// of the records they send, such as the rolling file appender, trigger this flush.
+    pub fn flush_level(mut self, flush_level: Level) -> Self {
+        self.flush_level = Some(flush_level);
+        self
+    }
+
     /// Override the worker thread's name.
     pub fn thread_name(mut self, name: impl Into<String>) -> Self {
         self.thread_name = name.into();
@@ -164,11 +519,324 @@ impl<T: Writer + Send + 'static> NonBlockingBuilder<T> {
 
     /// Completes the builder, returning the configured `NonBlocking`.
     pub fn finish(self) -> (NonBlocking<T>, WorkerGuard) {
-        NonBlocking::create(
-            self.writer,
-            self.thread_name,
-            self.buffered_lines_limit,
-            self.shutdown_timeout,
-        )
+        NonBlocking::create(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::io::Write;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use log::Level;
+
+    use super::NonBlockingBuilder;
+    use super::OverflowPolicy;
+
+    #[derive(Clone, Default)]
+    struct PanicWriter {
+        buf: Arc<Mutex<Vec<u8>>>,
+        panicked: Arc<AtomicBool>,
+    }
+
+    impl PanicWriter {
+        fn wait_for_panic(&self) {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while !self.panicked.load(Ordering::Acquire) && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+
+    impl Write for PanicWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf == b"panic" {
+                self.panicked.store(true, Ordering::Release);
+                panic!("writer panicked");
+            }
+            self.buf.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_flush_waits_for_worker() {
+        let writer = PanicWriter::default();
+        let (non_blocking, _guard) = NonBlockingBuilder::new("test-flush", writer.clone())
+            .shutdown_timeout(Duration::from_secs(5))
+            .finish();
+
+        non_blocking.send(b"first".to_vec(), Level::Info).unwrap();
+        non_blocking.send(b"second".to_vec(), Level::Info).unwrap();
+        non_blocking.flush().unwrap();
+        assert_eq!(writer.buf.lock().unwrap().as_slice(), b"firstsecond");
+    }
+
+    #[test]
+    fn test_worker_recovers_from_panic() {
+        let writer = PanicWriter::default();
+        let (non_blocking, guard) = NonBlockingBuilder::new("test-recover", writer.clone())
+            .shutdown_timeout(Duration::from_secs(5))
+            .finish();
+
+        // records batched with the panicking one are lost, so wait for the panic
+        non_blocking.send(b"panic".to_vec(), Level::Info).unwrap();
+        writer.wait_for_panic();
+        non_blocking.send(b"ok".to_vec(), Level::Info).unwrap();
+        assert!(non_blocking.is_healthy());
+        drop(guard);
+
+        assert_eq!(writer.buf.lock().unwrap().as_slice(), b"ok");
+    }
+
+    #[test]
+    fn test_worker_stops_on_panic() {
+        let writer = PanicWriter::default();
+        let (non_blocking, _guard) = NonBlockingBuilder::new("test-stop", writer.clone())
+            .recover_from_panic(false)
+            .finish();
+        assert!(non_blocking.is_healthy());
+
+        non_blocking.send(b"panic".to_vec(), Level::Info).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while non_blocking.is_healthy() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!non_blocking.is_healthy());
+    }
+
+    #[test]
+    fn test_worker_stops_on_panic_drop_oldest() {
+        let writer = PanicWriter::default();
+        let (non_blocking, _guard) = NonBlockingBuilder::new("test-stop-oldest", writer.clone())
+            .buffered_lines_limit(2)
+            .overflow_policy(OverflowPolicy::DropOldest)
+            .recover_from_panic(false)
+            .finish();
+
+        non_blocking.send(b"panic".to_vec(), Level::Info).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while non_blocking.is_healthy() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!non_blocking.is_healthy());
+        assert!(non_blocking.send(b"lost".to_vec(), Level::Info).is_err());
+    }
+
+    #[test]
+    fn test_max_record_size() {
+        let writer = PanicWriter::default();
+        let (non_blocking, guard) = NonBlockingBuilder::new("test-truncate", writer.clone())
+            .max_record_size(4)
+            .shutdown_timeout(Duration::from_secs(5))
+            .finish();
+
+        non_blocking.send(b"ok\n".to_vec(), Level::Info).unwrap();
+        non_blocking
+            .send(b"too long\n".to_vec(), Level::Info)
+            .unwrap();
+        assert_eq!(non_blocking.truncated_count(), 1);
+        drop(guard);
+
+        assert_eq!(
+            writer.buf.lock().unwrap().as_slice(),
+            b"ok\ntoo ...[truncated]\n"
+        );
+    }
+
+    #[test]
+    fn test_max_record_size_utf8() {
+        let writer = PanicWriter::default();
+        let (non_blocking, guard) = NonBlockingBuilder::new("test-truncate-utf8", writer.clone())
+            .max_record_size(5)
+            .shutdown_timeout(Duration::from_secs(5))
+            .finish();
+
+        non_blocking
+            .send("日本語\n".as_bytes().to_vec(), Level::Info)
+            .unwrap();
+        drop(guard);
+
+        let written = writer.buf.lock().unwrap().clone();
+        assert_eq!(String::from_utf8(written).unwrap(), "日...[truncated]\n");
+    }
+
+    #[test]
+    fn test_buffering() {
+        let writer = PanicWriter::default();
+        let written = || writer.buf.lock().unwrap().clone();
+        let wait_for = |expected: &[u8]| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while written() != expected && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            assert_eq!(written(), expected);
+        };
+        let (non_blocking, _guard) = NonBlockingBuilder::new("test-buffering", writer.clone())
+            .buffer_size(8)
+            .flush_interval(Duration::from_secs(3600))
+            .shutdown_timeout(Duration::from_secs(5))
+            .finish();
+
+        // buffered until the buffer is full
+        non_blocking.send(b"1234".to_vec(), Level::Info).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(written(), b"");
+        non_blocking.send(b"5678".to_vec(), Level::Info).unwrap();
+        wait_for(b"12345678");
+
+        // errors are written right away along with the buffered records
+        non_blocking.send(b"a".to_vec(), Level::Info).unwrap();
+        non_blocking.send(b"b".to_vec(), Level::Error).unwrap();
+        wait_for(b"12345678ab");
+
+        // explicit flushes write the buffered records
+        non_blocking.send(b"c".to_vec(), Level::Info).unwrap();
+        non_blocking.flush().unwrap();
+        assert_eq!(written(), b"12345678abc");
+    }
+
+    #[test]
+    fn test_flush_interval() {
+        let writer = PanicWriter::default();
+        let (non_blocking, _guard) = NonBlockingBuilder::new("test-interval", writer.clone())
+            .buffer_size(1024)
+            .flush_interval(Duration::from_millis(20))
+            .finish();
+
+        non_blocking.send(b"idle".to_vec(), Level::Info).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while writer.buf.lock().unwrap().is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(writer.buf.lock().unwrap().as_slice(), b"idle");
+    }
+
+    #[derive(Clone, Default)]
+    struct GateWriter {
+        gate: Arc<Mutex<()>>,
+        entered: Arc<AtomicBool>,
+        buf: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl GateWriter {
+        fn wait_for_entered(&self) {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while !self.entered.load(Ordering::Acquire) && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+
+    impl Write for GateWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.entered.store(true, Ordering::Release);
+            let _gate = self.gate.lock().unwrap();
+            self.buf.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_drop_incoming() {
+        let writer = GateWriter::default();
+        let gate = writer.gate.lock().unwrap();
+        let (non_blocking, guard) = NonBlockingBuilder::new("test-drop", writer.clone())
+            .buffered_lines_limit(1)
+            .overflow_policy(OverflowPolicy::DropIncoming)
+            .shutdown_timeout(Duration::from_secs(5))
+            .finish();
+
+        // the worker blocks on the first record, the second fills the queue
+        non_blocking.send(b"1".to_vec(), Level::Info).unwrap();
+        writer.wait_for_entered();
+        non_blocking.send(b"2".to_vec(), Level::Info).unwrap();
+        non_blocking.send(b"3".to_vec(), Level::Info).unwrap();
+        assert_eq!(non_blocking.dropped_count(), 1);
+
+        drop(gate);
+        drop(guard);
+        assert_eq!(writer.buf.lock().unwrap().as_slice(), b"12");
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let writer = GateWriter::default();
+        let gate = writer.gate.lock().unwrap();
+        let (non_blocking, guard) = NonBlockingBuilder::new("test-drop-oldest", writer.clone())
+            .buffered_lines_limit(2)
+            .overflow_policy(OverflowPolicy::DropOldest)
+            .shutdown_timeout(Duration::from_secs(5))
+            .finish();
+
+        // the worker blocks on the first record, the next two fill the queue
+        non_blocking.send(b"1".to_vec(), Level::Info).unwrap();
+        writer.wait_for_entered();
+        for record in [b"2", b"3", b"4", b"5"] {
+            non_blocking.send(record.to_vec(), Level::Info).unwrap();
+        }
+        assert_eq!(non_blocking.dropped_count(), 2);
+
+        drop(gate);
+        drop(guard);
+        assert_eq!(writer.buf.lock().unwrap().as_slice(), b"145");
+    }
+
+    #[test]
+    fn test_flush_timeout() {
+        let writer = GateWriter::default();
+        let gate = writer.gate.lock().unwrap();
+        let (non_blocking, guard) = NonBlockingBuilder::new("test-flush-timeout", writer.clone())
+            .buffered_lines_limit(1)
+            .shutdown_timeout(Duration::from_millis(50))
+            .finish();
+
+        // the worker blocks on the first record, the second fills the queue
+        non_blocking.send(b"1".to_vec(), Level::Info).unwrap();
+        writer.wait_for_entered();
+        non_blocking.send(b"2".to_vec(), Level::Info).unwrap();
+        let start = Instant::now();
+        assert!(non_blocking.flush().is_err());
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        drop(gate);
+        drop(guard);
+    }
+
+    #[test]
+    fn test_block_with_timeout() {
+        let writer = GateWriter::default();
+        let gate = writer.gate.lock().unwrap();
+        let (non_blocking, guard) = NonBlockingBuilder::new("test-block-timeout", writer.clone())
+            .buffered_lines_limit(1)
+            .overflow_policy(OverflowPolicy::BlockWithTimeout(Duration::from_millis(20)))
+            .shutdown_timeout(Duration::from_secs(5))
+            .finish();
+
+        non_blocking.send(b"1".to_vec(), Level::Info).unwrap();
+        writer.wait_for_entered();
+        non_blocking.send(b"2".to_vec(), Level::Info).unwrap();
+        let start = Instant::now();
+        non_blocking.send(b"3".to_vec(), Level::Info).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(non_blocking.dropped_count(), 1);
+
+        drop(gate);
+        drop(guard);
+        assert_eq!(writer.buf.lock().unwrap().as_slice(), b"12");
     }
 }
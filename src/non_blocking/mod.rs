@@ -17,11 +17,16 @@ mod worker;
 
 pub use builder::NonBlocking;
 pub use builder::NonBlockingBuilder;
+pub use builder::OverflowPolicy;
 pub use builder::WorkerGuard;
 pub use worker::Writer;
 
 #[derive(Debug)]
 enum Message {
     Record(Vec<u8>),
+    // a record to write and flush without waiting for the buffer to fill
+    Urgent(Vec<u8>),
+    // acknowledged once the records sent before it are written and flushed
+    Flush(crossbeam_channel::Sender<()>),
     Shutdown,
 }
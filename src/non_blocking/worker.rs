@@ -12,19 +12,42 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::any::Any;
 use std::io;
+use std::io::IoSlice;
 use std::io::Write;
+use std::panic::catch_unwind;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
 use crossbeam_channel::Receiver;
-use crossbeam_channel::RecvError;
+use crossbeam_channel::RecvTimeoutError;
 use crossbeam_channel::TryRecvError;
 
 use super::Message;
+use crate::pool;
+
+// The maximum number of records written in one batch; also a portable lower bound of IOV_MAX.
+const MAX_BATCH_SIZE: usize = 1024;
 
 /// A trait for the writer used in non-blocking background thread.
 pub trait Writer {
     /// Write all the formatted record bytes to the writer.
     fn write_all(&mut self, buf: &[u8]) -> io::Result<()>;
+    /// Write a batch of formatted records to the writer.
+    ///
+    /// The default implementation calls [`Writer::write_all`] for each record in order. Writers
+    /// that can submit many buffers at once (e.g., with `writev`) should override this method.
+    fn write_all_batch(&mut self, bufs: &[Vec<u8>]) -> io::Result<()> {
+        for buf in bufs {
+            self.write_all(buf)?;
+        }
+        Ok(())
+    }
     /// Flush the writer.
     fn flush(&mut self) -> io::Result<()>;
 }
@@ -34,15 +57,74 @@ impl<T: Write> Writer for T {
         Write::write_all(self, buf)
     }
 
+    fn write_all_batch(&mut self, bufs: &[Vec<u8>]) -> io::Result<()> {
+        write_all_vectored(self, bufs)
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         Write::flush(self)
     }
 }
 
+// TODO: use Write::write_all_vectored when it is stable - https://github.com/rust-lang/rust/issues/70436
+fn write_all_vectored<W: Write + ?Sized>(writer: &mut W, bufs: &[Vec<u8>]) -> io::Result<()> {
+    let mut bufs = bufs
+        .iter()
+        .filter(|buf| !buf.is_empty())
+        .map(Vec::as_slice)
+        .collect::<Vec<_>>();
+    let mut start = 0;
+    while start < bufs.len() {
+        let end = bufs.len().min(start + MAX_BATCH_SIZE);
+        let slices = bufs[start..end]
+            .iter()
+            .map(|buf| IoSlice::new(buf))
+            .collect::<Vec<_>>();
+        let mut n = match writer.write_vectored(&slices) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole buffer",
+                ))
+            }
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        // advance past the written bytes
+        while n > 0 {
+            let len = bufs[start].len();
+            if n >= len {
+                n -= len;
+                start += 1;
+            } else {
+                bufs[start] = &bufs[start][n..];
+                n = 0;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// When the worker writes buffered records to the writer, besides flush requests and records
+/// at or above the flush level.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Buffering {
+    pub(crate) size: usize,
+    pub(crate) interval: Duration,
+}
+
 pub(crate) struct Worker<T: Writer + Send + 'static> {
     writer: T,
     receiver: Receiver<Message>,
     shutdown: Receiver<()>,
+    healthy: Arc<AtomicBool>,
+    recover_from_panic: bool,
+    buffering: Option<Buffering>,
+    // the records received but not written yet
+    pending: Vec<Vec<u8>>,
+    pending_bytes: usize,
+    last_write: Instant,
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -54,68 +136,219 @@ pub(crate) enum WorkerState {
 }
 
 impl<T: Writer + Send + 'static> Worker<T> {
-    pub(crate) fn new(writer: T, receiver: Receiver<Message>, shutdown: Receiver<()>) -> Worker<T> {
+    pub(crate) fn new(
+        writer: T,
+        receiver: Receiver<Message>,
+        shutdown: Receiver<()>,
+        healthy: Arc<AtomicBool>,
+        recover_from_panic: bool,
+        buffering: Option<Buffering>,
+    ) -> Worker<T> {
         Self {
             writer,
             receiver,
             shutdown,
+            healthy,
+            recover_from_panic,
+            buffering,
+            pending: Vec::new(),
+            pending_bytes: 0,
+            last_write: Instant::now(),
         }
     }
 
-    fn recv(&mut self) -> io::Result<WorkerState> {
-        match self.receiver.recv() {
-            Ok(Message::Record(record)) => {
-                self.writer.write_all(&record)?;
-                Ok(WorkerState::Continue)
+    pub(crate) fn work(&mut self) -> io::Result<WorkerState> {
+        let mut acks = Vec::new();
+        let mut urgent = false;
+        // wait for the next message, or until the buffered records are due
+        let timeout = match self.buffering {
+            Some(buffering) if !self.pending.is_empty() => {
+                buffering.interval.saturating_sub(self.last_write.elapsed())
+            }
+            _ => Duration::MAX,
+        };
+        let mut worker_state = match self.receiver.recv_timeout(timeout) {
+            Ok(message) => self.receive(message, &mut acks, &mut urgent),
+            Err(RecvTimeoutError::Timeout) => WorkerState::Empty,
+            Err(RecvTimeoutError::Disconnected) => WorkerState::Disconnected,
+        };
+
+        while worker_state == WorkerState::Continue {
+            worker_state = match self.receiver.try_recv() {
+                Ok(message) => self.receive(message, &mut acks, &mut urgent),
+                Err(TryRecvError::Empty) => WorkerState::Empty,
+                Err(TryRecvError::Disconnected) => WorkerState::Disconnected,
+            };
+
+            if self.pending.len() >= MAX_BATCH_SIZE {
+                self.write_pending()?;
             }
-            Ok(Message::Shutdown) => Ok(WorkerState::Shutdown),
-            Err(RecvError) => Ok(WorkerState::Disconnected),
         }
-    }
 
-    fn try_recv(&mut self) -> io::Result<WorkerState> {
-        match self.receiver.try_recv() {
-            Ok(Message::Record(record)) => {
-                self.writer.write_all(&record)?;
-                Ok(WorkerState::Continue)
+        let due = match self.buffering {
+            None => true,
+            Some(buffering) => {
+                urgent
+                    || !acks.is_empty()
+                    || worker_state != WorkerState::Empty
+                    || self.pending_bytes >= buffering.size
+                    || self.last_write.elapsed() >= buffering.interval
             }
-            Ok(Message::Shutdown) => Ok(WorkerState::Shutdown),
-            Err(TryRecvError::Empty) => Ok(WorkerState::Empty),
-            Err(TryRecvError::Disconnected) => Ok(WorkerState::Disconnected),
+        };
+        if due {
+            self.write_pending()?;
+            self.writer.flush()?;
+            self.last_write = Instant::now();
+        }
+        for ack in acks {
+            let _ = ack.send(());
         }
+        Ok(worker_state)
     }
 
-    pub(crate) fn work(&mut self) -> io::Result<WorkerState> {
-        let mut worker_state = self.recv()?;
-
-        while worker_state == WorkerState::Continue {
-            worker_state = self.try_recv()?;
+    fn receive(
+        &mut self,
+        message: Message,
+        acks: &mut Vec<crossbeam_channel::Sender<()>>,
+        urgent: &mut bool,
+    ) -> WorkerState {
+        match message {
+            Message::Record(record) => {
+                self.pending_bytes += record.len();
+                self.pending.push(record);
+            }
+            Message::Urgent(record) => {
+                self.pending_bytes += record.len();
+                self.pending.push(record);
+                *urgent = true;
+            }
+            Message::Flush(ack) => acks.push(ack),
+            Message::Shutdown => return WorkerState::Shutdown,
         }
+        WorkerState::Continue
+    }
 
-        self.writer.flush()?;
-        Ok(worker_state)
+    /// Writes the pending records, which are dropped if the writer fails.
+    fn write_pending(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let pending = std::mem::take(&mut self.pending);
+        self.pending_bytes = 0;
+        let result = self.writer.write_all_batch(&pending);
+        pending.into_iter().for_each(pool::recycle);
+        result
     }
 
     pub(crate) fn make_thread(mut self, name: String) -> std::thread::JoinHandle<()> {
         std::thread::Builder::new()
             .name(name)
             .spawn(move || {
+                let mut shutdown = false;
                 loop {
-                    match self.work() {
-                        Ok(WorkerState::Continue) | Ok(WorkerState::Empty) => {}
-                        Ok(WorkerState::Shutdown) | Ok(WorkerState::Disconnected) => {
-                            let _ = self.shutdown.recv();
+                    // the writer is only touched from this thread; a panic leaves it in whatever
+                    // state it was in, which is no worse than an I/O error mid-write
+                    match catch_unwind(AssertUnwindSafe(|| self.work())) {
+                        Ok(Ok(WorkerState::Continue)) | Ok(Ok(WorkerState::Empty)) => {}
+                        Ok(Ok(WorkerState::Shutdown)) | Ok(Ok(WorkerState::Disconnected)) => {
+                            shutdown = true;
                             break;
                         }
-                        Err(err) => {
+                        Ok(Err(err)) => {
                             eprintln!("failed to write log: {err}");
                         }
+                        Err(panic) => {
+                            let reason = panic_message(&*panic);
+                            if self.recover_from_panic {
+                                eprintln!("logging worker panicked and recovered: {reason}");
+                            } else {
+                                eprintln!("logging worker panicked: {reason}");
+                                break;
+                            }
+                        }
                     }
                 }
+                self.healthy.store(false, Ordering::Release);
                 if let Err(err) = self.writer.flush() {
                     eprintln!("failed to flush: {err}");
                 }
+                // the writer is dropped before the guard is released, so that the guard waits
+                // for the writer's own background work, e.g., compressing rotated files
+                let Worker {
+                    writer,
+                    shutdown: shutdown_receiver,
+                    ..
+                } = self;
+                drop(writer);
+                if shutdown {
+                    let _ = shutdown_receiver.recv();
+                }
             })
             .expect("failed to spawn the non-blocking rolling file writer thread")
     }
 }
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(s) = panic.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = panic.downcast_ref::<String>() {
+        s
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::io::IoSlice;
+    use std::io::Write;
+
+    use super::write_all_vectored;
+
+    // A writer that accepts at most a few bytes per call to exercise partial writes.
+    struct ShortWriter {
+        buf: Vec<u8>,
+        limit: usize,
+    }
+
+    impl Write for ShortWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.limit);
+            self.buf.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+            let mut written = 0;
+            for buf in bufs {
+                let n = buf.len().min(self.limit - written);
+                self.buf.extend_from_slice(&buf[..n]);
+                written += n;
+                if written == self.limit {
+                    break;
+                }
+            }
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_all_vectored() {
+        let records = vec![
+            b"first\n".to_vec(),
+            vec![],
+            b"second\n".to_vec(),
+            b"third\n".to_vec(),
+        ];
+        for limit in [1, 3, 7, 64] {
+            let mut writer = ShortWriter { buf: vec![], limit };
+            write_all_vectored(&mut writer, &records).unwrap();
+            assert_eq!(writer.buf, b"first\nsecond\nthird\n");
+        }
+    }
+}
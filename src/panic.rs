@@ -0,0 +1,202 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Structured key-values for panic records.
+//!
+//! Panic messages forwarded to the logger, e.g., by a panic hook logging the standard library's
+//! panic output or by `log_panics`-style crates, arrive as one opaque message such as
+//! `thread 'main' panicked at src/main.rs:2:5:\nboom`. With
+//! [`Builder::normalize_panics`](crate::Builder::normalize_panics), the logger recognizes the
+//! conventional formats in error records, replaces the message with the panic message, and
+//! attaches the thread and the location as key-values under [`PANIC_THREAD_KEY`],
+//! [`PANIC_FILE_KEY`], [`PANIC_LINE_KEY`] and [`PANIC_COLUMN_KEY`].
+//!
+//! # Examples
+//!
+//! ```
+//! use logforth::append;
+//!
+//! logforth::builder()
+//!     .normalize_panics(true)
+//!     .dispatch(|d| d.append(append::Stderr::default()))
+//!     .apply();
+//!
+//! // logged as "boom" with panic.thread="main", panic.file="src/main.rs", panic.line=2, etc.
+//! log::error!("thread 'main' panicked at src/main.rs:2:5:\nboom");
+//! ```
+
+use std::borrow::Cow;
+
+use log::kv::Error;
+use log::kv::Key;
+use log::kv::Source;
+use log::kv::Value;
+use log::kv::VisitSource;
+
+/// The key under which the name of the panicking thread is attached to a record.
+pub const PANIC_THREAD_KEY: &str = "panic.thread";
+
+/// The key under which the file of the panic location is attached to a record.
+pub const PANIC_FILE_KEY: &str = "panic.file";
+
+/// The key under which the line of the panic location is attached to a record.
+pub const PANIC_LINE_KEY: &str = "panic.line";
+
+/// The key under which the column of the panic location is attached to a record, if known.
+pub const PANIC_COLUMN_KEY: &str = "panic.column";
+
+/// A panic parsed from a record's message.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Panic<'a> {
+    pub(crate) thread: &'a str,
+    pub(crate) message: Cow<'a, str>,
+    pub(crate) file: &'a str,
+    pub(crate) line: u32,
+    pub(crate) column: Option<u32>,
+}
+
+/// Parses a panic in one of the conventional formats:
+///
+/// * `thread '{thread}' panicked at {file}:{line}:{column}:\n{message}`, the standard library's
+///   since Rust 1.73;
+/// * `thread '{thread}' panicked at '{message}', {file}:{line}:{column}`, the standard library's
+///   before;
+/// * `thread '{thread}' panicked at '{message}': {file}:{line}`, the `log-panics` crate's, possibly
+///   followed by a backtrace.
+///
+/// Any text following the location, such as a backtrace, is kept at the end of the message.
+pub(crate) fn parse(text: &str) -> Option<Panic<'_>> {
+    let rest = text.strip_prefix("thread '")?;
+    let (thread, rest) = rest.split_once("' panicked at ")?;
+
+    if let Some(quoted) = rest.strip_prefix('\'') {
+        // the message may contain quotes, so find the last separator followed by a location
+        return ["', ", "': "].into_iter().find_map(|separator| {
+            quoted.rmatch_indices(separator).find_map(|(i, separator)| {
+                let (location, trailing) = split_location(&quoted[i + separator.len()..])?;
+                let message = match trailing.trim_start_matches(['\r', '\n']).trim_end() {
+                    "" => Cow::Borrowed(&quoted[..i]),
+                    trailing => Cow::Owned(format!("{}\n{trailing}", &quoted[..i])),
+                };
+                Some(Panic {
+                    thread,
+                    message,
+                    file: location.0,
+                    line: location.1,
+                    column: location.2,
+                })
+            })
+        });
+    }
+
+    let (location, message) = rest.split_once(":\n")?;
+    let ((file, line, column), trailing) = split_location(location)?;
+    if !trailing.is_empty() {
+        return None;
+    }
+    Some(Panic {
+        thread,
+        message: Cow::Borrowed(message),
+        file,
+        line,
+        column,
+    })
+}
+
+type Location<'a> = (&'a str, u32, Option<u32>);
+
+/// Splits a `{file}:{line}[:{column}]` location, ending at a whitespace, off the text.
+fn split_location(text: &str) -> Option<(Location<'_>, &str)> {
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    let (location, trailing) = text.split_at(end);
+
+    let (rest, last) = location.rsplit_once(':')?;
+    let last = last.parse().ok()?;
+    let location = match rest.rsplit_once(':') {
+        Some((file, line)) if line.parse::<u32>().is_ok() => (file, line.parse().ok()?, Some(last)),
+        _ => (rest, last, None),
+    };
+    if location.0.is_empty() {
+        return None;
+    }
+    Some((location, trailing))
+}
+
+/// The key-values of a record followed by the location of a panic.
+pub(crate) struct WithPanic<'a> {
+    pub(crate) source: &'a dyn Source,
+    pub(crate) panic: &'a Panic<'a>,
+}
+
+impl Source for WithPanic<'_> {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), Error> {
+        self.source.visit(visitor)?;
+        visitor.visit_pair(
+            Key::from_str(PANIC_THREAD_KEY),
+            Value::from(self.panic.thread),
+        )?;
+        visitor.visit_pair(Key::from_str(PANIC_FILE_KEY), Value::from(self.panic.file))?;
+        visitor.visit_pair(Key::from_str(PANIC_LINE_KEY), Value::from(self.panic.line))?;
+        if let Some(column) = self.panic.column {
+            visitor.visit_pair(Key::from_str(PANIC_COLUMN_KEY), Value::from(column))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn panic<'a>(
+        thread: &'a str,
+        message: &'a str,
+        file: &'a str,
+        line: u32,
+        column: Option<u32>,
+    ) -> Option<Panic<'a>> {
+        Some(Panic {
+            thread,
+            message: Cow::Borrowed(message),
+            file,
+            line,
+            column,
+        })
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("thread 'main' panicked at src/main.rs:2:5:\nboom\nagain"),
+            panic("main", "boom\nagain", "src/main.rs", 2, Some(5))
+        );
+        assert_eq!(
+            parse("thread '<unnamed>' panicked at 'it's, broken', src/lib.rs:10:1"),
+            panic("<unnamed>", "it's, broken", "src/lib.rs", 10, Some(1))
+        );
+        assert_eq!(
+            parse("thread 'worker' panicked at 'boom': src/lib.rs:7"),
+            panic("worker", "boom", "src/lib.rs", 7, None)
+        );
+        assert_eq!(
+            parse("thread 'worker' panicked at 'boom': src/lib.rs:7\n   0: main")
+                .unwrap()
+                .message,
+            "boom\n   0: main"
+        );
+
+        assert_eq!(parse("thread 'main' panicked at nowhere:\nboom"), None);
+        assert_eq!(parse("something else"), None);
+    }
+}
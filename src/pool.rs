@@ -0,0 +1,130 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A pool of buffers for formatted log records.
+//!
+//! Built-in layouts take buffers from the pool to format records, and built-in appenders return
+//! them once written, so that buffers are recycled rather than allocated and freed per record.
+//!
+//! The pool is disabled by default. Enable it with [`set_limits`], and monitor it with [`stats`].
+//!
+//! # Examples
+//!
+//! ```
+//! // keep up to 256 buffers of at most 4 KiB each
+//! logforth::pool::set_limits(256, 4096);
+//!
+//! logforth::stdout().apply();
+//! log::info!("This record is formatted into a pooled buffer.");
+//!
+//! let stats = logforth::pool::stats();
+//! println!("reused {} buffers", stats.reused);
+//! ```
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+static BUFFERS: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+static MAX_BUFFERS: AtomicUsize = AtomicUsize::new(0);
+static MAX_BUFFER_CAPACITY: AtomicUsize = AtomicUsize::new(0);
+
+static REUSED: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static RECYCLED: AtomicU64 = AtomicU64::new(0);
+static DISCARDED: AtomicU64 = AtomicU64::new(0);
+
+/// Statistics of the buffer pool.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PoolStats {
+    /// The number of buffers taken from the pool.
+    pub reused: u64,
+    /// The number of buffers allocated because the pool was empty.
+    pub allocated: u64,
+    /// The number of buffers returned to the pool.
+    pub recycled: u64,
+    /// The number of buffers dropped because the pool was full or the buffer was too large.
+    pub discarded: u64,
+}
+
+/// Sets the limits of the buffer pool.
+///
+/// The pool keeps at most `max_buffers` buffers, each with a capacity of at most
+/// `max_buffer_capacity` bytes; larger buffers are dropped so that a few huge records do not pin
+/// memory. Setting `max_buffers` to zero disables the pool, which is the default.
+pub fn set_limits(max_buffers: usize, max_buffer_capacity: usize) {
+    MAX_BUFFER_CAPACITY.store(max_buffer_capacity, Ordering::Relaxed);
+    MAX_BUFFERS.store(max_buffers, Ordering::Relaxed);
+
+    let mut buffers = BUFFERS.lock().unwrap_or_else(|e| e.into_inner());
+    buffers.truncate(max_buffers);
+    buffers.retain(|buffer| buffer.capacity() <= max_buffer_capacity);
+    buffers.shrink_to_fit();
+}
+
+/// Returns the statistics of the buffer pool.
+pub fn stats() -> PoolStats {
+    PoolStats {
+        reused: REUSED.load(Ordering::Relaxed),
+        allocated: ALLOCATED.load(Ordering::Relaxed),
+        recycled: RECYCLED.load(Ordering::Relaxed),
+        discarded: DISCARDED.load(Ordering::Relaxed),
+    }
+}
+
+/// Takes an empty buffer from the pool, or allocates a new one if the pool is empty.
+pub(crate) fn take() -> Vec<u8> {
+    if MAX_BUFFERS.load(Ordering::Relaxed) == 0 {
+        return Vec::new();
+    }
+
+    let buffer = BUFFERS.lock().unwrap_or_else(|e| e.into_inner()).pop();
+    match buffer {
+        Some(buffer) => {
+            REUSED.fetch_add(1, Ordering::Relaxed);
+            buffer
+        }
+        None => {
+            ALLOCATED.fetch_add(1, Ordering::Relaxed);
+            Vec::new()
+        }
+    }
+}
+
+/// Takes an empty buffer from the pool as a string.
+pub(crate) fn take_string() -> String {
+    // an empty buffer is always valid UTF-8
+    String::from_utf8(take()).unwrap_or_default()
+}
+
+/// Returns a buffer to the pool.
+pub(crate) fn recycle(mut buffer: Vec<u8>) {
+    let max_buffers = MAX_BUFFERS.load(Ordering::Relaxed);
+    if max_buffers == 0 {
+        return;
+    }
+
+    if buffer.capacity() <= MAX_BUFFER_CAPACITY.load(Ordering::Relaxed) {
+        let mut buffers = BUFFERS.lock().unwrap_or_else(|e| e.into_inner());
+        if buffers.len() < max_buffers {
+            buffer.clear();
+            buffers.push(buffer);
+            RECYCLED.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    }
+    DISCARDED.fetch_add(1, Ordering::Relaxed);
+}
@@ -0,0 +1,362 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An owned log record with a canonical JSON representation.
+//!
+//! [`RecordOwned`] is the interchange format of records between processes and tools, e.g., the
+//! [IPC appender](crate::append::ipc) and its agents. Its JSON shape is the output of
+//! [`JsonLayout`]:
+//!
+//! | Field                   | Type    | Description                                             |
+//! |-------------------------|---------|---------------------------------------------------------|
+//! | `timestamp`             | string  | RFC 3339 with a time zone annotation, e.g., `[UTC]`     |
+//! | `level`                 | string  | `ERROR`, `WARN`, `INFO`, `DEBUG` or `TRACE`             |
+//! | `target`                | string  | The target of the record                                |
+//! | `file`                  | string  | The source file, or empty                               |
+//! | `line`                  | integer | The source line, or `0`                                 |
+//! | `message`               | string  | The formatted message                                   |
+//! | `message_template`      | string  | Optional, the [message template](crate::template)       |
+//! | `message_template_hash` | integer | Optional, the [hash](crate::template::template_hash) of the template |
+//! | `backtrace`             | string  | Optional, the captured [backtrace](crate::backtrace)    |
+//! | `kvs`                   | object  | The key-values of the record and its diagnostics        |
+//!
+//! Values of `kvs` are strings; other JSON values are accepted when parsing and kept as their
+//! JSON text. As in any JSON object, each key appears once: a record key-value and a diagnostic
+//! of the same name collapse into the one [`JsonLayout`] writes last, and the order of the
+//! key-values is not kept. Unknown fields are ignored when parsing.
+//!
+//! # Examples
+//!
+//! ```
+//! use logforth::record::RecordOwned;
+//!
+//! let record = log::Record::builder()
+//!     .args(format_args!("Hello"))
+//!     .level(log::Level::Info)
+//!     .target("app")
+//!     .build();
+//! let owned = RecordOwned::from_record(&record, &[]).unwrap();
+//! let json = owned.to_json();
+//! assert_eq!(json["message"], "Hello");
+//! assert_eq!(RecordOwned::from_json(&json).unwrap(), owned);
+//! ```
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use anyhow::Context;
+use jiff::Zoned;
+use log::kv::Key;
+use log::kv::Source;
+use log::kv::Value;
+use log::kv::VisitSource;
+use log::Level;
+use log::Record;
+use serde_json::Map;
+
+use crate::backtrace::BACKTRACE_KEY;
+use crate::layout::JsonLayout;
+use crate::template::template_hash;
+use crate::template::MESSAGE_TEMPLATE_KEY;
+use crate::Diagnostic;
+
+/// An owned log record; see the [module documentation](self) for its JSON shape.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct RecordOwned {
+    /// The time of the record.
+    pub timestamp: Zoned,
+    /// The level of the record.
+    pub level: Level,
+    /// The target of the record.
+    pub target: String,
+    /// The source file of the record, if known.
+    pub file: Option<String>,
+    /// The source line of the record, if known.
+    pub line: Option<u32>,
+    /// The formatted message of the record.
+    pub message: String,
+    /// The message template of the record, if any.
+    pub message_template: Option<String>,
+    /// The captured backtrace of the record, if any.
+    pub backtrace: Option<String>,
+    /// The key-values of the record and its diagnostics, unique and sorted by key.
+    pub kvs: BTreeMap<String, String>,
+}
+
+impl RecordOwned {
+    /// Captures a record and its diagnostics, timestamped now in the system time zone.
+    ///
+    /// The record is captured as [`JsonLayout`] formats it, e.g., secret key-values are masked.
+    pub fn from_record(record: &Record, diagnostics: &[Diagnostic]) -> anyhow::Result<Self> {
+        let bytes = JsonLayout::default()
+            .backtrace(true)
+            .format(record, diagnostics)?;
+        let json = serde_json::from_slice(&bytes)?;
+        Self::from_json(&json)
+    }
+
+    /// Parses a record from its JSON representation.
+    pub fn from_json(json: &serde_json::Value) -> anyhow::Result<Self> {
+        let object = json.as_object().context("record must be a JSON object")?;
+        let str_field = |name: &str| -> anyhow::Result<Option<&str>> {
+            match object.get(name) {
+                None | Some(serde_json::Value::Null) => Ok(None),
+                Some(value) => value
+                    .as_str()
+                    .map(Some)
+                    .with_context(|| format!("record field {name} must be a string")),
+            }
+        };
+        let required = |name: &str| -> anyhow::Result<&str> {
+            str_field(name)?.with_context(|| format!("record field {name} is missing"))
+        };
+
+        let timestamp = required("timestamp")?;
+        let timestamp = Zoned::from_str(timestamp)
+            .with_context(|| format!("invalid record timestamp: {timestamp}"))?;
+        let level = required("level")?;
+        let level =
+            Level::from_str(level).with_context(|| format!("invalid record level: {level}"))?;
+        let line = match object.get("line") {
+            None | Some(serde_json::Value::Null) => None,
+            Some(line) => Some(
+                line.as_u64()
+                    .and_then(|line| u32::try_from(line).ok())
+                    .context("record field line must be an unsigned 32-bit integer")?,
+            ),
+        }
+        .filter(|line| *line != 0);
+        let kvs = match object.get("kvs") {
+            None | Some(serde_json::Value::Null) => BTreeMap::new(),
+            Some(kvs) => kvs
+                .as_object()
+                .context("record field kvs must be an object")?
+                .iter()
+                .map(|(key, value)| match value {
+                    serde_json::Value::String(value) => (key.clone(), value.clone()),
+                    value => (key.clone(), value.to_string()),
+                })
+                .collect(),
+        };
+
+        Ok(Self {
+            timestamp,
+            level,
+            target: str_field("target")?.unwrap_or_default().to_string(),
+            file: str_field("file")?
+                .filter(|file| !file.is_empty())
+                .map(str::to_string),
+            line,
+            message: required("message")?.to_string(),
+            message_template: str_field("message_template")?.map(str::to_string),
+            backtrace: str_field("backtrace")?.map(str::to_string),
+            kvs,
+        })
+    }
+
+    /// Returns the JSON representation of the record.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut object = Map::new();
+        let timestamp = format!("{:.6}", self.timestamp);
+        object.insert("timestamp".to_string(), timestamp.into());
+        object.insert("level".to_string(), self.level.as_str().into());
+        object.insert("target".to_string(), self.target.as_str().into());
+        let file = self.file.as_deref().unwrap_or_default();
+        object.insert("file".to_string(), file.into());
+        object.insert("line".to_string(), self.line.unwrap_or_default().into());
+        object.insert("message".to_string(), self.message.as_str().into());
+        if let Some(template) = &self.message_template {
+            object.insert("message_template".to_string(), template.as_str().into());
+            let hash = template_hash(template);
+            object.insert("message_template_hash".to_string(), hash.into());
+        }
+        if let Some(backtrace) = &self.backtrace {
+            object.insert("backtrace".to_string(), backtrace.as_str().into());
+        }
+        let kvs = self
+            .kvs
+            .iter()
+            .map(|(key, value)| (key.clone(), value.as_str().into()))
+            .collect();
+        object.insert("kvs".to_string(), serde_json::Value::Object(kvs));
+        serde_json::Value::Object(object)
+    }
+
+    /// Logs the record to `logger`, e.g., to replay records received from another process with
+    /// [`log::logger()`].
+    ///
+    /// The message template and the backtrace are passed as key-values, so layouts render them as
+    /// for the original record. The timestamp is not passed on: layouts stamp the replayed record
+    /// with the current time.
+    pub fn log(&self, logger: &dyn log::Log) {
+        logger.log(
+            &Record::builder()
+                .level(self.level)
+                .target(&self.target)
+                .file(self.file.as_deref())
+                .line(self.line)
+                .args(format_args!("{}", self.message))
+                .key_values(self)
+                .build(),
+        );
+    }
+}
+
+impl Source for RecordOwned {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), log::kv::Error> {
+        if let Some(template) = &self.message_template {
+            visitor.visit_pair(
+                Key::from_str(MESSAGE_TEMPLATE_KEY),
+                Value::from(template.as_str()),
+            )?;
+        }
+        for (key, value) in &self.kvs {
+            visitor.visit_pair(Key::from_str(key), Value::from(value.as_str()))?;
+        }
+        if let Some(backtrace) = &self.backtrace {
+            visitor.visit_pair(
+                Key::from_str(BACKTRACE_KEY),
+                Value::from(backtrace.as_str()),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append::Testing;
+    use crate::Append;
+
+    #[test]
+    fn test_json_round_trip() {
+        let kvs = [
+            (MESSAGE_TEMPLATE_KEY, "user {user_id} logged in"),
+            ("user_id", "42"),
+        ];
+        let record = Record::builder()
+            .args(format_args!("user 42 logged in"))
+            .level(Level::Warn)
+            .target("app")
+            .file(Some("src/main.rs"))
+            .line(Some(7))
+            .key_values(&kvs)
+            .build();
+        let owned = RecordOwned::from_record(&record, &[]).unwrap();
+        assert_eq!(owned.level, Level::Warn);
+        assert_eq!(owned.file.as_deref(), Some("src/main.rs"));
+        assert_eq!(
+            owned.message_template.as_deref(),
+            Some("user {user_id} logged in")
+        );
+        assert_eq!(
+            owned.kvs,
+            BTreeMap::from([("user_id".to_string(), "42".to_string())])
+        );
+
+        // the canonical shape is the output of the JSON layout
+        let json = owned.to_json();
+        let formatted = JsonLayout::default().format(&record, &[]).unwrap();
+        let mut formatted: serde_json::Value = serde_json::from_slice(&formatted).unwrap();
+        formatted["timestamp"] = json["timestamp"].clone();
+        assert_eq!(json, formatted);
+        assert_eq!(RecordOwned::from_json(&json).unwrap(), owned);
+    }
+
+    #[test]
+    fn test_kvs_sorted_and_unique() {
+        let kvs = [
+            ("zone", "eu"),
+            ("attempt", "1"),
+            ("method", "GET"),
+            ("attempt", "2"),
+        ];
+        let record = Record::builder()
+            .args(format_args!("request"))
+            .key_values(&kvs)
+            .build();
+        let owned = RecordOwned::from_record(&record, &[]).unwrap();
+        assert_eq!(
+            owned.kvs.keys().collect::<Vec<_>>(),
+            ["attempt", "method", "zone"]
+        );
+        assert_eq!(owned.kvs["attempt"], "2");
+        assert_eq!(RecordOwned::from_json(&owned.to_json()).unwrap(), owned);
+    }
+
+    #[test]
+    fn test_from_json_lenient() {
+        let json = serde_json::json!({
+            "timestamp": "2024-08-11T22:44:57.172051+08:00[+08:00]",
+            "level": "info",
+            "message": "Hello",
+            "file": "",
+            "line": 0,
+            "kvs": { "count": 3, "ok": true },
+            "extra": "ignored",
+        });
+        let owned = RecordOwned::from_json(&json).unwrap();
+        assert_eq!(owned.level, Level::Info);
+        assert_eq!(owned.target, "");
+        assert_eq!(owned.file, None);
+        assert_eq!(owned.line, None);
+        assert_eq!(
+            owned.kvs,
+            BTreeMap::from([
+                ("count".to_string(), "3".to_string()),
+                ("ok".to_string(), "true".to_string())
+            ])
+        );
+
+        let err = RecordOwned::from_json(&serde_json::json!({ "level": "INFO" })).unwrap_err();
+        assert_eq!(err.to_string(), "record field timestamp is missing");
+    }
+
+    #[test]
+    fn test_replay() {
+        #[derive(Debug)]
+        struct Replay(Testing);
+
+        impl log::Log for Replay {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &Record) {
+                self.0.append(record, &[]).unwrap();
+            }
+
+            fn flush(&self) {}
+        }
+
+        let testing = Testing::default();
+        let handle = testing.handle();
+        let json = serde_json::json!({
+            "timestamp": "2024-08-11T22:44:57.172051+08:00[+08:00]",
+            "level": "ERROR",
+            "target": "remote",
+            "message": "failed",
+            "message_template": "failed",
+            "kvs": { "code": "E42" },
+        });
+        RecordOwned::from_json(&json).unwrap().log(&Replay(testing));
+
+        let records = handle.records();
+        assert_eq!(records[0].target, "remote");
+        assert_eq!(records[0].kv("code"), Some("E42"));
+        assert_eq!(records[0].kv(MESSAGE_TEMPLATE_KEY), Some("failed"));
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Per-record routing to dispatches.
+//!
+//! A record with the reserved key-value [`ROUTE_KEY`] is delivered only to the dispatches tagged
+//! with that route by [`DispatchBuilder::route`], regardless of their filters. This lets call
+//! sites direct special records, e.g., audit or security events, to dedicated sinks. Records
+//! routed to a route no dispatch is tagged with are dispatched as usual.
+//!
+//! The global max level of [`log::set_max_level`] still applies to routed records.
+//!
+//! # Examples
+//!
+//! ```
+//! use logforth::append;
+//!
+//! logforth::builder()
+//!     .dispatch(|d| d.filter(log::LevelFilter::Info).append(append::Stdout::default()))
+//!     .dispatch(|d| {
+//!         d.filter(log::LevelFilter::Off)
+//!             .route("audit")
+//!             .append(append::Stderr::default())
+//!     })
+//!     .apply();
+//!
+//! log::debug!("logforth.route" = "audit", user_id = 42; "user deleted");
+//! ```
+//!
+//! [`DispatchBuilder::route`]: crate::DispatchBuilder::route
+
+use log::kv::Key;
+use log::Record;
+
+/// The key of the route of a record.
+pub const ROUTE_KEY: &str = "logforth.route";
+
+/// Returns the route of the record, if it has one.
+pub fn route(record: &Record) -> Option<String> {
+    record
+        .key_values()
+        .get(Key::from_str(ROUTE_KEY))
+        .map(|value| value.to_string())
+}
@@ -0,0 +1,54 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Masking of sensitive key-values.
+//!
+//! Key-values whose key starts with [`SECRET_KEY_PREFIX`] are considered secrets. Built-in layouts
+//! and appenders render them with the prefix stripped from the key and the value replaced by
+//! [`MASK`]. The raw value is still available on the record, so that explicitly trusted appenders
+//! (e.g., a custom appender writing to an encrypted audit file) can read it.
+//!
+//! Masking cannot be turned off: rename keys that happen to start with the prefix to keep their
+//! values in the output.
+//!
+//! # Examples
+//!
+//! ```
+//! logforth::stdout().apply();
+//!
+//! let token = "s3cr3t";
+//! // written as `... user logged in user=alice token=[REDACTED]`
+//! log::info!(user = "alice", secret_token = token; "user logged in");
+//! ```
+
+/// The key prefix marking a key-value as a secret.
+pub const SECRET_KEY_PREFIX: &str = "secret_";
+
+/// The text rendered in place of a secret value.
+pub const MASK: &str = "[REDACTED]";
+
+/// Returns the key with [`SECRET_KEY_PREFIX`] stripped if the key is a secret; otherwise,
+/// returns `None`.
+///
+/// # Examples
+///
+/// ```
+/// use logforth::secret::secret_key;
+///
+/// assert_eq!(secret_key("secret_token"), Some("token"));
+/// assert_eq!(secret_key("user"), None);
+/// ```
+pub fn secret_key(key: &str) -> Option<&str> {
+    key.strip_prefix(SECRET_KEY_PREFIX)
+}
@@ -0,0 +1,201 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Lightweight spans that log their duration.
+//!
+//! The [`span!`](crate::span!) macro starts a [`Span`], which logs one record when it's dropped,
+//! with the name of the span as the message, the key-values given to the macro, and
+//! [`SPAN_NAME_KEY`] and [`SPAN_DURATION_KEY`]. The record goes through the installed logger like
+//! any other, so it's filtered and enriched with diagnostics as usual. Nothing is measured if the
+//! level of the span is disabled when the span starts.
+//!
+//! # Examples
+//!
+//! ```
+//! use log::Level;
+//!
+//! fn query(query_id: u64) -> usize {
+//!     let mut span = logforth::span!("db_query", query_id = query_id);
+//!     let rows = 42;
+//!     span.record("rows", rows);
+//!     rows
+//! }
+//!
+//! let _span = logforth::span!(level: Level::Debug, "startup");
+//! query(7);
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+use std::time::Instant;
+
+use log::kv::Value;
+use log::Level;
+use log::Metadata;
+use log::Record;
+
+use crate::clock;
+
+/// The key under which the name of the span is attached to its record.
+pub const SPAN_NAME_KEY: &str = "span.name";
+
+/// The key under which the duration of the span in microseconds is attached to its record.
+pub const SPAN_DURATION_KEY: &str = "span.duration_us";
+
+/// A span that logs its duration when dropped.
+///
+/// Created with the [`span!`](crate::span!) macro.
+#[must_use = "the span ends when it's dropped"]
+#[derive(Debug)]
+pub struct Span {
+    // `None` if the level of the span is disabled
+    inner: Option<SpanInner>,
+}
+
+#[derive(Debug)]
+struct SpanInner {
+    name: Cow<'static, str>,
+    level: Level,
+    target: &'static str,
+    file: &'static str,
+    line: u32,
+    kvs: Vec<(&'static str, String)>,
+    start: Instant,
+}
+
+impl Span {
+    #[doc(hidden)]
+    pub fn start(
+        level: Level,
+        target: &'static str,
+        file: &'static str,
+        line: u32,
+        name: impl Into<Cow<'static, str>>,
+        kvs: impl FnOnce(&mut Vec<(&'static str, String)>),
+    ) -> Self {
+        let metadata = Metadata::builder().level(level).target(target).build();
+        if level > log::max_level() || !log::logger().enabled(&metadata) {
+            return Span { inner: None };
+        }
+
+        let mut inner = SpanInner {
+            name: name.into(),
+            level,
+            target,
+            file,
+            line,
+            kvs: vec![],
+            start: clock::instant(),
+        };
+        kvs(&mut inner.kvs);
+        Span { inner: Some(inner) }
+    }
+
+    /// Attaches a key-value to the record of the span, e.g., a result known only at the end.
+    pub fn record(&mut self, key: &'static str, value: impl fmt::Display) {
+        if let Some(inner) = &mut self.inner {
+            inner.kvs.push((key, value.to_string()));
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            inner.end(log::logger());
+        }
+    }
+}
+
+impl SpanInner {
+    fn end(self, logger: &dyn log::Log) {
+        let duration = clock::instant().duration_since(self.start);
+        let duration_us = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        let mut kvs = self
+            .kvs
+            .iter()
+            .map(|(key, value)| (*key, Value::from(value.as_str())))
+            .collect::<Vec<_>>();
+        kvs.push((SPAN_NAME_KEY, Value::from(&*self.name)));
+        kvs.push((SPAN_DURATION_KEY, Value::from(duration_us)));
+
+        logger.log(
+            &Record::builder()
+                .level(self.level)
+                .target(self.target)
+                .module_path_static(Some(self.target))
+                .file_static(Some(self.file))
+                .line(Some(self.line))
+                .args(format_args!("{}", self.name))
+                .key_values(&kvs)
+                .build(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use log::kv::Key;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl log::Log for Recorder {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            let kvs = record.key_values();
+            let get = |key| kvs.get(Key::from_str(key)).map(|v| v.to_string());
+            self.0.lock().unwrap().push(format!(
+                "{} {} {:?} {:?} {}",
+                record.level(),
+                record.args(),
+                get("query_id"),
+                get(SPAN_NAME_KEY),
+                get(SPAN_DURATION_KEY)
+                    .and_then(|us| us.parse::<u64>().ok())
+                    .is_some_and(|us| us >= 1000),
+            ));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_span_record() {
+        let recorder = Recorder::default();
+        let inner = SpanInner {
+            name: "db_query".into(),
+            level: Level::Debug,
+            target: module_path!(),
+            file: file!(),
+            line: line!(),
+            kvs: vec![("query_id", "7".to_string())],
+            start: clock::instant() - Duration::from_millis(1),
+        };
+        inner.end(&recorder);
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [r#"DEBUG db_query Some("7") Some("db_query") true"#]
+        );
+    }
+}
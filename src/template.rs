@@ -0,0 +1,105 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Message templates.
+//!
+//! A message template is the format string of a log message before the arguments are rendered,
+//! e.g., `User {user_id} logged in from {ip}`. Records of the same event share one template
+//! regardless of the argument values, which makes templates a good key for aggregating logs.
+//!
+//! The logging macros of this crate ([`logforth::info!`](crate::info!), etc.) attach the
+//! template to the record as a key-value under [`MESSAGE_TEMPLATE_KEY`]. Layouts can retrieve it
+//! with [`message_template`] and compute a stable identifier with [`template_hash`].
+//!
+//! # Examples
+//!
+//! ```
+//! logforth::stdout().apply();
+//!
+//! let user_id = 42;
+//! let ip = "127.0.0.1";
+//! logforth::info!("User {user_id} logged in from {ip}");
+//! ```
+
+use log::kv::Key;
+use log::Record;
+
+/// The key under which the message template is attached to a record.
+pub const MESSAGE_TEMPLATE_KEY: &str = "logforth.message_template";
+
+/// The name under which appenders write the message template.
+#[cfg(any(
+    feature = "fastrace",
+    feature = "opentelemetry",
+    all(unix, feature = "journald")
+))]
+pub(crate) const MESSAGE_TEMPLATE_FIELD: &str = "message_template";
+
+/// Returns the message template of the record, if it has one.
+pub fn message_template(record: &Record) -> Option<String> {
+    record
+        .key_values()
+        .get(Key::from_str(MESSAGE_TEMPLATE_KEY))
+        .map(|value| value.to_string())
+}
+
+/// Computes a stable 32-bit hash of the message template.
+///
+/// The hash is stable across processes and versions, so it can be used as an event type
+/// identifier by log backends.
+pub fn template_hash(template: &str) -> u32 {
+    // 32-bit FNV-1a
+    const OFFSET_BASIS: u32 = 0x811c9dc5;
+    const PRIME: u32 = 0x01000193;
+
+    template.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+pub(crate) fn is_message_template_key(key: &str) -> bool {
+    key == MESSAGE_TEMPLATE_KEY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_hash() {
+        assert_eq!(template_hash(""), 0x811c9dc5);
+        assert_eq!(template_hash("a"), 0xe40c292c);
+        assert_eq!(template_hash("foobar"), 0xbf9cf968);
+        assert_ne!(
+            template_hash("User {user_id} logged in"),
+            template_hash("User {user_id} logged out")
+        );
+    }
+
+    #[test]
+    fn test_message_template() {
+        let kvs = [(MESSAGE_TEMPLATE_KEY, "User {user_id} logged in")];
+        let record = Record::builder()
+            .args(format_args!("User 42 logged in"))
+            .key_values(&kvs)
+            .build();
+        assert_eq!(
+            message_template(&record).as_deref(),
+            Some("User {user_id} logged in")
+        );
+
+        let record = Record::builder().args(format_args!("hello")).build();
+        assert_eq!(message_template(&record), None);
+    }
+}
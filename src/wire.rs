@@ -0,0 +1,136 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A compact wire representation of levels and filter results.
+//!
+//! External processes, such as sidecar shippers or admin CLIs, can use these helpers to exchange
+//! levels and filter decisions with the in-process logger over IPC. Each value is encoded as a
+//! single byte, which is stable across releases:
+//!
+//! | Byte | [`LevelFilter`] | [`Level`] | [`FilterResult`] |
+//! |------|-----------------|-----------|------------------|
+//! | 0    | `Off`           | -         | `Accept`         |
+//! | 1    | `Error`         | `Error`   | `Reject`         |
+//! | 2    | `Warn`          | `Warn`    | `Neutral`        |
+//! | 3    | `Info`          | `Info`    | `Unknown`        |
+//! | 4    | `Debug`         | `Debug`   | -                |
+//! | 5    | `Trace`         | `Trace`   | -                |
+//!
+//! [`FilterResult::AcceptOnly`] refers to appenders of the local process and has no wire
+//! representation. With the `serde` feature, [`Level`], [`LevelFilter`] and [`FilterResult`] also
+//! implement `Serialize` and `Deserialize`.
+//!
+//! # Examples
+//!
+//! ```
+//! use log::Level;
+//! use logforth::wire;
+//!
+//! let byte = wire::level_to_u8(Level::Info);
+//! assert_eq!(wire::level_from_u8(byte), Some(Level::Info));
+//! ```
+
+use log::Level;
+use log::LevelFilter;
+
+use crate::filter::FilterResult;
+
+/// Encodes a level as a byte.
+pub fn level_to_u8(level: Level) -> u8 {
+    level as u8
+}
+
+/// Decodes a level from a byte, or returns `None` if the byte is not a valid level.
+pub fn level_from_u8(byte: u8) -> Option<Level> {
+    match byte {
+        1 => Some(Level::Error),
+        2 => Some(Level::Warn),
+        3 => Some(Level::Info),
+        4 => Some(Level::Debug),
+        5 => Some(Level::Trace),
+        _ => None,
+    }
+}
+
+/// Encodes a level filter as a byte.
+pub fn level_filter_to_u8(filter: LevelFilter) -> u8 {
+    filter as u8
+}
+
+/// Decodes a level filter from a byte, or returns `None` if the byte is not a valid level filter.
+pub fn level_filter_from_u8(byte: u8) -> Option<LevelFilter> {
+    match byte {
+        0 => Some(LevelFilter::Off),
+        _ => level_from_u8(byte).map(|level| level.to_level_filter()),
+    }
+}
+
+/// Encodes a filter result as a byte, or returns `None` for [`FilterResult::AcceptOnly`].
+pub fn filter_result_to_u8(result: FilterResult) -> Option<u8> {
+    match result {
+        FilterResult::Accept => Some(0),
+        FilterResult::Reject => Some(1),
+        FilterResult::Neutral => Some(2),
+        FilterResult::Unknown => Some(3),
+        FilterResult::AcceptOnly(_) => None,
+    }
+}
+
+/// Decodes a filter result from a byte, or returns `None` if the byte is not a valid filter
+/// result.
+pub fn filter_result_from_u8(byte: u8) -> Option<FilterResult> {
+    match byte {
+        0 => Some(FilterResult::Accept),
+        1 => Some(FilterResult::Reject),
+        2 => Some(FilterResult::Neutral),
+        3 => Some(FilterResult::Unknown),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for filter in LevelFilter::iter() {
+            assert_eq!(
+                level_filter_from_u8(level_filter_to_u8(filter)),
+                Some(filter)
+            );
+            if let Some(level) = filter.to_level() {
+                assert_eq!(level_to_u8(level), level_filter_to_u8(filter));
+                assert_eq!(level_from_u8(level_to_u8(level)), Some(level));
+            }
+        }
+        assert_eq!(level_from_u8(0), None);
+        assert_eq!(level_filter_from_u8(6), None);
+
+        for result in [
+            FilterResult::Accept,
+            FilterResult::Reject,
+            FilterResult::Neutral,
+            FilterResult::Unknown,
+        ] {
+            let byte = filter_result_to_u8(result.clone()).unwrap();
+            assert_eq!(filter_result_from_u8(byte), Some(result));
+        }
+        assert_eq!(
+            filter_result_to_u8(FilterResult::accept_only(["stdout"])),
+            None
+        );
+        assert_eq!(filter_result_from_u8(4), None);
+    }
+}
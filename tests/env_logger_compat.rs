@@ -0,0 +1,27 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// The max level is global to the process, so the test runs in its own binary rather than
+// alongside the unit tests.
+
+use log::LevelFilter;
+use logforth::env_logger_compat::Builder;
+
+#[test]
+fn test_init_sets_max_level() {
+    Builder::new()
+        .parse_filters("warn,app=debug,app::db=info")
+        .init();
+    assert_eq!(log::max_level(), LevelFilter::Debug);
+}
@@ -0,0 +1,71 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "derive")]
+
+use std::collections::BTreeMap;
+
+use log::kv::Source;
+use log::kv::VisitSource;
+use logforth::LogFields;
+
+#[derive(LogFields)]
+struct Request<'a> {
+    method: &'a str,
+    #[log(rename = "http.path", display)]
+    path: String,
+    #[log(skip)]
+    #[allow(dead_code)]
+    body: Vec<u8>,
+    status: u16,
+    #[log(debug)]
+    tags: Vec<&'a str>,
+}
+
+struct Collect(BTreeMap<String, String>);
+
+impl<'kvs> VisitSource<'kvs> for Collect {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        self.0.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+}
+
+#[test]
+fn test_derive_log_fields() {
+    let request = Request {
+        method: "GET",
+        path: "/users".to_string(),
+        body: b"secret".to_vec(),
+        status: 200,
+        tags: vec!["internal"],
+    };
+    assert_eq!(request.count(), 4);
+
+    let mut collect = Collect(BTreeMap::new());
+    request.visit(&mut collect).unwrap();
+    assert_eq!(
+        collect.0.into_iter().collect::<Vec<_>>(),
+        [
+            ("http.path".to_string(), "/users".to_string()),
+            ("method".to_string(), "GET".to_string()),
+            ("status".to_string(), "200".to_string()),
+            ("tags".to_string(), r#"["internal"]"#.to_string()),
+        ]
+    );
+}
@@ -0,0 +1,72 @@
+// Copyright 2024 FastLabs Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// The manual clock is global to the process, so the test runs in its own binary rather than
+// alongside the unit tests.
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+
+use log::Level;
+use log::Record;
+use logforth::append::ErrorStorm;
+use logforth::append::Memory;
+use logforth::Append;
+use logforth::ManualClock;
+
+fn append(append: &impl Append, level: Level, message: &str) {
+    append
+        .append(
+            &Record::builder()
+                .level(level)
+                .target("app")
+                .args(format_args!("{message}"))
+                .build(),
+            &[],
+        )
+        .unwrap();
+}
+
+#[test]
+fn test_install_manual_clock() {
+    let start = "2024-08-10T17:12:52+08[+08]".parse().unwrap();
+    let clock = ManualClock::new(start);
+    let memory = Memory::new(10);
+    let handle = memory.handle();
+    let storm = ErrorStorm::new(memory, 1, Duration::from_secs(10));
+
+    {
+        let _guard = clock.install();
+        append(&storm, Level::Error, "error 0");
+        append(&storm, Level::Error, "error 1");
+        // the storm window follows the manual clock, not the real one
+        storm.flush();
+        assert_eq!(handle.len(), 1);
+        clock.advance(Duration::from_secs(10));
+        storm.flush();
+
+        let records = handle.drain();
+        assert_eq!(records.len(), 2);
+        assert!(records[1].message.contains("suppressed 1 records"));
+        let times = records
+            .iter()
+            .map(|r| r.time.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(times, ["2024-08-10T09:12:52Z", "2024-08-10T09:13:02Z"]);
+    }
+
+    // uninstalled with the guard
+    append(&storm, Level::Warn, "warn");
+    assert_ne!(handle.snapshot()[0].time, clock.now().timestamp());
+}